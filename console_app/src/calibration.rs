@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use clap::ArgEnum;
+use raytracer_weekend_lib::{
+    camera::Camera,
+    hittable::{triangular::Triangle, Hittable},
+    material::{Lambertian, Material, MattePainting},
+    texture::SolidColor,
+    tonemap::Tonemap,
+    vec3::{Color, Point3},
+    Pixel,
+};
+
+const COLUMNS: usize = 6;
+const ROWS: usize = 4;
+/// Gap between patches, relative to the patch size.
+const GAP: f32 = 0.1;
+/// Only the inner part of each patch is measured, so anti-aliased edges don't skew the average.
+const MEASURED_FRACTION: f32 = 0.5;
+
+/// The 24 patches of the classic ColorChecker chart as 8-bit sRGB, row by row from the top left.
+const PATCHES: [(&str, [u8; 3]); COLUMNS * ROWS] = [
+    ("dark skin", [115, 82, 68]),
+    ("light skin", [194, 150, 130]),
+    ("blue sky", [98, 122, 157]),
+    ("foliage", [87, 108, 67]),
+    ("blue flower", [133, 128, 177]),
+    ("bluish green", [103, 189, 170]),
+    ("orange", [214, 126, 44]),
+    ("purplish blue", [80, 91, 166]),
+    ("moderate red", [193, 90, 99]),
+    ("purple", [94, 60, 108]),
+    ("yellow green", [157, 188, 64]),
+    ("orange yellow", [224, 163, 46]),
+    ("blue", [56, 61, 150]),
+    ("green", [70, 148, 73]),
+    ("red", [175, 54, 60]),
+    ("yellow", [231, 199, 31]),
+    ("magenta", [187, 86, 149]),
+    ("cyan", [8, 133, 161]),
+    ("white 9.5", [243, 243, 242]),
+    ("neutral 8", [200, 200, 200]),
+    ("neutral 6.5", [160, 160, 160]),
+    ("neutral 5", [122, 122, 121]),
+    ("neutral 3.5", [85, 85, 85]),
+    ("black 2", [52, 52, 52]),
+];
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ChartLighting {
+    /// Diffuse patches, lit by the scene like any other object.
+    Lit,
+    /// Self-lit patches only the camera sees, which show their reference values as is.
+    Unlit,
+}
+
+/// Where the chart goes on screen, in the same `0.0..=1.0` screen coordinates the camera uses.
+#[derive(Debug, Clone, Copy)]
+pub struct ChartPlacement {
+    pub left: f32,
+    pub bottom: f32,
+    pub width: f32,
+    /// Distance in front of the camera. Keep it short so the chart ends up in front of the scene.
+    pub distance: f32,
+}
+
+/// The screen rectangle of a single patch, as `(s0, t0, s1, t1)`.
+type ScreenRect = (f32, f32, f32, f32);
+
+pub struct CalibrationChart {
+    lighting: ChartLighting,
+    placement: ChartPlacement,
+    aspect_ratio: f32,
+}
+
+impl CalibrationChart {
+    pub fn new(lighting: ChartLighting, placement: ChartPlacement, aspect_ratio: f32) -> Self {
+        Self {
+            lighting,
+            placement,
+            aspect_ratio,
+        }
+    }
+
+    fn patch_rects(&self) -> impl Iterator<Item = ScreenRect> + '_ {
+        let pitch_s = self.placement.width / COLUMNS as f32;
+        // Square patches on screen, so correct for the aspect ratio in t.
+        let pitch_t = pitch_s * self.aspect_ratio;
+        let top = self.placement.bottom + ROWS as f32 * pitch_t;
+
+        (0..PATCHES.len()).map(move |index| {
+            let s0 = self.placement.left + (index % COLUMNS) as f32 * pitch_s;
+            let t1 = top - (index / COLUMNS) as f32 * pitch_t;
+            let inset_s = 0.5 * GAP * pitch_s;
+            let inset_t = 0.5 * GAP * pitch_t;
+
+            (
+                s0 + inset_s,
+                t1 - pitch_t + inset_t,
+                s0 + pitch_s - inset_s,
+                t1 - inset_t,
+            )
+        })
+    }
+
+    /// Builds the patches as screen-aligned quads in front of `cam`.
+    pub fn hittables(&self, cam: &Camera) -> Vec<Box<dyn Hittable>> {
+        let distance = self.placement.distance;
+
+        self.patch_rects()
+            .zip(PATCHES)
+            .flat_map(|((s0, t0, s1, t1), (_, srgb))| {
+                let color = srgb_to_linear(srgb);
+                let material: Arc<dyn Material> = match self.lighting {
+                    ChartLighting::Lit => Arc::new(Lambertian::new_solid_color(color)),
+                    ChartLighting::Unlit => Arc::new(MattePainting::new(SolidColor::new(color))),
+                };
+
+                let corners: [Point3; 4] = [
+                    cam.screen_point(s0, t0, distance),
+                    cam.screen_point(s1, t0, distance),
+                    cam.screen_point(s1, t1, distance),
+                    cam.screen_point(s0, t1, distance),
+                ];
+
+                [
+                    Box::new(Triangle::new_flat_shaded(
+                        [corners[0], corners[1], corners[2]],
+                        material.clone(),
+                    )) as Box<dyn Hittable>,
+                    Box::new(Triangle::new_flat_shaded(
+                        [corners[0], corners[2], corners[3]],
+                        material,
+                    )),
+                ]
+            })
+            .collect()
+    }
+
+    /// Averages the rendered patches and prints them next to their reference values.
+    ///
+    /// Both are compared after tone mapping, with the reference passed through the plain gamma
+    /// curve only, so any difference is down to lighting, exposure or the tone mapping operator.
+    pub fn print_report(&self, pixels: &[Pixel], width: u32, height: u32, tonemap: &Tonemap) {
+        let mut sums = vec![([0.0_f32; 3], 0_u32); PATCHES.len()];
+        let rects: Vec<_> = self
+            .patch_rects()
+            .map(|rect| shrink(rect, MEASURED_FRACTION))
+            .collect();
+
+        for pixel in pixels {
+            let s = pixel.column as f32 / (width - 1) as f32;
+            let t = pixel.row as f32 / (height - 1) as f32;
+
+            let index = match rects
+                .iter()
+                .position(|&(s0, t0, s1, t1)| (s0..=s1).contains(&s) && (t0..=t1).contains(&t))
+            {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let rgb = tonemap.to_rgb8(pixel.color, pixel.sample_count);
+            let (sum, count) = &mut sums[index];
+            for (sum, channel) in sum.iter_mut().zip(rgb) {
+                *sum += channel as f32;
+            }
+            *count += 1;
+        }
+
+        println!(
+            "{:<14} {:>17} {:>17} {:>7}",
+            "patch", "reference", "rendered", "delta-E"
+        );
+        for ((name, srgb), (sum, count)) in PATCHES.iter().zip(sums) {
+            let reference = Tonemap::default().to_rgb8(srgb_to_linear(*srgb), 1);
+            let reference = reference.map(|channel| channel as f32);
+
+            if count == 0 {
+                println!(
+                    "{:<14} {:>17} {:>17}",
+                    name,
+                    format_rgb(reference),
+                    "off screen"
+                );
+                continue;
+            }
+
+            let rendered = sum.map(|channel| channel / count as f32);
+            let delta_e = delta_e(reference, rendered);
+            println!(
+                "{:<14} {:>17} {:>17} {:>7.2}",
+                name,
+                format_rgb(reference),
+                format_rgb(rendered),
+                delta_e
+            );
+        }
+    }
+}
+
+fn shrink((s0, t0, s1, t1): ScreenRect, fraction: f32) -> ScreenRect {
+    let margin_s = 0.5 * (1.0 - fraction) * (s1 - s0);
+    let margin_t = 0.5 * (1.0 - fraction) * (t1 - t0);
+
+    (s0 + margin_s, t0 + margin_t, s1 - margin_s, t1 - margin_t)
+}
+
+fn format_rgb(rgb: [f32; 3]) -> String {
+    format!("{:.1} {:.1} {:.1}", rgb[0], rgb[1], rgb[2])
+}
+
+fn srgb_to_linear(srgb: [u8; 3]) -> Color {
+    let decode = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    Color::new(decode(srgb[0]), decode(srgb[1]), decode(srgb[2]))
+}
+
+/// CIE76 colour difference between two display values, which use the renderer's gamma of 2.0.
+fn delta_e(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (l_a, a_a, b_a) = display_to_lab(a);
+    let (l_b, a_b, b_b) = display_to_lab(b);
+
+    ((l_a - l_b).powi(2) + (a_a - a_b).powi(2) + (b_a - b_b).powi(2)).sqrt()
+}
+
+fn display_to_lab(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb.map(|channel| (channel / 255.0).powi(2));
+
+    // Linear sRGB to XYZ, normalised to the D65 white point.
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
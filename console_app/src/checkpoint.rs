@@ -0,0 +1,106 @@
+//! Snapshots of unfinished renders, so a render that was interrupted can pick up where it left off.
+//!
+//! A checkpoint stores the command line of the render instead of its parsed options, and the seed
+//! the scene was generated with instead of the scene itself. Resuming parses the command line
+//! again and regenerates the scene, which is then compared against a hash of the original to make
+//! sure the scene code hasn't changed in between.
+
+use std::{
+    error::Error,
+    fmt::{self, Write as _},
+    fs,
+    path::Path,
+};
+
+use raytracer_weekend_lib::{background::Background, camera::Camera, hittable::Hittable, Pixel};
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the layout of [`Checkpoint`] changes, as postcard can't tell layouts apart.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    version: u32,
+    /// The command line of the render, program name included.
+    pub args: Vec<String>,
+    pub scene_seed: u64,
+    pub scene_hash: u64,
+    /// The frame currently being rendered.
+    pub frame: usize,
+    /// How many samples per pixel of `frame` are in `pixels`.
+    pub samples_done: u32,
+    /// The accumulated pixels of `frame`, top row first. Empty until the first pass is done.
+    pub pixels: Vec<Pixel>,
+}
+
+impl Checkpoint {
+    pub fn new(args: Vec<String>, scene_seed: u64, scene_hash: u64) -> Self {
+        Self {
+            version: VERSION,
+            args,
+            scene_seed,
+            scene_hash,
+            frame: 0,
+            samples_done: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+
+        // The version comes first, so it can be checked before the rest is misread.
+        let (version, _): (u32, _) = postcard::take_from_bytes(&bytes)?;
+        if version != VERSION {
+            return Err(format!(
+                "{} is a version {} checkpoint, but only version {} is supported",
+                path.display(),
+                version,
+                VERSION
+            )
+            .into());
+        }
+
+        Ok(postcard::from_bytes(&bytes)?)
+    }
+
+    /// Replaces the file at `path`. The checkpoint is written next to it first, so a crash while
+    /// writing leaves the previous checkpoint intact.
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let temporary_path = path.with_extension("partial");
+        fs::write(&temporary_path, postcard::to_stdvec(self)?)?;
+        fs::rename(&temporary_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Hashes the debug representation of a scene, which covers every object, material and camera
+/// parameter.
+pub fn scene_hash(world: &[Box<dyn Hittable>], cameras: &[Camera], background: &Background) -> u64 {
+    let mut hasher = Fnv1a::default();
+    write!(hasher, "{:?}{:?}{:?}", world, cameras, background).expect("Hashing never fails");
+
+    hasher.0
+}
+
+/// The 64 bit FNV-1a hash. Unlike the hashers of the standard library, it is guaranteed to stay
+/// the same across Rust versions.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl fmt::Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Rendering just a window of the frame, to iterate on part of a scene without paying for the
+//! rest of it.
+
+use std::str::FromStr;
+
+use raytracer_weekend_lib::{vec3::Color, Pixel, PixelRect};
+
+/// A window of the image, `width`×`height` pixels large with its top left corner at column `x`
+/// and row `y`. Unlike [`Pixel::row`], rows count from the top, like in image editors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    /// Parses `x,y,w,h`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split(',')
+            .map(|number| number.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid crop {:?}: {}", s, e))?;
+
+        match numbers[..] {
+            [x, y, width, height] => Ok(Self {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!("invalid crop {:?}, expected x,y,w,h", s)),
+        }
+    }
+}
+
+impl Crop {
+    /// The part of the crop that lies within an image of the given size, with a warning if that
+    /// isn't all of it. Fails if nothing is left.
+    pub fn clamped(&self, image_width: u32, image_height: u32) -> Result<Self, String> {
+        let x = self.x.min(image_width);
+        let y = self.y.min(image_height);
+        let clamped = Self {
+            x,
+            y,
+            width: self.width.min(image_width - x),
+            height: self.height.min(image_height - y),
+        };
+
+        if clamped.width == 0 || clamped.height == 0 {
+            return Err(format!(
+                "The crop {},{},{},{} leaves nothing of the {}×{} image",
+                self.x, self.y, self.width, self.height, image_width, image_height
+            ));
+        }
+        if clamped != *self {
+            eprintln!(
+                "Warning: the crop reaches past the {}×{} image, rendering {},{},{},{} instead",
+                image_width, image_height, clamped.x, clamped.y, clamped.width, clamped.height
+            );
+        }
+
+        Ok(clamped)
+    }
+
+    /// The crop in the renderer's terms, with rows counting from the bottom.
+    pub fn to_pixel_rect(self, image_height: u32) -> PixelRect {
+        PixelRect {
+            x0: self.x,
+            y0: image_height - self.y - self.height,
+            x1: self.x + self.width,
+            y1: image_height - self.y,
+        }
+    }
+}
+
+/// Turns the pixels rendered for `rect` into a frame the size of the whole image, with black
+/// everywhere else.
+pub fn full_frame(pixels: Vec<Pixel>, rect: PixelRect, width: u32, height: u32) -> Vec<Pixel> {
+    let mut rendered = pixels.into_iter();
+
+    // Both are in the order the image is written in: top row first, left to right.
+    (0..height)
+        .rev()
+        .flat_map(|row| (0..width).map(move |column| (row, column)))
+        .map(|(row, column)| match rect.contains(row, column) {
+            true => rendered.next().expect("a pixel for every one in the crop"),
+            false => Pixel {
+                row,
+                column,
+                color: Color::new(0.0, 0.0, 0.0),
+                sample_count: 0,
+                aux: None,
+            },
+        })
+        .collect()
+}
+
+/// Moves the pixels rendered for `rect` to where they go in an image of just the crop.
+pub fn cropped_frame(pixels: Vec<Pixel>, rect: PixelRect) -> Vec<Pixel> {
+    pixels
+        .into_iter()
+        .map(|pixel| Pixel {
+            row: pixel.row - rect.y0,
+            column: pixel.column - rect.x0,
+            ..pixel
+        })
+        .collect()
+}
@@ -0,0 +1,76 @@
+//! Printing the paths traced by `Raytracer::trace_pixel` in a form that can be read through.
+
+use raytracer_weekend_lib::{
+    path_trace::PathEvent,
+    vec3::{Color, Vec3},
+};
+
+pub fn print_trace(sample_index: u32, color: Color, events: &[PathEvent]) {
+    println!("Sample {}: {}", sample_index, format_vec3(color));
+    if events.is_empty() {
+        println!("  The camera doesn't cover this sample.");
+    }
+
+    for event in events {
+        match event {
+            PathEvent::Hit {
+                bounce,
+                point,
+                object_name,
+                material,
+                emitted,
+                attenuation,
+                pdf,
+            } => {
+                println!(
+                    "  {:>3} hit {} at {}",
+                    bounce,
+                    object_name.as_deref().unwrap_or("an unnamed object"),
+                    format_vec3(*point)
+                );
+                println!("      material    {}", short_type_name(material));
+                println!("      emitted     {}", format_vec3(*emitted));
+                match attenuation {
+                    Some(attenuation) => {
+                        println!("      attenuation {}", format_vec3(*attenuation))
+                    }
+                    None => println!("      the path ends here"),
+                }
+                if let Some(pdf) = pdf {
+                    println!("      pdf         {:.4}", pdf);
+                }
+            }
+            PathEvent::Background { bounce, radiance } => {
+                println!("  {:>3} background {}", bounce, format_vec3(*radiance));
+            }
+        }
+    }
+}
+
+fn format_vec3(v: Vec3) -> String {
+    format!("{:.4} {:.4} {:.4}", v.x(), v.y(), v.z())
+}
+
+/// Strips the module paths off every type in `type_name`, e.g.
+/// `raytracer_weekend_lib::material::Lambertian<raytracer_weekend_lib::texture::SolidColor>`
+/// becomes `Lambertian<SolidColor>`.
+fn short_type_name(type_name: &str) -> String {
+    let mut short = String::new();
+    let mut path = String::new();
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            short.push_str(last_segment(&path));
+            path.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(last_segment(&path));
+
+    short
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or_default()
+}
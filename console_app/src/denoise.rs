@@ -0,0 +1,233 @@
+//! Removes the noise of renders with few samples, after rendering and before tone mapping.
+//!
+//! Both denoisers lean on the auxiliary buffers of the pixels (see `Raytracer::with_aux`) to tell
+//! noise from edges, so the frame has to be rendered with them.
+
+use clap::ArgEnum;
+use rayon::prelude::*;
+use raytracer_weekend_lib::{
+    vec3::{Color, Vec3},
+    Pixel, PixelAux,
+};
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Denoiser {
+    Off,
+    /// Edge-avoiding à-trous wavelet filter, a fast approximation of a large bilateral filter.
+    Bilateral,
+    /// Intel Open Image Denoise, fed with the albedo and normal buffers.
+    #[cfg(feature = "oidn")]
+    Oidn,
+}
+
+/// How often the à-trous filter runs. Every pass doubles the spacing of its taps, so the filter
+/// covers `4 * 2^ITERATIONS` pixels across in the end.
+const ITERATIONS: u32 = 5;
+
+/// Weights of the B3 spline the à-trous filter is built on.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// How different the colors of two pixels may get before they stop being averaged, in the first
+/// pass. Later passes halve it, as the noise has gone down by then.
+const COLOR_SIGMA: f32 = 0.6;
+/// Same for the squared distance between normals.
+const NORMAL_SIGMA: f32 = 0.1;
+/// Same for the depth difference, relative to the depth of the pixel being filtered.
+const DEPTH_SIGMA: f32 = 0.05;
+
+/// Returns the denoised pixels in the order they were given in, which may be any. Sample counts
+/// and auxiliary buffers are kept, so the result can be tone mapped and saved like the input.
+pub fn denoise(pixels: &[Pixel], width: u32, height: u32, denoiser: Denoiser) -> Vec<Pixel> {
+    let frame = Frame::new(pixels, width, height);
+
+    let denoised = match denoiser {
+        Denoiser::Off => frame.colors.clone(),
+        Denoiser::Bilateral => frame.remodulate(&frame.a_trous()),
+        #[cfg(feature = "oidn")]
+        Denoiser::Oidn => frame.oidn(),
+    };
+
+    pixels
+        .iter()
+        .map(|pixel| Pixel {
+            color: pixel.sample_count.max(1) as f32 * denoised[frame.index(pixel)],
+            ..pixel.clone()
+        })
+        .collect()
+}
+
+/// The pixels of a frame laid out as an image: top row first, left to right.
+struct Frame {
+    width: usize,
+    height: usize,
+    /// Linear colors, i.e. already divided by the sample counts.
+    colors: Vec<Color>,
+    aux: Vec<Option<PixelAux>>,
+}
+
+impl Frame {
+    fn new(pixels: &[Pixel], width: u32, height: u32) -> Self {
+        let pixel_count = (width * height) as usize;
+        assert_eq!(pixels.len(), pixel_count);
+
+        let mut frame = Self {
+            width: width as usize,
+            height: height as usize,
+            colors: vec![Color::new(0.0, 0.0, 0.0); pixel_count],
+            aux: vec![None; pixel_count],
+        };
+
+        // Place every pixel by its row and column rather than trusting the order it came in.
+        for pixel in pixels {
+            let index = frame.index(pixel);
+            frame.colors[index] = pixel.color / pixel.sample_count.max(1) as f32;
+            frame.aux[index] = pixel.aux;
+        }
+
+        frame
+    }
+
+    fn index(&self, pixel: &Pixel) -> usize {
+        // Rows count from the bottom.
+        let y = self.height - 1 - pixel.row as usize;
+        y * self.width + pixel.column as usize
+    }
+
+    /// Divides out the albedo, so textures don't count as noise and aren't blurred.
+    fn demodulated(&self) -> Vec<Color> {
+        self.colors
+            .iter()
+            .zip(&self.aux)
+            .map(|(&color, aux)| match aux {
+                Some(aux) => color / nonzero_albedo(aux.albedo),
+                None => color,
+            })
+            .collect()
+    }
+
+    fn remodulate(&self, colors: &[Color]) -> Vec<Color> {
+        colors
+            .iter()
+            .zip(&self.aux)
+            .map(|(&color, aux)| match aux {
+                Some(aux) => color * nonzero_albedo(aux.albedo),
+                None => color,
+            })
+            .collect()
+    }
+
+    /// The edge-avoiding à-trous wavelet transform of Dammertz et al., applied to the
+    /// demodulated colors.
+    fn a_trous(&self) -> Vec<Color> {
+        (0..ITERATIONS).fold(self.demodulated(), |colors, iteration| {
+            let step = 1 << iteration;
+            let color_sigma = COLOR_SIGMA / (1 << iteration) as f32;
+
+            (0..self.width * self.height)
+                .into_par_iter()
+                .map(|index| self.a_trous_pixel(&colors, index, step, color_sigma))
+                .collect()
+        })
+    }
+
+    fn a_trous_pixel(
+        &self,
+        colors: &[Color],
+        index: usize,
+        step: isize,
+        color_sigma: f32,
+    ) -> Color {
+        let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+        let center_color = colors[index];
+        let center_aux = self.aux[index];
+
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        let mut weight_sum = 0.0;
+        for (dy, kernel_y) in (-2..=2).zip(KERNEL) {
+            for (dx, kernel_x) in (-2..=2).zip(KERNEL) {
+                let (tap_x, tap_y) = (x + dx * step, y + dy * step);
+                // Taps beyond the border are left out, and the weights renormalised below.
+                if tap_x < 0
+                    || tap_y < 0
+                    || tap_x >= self.width as isize
+                    || tap_y >= self.height as isize
+                {
+                    continue;
+                }
+                let tap = tap_y as usize * self.width + tap_x as usize;
+
+                let color_distance = (colors[tap] - center_color).length_squared();
+                let mut weight =
+                    kernel_x * kernel_y * (-color_distance / (color_sigma * color_sigma)).exp();
+                weight *= match (center_aux, self.aux[tap]) {
+                    (Some(center), Some(other)) => geometry_weight(&center, &other),
+                    (None, None) => 1.0,
+                    // The edge of an object against the background.
+                    _ => 0.0,
+                };
+
+                sum += weight * colors[tap];
+                weight_sum += weight;
+            }
+        }
+
+        // The center tap always has a positive weight.
+        sum / weight_sum
+    }
+
+    #[cfg(feature = "oidn")]
+    fn oidn(&self) -> Vec<Color> {
+        fn flatten(colors: impl Iterator<Item = Color>) -> Vec<f32> {
+            colors.flat_map(|c| [c.x(), c.y(), c.z()]).collect()
+        }
+
+        // Background pixels get black buffers, which is what the denoiser expects for them.
+        let input = flatten(self.colors.iter().copied());
+        let albedo = flatten(
+            self.aux
+                .iter()
+                .map(|aux| aux.map_or(Color::default(), |a| a.albedo)),
+        );
+        let normal = flatten(
+            self.aux
+                .iter()
+                .map(|aux| aux.map_or(Vec3::default(), |a| a.normal)),
+        );
+
+        let mut output = vec![0.0; input.len()];
+        let device = oidn::Device::new();
+        oidn::RayTracing::new(&device)
+            .hdr(true)
+            .srgb(false)
+            .image_dimensions(self.width, self.height)
+            .albedo_normal(&albedo, &normal)
+            .filter(&input, &mut output)
+            .expect("The denoiser is set up for the buffers it gets");
+        if let Err((_, message)) = device.get_error() {
+            panic!("Open Image Denoise failed: {}", message);
+        }
+
+        output
+            .chunks_exact(3)
+            .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2]))
+            .collect()
+    }
+}
+
+/// How much a neighbouring pixel lies on the same surface as the one being filtered.
+fn geometry_weight(center: &PixelAux, other: &PixelAux) -> f32 {
+    let normal_distance = (center.normal - other.normal).length_squared();
+    let depth_distance = (center.depth - other.depth) / (DEPTH_SIGMA * center.depth.max(1e-4));
+
+    (-normal_distance / NORMAL_SIGMA - depth_distance * depth_distance).exp()
+}
+
+/// Keeps black surfaces from turning their pixels into infinities when demodulating.
+fn nonzero_albedo(albedo: Color) -> Color {
+    const MIN_ALBEDO: f32 = 0.01;
+    Vec3::new(
+        albedo.x().max(MIN_ALBEDO),
+        albedo.y().max(MIN_ALBEDO),
+        albedo.z().max(MIN_ALBEDO),
+    )
+}
@@ -1,12 +1,41 @@
+mod calibration;
+mod checkpoint;
+mod crop;
+mod debug_pixel;
+mod denoise;
+mod output;
+mod ray_batch;
 mod scenes;
+mod stats;
+mod video;
 
-use clap::Parser;
-use image::{Rgb, RgbImage};
+use std::{
+    cmp::Reverse,
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use calibration::{CalibrationChart, ChartLighting, ChartPlacement};
+use checkpoint::Checkpoint;
+use clap::{Parser, Subcommand};
+use crop::Crop;
+use denoise::Denoiser;
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressIterator, ProgressStyle};
-use rand::thread_rng;
+use output::{Aov, OutputFormat, OutputTarget};
+use rand::{rngs::SmallRng, thread_rng, Rng};
+use ray_batch::Tolerances;
 use rayon::prelude::*;
-use raytracer_weekend_lib::Raytracer;
-use scenes::Scene;
+use raytracer_weekend_lib::{
+    adaptive::AdaptiveRaytracer,
+    mix_seed,
+    ray_batch::trace_ray_batch,
+    sampler::SamplerKind,
+    stats::RenderStats,
+    tonemap::{Tonemap, TonemapOperator},
+    Estimator, Raytracer,
+};
+use scenes::{Assets, Scene};
 
 const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 const CRATE_AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
@@ -16,81 +45,671 @@ const CRATE_AUTHOR: &str = env!("CARGO_PKG_AUTHORS");
 #[clap(version = CRATE_VERSION, author = CRATE_AUTHOR)]
 struct Opts {
     #[clap(subcommand)]
-    scene: Scene,
+    command: Command,
     #[clap(long, short, default_value = "400")]
     width: u32,
     #[clap(long, short, default_value = "1.7777778")]
     aspect_ratio: f64,
     #[clap(long, short, default_value = "100")]
     samples_per_pixel: u32,
+    /// How many times rays may bounce. 1 renders direct lighting only.
+    #[clap(long, default_value = "50")]
+    max_depth: usize,
+    /// How the samples of a pixel are spread over it: uniform, stratified or halton. Stratified
+    /// sampling puts one sample in every cell of a grid over the pixel, halton follows a scrambled
+    /// low-discrepancy sequence. Both even out edges and soft shadows. Neither applies in
+    /// adaptive mode.
+    #[clap(long, default_value = "uniform")]
+    sampler: SamplerKind,
+    /// Scales samples brighter than this down to it, trading a little energy for fewer fireflies.
+    #[clap(long, default_value = "inf", conflicts_with = "reference")]
+    max_luminance: f32,
+    /// Follows every path up to the maximum depth instead of ending dim ones early at random.
+    #[clap(long)]
+    no_russian_roulette: bool,
+    /// Prints how many rays and intersection tests every frame took, and how long.
+    #[clap(long)]
+    stats: bool,
+    /// Also writes the render statistics of every frame as JSON, next to the image.
+    #[clap(long)]
+    stats_json: bool,
+    /// Directory the models and textures of the scenes are looked up in.
+    #[clap(long, default_value = ".")]
+    asset_root: PathBuf,
+    /// Builds the BVHs of OBJ models from scratch instead of using the `.bvh` files cached next
+    /// to them, which are left alone.
+    #[clap(long)]
+    no_bvh_cache: bool,
+    /// Edge length of the square tiles frames are rendered in, in pixels.
+    #[clap(long, default_value = "64")]
+    tile_size: u32,
+    /// Renders only the window of `x,y,w,h` pixels, counted from the top left, e.g.
+    /// `100,50,200,150`. The rest of the image is left black. Crops reaching past the image are
+    /// cut down to it.
+    #[clap(long)]
+    crop: Option<Crop>,
+    /// Writes just the cropped window instead of a full-size image.
+    #[clap(long, requires = "crop", conflicts_with = "calibration-chart")]
+    crop_only: bool,
+    /// Renders a brute force ground truth image without any variance reduction.
+    #[clap(long)]
+    reference: bool,
+    /// In reference mode, samples diffuse surfaces uniformly instead of by their cosine term.
+    #[clap(long, requires = "reference")]
+    uniform_hemisphere: bool,
+    /// Stops sampling pixels once they have converged, taking at most `samples-per-pixel`.
+    #[clap(long, conflicts_with = "reference")]
+    adaptive: bool,
+    /// In adaptive mode, the least number of samples every pixel gets.
+    #[clap(long, default_value = "16", requires = "adaptive")]
+    min_samples: u32,
+    /// In adaptive mode, how small the variance of a pixel's mean, relative to its brightness,
+    /// has to get before sampling stops.
+    #[clap(long, default_value = "0.001", requires = "adaptive")]
+    variance_threshold: f32,
+    /// Saves the progress of the render to this file, so it can be continued with `resume` if it
+    /// gets interrupted. The file is removed once the render is done.
+    #[clap(long, conflicts_with = "adaptive")]
+    checkpoint: Option<PathBuf>,
+    /// How many samples per pixel to render between two checkpoints.
+    #[clap(long, default_value = "16", requires = "checkpoint")]
+    checkpoint_samples: u32,
+    /// Output directory or file name pattern; `{frame}` is replaced by the frame number, padded to
+    /// four digits, or to N with `{frame:0N}`. Defaults to `render/image_{frame}.png`.
+    #[clap(long, short, validator = output::validate_output_path)]
+    output: Option<String>,
+    /// Directory to write the frames to, with the default file names. Missing directories are
+    /// created either way.
+    #[clap(long, conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    /// Replaces files left by earlier renders instead of refusing to start.
+    #[clap(long)]
+    overwrite: bool,
+    /// Image format. Defaults to the output file extension, or PNG for directories.
+    #[clap(long, arg_enum, alias = "output-format")]
+    format: Option<OutputFormat>,
+    #[clap(long, default_value = "90")]
+    jpeg_quality: u8,
+    /// Once all frames are rendered, also puts them together into this GIF or video file. Formats
+    /// other than GIF are written by ffmpeg, which has to be installed.
+    #[clap(long)]
+    video: Option<PathBuf>,
+    /// Frames per second of the video.
+    #[clap(long, default_value = "10", requires = "video")]
+    fps: u32,
+    /// Auxiliary images to write next to every frame, e.g. `normal,depth,albedo`. They show what
+    /// the camera sees first in every pixel, for denoising.
+    #[clap(long, arg_enum, use_value_delimiter = true)]
+    aov: Vec<Aov>,
+    /// Denoises every frame before it is tone mapped and saved.
+    #[clap(long, arg_enum, default_value = "off")]
+    denoise: Denoiser,
+    /// Tone mapping operator for 8 and 16 bit output: none, reinhard, reinhard-extended, aces or
+    /// uncharted2. The extended Reinhard operator takes the input that maps to white as well,
+    /// e.g. `reinhard-extended:8`; it defaults to 4.
+    #[clap(long, default_value = "none", alias = "tone-map")]
+    tonemap: TonemapOperator,
+    /// Exposure adjustment in stops, applied before tone mapping.
+    #[clap(long, default_value = "0.0", allow_hyphen_values = true)]
+    exposure: f32,
+    /// Overlays a 24-patch colour chart and prints how closely the render matches it.
+    #[clap(long, arg_enum)]
+    calibration_chart: Option<ChartLighting>,
+    /// Left edge of the chart, as a fraction of the image width.
+    #[clap(long, default_value = "0.05")]
+    chart_left: f32,
+    /// Bottom edge of the chart, as a fraction of the image height.
+    #[clap(long, default_value = "0.05")]
+    chart_bottom: f32,
+    /// Width of the chart, as a fraction of the image width.
+    #[clap(long, default_value = "0.3")]
+    chart_width: f32,
+    /// Distance of the chart in front of the camera, in scene units.
+    #[clap(long, default_value = "0.5")]
+    chart_distance: f32,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Renders one of the scenes.
+    #[clap(flatten)]
+    Render(Scene),
+    /// Continues an interrupted render from its checkpoint, with the options it was started with.
+    Resume { checkpoint: PathBuf },
+    /// Writes the camera rays of a scene's first camera to a CSV file, without tracing them.
+    ///
+    /// Width, aspect ratio and samples per pixel apply as for rendering.
+    ExportRays {
+        /// Seeds the random sample positions, so the same rays can be generated again.
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        #[clap(long, default_value = "rays.csv")]
+        output: PathBuf,
+        #[clap(subcommand)]
+        scene: Scene,
+    },
+    /// Traces the rays from a ray file against a scene and writes their first hits.
+    TraceRays {
+        #[clap(long, default_value = "rays.csv")]
+        rays: PathBuf,
+        #[clap(long, default_value = "hits.csv")]
+        output: PathBuf,
+        #[clap(subcommand)]
+        scene: Scene,
+    },
+    /// Traces single samples of one pixel and prints every step of their paths, e.g. to find out
+    /// where a firefly comes from.
+    ///
+    /// Width, aspect ratio, maximum depth and the estimator apply as for rendering.
+    DebugPixel {
+        /// Column of the pixel, counted from the left.
+        x: u32,
+        /// Row of the pixel, counted from the top like in image editors.
+        y: u32,
+        /// How many samples to trace.
+        #[clap(long, default_value = "1")]
+        samples: u32,
+        /// Seeds the scene and the samples, so the same paths can be traced again.
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        #[clap(subcommand)]
+        scene: Scene,
+    },
+    /// Compares two hit files and reports the rays they disagree on.
+    ///
+    /// Exits with status 1 if there are any mismatches.
+    CompareHits {
+        first: PathBuf,
+        second: PathBuf,
+        /// Largest acceptable difference in hit distance, in units of the ray direction.
+        #[clap(long, default_value = "0.0001")]
+        t_tolerance: f32,
+        /// Largest acceptable angle between the normals of two hits, in degrees.
+        #[clap(long, default_value = "0.1")]
+        angle_tolerance: f32,
+        /// Number of offending rays to list.
+        #[clap(long, default_value = "10")]
+        worst: usize,
+    },
+}
+
+impl Opts {
+    fn image_height(&self) -> u32 {
+        (self.width as f64 / self.aspect_ratio).round() as u32
+    }
+
+    fn assets(&self) -> Assets {
+        Assets::new(self.asset_root.clone(), !self.no_bvh_cache)
+    }
+
+    fn output_target(&self, tonemap: Tonemap) -> OutputTarget {
+        match (&self.output_dir, &self.output) {
+            (Some(directory), _) => {
+                OutputTarget::in_directory(directory, self.format, self.jpeg_quality, tonemap)
+            }
+            (None, output) => OutputTarget::new(
+                output.as_deref().unwrap_or(output::DEFAULT_OUTPUT),
+                self.format,
+                self.jpeg_quality,
+                tonemap,
+            ),
+        }
+    }
+
+    /// Everything written for a frame that went to `path`.
+    fn frame_outputs(&self, path: &Path) -> Vec<PathBuf> {
+        let mut outputs = vec![path.to_owned()];
+        outputs.extend(self.aov.iter().map(|&aov| output::aov_path(path, aov)));
+        if self.stats_json {
+            outputs.push(path.with_extension("json"));
+        }
+
+        outputs
+    }
+
+    fn estimator(&self) -> Estimator {
+        match self.reference {
+            true => Estimator::Reference {
+                uniform_hemisphere: self.uniform_hemisphere,
+            },
+            false => Estimator::Optimized,
+        }
+    }
 }
 
 fn main() {
     let opts: Opts = Opts::parse();
 
+    let result = match &opts.command {
+        Command::Render(scene) => render(&opts, scene, None),
+        Command::Resume { checkpoint } => resume(checkpoint),
+        Command::ExportRays {
+            seed,
+            output,
+            scene,
+        } => export_rays(&opts, scene, *seed, output),
+        Command::TraceRays {
+            rays,
+            output,
+            scene,
+        } => trace_rays(&opts, scene, rays, output),
+        Command::DebugPixel {
+            x,
+            y,
+            samples,
+            seed,
+            scene,
+        } => debug_pixel(&opts, scene, (*x, *y), *samples, *seed),
+        Command::CompareHits {
+            first,
+            second,
+            t_tolerance,
+            angle_tolerance,
+            worst,
+        } => {
+            let tolerances = Tolerances {
+                t: *t_tolerance,
+                normal_angle_degrees: *angle_tolerance,
+            };
+            compare_hits(first, second, tolerances, *worst)
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn export_rays(
+    opts: &Opts,
+    scene: &Scene,
+    seed: u64,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let image_height = opts.image_height();
+    let (world, cams, background) = scene.generate(
+        (opts.width as f32) / (image_height as f32),
+        &mut SmallRng::seed_from_u64(thread_rng().gen()),
+        &opts.assets(),
+    );
+
+    let raytracer = Raytracer::new(
+        &world,
+        &cams[0],
+        &background,
+        opts.width,
+        image_height,
+        opts.samples_per_pixel,
+    );
+    let rays: Vec<_> = raytracer
+        .primary_rays(opts.samples_per_pixel, seed)
+        .collect();
+
+    ray_batch::write_rays(output, &rays)?;
+    println!("Wrote {} rays to {}", rays.len(), output.display());
+
+    Ok(())
+}
+
+fn trace_rays(
+    opts: &Opts,
+    scene: &Scene,
+    rays: &Path,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let image_height = opts.image_height();
+    let (world, _, _) = scene.generate(
+        (opts.width as f32) / (image_height as f32),
+        &mut SmallRng::seed_from_u64(thread_rng().gen()),
+        &opts.assets(),
+    );
+
+    let rays = ray_batch::read_rays(rays)?;
+    // Only volumes need random numbers to find a hit, and those should be the same every time.
+    let hits = trace_ray_batch(&world, &rays, &mut SmallRng::seed_from_u64(0));
+
+    ray_batch::write_hits(output, &rays, &hits)?;
+    println!("Wrote {} hits to {}", rays.len(), output.display());
+
+    Ok(())
+}
+
+fn debug_pixel(
+    opts: &Opts,
+    scene: &Scene,
+    (x, y): (u32, u32),
+    samples: u32,
+    seed: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let image_height = opts.image_height();
+    if x >= opts.width || y >= image_height {
+        return Err(format!(
+            "The pixel {},{} lies outside the {}×{} image",
+            x, y, opts.width, image_height
+        )
+        .into());
+    }
+
+    let (world, cams, background) = scene.generate(
+        (opts.width as f32) / (image_height as f32),
+        &mut SmallRng::seed_from_u64(seed),
+        &opts.assets(),
+    );
+    let lights = scene.lights();
+
+    let raytracer = Raytracer::new(
+        &world,
+        &cams[0],
+        &background,
+        opts.width,
+        image_height,
+        samples,
+    )
+    .with_lights(&lights)
+    .with_estimator(opts.estimator())
+    .with_max_depth(opts.max_depth)
+    .with_russian_roulette(!opts.no_russian_roulette)
+    .with_seed(seed);
+
+    // Rows count from the bottom.
+    let row = image_height - 1 - y;
+    for sample_index in 0..samples {
+        let (color, events) = raytracer.trace_pixel(row, x, sample_index);
+        debug_pixel::print_trace(sample_index, color, &events);
+    }
+
+    Ok(())
+}
+
+fn compare_hits(
+    first: &Path,
+    second: &Path,
+    tolerances: Tolerances,
+    worst: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first = ray_batch::read_hits(first)?;
+    let second = ray_batch::read_hits(second)?;
+
+    let comparison = ray_batch::compare_hits(&first, &second, tolerances);
+    comparison.print_report(worst);
+
+    if !comparison.is_match() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Picks up the render a checkpoint was written for.
+fn resume(checkpoint_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let checkpoint = Checkpoint::read(checkpoint_path)?;
+
+    let mut opts = Opts::try_parse_from(&checkpoint.args)?;
+    // The checkpoint may have been moved since.
+    opts.checkpoint = Some(checkpoint_path.to_owned());
+
+    match &opts.command {
+        Command::Render(scene) => render(&opts, scene, Some(checkpoint)),
+        _ => Err(format!(
+            "{} is not a checkpoint of a render",
+            checkpoint_path.display()
+        )
+        .into()),
+    }
+}
+
+fn render(
+    opts: &Opts,
+    scene: &Scene,
+    resumed: Option<Checkpoint>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let image_width = opts.width;
-    let aspect_ratio = opts.aspect_ratio;
-    let image_height = (image_width as f64 / aspect_ratio).round() as u32;
+    let image_height = opts.image_height();
     let samples_per_pixel = opts.samples_per_pixel;
 
-    let pixel_count = (image_width * image_height) as u64;
+    let estimator = opts.estimator();
+
+    if opts.video.is_some() && opts.fps == 0 {
+        return Err("The video needs a frame rate of at least 1".into());
+    }
+
+    let crop = opts
+        .crop
+        .map(|crop| crop.clamped(image_width, image_height))
+        .transpose()?;
+    let crop_rect = crop.map(|crop| crop.to_pixel_rect(image_height));
+    let (frame_width, frame_height) = match crop {
+        Some(crop) if opts.crop_only => (crop.width, crop.height),
+        _ => (image_width, image_height),
+    };
 
-    let (world, cams, background) = opts.scene.generate(
+    let tonemap = Tonemap::new(opts.tonemap, opts.exposure);
+    let output = opts.output_target(tonemap);
+
+    // The scene is generated from a seed of its own, so a resumed render gets the same scene.
+    let scene_seed = match &resumed {
+        Some(checkpoint) => checkpoint.scene_seed,
+        None => thread_rng().gen(),
+    };
+    let (mut world, cams, background) = scene.generate(
         (image_width as f32) / (image_height as f32),
-        &mut thread_rng(),
+        &mut SmallRng::seed_from_u64(scene_seed),
+        &opts.assets(),
     );
+    let scene_object_count = world.len();
+    let lights = scene.lights();
+
+    let scene_hash = checkpoint::scene_hash(&world, &cams, &background);
+    let mut checkpoint = match resumed {
+        Some(checkpoint) if checkpoint.scene_hash != scene_hash => {
+            return Err("The scene has changed since the checkpoint was written".into())
+        }
+        Some(checkpoint) => Some(checkpoint),
+        None => opts
+            .checkpoint
+            .as_ref()
+            .map(|_| Checkpoint::new(env::args().collect(), scene_seed, scene_hash)),
+    };
+    let first_frame = checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.frame);
+
+    // The frames a resumed render wrote before it was interrupted are its own to keep.
+    if !opts.overwrite {
+        let frame_outputs = (first_frame..cams.len())
+            .flat_map(|frame_no| opts.frame_outputs(&output.frame_path(frame_no, cams.len())));
+        if let Some(existing) = frame_outputs
+            .chain(opts.video.clone())
+            .find(|path| path.exists())
+        {
+            return Err(format!(
+                "{} already exists, pass --overwrite to replace it",
+                existing.display()
+            )
+            .into());
+        }
+    }
+
+    let chart = opts.calibration_chart.map(|lighting| {
+        let placement = ChartPlacement {
+            left: opts.chart_left,
+            bottom: opts.chart_bottom,
+            width: opts.chart_width,
+            distance: opts.chart_distance,
+        };
+        CalibrationChart::new(
+            lighting,
+            placement,
+            (image_width as f32) / (image_height as f32),
+        )
+    });
 
     let overall_progress = ProgressBar::new(cams.len() as u64)
         .with_style(ProgressStyle::default_bar().template(
             "[{elapsed_precise} / {eta_precise}] {wide_bar} {pos:>7}/{len:7} ({per_sec}",
         ));
+    overall_progress.set_position(first_frame as u64);
 
-    for (frame_no, cam) in cams.iter().progress_with(overall_progress).enumerate() {
-        let raytracer = Raytracer::new(
-            &world,
-            &cam,
-            background,
-            image_width,
-            image_height,
-            samples_per_pixel,
-        );
-
-        let frame_progress =
-            ProgressBar::new(pixel_count).with_style(ProgressStyle::default_bar().template(
-                "[{elapsed_precise} / {eta_precise}] {wide_bar} {pos:>7}/{len:7} ({per_sec})",
-            ));
-        frame_progress.set_draw_delta(pixel_count / 100);
+    for (frame_no, cam) in cams
+        .iter()
+        .enumerate()
+        .skip(first_frame)
+        .progress_with(overall_progress)
+    {
+        // The chart has to face the current camera, so it is placed anew for every frame.
+        if let Some(chart) = &chart {
+            world.truncate(scene_object_count);
+            world.extend(chart.hittables(cam));
+        }
+
+        let (mut all_pixels, mut samples_done) = match checkpoint.as_mut() {
+            Some(checkpoint) if checkpoint.frame == frame_no => (
+                std::mem::take(&mut checkpoint.pixels),
+                checkpoint.samples_done,
+            ),
+            _ => (Vec::new(), 0),
+        };
+
+        // Without checkpoints, the whole frame is rendered in a single pass.
+        let samples_per_pass = match checkpoint {
+            Some(_) => opts.checkpoint_samples.max(1),
+            None => samples_per_pixel,
+        };
 
-        let all_pixels: Vec<_> = raytracer.render().progress_with(frame_progress).collect();
+        let raytracer = |samples| {
+            let raytracer =
+                Raytracer::new(&world, cam, &background, image_width, image_height, samples)
+                    .with_lights(&lights)
+                    .with_estimator(estimator)
+                    .with_max_depth(opts.max_depth)
+                    .with_russian_roulette(!opts.no_russian_roulette)
+                    .with_sampler(opts.sampler)
+                    .with_max_luminance(opts.max_luminance)
+                    .with_aux(!opts.aov.is_empty() || opts.denoise != Denoiser::Off);
+            match crop_rect {
+                Some(rect) => raytracer.with_crop(rect),
+                None => raytracer,
+            }
+        };
 
-        let mut image = RgbImage::new(image_width as u32, image_height as u32);
+        let pass_count =
+            (samples_per_pixel - samples_done.min(samples_per_pixel)).div_ceil(samples_per_pass);
+        let frame_progress = ProgressBar::new(
+            (raytracer(samples_per_pass).tile_count(opts.tile_size) * pass_count) as u64,
+        )
+        .with_style(ProgressStyle::default_bar().template(
+            "[{elapsed_precise} / {eta_precise}] {wide_bar} {pos:>7}/{len:7} ({per_sec})",
+        ));
 
-        image
-            .pixels_mut()
-            .zip(all_pixels.iter())
-            .for_each(|(img_pixel, render_pixel)| {
-                {
-                    let color = render_pixel.color;
-                    let r = color.x();
-                    let g = color.y();
-                    let b = color.z();
+        let render_stats = RenderStats::start();
+        while samples_done < samples_per_pixel {
+            let samples = samples_per_pass.min(samples_per_pixel - samples_done);
+            // Every pass needs samples of its own, or adding them up would gain nothing.
+            let raytracer = raytracer(samples).with_seed(mix_seed(
+                mix_seed(scene_seed, frame_no as u64),
+                samples_done as u64,
+            ));
 
-                    // Divide the color by the number of samples and gamma-correct for gamma=2.0.
-                    let scale = 1.0 / samples_per_pixel as f32;
-                    let r = (scale * r).sqrt();
-                    let g = (scale * g).sqrt();
-                    let b = (scale * b).sqrt();
+            let tiles: Vec<_> = match opts.adaptive {
+                true => {
+                    let max_samples = samples_per_pixel.max(2);
+                    let min_samples = opts.min_samples.clamp(2, max_samples);
+                    AdaptiveRaytracer::new(
+                        raytracer,
+                        min_samples,
+                        max_samples,
+                        opts.variance_threshold,
+                    )
+                    .render_tiles(opts.tile_size)
+                    .progress_with(frame_progress.clone())
+                    .collect()
+                }
+                false => raytracer
+                    .render_tiles(opts.tile_size)
+                    .progress_with(frame_progress.clone())
+                    .collect(),
+            };
+            samples_done += samples;
 
-                    let ir = (255.999 * r.clamp(0.0, 0.999)) as u8;
-                    let ig = (255.999 * g.clamp(0.0, 0.999)) as u8;
-                    let ib = (255.999 * b.clamp(0.0, 0.999)) as u8;
+            // Bring the pixels back into the order the image is written in: top row first, left
+            // to right.
+            let mut pass_pixels: Vec<_> = tiles.into_iter().flat_map(|tile| tile.pixels).collect();
+            pass_pixels.sort_unstable_by_key(|pixel| (Reverse(pixel.row), pixel.column));
 
-                    *img_pixel = Rgb([ir, ig, ib]);
+            match all_pixels.is_empty() {
+                true => all_pixels = pass_pixels,
+                false => {
+                    for (pixel, pass_pixel) in all_pixels.iter_mut().zip(pass_pixels) {
+                        pixel.color += pass_pixel.color;
+                        pixel.sample_count += pass_pixel.sample_count;
+                    }
                 }
-            });
+            }
+
+            if let (Some(checkpoint), Some(path)) = (checkpoint.as_mut(), &opts.checkpoint) {
+                checkpoint.frame = frame_no;
+                checkpoint.samples_done = samples_done;
+                checkpoint.pixels = all_pixels.clone();
+                checkpoint.write(path)?;
+            }
+        }
+
+        let all_pixels = match crop_rect {
+            Some(rect) if opts.crop_only => crop::cropped_frame(all_pixels, rect),
+            Some(rect) => crop::full_frame(all_pixels, rect, image_width, image_height),
+            None => all_pixels,
+        };
+
+        // Checkpoints keep the noisy pixels, so further samples can still be added to them.
+        let all_pixels = match opts.denoise {
+            Denoiser::Off => all_pixels,
+            denoiser => denoise::denoise(&all_pixels, frame_width, frame_height, denoiser),
+        };
 
-        image
-            .save(&format!("render/image_{:04}.png", frame_no))
-            .unwrap();
+        if let Some(chart) = &chart {
+            chart.print_report(&all_pixels, frame_width, frame_height, &tonemap);
+        }
+
+        let frame_stats = render_stats.snapshot();
+        if opts.stats {
+            stats::print_summary(&frame_stats);
+        }
+
+        let path = output.frame_path(frame_no, cams.len());
+        output
+            .save(&all_pixels, frame_width, frame_height, &path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        for &aov in &opts.aov {
+            let aov_path = output::aov_path(&path, aov);
+            output::save_aov(&all_pixels, frame_width, frame_height, aov, &aov_path)
+                .map_err(|e| format!("Failed to write {}: {}", aov_path.display(), e))?;
+        }
+
+        if opts.stats_json {
+            let stats_path = path.with_extension("json");
+            stats::write_json(&stats_path, &frame_stats)
+                .map_err(|e| format!("Failed to write {}: {}", stats_path.display(), e))?;
+        }
+
+        if let (Some(checkpoint), Some(path)) = (checkpoint.as_mut(), &opts.checkpoint) {
+            checkpoint.frame = frame_no + 1;
+            checkpoint.samples_done = 0;
+            checkpoint.pixels.clear();
+            checkpoint.write(path)?;
+        }
     }
+
+    // An interrupted render never gets here, which leaves its frames as they are.
+    if let Some(video) = &opts.video {
+        let frames: Vec<_> = (0..cams.len())
+            .map(|frame_no| output.frame_path(frame_no, cams.len()))
+            .collect();
+        video::assemble(&frames, video, opts.fps)?;
+        println!("Wrote {}", video.display());
+    }
+
+    if let Some(path) = &opts.checkpoint {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,460 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use clap::ArgEnum;
+use exr::prelude::{
+    Encoding, Image, Layer, LayerAttributes, SpecificChannels, Vec2, WritableImage,
+};
+use image::{
+    codecs::{hdr::HdrEncoder, jpeg::JpegEncoder},
+    ImageBuffer, ImageFormat, Rgb, RgbImage,
+};
+use raytracer_weekend_lib::{tonemap::Tonemap, vec3::Color, Pixel};
+
+pub const DEFAULT_OUTPUT: &str = "render/image_{frame}.png";
+const DEFAULT_FILE_NAME: &str = "image_{frame}";
+/// How many digits `{frame}` is padded to, unless it says otherwise.
+const DEFAULT_FRAME_DIGITS: usize = 4;
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    /// 16 bits per channel PNG, gamma-corrected like the 8-bit formats.
+    Png16,
+    Jpeg,
+    Bmp,
+    Ppm,
+    /// Radiance RGBE with linear, unclamped values. Tone mapping and exposure do not apply.
+    Hdr,
+    /// OpenEXR with linear, unclamped 32-bit floats in a layer called "beauty". Tone mapping and
+    /// exposure do not apply.
+    Exr,
+}
+
+/// Auxiliary output, see [`raytracer_weekend_lib::PixelAux`]. Always written as 8-bit PNG, with
+/// pixels showing the background left black.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    /// Normals, with each component mapped from `-1.0..=1.0` to `0.0..=1.0`.
+    Normal,
+    /// Distance from the camera, from black right at the camera to white at the farthest hit.
+    Depth,
+    /// The base color of the surface, unlit.
+    Albedo,
+}
+
+impl Aov {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Albedo => "albedo",
+        }
+    }
+}
+
+impl OutputFormat {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "bmp" => Some(Self::Bmp),
+            "ppm" => Some(Self::Ppm),
+            "hdr" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Png | Self::Png16 => "png",
+            Self::Jpeg => "jpg",
+            Self::Bmp => "bmp",
+            Self::Ppm => "ppm",
+            Self::Hdr => "hdr",
+            Self::Exr => "exr",
+        }
+    }
+}
+
+/// Rejects output paths whose extension we cannot write or whose frame placeholder is malformed,
+/// so this surfaces as a usage error rather than a panic once the first frame is done.
+pub fn validate_output_path(output: &str) -> Result<(), String> {
+    find_frame_placeholder(output)?;
+
+    match Path::new(output).extension() {
+        None => Ok(()),
+        Some(extension) => extension
+            .to_str()
+            .and_then(OutputFormat::from_extension)
+            .map(|_| ())
+            .ok_or_else(|| {
+                format!(
+                    "unsupported image extension {:?}, expected one of png, jpg, jpeg, bmp, ppm, hdr or exr",
+                    extension
+                )
+            }),
+    }
+}
+
+/// Finds the frame number placeholder in `pattern`: `{frame}`, or `{frame:0N}` for a frame number
+/// padded to N digits instead of the default four. Returns where it is and how many digits it
+/// wants.
+fn find_frame_placeholder(pattern: &str) -> Result<Option<(Range<usize>, usize)>, String> {
+    let start = match pattern.find("{frame") {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let end = match pattern[start..].find('}') {
+        Some(length) => start + length + 1,
+        None => return Err(format!("unclosed frame placeholder in {:?}", pattern)),
+    };
+
+    let digits = match &pattern[start..end] {
+        "{frame}" => DEFAULT_FRAME_DIGITS,
+        placeholder => placeholder
+            .strip_prefix("{frame:0")
+            .and_then(|digits| digits.strip_suffix('}'))
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| {
+                format!(
+                    "invalid frame placeholder {:?}, expected {{frame}} or {{frame:0N}}",
+                    placeholder
+                )
+            })?,
+    };
+
+    Ok(Some((start..end, digits)))
+}
+
+/// Works out where and in which format each frame is written.
+///
+/// The output may either be a directory, in which case frames are called `image_0000.png` and so
+/// on, or a file name pattern where `{frame}` is replaced by the zero-padded frame number. See
+/// [`find_frame_placeholder`] for how to pad it differently.
+pub struct OutputTarget {
+    pattern: PathBuf,
+    format: OutputFormat,
+    jpeg_quality: u8,
+    tonemap: Tonemap,
+}
+
+impl OutputTarget {
+    pub fn new(
+        output: &str,
+        format: Option<OutputFormat>,
+        jpeg_quality: u8,
+        tonemap: Tonemap,
+    ) -> Self {
+        let output = Path::new(output);
+
+        let extension_format = output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(OutputFormat::from_extension);
+        let format = format.or(extension_format).unwrap_or(OutputFormat::Png);
+
+        let pattern = match extension_format {
+            Some(_) => output.to_owned(),
+            None => Self::default_pattern(output, format),
+        };
+
+        Self {
+            pattern,
+            format,
+            jpeg_quality,
+            tonemap,
+        }
+    }
+
+    /// Frames named like the default output, in `directory`. Unlike with [`OutputTarget::new`],
+    /// directories whose names look like they have an extension work as well.
+    pub fn in_directory(
+        directory: &Path,
+        format: Option<OutputFormat>,
+        jpeg_quality: u8,
+        tonemap: Tonemap,
+    ) -> Self {
+        let format = format.unwrap_or(OutputFormat::Png);
+
+        Self {
+            pattern: Self::default_pattern(directory, format),
+            format,
+            jpeg_quality,
+            tonemap,
+        }
+    }
+
+    fn default_pattern(directory: &Path, format: OutputFormat) -> PathBuf {
+        directory.join(format!("{}.{}", DEFAULT_FILE_NAME, format.extension()))
+    }
+
+    pub fn frame_path(&self, frame_no: usize, frame_count: usize) -> PathBuf {
+        let pattern = self.pattern.to_string_lossy();
+
+        if let Ok(Some((placeholder, digits))) = find_frame_placeholder(&pattern) {
+            let frame = format!("{:0digits$}", frame_no, digits = digits);
+            let mut path = pattern.into_owned();
+            path.replace_range(placeholder, &frame);
+            return PathBuf::from(path);
+        }
+
+        let frame = format!("{:0digits$}", frame_no, digits = DEFAULT_FRAME_DIGITS);
+
+        if frame_count == 1 {
+            return self.pattern.clone();
+        }
+
+        // Without a placeholder, all frames would overwrite each other.
+        let stem = self
+            .pattern
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let file_name = match self.pattern.extension() {
+            Some(extension) => format!("{}_{}.{}", stem, frame, extension.to_string_lossy()),
+            None => format!("{}_{}", stem, frame),
+        };
+        self.pattern.with_file_name(file_name)
+    }
+
+    /// Writes the rendered pixels of one frame, which must be in the order `Raytracer::render`
+    /// yields them.
+    pub fn save(
+        &self,
+        pixels: &[Pixel],
+        width: u32,
+        height: u32,
+        path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let ldr = || to_rgb8(pixels, width, height, &self.tonemap);
+
+        match self.format {
+            OutputFormat::Png => ldr().save_with_format(path, ImageFormat::Png)?,
+            OutputFormat::Png16 => to_rgb16(pixels, width, height, &self.tonemap)
+                .save_with_format(path, ImageFormat::Png)?,
+            OutputFormat::Bmp => ldr().save_with_format(path, ImageFormat::Bmp)?,
+            OutputFormat::Jpeg => {
+                let file = BufWriter::new(File::create(path)?);
+                JpegEncoder::new_with_quality(file, self.jpeg_quality).encode_image(&ldr())?;
+            }
+            OutputFormat::Ppm => write_ppm(&ldr(), path)?,
+            OutputFormat::Hdr => {
+                let data: Vec<_> = to_rgb32f(pixels, width, height)
+                    .into_iter()
+                    .map(Rgb)
+                    .collect();
+                let file = BufWriter::new(File::create(path)?);
+                HdrEncoder::new(file).encode(&data, width as usize, height as usize)?;
+            }
+            OutputFormat::Exr => write_exr(&to_rgb32f(pixels, width, height), width, path)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Where an auxiliary image of the frame written to `frame_path` goes: next to it, with the name of
+/// the AOV appended to the file name.
+pub fn aov_path(frame_path: &Path, aov: Aov) -> PathBuf {
+    let stem = frame_path.file_stem().unwrap_or_default().to_string_lossy();
+    frame_path.with_file_name(format!("{}_{}.png", stem, aov.name()))
+}
+
+/// Writes one auxiliary image of a frame. The pixels must have been rendered with
+/// `Raytracer::with_aux`, or the image comes out black.
+pub fn save_aov(
+    pixels: &[Pixel],
+    width: u32,
+    height: u32,
+    aov: Aov,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let farthest = pixels
+        .iter()
+        .filter_map(|pixel| pixel.aux)
+        .map(|aux| aux.depth)
+        .fold(0.0, f32::max);
+
+    let mut image = RgbImage::new(width, height);
+    for (img_pixel, render_pixel) in image.pixels_mut().zip(pixels) {
+        let color = match render_pixel.aux {
+            None => Color::new(0.0, 0.0, 0.0),
+            Some(aux) => match aov {
+                Aov::Normal => 0.5 * (aux.normal + Color::new(1.0, 1.0, 1.0)),
+                Aov::Depth if farthest > 0.0 => {
+                    let depth = aux.depth / farthest;
+                    Color::new(depth, depth, depth)
+                }
+                Aov::Depth => Color::new(0.0, 0.0, 0.0),
+                Aov::Albedo => aux.albedo,
+            },
+        };
+
+        let to_u8 = |channel: f32| (255.999 * channel.clamp(0.0, 1.0)) as u8;
+        *img_pixel = Rgb([to_u8(color.x()), to_u8(color.y()), to_u8(color.z())]);
+    }
+
+    Ok(image.save_with_format(path, ImageFormat::Png)?)
+}
+
+/// A pixel as it is stored in a file of some [`OutputFormat`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodedPixel {
+    Rgb8([u8; 3]),
+    Rgb16([u16; 3]),
+    Rgb32F([f32; 3]),
+}
+
+/// Turns the sum of `samples` samples into what a file of `format` stores. The 8 and 16-bit
+/// formats get tone mapped, gamma-corrected and clamped values, HDR and EXR linear, unclamped
+/// ones.
+pub fn encode_pixel(
+    color: Color,
+    samples: u32,
+    format: OutputFormat,
+    tonemap: &Tonemap,
+) -> EncodedPixel {
+    match format {
+        OutputFormat::Png | OutputFormat::Jpeg | OutputFormat::Bmp | OutputFormat::Ppm => {
+            EncodedPixel::Rgb8(tonemap.to_rgb8(color, samples))
+        }
+        OutputFormat::Png16 => {
+            let color = tonemap.display_color(color, samples);
+            let to_u16 = |channel: f32| (65535.999 * channel) as u16;
+            EncodedPixel::Rgb16([to_u16(color.x()), to_u16(color.y()), to_u16(color.z())])
+        }
+        OutputFormat::Hdr | OutputFormat::Exr => {
+            let color = color / samples.max(1) as f32;
+            EncodedPixel::Rgb32F([color.x(), color.y(), color.z()])
+        }
+    }
+}
+
+fn to_rgb8(pixels: &[Pixel], width: u32, height: u32, tonemap: &Tonemap) -> RgbImage {
+    let mut image = RgbImage::new(width, height);
+    for (img_pixel, render_pixel) in image.pixels_mut().zip(pixels) {
+        let encoded = encode_pixel(
+            render_pixel.color,
+            render_pixel.sample_count,
+            OutputFormat::Png,
+            tonemap,
+        );
+        *img_pixel = match encoded {
+            EncodedPixel::Rgb8(rgb) => Rgb(rgb),
+            other => unreachable!("{:?} for an 8-bit format", other),
+        };
+    }
+    image
+}
+
+fn to_rgb16(
+    pixels: &[Pixel],
+    width: u32,
+    height: u32,
+    tonemap: &Tonemap,
+) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    let mut image = ImageBuffer::new(width, height);
+    for (img_pixel, render_pixel) in image.pixels_mut().zip(pixels) {
+        let encoded = encode_pixel(
+            render_pixel.color,
+            render_pixel.sample_count,
+            OutputFormat::Png16,
+            tonemap,
+        );
+        *img_pixel = match encoded {
+            EncodedPixel::Rgb16(rgb) => Rgb(rgb),
+            other => unreachable!("{:?} for a 16-bit format", other),
+        };
+    }
+    image
+}
+
+/// The linear colors of the pixels, for the HDR formats.
+fn to_rgb32f(pixels: &[Pixel], width: u32, height: u32) -> Vec<[f32; 3]> {
+    assert_eq!(pixels.len(), (width * height) as usize);
+
+    pixels
+        .iter()
+        .map(|pixel| {
+            match encode_pixel(
+                pixel.color,
+                pixel.sample_count,
+                OutputFormat::Exr,
+                &Tonemap::default(),
+            ) {
+                EncodedPixel::Rgb32F(rgb) => rgb,
+                other => unreachable!("{:?} for a floating point format", other),
+            }
+        })
+        .collect()
+}
+
+/// Writes a single RGB layer called "beauty", with 32-bit float channels, so compositing tools
+/// pick the image up as the main pass.
+fn write_exr(rgb: &[[f32; 3]], width: u32, path: &Path) -> Result<(), Box<dyn Error>> {
+    let height = rgb.len() / width as usize;
+    let layer = Layer::new(
+        (width as usize, height),
+        LayerAttributes::named("beauty"),
+        Encoding::FAST_LOSSLESS,
+        SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+            let [r, g, b] = rgb[y * width as usize + x];
+            (r, g, b)
+        }),
+    );
+
+    Image::from_layer(layer).write().to_file(path)?;
+    Ok(())
+}
+
+/// Writes a plain-text (P3) PPM like the one the book produces, which is handy for diffing.
+fn write_ppm(image: &RgbImage, path: &Path) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(file, "P3\n{} {}\n255", image.width(), image.height())?;
+    for Rgb([r, g, b]) in image.pixels() {
+        writeln!(file, "{} {} {}", r, g, b)?;
+    }
+
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use exr::prelude::{read_first_rgba_layer_from_file, Text};
+
+    use super::*;
+
+    #[test]
+    fn exr_files_keep_the_exact_floats() {
+        let path = env::temp_dir().join(format!("output_{}_single_pixel.exr", std::process::id()));
+        let rgb = [0.1, 12.5, 1.0 / 3.0];
+
+        write_exr(&[rgb], 1, &path).unwrap();
+        let image = read_first_rgba_layer_from_file(
+            &path,
+            |_, _| [0.0; 3],
+            |pixel: &mut [f32; 3], _, (r, g, b, _): (f32, f32, f32, f32)| *pixel = [r, g, b],
+        )
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let layer = image.layer_data;
+        assert_eq!(layer.size, Vec2(1, 1));
+        assert_eq!(layer.attributes.layer_name, Some(Text::from("beauty")));
+        assert_eq!(layer.channel_data.pixels, rgb);
+    }
+}
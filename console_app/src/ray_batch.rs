@@ -0,0 +1,442 @@
+//! CSV files of camera rays and their first hits, for checking the intersection code against other
+//! renderers.
+//!
+//! Floats are written in Rust's shortest round-trip notation, so reading a file back yields
+//! exactly the values that were written. Misses leave all hit columns empty.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use raytracer_weekend_lib::{
+    ray_batch::{FirstHit, PrimaryRay},
+    vec3::Vec3,
+};
+
+const RAY_HEADER: &str =
+    "row,column,sample,origin_x,origin_y,origin_z,direction_x,direction_y,direction_z,time";
+const HIT_HEADER: &str = "row,column,sample,t,p_x,p_y,p_z,normal_x,normal_y,normal_z,front_face";
+
+/// Identifies a ray across files: pixel row, pixel column and sample index.
+type RayKey = (u32, u32, u32);
+
+pub fn write_rays(path: &Path, rays: &[PrimaryRay]) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "{}", RAY_HEADER)?;
+    for ray in rays {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            ray.row,
+            ray.column,
+            ray.sample,
+            ray.origin.x(),
+            ray.origin.y(),
+            ray.origin.z(),
+            ray.direction.x(),
+            ray.direction.y(),
+            ray.direction.z(),
+            ray.time
+        )?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+pub fn read_rays(path: &Path) -> Result<Vec<PrimaryRay>, Box<dyn Error>> {
+    read_records(path, RAY_HEADER, |fields| {
+        Ok(PrimaryRay {
+            row: fields[0].parse()?,
+            column: fields[1].parse()?,
+            sample: fields[2].parse()?,
+            origin: parse_vec3(&fields[3..6])?,
+            direction: parse_vec3(&fields[6..9])?,
+            time: fields[9].parse()?,
+        })
+    })
+}
+
+/// The first hit of one ray, as stored in a hit file.
+#[derive(Debug, Clone)]
+pub struct HitRow {
+    pub key: RayKey,
+    pub hit: Option<FirstHit>,
+}
+
+pub fn write_hits(
+    path: &Path,
+    rays: &[PrimaryRay],
+    hits: &[Option<FirstHit>],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "{}", HIT_HEADER)?;
+    for (ray, hit) in rays.iter().zip(hits) {
+        write!(writer, "{},{},{},", ray.row, ray.column, ray.sample)?;
+        match hit {
+            Some(hit) => writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                hit.t,
+                hit.p.x(),
+                hit.p.y(),
+                hit.p.z(),
+                hit.normal.x(),
+                hit.normal.y(),
+                hit.normal.z(),
+                hit.is_front_face
+            )?,
+            None => writeln!(writer, ",,,,,,,")?,
+        }
+    }
+
+    Ok(writer.flush()?)
+}
+
+pub fn read_hits(path: &Path) -> Result<Vec<HitRow>, Box<dyn Error>> {
+    read_records(path, HIT_HEADER, |fields| {
+        let key = (fields[0].parse()?, fields[1].parse()?, fields[2].parse()?);
+
+        let hit = match fields[3].is_empty() {
+            true => None,
+            false => Some(FirstHit {
+                t: fields[3].parse()?,
+                p: parse_vec3(&fields[4..7])?,
+                normal: parse_vec3(&fields[7..10])?,
+                is_front_face: fields[10].parse()?,
+            }),
+        };
+
+        Ok(HitRow { key, hit })
+    })
+}
+
+fn read_records<T>(
+    path: &Path,
+    header: &str,
+    parse: impl Fn(&[&str]) -> Result<T, Box<dyn Error>>,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let column_count = header.split(',').count();
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let first_line = lines.next().transpose()?;
+    if first_line.as_deref().map(str::trim_end) != Some(header) {
+        return Err(format!("{} does not start with {:?}", path.display(), header).into());
+    }
+
+    lines
+        .enumerate()
+        .map(|(index, line)| {
+            let line = line?;
+            let fields: Vec<&str> = line.trim_end().split(',').collect();
+            if fields.len() != column_count {
+                return Err(format!(
+                    "{} line {}: expected {} columns, found {}",
+                    path.display(),
+                    index + 2,
+                    column_count,
+                    fields.len()
+                )
+                .into());
+            }
+
+            parse(&fields)
+                .map_err(|e| format!("{} line {}: {}", path.display(), index + 2, e).into())
+        })
+        .collect()
+}
+
+fn parse_vec3(fields: &[&str]) -> Result<Vec3, Box<dyn Error>> {
+    Ok(Vec3::new(
+        fields[0].parse()?,
+        fields[1].parse()?,
+        fields[2].parse()?,
+    ))
+}
+
+/// How far two hits of the same ray may be apart before they count as a mismatch.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerances {
+    pub t: f32,
+    pub normal_angle_degrees: f32,
+}
+
+/// Two hits of the same ray that disagree beyond the [`Tolerances`].
+#[derive(Debug, Clone)]
+pub struct HitDifference {
+    pub key: RayKey,
+    pub t: f32,
+    pub normal_angle_degrees: f32,
+}
+
+/// The result of [`compare_hits`].
+#[derive(Debug, Default)]
+pub struct HitComparison {
+    /// Rays present in both files.
+    pub compared: usize,
+    /// Rays present in only one of the files.
+    pub unmatched: Vec<RayKey>,
+    /// Rays hitting something in one file and nothing in the other.
+    pub hit_miss: Vec<RayKey>,
+    /// Rays hitting in both files, but at different distances or with different normals.
+    pub differences: Vec<HitDifference>,
+    pub max_t_difference: f32,
+    pub mean_t_difference: f32,
+    pub max_normal_angle_degrees: f32,
+}
+
+impl HitComparison {
+    pub fn is_match(&self) -> bool {
+        self.unmatched.is_empty() && self.hit_miss.is_empty() && self.differences.is_empty()
+    }
+
+    pub fn print_report(&self, worst: usize) {
+        println!("Compared rays:         {}", self.compared);
+        println!("Rays in only one file: {}", self.unmatched.len());
+        println!("Hit/miss mismatches:   {}", self.hit_miss.len());
+        println!("Differing hits:        {}", self.differences.len());
+        println!("Max t difference:      {}", self.max_t_difference);
+        println!("Mean t difference:     {}", self.mean_t_difference);
+        println!(
+            "Max normal angle:      {} degrees",
+            self.max_normal_angle_degrees
+        );
+
+        let print_keys = |title: &str, keys: &[RayKey]| {
+            if keys.is_empty() {
+                return;
+            }
+
+            println!("{}", title);
+            for (row, column, sample) in keys.iter().take(worst) {
+                println!("  pixel ({}, {}), sample {}", column, row, sample);
+            }
+        };
+        print_keys("Rays in only one file:", &self.unmatched);
+        print_keys("Hit/miss mismatches:", &self.hit_miss);
+
+        if !self.differences.is_empty() {
+            let mut worst_differences: Vec<_> = self.differences.iter().collect();
+            worst_differences.sort_by(|a, b| {
+                let a = (a.t, a.normal_angle_degrees);
+                let b = (b.t, b.normal_angle_degrees);
+                b.partial_cmp(&a).unwrap()
+            });
+
+            println!("Worst offenders:");
+            for difference in worst_differences.into_iter().take(worst) {
+                let (row, column, sample) = difference.key;
+                println!(
+                    "  pixel ({}, {}), sample {}: t differs by {}, normals by {} degrees",
+                    column, row, sample, difference.t, difference.normal_angle_degrees
+                );
+            }
+        }
+    }
+}
+
+/// Matches up the rays of two hit files by pixel and sample and reports where they disagree.
+pub fn compare_hits(first: &[HitRow], second: &[HitRow], tolerances: Tolerances) -> HitComparison {
+    let mut second_by_key: HashMap<RayKey, &HitRow> =
+        second.iter().map(|row| (row.key, row)).collect();
+
+    let mut comparison = HitComparison::default();
+    let mut t_difference_sum = 0.0;
+    let mut hit_count = 0;
+
+    for row in first {
+        let other = match second_by_key.remove(&row.key) {
+            Some(other) => other,
+            None => {
+                comparison.unmatched.push(row.key);
+                continue;
+            }
+        };
+        comparison.compared += 1;
+
+        let (hit, other_hit) = match (&row.hit, &other.hit) {
+            (None, None) => continue,
+            (Some(hit), Some(other_hit)) => (hit, other_hit),
+            _ => {
+                comparison.hit_miss.push(row.key);
+                continue;
+            }
+        };
+
+        let t = (hit.t - other_hit.t).abs();
+        let cosine = hit
+            .normal
+            .unit_vector()
+            .dot(&other_hit.normal.unit_vector());
+        let normal_angle_degrees = cosine.clamp(-1.0, 1.0).acos().to_degrees();
+
+        hit_count += 1;
+        t_difference_sum += t;
+        comparison.max_t_difference = comparison.max_t_difference.max(t);
+        comparison.max_normal_angle_degrees = comparison
+            .max_normal_angle_degrees
+            .max(normal_angle_degrees);
+
+        if t > tolerances.t || normal_angle_degrees > tolerances.normal_angle_degrees {
+            comparison.differences.push(HitDifference {
+                key: row.key,
+                t,
+                normal_angle_degrees,
+            });
+        }
+    }
+
+    comparison.unmatched.extend(second_by_key.into_keys());
+    if hit_count > 0 {
+        comparison.mean_t_difference = t_difference_sum / hit_count as f32;
+    }
+
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::*;
+
+    const TOLERANCES: Tolerances = Tolerances {
+        t: 1e-4,
+        normal_angle_degrees: 0.1,
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("ray_batch_{}_{}.csv", std::process::id(), name))
+    }
+
+    /// Rays whose components need all the digits of an f32 to be written exactly.
+    fn rays() -> Vec<PrimaryRay> {
+        (0..6)
+            .map(|i| {
+                let x = i as f32;
+                PrimaryRay {
+                    row: i / 3,
+                    column: i % 3,
+                    sample: i * 7,
+                    origin: Vec3::new(0.1 * x, -1.0 / 3.0, f32::MIN_POSITIVE),
+                    direction: Vec3::new(1e-7 * x, 1e30, core::f32::consts::PI / (x + 1.0)),
+                    time: x / 7.0,
+                }
+            })
+            .collect()
+    }
+
+    fn hits(rays: &[PrimaryRay]) -> Vec<Option<FirstHit>> {
+        rays.iter()
+            .map(|ray| {
+                (ray.column != 1).then(|| FirstHit {
+                    t: 1.0 / (1.0 + ray.sample as f32),
+                    p: ray.origin + ray.direction * 0.3,
+                    normal: Vec3::new(0.0, 0.6, -0.8),
+                    is_front_face: ray.row == 0,
+                })
+            })
+            .collect()
+    }
+
+    fn bits(v: Vec3) -> [u32; 3] {
+        [v.x().to_bits(), v.y().to_bits(), v.z().to_bits()]
+    }
+
+    /// Writes the hits to a file named after the test and reads them back.
+    fn hit_rows(test: &str, rays: &[PrimaryRay], hits: &[Option<FirstHit>]) -> Vec<HitRow> {
+        let path = temp_path(test);
+        write_hits(&path, rays, hits).unwrap();
+        let rows = read_hits(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        rows
+    }
+
+    #[test]
+    fn rays_round_trip_exactly() {
+        let path = temp_path("rays");
+        let rays = rays();
+        write_rays(&path, &rays).unwrap();
+        let read = read_rays(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), rays.len());
+        for (read, ray) in read.iter().zip(&rays) {
+            assert_eq!(
+                (read.row, read.column, read.sample),
+                (ray.row, ray.column, ray.sample)
+            );
+            assert_eq!(bits(read.origin), bits(ray.origin));
+            assert_eq!(bits(read.direction), bits(ray.direction));
+            assert_eq!(read.time.to_bits(), ray.time.to_bits());
+        }
+    }
+
+    #[test]
+    fn hits_round_trip_exactly() {
+        let rays = rays();
+        let hits = hits(&rays);
+        let rows = hit_rows("hits_round_trip_exactly", &rays, &hits);
+
+        assert_eq!(rows.len(), hits.len());
+        for ((row, hit), ray) in rows.iter().zip(&hits).zip(&rays) {
+            assert_eq!(row.key, (ray.row, ray.column, ray.sample));
+            match (&row.hit, hit) {
+                (None, None) => {}
+                (Some(read), Some(hit)) => {
+                    assert_eq!(read.t.to_bits(), hit.t.to_bits());
+                    assert_eq!(bits(read.p), bits(hit.p));
+                    assert_eq!(bits(read.normal), bits(hit.normal));
+                    assert_eq!(read.is_front_face, hit.is_front_face);
+                }
+                _ => panic!("{:?} was read back as {:?}", hit, row.hit),
+            }
+        }
+    }
+
+    #[test]
+    fn a_hit_file_matches_itself() {
+        let rays = rays();
+        let rows = hit_rows("a_hit_file_matches_itself", &rays, &hits(&rays));
+
+        let comparison = compare_hits(&rows, &rows, TOLERANCES);
+        assert!(comparison.is_match());
+        assert_eq!(comparison.compared, rays.len());
+        assert_eq!(comparison.max_t_difference, 0.0);
+        assert_eq!(comparison.max_normal_angle_degrees, 0.0);
+    }
+
+    #[test]
+    fn a_perturbed_hit_is_reported() {
+        let rays = rays();
+        let rows = hit_rows("a_perturbed_hit_is_reported", &rays, &hits(&rays));
+
+        let mut perturbed = rows.clone();
+        perturbed[3].hit.as_mut().unwrap().t += 0.01;
+
+        let comparison = compare_hits(&rows, &perturbed, TOLERANCES);
+        assert!(comparison.unmatched.is_empty());
+        assert!(comparison.hit_miss.is_empty());
+        assert_eq!(comparison.differences.len(), 1);
+        assert_eq!(comparison.differences[0].key, rows[3].key);
+    }
+
+    #[test]
+    fn a_hit_turned_miss_is_reported() {
+        let rays = rays();
+        let rows = hit_rows("a_hit_turned_miss_is_reported", &rays, &hits(&rays));
+
+        let mut perturbed = rows.clone();
+        perturbed[0].hit = None;
+
+        let comparison = compare_hits(&rows, &perturbed, TOLERANCES);
+        assert_eq!(comparison.hit_miss, [rows[0].key]);
+        assert!(comparison.differences.is_empty());
+    }
+}
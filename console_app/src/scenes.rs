@@ -1,23 +1,34 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use clap::Parser;
 use rand::prelude::*;
+#[cfg(feature = "gltf")]
+use raytracer_weekend_lib::hittable::gltf_import::load_gltf;
 use raytracer_weekend_lib::{
-    bvh::BvhNode,
-    camera::Camera,
+    background::Background,
+    bvh::{build_world, BvhNode},
+    camera::{Camera, CameraKeyframe, CameraPath, Interpolation},
     hittable::{
-        rectangular::{Cuboid, XYRectangle, XZRectangle, YZRectangle},
+        circular::{Disk, DiskLight},
+        infinite_plane::InfinitePlane,
+        named::Named,
+        rectangular::{Cuboid, RoundedCuboid, XYRectangle, XZRectangle, YZRectangle},
         spherical::{MovingSphere, Sphere},
-        transformations::{Transformable, Translation, YRotation},
-        triangular::{load_wavefront_obj, Triangle},
-        volumes::ConstantMedium,
+        toroidal::Torus,
+        transformations::{KeyframedTranslation, Transformable, Translation, YRotation},
+        triangular::{load_wavefront_obj, load_wavefront_obj_cached, Triangle},
+        visibility::{Visibility, VisibilityWrapper},
+        volumes::{ConstantMedium, Subsurface},
         Hittable,
     },
     image_texture::ImageTexture,
     light_source::DiffuseLight,
-    material::{Dielectric, Lambertian, Material, Metal},
+    material::{
+        Dielectric, Lambertian, Material, MattePainting, Metal, MixMaterial, RoughDielectric,
+    },
     perlin::Perlin,
-    texture::{Checker, Noise, SolidColor, UVDebug},
+    scene_generator::{self, Preset, SceneParameters},
+    texture::{Checker, Marble, Noise, SolidColor, Turbulence, UVDebug, Wood},
     vec3::{Color, Point3, Vec3},
 };
 
@@ -32,14 +43,43 @@ pub enum Scene {
     SmokeyCornellBox,
     Book2FinalScene,
     AnimatedBook2FinalScene,
+    /// A ball rolling around a still camera, with motion blur, over three seconds of frames.
+    KeyframedBall,
     SimpleTriangle,
     WavefrontCowObj,
+    MattePaintingCow,
     WavefrontSuspensionObj,
     TexturedMonument,
+    /// The Khronos glTF sample duck. Duck.glb isn't shipped with the other models; download it
+    /// from the glTF sample models into the models folder of the asset root.
+    #[cfg(feature = "gltf")]
+    GltfDuck,
+    RoundedCuboids,
+    Toruses,
+    PlanesAndDisks,
+    /// The turbulence, marble and wood textures on three spheres next to each other.
+    MaterialZoo,
+    /// Glass spheres of roughness 0, 0.2 and 0.5 in front of a checkered backdrop.
+    FrostedGlass,
+    /// A waxy subsurface scattering sphere next to a diffuse one, both lit from behind.
+    BacklitSubsurface,
+    /// A sphere that is half diffuse, half mirror, in a checker pattern.
+    MixedMaterials,
+    /// A procedurally generated scene, for benchmarking and stress testing.
+    #[clap(name = "test-scene")]
+    Generated {
+        #[clap(default_value = "bvh_depth", possible_values = Preset::NAMES)]
+        preset: Preset,
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        /// Overrides the number of objects of the preset.
+        #[clap(long)]
+        objects: Option<usize>,
+    },
 }
 
 impl Scene {
-    pub fn generate(&self, aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+    pub fn generate(&self, aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
         let generator = match self {
             Scene::JumpyBalls => jumpy_balls,
             Scene::TwoSpheres => two_spheres,
@@ -50,17 +90,83 @@ impl Scene {
             Scene::SmokeyCornellBox => smokey_cornell_box,
             Scene::Book2FinalScene => book2_final_scene,
             Scene::AnimatedBook2FinalScene => animated_book2_final,
+            Scene::KeyframedBall => keyframed_ball,
             Scene::SimpleTriangle => simple_triangle,
             Scene::WavefrontCowObj => wavefront_cow_obj,
+            Scene::MattePaintingCow => matte_painting_cow,
             Scene::WavefrontSuspensionObj => wavefront_suspension_obj,
             Scene::TexturedMonument => textured_monument,
+            #[cfg(feature = "gltf")]
+            Scene::GltfDuck => gltf_duck,
+            Scene::RoundedCuboids => rounded_cuboids,
+            Scene::Toruses => toruses,
+            Scene::PlanesAndDisks => planes_and_disks,
+            Scene::MaterialZoo => material_zoo,
+            Scene::FrostedGlass => frosted_glass,
+            Scene::BacklitSubsurface => backlit_subsurface,
+            Scene::MixedMaterials => mixed_materials,
+            Scene::Generated { .. } => {
+                return test_scene(&self.test_scene_parameters(), aspect_ratio, rng)
+            }
+        };
+
+        generator(aspect_ratio, rng, assets)
+    }
+
+    /// The emitters of the scene, for the renderer to aim scattered rays at. Scenes whose lights
+    /// can't be sampled, or that are mostly lit by the background, return none.
+    pub fn lights(&self) -> Vec<Box<dyn Hittable>> {
+        // Facing down and one-sided, like the lights in the scenes themselves.
+        let ceiling_light = |x0, x1, z0, z1| -> Vec<Box<dyn Hittable>> {
+            let material = Box::new(
+                DiffuseLight::new(SolidColor::new_rgb(1.0, 1.0, 1.0)).with_two_sided(false),
+            );
+            vec![Box::new(
+                XZRectangle::new(x0, x1, z0, z1, 554.0, material).flip_normals(),
+            )]
         };
 
-        generator(aspect_ratio, rng)
+        match self {
+            Scene::CornellBox => ceiling_light(213.0, 343.0, 227.0, 332.0),
+            Scene::SmokeyCornellBox => ceiling_light(113.0, 443.0, 127.0, 432.0),
+            Scene::Book2FinalScene | Scene::AnimatedBook2FinalScene => {
+                ceiling_light(123.0, 423.0, 147.0, 412.0)
+            }
+            Scene::RoundedCuboids => vec![Box::new(XZRectangle::new(
+                -2.0,
+                2.0,
+                -2.0,
+                2.0,
+                5.0,
+                Box::new(DiffuseLight::new(SolidColor::new_rgb(1.0, 1.0, 1.0))),
+            ))],
+            Scene::BacklitSubsurface => vec![Box::new(subsurface_backlight())],
+            Scene::Generated { .. } => {
+                scene_generator::generate(&self.test_scene_parameters(), 1.0).lights
+            }
+            _ => vec![],
+        }
+    }
+
+    fn test_scene_parameters(&self) -> SceneParameters {
+        match self {
+            Scene::Generated {
+                preset,
+                seed,
+                objects,
+            } => {
+                let mut parameters = preset.parameters(*seed);
+                if let Some(objects) = objects {
+                    parameters.object_count = *objects;
+                }
+                parameters
+            }
+            _ => unreachable!("only test scenes have parameters"),
+        }
     }
 }
 
-pub fn jumpy_balls(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn jumpy_balls(aspect_ratio: f32, rng: &mut SmallRng, _assets: &Assets) -> World {
     let checker = Checker::new(
         SolidColor::new_rgb(0.2, 0.3, 0.1),
         SolidColor::new_rgb(0.9, 0.9, 0.9),
@@ -158,10 +264,10 @@ pub fn jumpy_balls(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         1.0,
     );
 
-    (world, vec![cam], DEFAULT_BACKGROUND)
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
 }
 
-pub fn two_spheres(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
+pub fn two_spheres(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
     // World
     let checker = Checker::new(
         SolidColor::new_rgb(0.2, 0.3, 0.1),
@@ -205,10 +311,10 @@ pub fn two_spheres(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], DEFAULT_BACKGROUND)
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
 }
 
-pub fn two_perlin_spheres(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn two_perlin_spheres(aspect_ratio: f32, rng: &mut SmallRng, _assets: &Assets) -> World {
     // World
     let perlin_material = Noise::new(Perlin::new(rng), 4.0);
     let material_ground = Lambertian::new(perlin_material);
@@ -248,12 +354,12 @@ pub fn two_perlin_spheres(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], DEFAULT_BACKGROUND)
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
 }
 
-pub fn earth(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
+pub fn earth(aspect_ratio: f32, _rng: &mut SmallRng, assets: &Assets) -> World {
     // World
-    let earth_texture = ImageTexture::open("models/earthmap.jpg").unwrap();
+    let earth_texture = ImageTexture::open(&assets.path("models/earthmap.jpg")).unwrap();
     let earth_surface = Lambertian::new(earth_texture);
 
     let world: Vec<Box<dyn Hittable>> = vec![Box::new(Sphere::new(
@@ -284,12 +390,12 @@ pub fn earth(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], DEFAULT_BACKGROUND)
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
 }
 
-pub fn simple_light(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn simple_light(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
     // World
-    let earth_texture = ImageTexture::open("models/earthmap.jpg").unwrap();
+    let earth_texture = ImageTexture::open(&assets.path("models/earthmap.jpg")).unwrap();
     let earth_surface = DiffuseLight::new(earth_texture);
     // let earth_surface = DiffuseLight::new(SolidColor::new_rgb(4.0, 4.0, 4.0));
 
@@ -344,15 +450,16 @@ pub fn simple_light(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], Color::new(0.0, 0.0, 0.0))
+    (world, vec![cam], Color::new(0.0, 0.0, 0.0).into())
 }
 
-pub fn cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
+pub fn cornell_box(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
     // World
     let red = Box::new(Lambertian::new_solid_color(Color::new(0.65, 0.05, 0.05)));
     let white = Box::new(Lambertian::new_solid_color(Color::new(0.73, 0.73, 0.73)));
     let green = Box::new(Lambertian::new_solid_color(Color::new(0.12, 0.45, 0.15)));
-    let light = Box::new(DiffuseLight::new(SolidColor::new_rgb(15.0, 15.0, 15.0)));
+    let light =
+        Box::new(DiffuseLight::new(SolidColor::new_rgb(15.0, 15.0, 15.0)).with_two_sided(false));
 
     let box1 = Cuboid::new(
         Point3::new(0.0, 0.0, 0.0),
@@ -370,22 +477,39 @@ pub fn cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
     .rotate_y(-18.0)
     .translate(Vec3::new(130.0, 0.0, 65.0));
 
+    // Named, so they can be told apart in the output of debug-pixel.
     let world: Vec<Box<dyn Hittable>> = vec![
-        Box::new(YZRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, green)),
-        Box::new(YZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, red)),
-        Box::new(XZRectangle::new(213.0, 343.0, 227.0, 332.0, 554.0, light)),
-        Box::new(XZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone())),
-        Box::new(XZRectangle::new(
-            0.0,
-            555.0,
-            0.0,
-            555.0,
-            555.0,
-            white.clone(),
+        Box::new(Named::new(
+            YZRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, green),
+            "green wall".to_owned(),
         )),
-        Box::new(XYRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, white)),
-        Box::new(box1),
-        Box::new(box2),
+        Box::new(Named::new(
+            YZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, red),
+            "red wall".to_owned(),
+        )),
+        // Shines down only, instead of also lighting the ceiling right above it. The camera sees
+        // the ceiling instead of the light itself.
+        Box::new(Named::new(
+            VisibilityWrapper::new(
+                XZRectangle::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_normals(),
+                Visibility::HIDDEN_FROM_CAMERA,
+            ),
+            "light".to_owned(),
+        )),
+        Box::new(Named::new(
+            XZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()),
+            "floor".to_owned(),
+        )),
+        Box::new(Named::new(
+            XZRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()),
+            "ceiling".to_owned(),
+        )),
+        Box::new(Named::new(
+            XYRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, white),
+            "back wall".to_owned(),
+        )),
+        Box::new(Named::new(box1, "tall box".to_owned())),
+        Box::new(Named::new(box2, "short box".to_owned())),
     ];
 
     // Camera
@@ -410,15 +534,16 @@ pub fn cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], Color::new(0.0, 0.0, 0.0))
+    (world, vec![cam], Color::new(0.0, 0.0, 0.0).into())
 }
 
-pub fn smokey_cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
+pub fn smokey_cornell_box(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
     // World
     let red = Box::new(Lambertian::new_solid_color(Color::new(0.65, 0.05, 0.05)));
     let white = Box::new(Lambertian::new_solid_color(Color::new(0.73, 0.73, 0.73)));
     let green = Box::new(Lambertian::new_solid_color(Color::new(0.12, 0.45, 0.15)));
-    let light = Box::new(DiffuseLight::new(SolidColor::new_rgb(7.0, 7.0, 7.0)));
+    let light =
+        Box::new(DiffuseLight::new(SolidColor::new_rgb(7.0, 7.0, 7.0)).with_two_sided(false));
 
     let box1 = Cuboid::new(
         Point3::new(0.0, 0.0, 0.0),
@@ -442,7 +567,7 @@ pub fn smokey_cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
     let world: Vec<Box<dyn Hittable>> = vec![
         Box::new(YZRectangle::new(0.0, 555.0, 0.0, 555.0, 555.0, green)),
         Box::new(YZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, red)),
-        Box::new(XZRectangle::new(113.0, 443.0, 127.0, 432.0, 554.0, light)),
+        Box::new(XZRectangle::new(113.0, 443.0, 127.0, 432.0, 554.0, light).flip_normals()),
         Box::new(XZRectangle::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone())),
         Box::new(XZRectangle::new(
             0.0,
@@ -479,10 +604,10 @@ pub fn smokey_cornell_box(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], Color::new(0.0, 0.0, 0.0))
+    (world, vec![cam], Color::new(0.0, 0.0, 0.0).into())
 }
 
-pub fn book2_final_scene(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn book2_final_scene(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
     let mut boxes1: Vec<Box<dyn Hittable>> = Vec::new();
     let ground = Box::new(Lambertian::new_solid_color(Color::new(0.48, 0.83, 0.53)));
 
@@ -512,10 +637,11 @@ pub fn book2_final_scene(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
 
     objects.push(Box::new(BvhNode::new(boxes1, 0.0, 1.0, rng)));
 
-    let light = Box::new(DiffuseLight::new(SolidColor::new_rgb(7.0, 7.0, 7.0)));
-    objects.push(Box::new(XZRectangle::new(
-        123.0, 423.0, 147.0, 412.0, 554.0, light,
-    )));
+    let light =
+        Box::new(DiffuseLight::new(SolidColor::new_rgb(7.0, 7.0, 7.0)).with_two_sided(false));
+    objects.push(Box::new(
+        XZRectangle::new(123.0, 423.0, 147.0, 412.0, 554.0, light).flip_normals(),
+    ));
 
     let center1 = Point3::new(400.0, 400.0, 200.0);
     let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
@@ -564,7 +690,7 @@ pub fn book2_final_scene(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
     )));
 
     let emat = Box::new(Lambertian::new(
-        ImageTexture::open("models/earthmap.jpg").unwrap(),
+        ImageTexture::open(&assets.path("models/earthmap.jpg")).unwrap(),
     ));
     objects.push(Box::new(Sphere::new(
         Point3::new(400.0, 200.0, 400.0),
@@ -616,14 +742,15 @@ pub fn book2_final_scene(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (objects, vec![cam], Color::new(0.0, 0.0, 0.0))
+    (objects, vec![cam], Color::new(0.0, 0.0, 0.0).into())
 }
 
 pub fn animated_book2_final(
     aspect_ratio: f32,
-    rng: &mut ThreadRng,
-) -> (Vec<Box<dyn Hittable>>, Vec<Camera>, Color) {
-    let (world, _, background) = book2_final_scene(aspect_ratio, rng);
+    rng: &mut SmallRng,
+    assets: &Assets,
+) -> (Vec<Box<dyn Hittable>>, Vec<Camera>, Background) {
+    let (world, _, background) = book2_final_scene(aspect_ratio, rng, assets);
 
     // Camera
     let look_at = Point3::new(278.0, 278.0, 278.0);
@@ -637,36 +764,86 @@ pub fn animated_book2_final(
     let fps = 10.0;
     let frames = fps * len_s;
 
-    let cameras: Vec<_> = (0..(frames as usize))
-        .into_iter()
-        .map(|frame| {
-            let from_x = 478.0 - frame as f32 * (2.0 * 478.0) / frames;
-            let from_y = 278.0;
-            let from_z = -600.0;
-
-            let look_from = (from_x, from_y, from_z).into();
-            let distance_to_focus = (look_at - look_from).length();
-
-            Camera::new(
-                look_from,
-                look_at,
-                v_up,
-                vfow,
-                aspect_ratio,
-                aperture,
-                distance_to_focus,
-                time0,
-                time1,
-            )
-        })
-        .collect();
+    let keyframe = |time, look_from: Point3| {
+        let distance_to_focus = (look_at - look_from).length();
+        CameraKeyframe::new(
+            time,
+            look_from,
+            look_at,
+            v_up,
+            vfow,
+            aperture,
+            distance_to_focus,
+        )
+    };
+    let path = CameraPath::new(
+        vec![
+            keyframe(0.0, Point3::new(478.0, 278.0, -600.0)),
+            keyframe(len_s, Point3::new(-478.0, 278.0, -600.0)),
+        ],
+        Interpolation::Linear,
+    );
+    let cameras = path.to_cameras(frames as usize, aspect_ratio, time0, time1);
 
     let world: Vec<Box<dyn Hittable>> = vec![Box::new(BvhNode::new(world, 0.0, 1.0, rng))];
 
     (world, cameras, background)
 }
 
-pub fn simple_triangle(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
+pub fn keyframed_ball(aspect_ratio: f32, rng: &mut SmallRng, _assets: &Assets) -> World {
+    let len_s = 3.0;
+    let fps = 10.0;
+    let frames = fps * len_s;
+    // Half of every frame, like a 180° film camera.
+    let shutter = 0.5 / fps;
+
+    // World
+    let ball = KeyframedTranslation::new(
+        Sphere::new(
+            Point3::new(0.0, 0.5, 0.0),
+            0.5,
+            Box::new(Lambertian::new_solid_color(Color::new(0.8, 0.3, 0.1))),
+        ),
+        vec![
+            (0.0, Vec3::new(-2.0, 0.0, 0.0)),
+            (1.0, Vec3::new(0.0, 0.0, -2.0)),
+            (2.0, Vec3::new(2.0, 0.0, 0.0)),
+            (len_s, Vec3::new(0.0, 0.0, 2.0)),
+        ],
+        Interpolation::CatmullRom,
+    );
+
+    let objects: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Box::new(ball),
+    ];
+    let world = build_world(objects, 0.0, len_s, rng);
+
+    // Camera
+    let look_from = Point3::new(0.0, 3.0, 9.0);
+    let look_at = Point3::new(0.0, 0.5, 0.0);
+    let keyframe = |time| {
+        CameraKeyframe::new(
+            time,
+            look_from,
+            look_at,
+            Vec3::new(0.0, 1.0, 0.0),
+            30.0,
+            0.0,
+            (look_at - look_from).length(),
+        )
+    };
+    let path = CameraPath::new(vec![keyframe(0.0), keyframe(len_s)], Interpolation::Linear);
+    let cameras = path.to_cameras_with_shutter(frames as usize, aspect_ratio, shutter);
+
+    (world, cameras, DEFAULT_BACKGROUND.into())
+}
+
+pub fn simple_triangle(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
     // World
     let checker = Checker::new(
         SolidColor::new_rgb(0.2, 0.3, 0.1),
@@ -713,10 +890,64 @@ pub fn simple_triangle(aspect_ratio: f32, _rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], DEFAULT_BACKGROUND)
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn wavefront_cow_obj(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
+    // World
+    let checker = Checker::new(
+        SolidColor::new_rgb(0.2, 0.3, 0.1),
+        SolidColor::new_rgb(0.9, 0.9, 0.9),
+        10.0,
+    );
+    let material_ground = Lambertian::new(checker);
+
+    let cow = assets.load_obj("models/cow-nonormals.obj", rng);
+    let cow = Box::new(Translation::new(cow, Vec3::new(0.0, 2.5, 0.0))) as Box<dyn Hittable>;
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -10.6, 0.0),
+            10.0,
+            Box::new(material_ground),
+        )),
+        Box::new(XYRectangle::new(
+            1.0,
+            5.0,
+            1.0,
+            7.0,
+            5.0,
+            Box::new(DiffuseLight::new(SolidColor::new_rgb(1.4, 1.3, 1.3))),
+        )),
+        cow,
+    ];
+
+    // Camera
+    let look_from = Point3::new(13.0, 2.0, 3.0);
+    let look_at = Point3::new(0.0, 2.5, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 40.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125).into())
 }
 
-pub fn wavefront_cow_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn matte_painting_cow(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
     // World
     let checker = Checker::new(
         SolidColor::new_rgb(0.2, 0.3, 0.1),
@@ -725,9 +956,14 @@ pub fn wavefront_cow_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
     );
     let material_ground = Lambertian::new(checker);
 
-    let cow = load_wavefront_obj("models/cow-nonormals.obj", rng).unwrap();
+    let cow = assets.load_obj("models/cow-nonormals.obj", rng);
     let cow = Box::new(Translation::new(cow, Vec3::new(0.0, 2.5, 0.0))) as Box<dyn Hittable>;
 
+    // The card faces the camera from behind the cow. It must neither tint the cow nor show up in
+    // the mirror sphere.
+    let painting =
+        MattePainting::new(ImageTexture::open(&assets.path("models/earthmap.jpg")).unwrap());
+
     let world: Vec<Box<dyn Hittable>> = vec![
         Box::new(Sphere::new(
             Point3::new(0.0, -10.6, 0.0),
@@ -742,6 +978,19 @@ pub fn wavefront_cow_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
             5.0,
             Box::new(DiffuseLight::new(SolidColor::new_rgb(1.4, 1.3, 1.3))),
         )),
+        Box::new(YZRectangle::new(
+            -3.0,
+            9.0,
+            -12.0,
+            12.0,
+            -8.0,
+            Box::new(painting),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(2.0, 0.4, 4.0),
+            1.0,
+            Box::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.0)),
+        )),
         cow,
     ];
 
@@ -767,12 +1016,12 @@ pub fn wavefront_cow_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125))
+    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125).into())
 }
 
-pub fn wavefront_suspension_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+pub fn wavefront_suspension_obj(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
     // World
-    let suspension = load_wavefront_obj("models/Normals_Try3.obj", rng).unwrap();
+    let suspension = assets.load_obj("models/Normals_Try3.obj", rng);
     let suspension =
         Box::new(Translation::new(suspension, Vec3::new(0.0, 2.5, 0.0))) as Box<dyn Hittable>;
 
@@ -810,13 +1059,67 @@ pub fn wavefront_suspension_obj(aspect_ratio: f32, rng: &mut ThreadRng) -> World
         time1,
     );
 
-    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125))
+    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125).into())
 }
 
-pub fn textured_monument(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
+#[cfg(feature = "gltf")]
+pub fn gltf_duck(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
+    // World
+    let checker = Checker::new(
+        SolidColor::new_rgb(0.2, 0.3, 0.1),
+        SolidColor::new_rgb(0.9, 0.9, 0.9),
+        10.0,
+    );
+    let material_ground = Lambertian::new(checker);
+
+    let duck = load_gltf(&assets.path("models/Duck.glb"), rng).unwrap();
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(material_ground),
+        )),
+        Box::new(XYRectangle::new(
+            -1.0,
+            2.0,
+            1.0,
+            3.0,
+            3.0,
+            Box::new(DiffuseLight::new(SolidColor::new_rgb(4.0, 3.8, 3.6))),
+        )),
+        duck,
+    ];
+
+    // Camera
+    let look_from = Point3::new(3.0, 1.5, 4.0);
+    let look_at = Point3::new(0.1, 0.8, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 30.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], Color::new_const(0.5, 0.6, 0.75).into())
+}
+
+pub fn textured_monument(aspect_ratio: f32, rng: &mut SmallRng, assets: &Assets) -> World {
     // World
     let monument = Box::new(Translation::new(
-        load_wavefront_obj("models/monument_downscaled_polygon_reduced.obj", rng).unwrap(),
+        assets.load_obj("models/monument_downscaled_polygon_reduced.obj", rng),
         Vec3::new(0.0, 0.0, -19.0),
     ));
 
@@ -854,9 +1157,524 @@ pub fn textured_monument(aspect_ratio: f32, rng: &mut ThreadRng) -> World {
         time1,
     );
 
-    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125))
+    (world, vec![cam], Color::new_const(0.085, 0.1, 0.125).into())
 }
 
-type World = (Vec<Box<dyn Hittable>>, Vec<Camera>, Color);
+/// A sharp and a rounded metal cuboid side by side, to compare how their edges catch the light.
+pub fn rounded_cuboids(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let ground = Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5)));
+    let metal = Box::new(Metal::new(Color::new(0.8, 0.8, 0.85), 0.05));
+    let light = Box::new(DiffuseLight::new(SolidColor::new_rgb(6.0, 6.0, 6.0)));
+
+    let sharp = Cuboid::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        Point3::new(1.0, 2.0, 1.0),
+        metal.clone(),
+    )
+    .rotate_y(30.0)
+    .translate(Vec3::new(-1.6, 0.0, 0.0));
+
+    let rounded = RoundedCuboid::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        Point3::new(1.0, 2.0, 1.0),
+        0.15,
+        metal,
+    )
+    .rotate_y(30.0)
+    .translate(Vec3::new(1.6, 0.0, 0.0));
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(XZRectangle::new(-20.0, 20.0, -20.0, 20.0, 0.0, ground)),
+        Box::new(XZRectangle::new(-2.0, 2.0, -2.0, 2.0, 5.0, light)),
+        Box::new(sharp),
+        Box::new(rounded),
+    ];
+
+    // Camera
+    let look_from = Point3::new(0.0, 3.5, -9.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 35.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], Color::new(0.02, 0.02, 0.03).into())
+}
+
+/// A glass and a metal torus, tilted so rays can pass through the tube several times.
+pub fn toruses(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let checker = Checker::new(
+        SolidColor::new_rgb(0.2, 0.3, 0.1),
+        SolidColor::new_rgb(0.9, 0.9, 0.9),
+        10.0,
+    );
+
+    let glass = Torus::new(
+        Point3::new(0.0, 0.0, 0.0),
+        1.0,
+        0.35,
+        Box::new(Dielectric::new(1.5)),
+    )
+    .rotate_x(60.0)
+    .translate(Vec3::new(-1.3, 1.2, 0.0));
+
+    let metal = Torus::new(
+        Point3::new(0.0, 0.0, 0.0),
+        1.0,
+        0.35,
+        Box::new(Metal::new(Color::new(0.8, 0.6, 0.3), 0.05)),
+    )
+    .rotate_z(-30.0)
+    .translate(Vec3::new(1.3, 1.2, 0.0));
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new(checker)),
+        )),
+        Box::new(glass),
+        Box::new(metal),
+    ];
+
+    // Camera
+    let look_from = Point3::new(0.0, 2.5, 8.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 35.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn planes_and_disks(aspect_ratio: f32, rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let checker = Checker::new(
+        SolidColor::new_rgb(0.2, 0.3, 0.1),
+        SolidColor::new_rgb(0.9, 0.9, 0.9),
+        10.0,
+    );
+
+    let objects: Vec<Box<dyn Hittable>> = vec![
+        Box::new(InfinitePlane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Box::new(Lambertian::new(checker)),
+        )),
+        Box::new(Disk::new(
+            Point3::new(-1.5, 1.0, -0.5),
+            Vec3::new(0.3, 0.0, 1.0),
+            1.0,
+            Box::new(Lambertian::new(UVDebug::new())),
+        )),
+        Box::new(Disk::new(
+            Point3::new(1.5, 1.0, -0.5),
+            Vec3::new(-0.3, 0.0, 1.0),
+            1.0,
+            Box::new(Metal::new(Color::new(0.7, 0.7, 0.8), 0.0)),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 0.5, 1.0),
+            0.5,
+            Box::new(Dielectric::new(1.5)),
+        )),
+    ];
+
+    let world = build_world(objects, 0.0, 1.0, rng);
+
+    // Camera
+    let look_from = Point3::new(0.0, 1.5, 8.0);
+    let look_at = Point3::new(0.0, 0.8, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 35.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn material_zoo(aspect_ratio: f32, rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let turbulence = Turbulence::new(
+        Perlin::new(rng),
+        2.0,
+        7,
+        SolidColor::new_rgb(0.1, 0.2, 0.5),
+        SolidColor::new_rgb(0.9, 0.9, 0.8),
+    );
+    let marble = Marble::new(
+        Perlin::new(rng),
+        4.0,
+        7,
+        SolidColor::new_rgb(0.9, 0.88, 0.85),
+        SolidColor::new_rgb(0.2, 0.25, 0.2),
+    );
+    let wood = Wood::new(
+        Perlin::new(rng),
+        6.0,
+        0.5,
+        SolidColor::new_rgb(0.75, 0.55, 0.3),
+        SolidColor::new_rgb(0.4, 0.22, 0.1),
+    );
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, -2.2),
+            1.0,
+            Box::new(Lambertian::new(turbulence)),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            Box::new(Lambertian::new(marble)),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, 2.2),
+            1.0,
+            Box::new(Lambertian::new(wood)),
+        )),
+    ];
+
+    // Camera
+    let look_from = Point3::new(13.0, 2.0, 3.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 10.0;
+    let aperture = 0.0;
+    let vfow = 30.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn frosted_glass(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let checker = Checker::new(
+        SolidColor::new_rgb(0.2, 0.3, 0.1),
+        SolidColor::new_rgb(0.9, 0.9, 0.9),
+        2.0,
+    );
+
+    let mut world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )),
+        // Behind the spheres, so the blur of the refraction shows.
+        Box::new(XYRectangle::new(
+            -8.0,
+            8.0,
+            0.0,
+            6.0,
+            -3.0,
+            Box::new(Lambertian::new(checker)),
+        )),
+    ];
+    for (x, roughness) in [(-2.2, 0.0), (0.0, 0.2), (2.2, 0.5)] {
+        world.push(Box::new(Sphere::new(
+            Point3::new(x, 1.0, 0.0),
+            1.0,
+            Box::new(RoughDielectric::new(1.5, roughness)),
+        )));
+    }
+
+    // Camera
+    let look_from = Point3::new(0.0, 2.0, 12.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 12.0;
+    let aperture = 0.0;
+    let vfow = 30.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn backlit_subsurface(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    // Absorbs blue more than red, so light coming through the wax turns warm.
+    let wax = Subsurface::new(
+        Sphere::new(
+            Point3::new(-1.2, 1.0, 0.0),
+            1.0,
+            Box::new(Dielectric::new(1.4)),
+        ),
+        Color::new(4.0, 4.0, 4.0),
+        Color::new(0.05, 0.2, 0.6),
+        1.4,
+    );
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Box::new(wax),
+        Box::new(Sphere::new(
+            Point3::new(1.2, 1.0, 0.0),
+            1.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.9, 0.8, 0.6))),
+        )),
+        Box::new(subsurface_backlight()),
+    ];
+
+    // Camera
+    let look_from = Point3::new(0.0, 1.5, 8.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 8.0;
+    let aperture = 0.0;
+    let vfow = 30.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], Color::new(0.02, 0.02, 0.03).into())
+}
+
+/// Behind and above the spheres of [`backlit_subsurface`], facing the camera.
+fn subsurface_backlight() -> DiskLight {
+    DiskLight::new(
+        Point3::new(0.0, 2.5, -3.0),
+        Vec3::new(0.0, -0.3, 1.0),
+        1.5,
+        Color::new(8.0, 8.0, 8.0),
+    )
+}
+
+pub fn mixed_materials(aspect_ratio: f32, _rng: &mut SmallRng, _assets: &Assets) -> World {
+    // World
+    let mask = Checker::new(
+        SolidColor::new_rgb(0.0, 0.0, 0.0),
+        SolidColor::new_rgb(1.0, 1.0, 1.0),
+        10.0,
+    );
+    let mixed = MixMaterial::new(
+        Box::new(Lambertian::new_solid_color(Color::new(0.7, 0.2, 0.1))),
+        Box::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05)),
+        mask,
+    );
+
+    let world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            Box::new(mixed),
+        )),
+    ];
+
+    // Camera
+    let look_from = Point3::new(0.0, 2.0, 6.0);
+    let look_at = Point3::new(0.0, 1.0, 0.0);
+    let v_up = Vec3::new(0.0, 1.0, 0.0);
+    let distance_to_focus = 6.0;
+    let aperture = 0.0;
+    let vfow = 30.0;
+    let time0 = 0.0;
+    let time1 = 1.0;
+
+    let cam = Camera::new(
+        look_from,
+        look_at,
+        v_up,
+        vfow,
+        aspect_ratio,
+        aperture,
+        distance_to_focus,
+        time0,
+        time1,
+    );
+
+    (world, vec![cam], DEFAULT_BACKGROUND.into())
+}
+
+pub fn test_scene(parameters: &SceneParameters, aspect_ratio: f32, rng: &mut SmallRng) -> World {
+    let scene = scene_generator::generate(parameters, aspect_ratio);
+    let world = build_world(scene.objects, 0.0, 1.0, rng);
+
+    (world, vec![scene.camera], scene.background)
+}
+
+type World = (Vec<Box<dyn Hittable>>, Vec<Camera>, Background);
+
+/// Where scenes load their models and textures from. Relative asset paths are taken relative to
+/// `root`, absolute ones as they are.
+#[derive(Debug, Clone)]
+pub struct Assets {
+    root: PathBuf,
+    /// Whether OBJ models reuse the BVHs cached next to them.
+    bvh_cache: bool,
+}
+
+impl Assets {
+    pub fn new(root: PathBuf, bvh_cache: bool) -> Self {
+        Self { root, bvh_cache }
+    }
+
+    fn path(&self, asset: &str) -> String {
+        self.root.join(asset).to_string_lossy().into_owned()
+    }
+
+    fn load_obj(&self, asset: &str, rng: &mut SmallRng) -> Box<dyn Hittable> {
+        let path = self.path(asset);
+        let mesh = if self.bvh_cache {
+            load_wavefront_obj_cached(&path, rng)
+        } else {
+            load_wavefront_obj(&path, rng)
+        };
+
+        mesh.unwrap()
+    }
+}
 
 static DEFAULT_BACKGROUND: Color = Color::new_const(0.7, 0.8, 1.00);
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    /// An asset root in the temp directory holding a single triangle at `models/triangle.obj`.
+    fn asset_root(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("scenes_{}_{}", process::id(), name));
+        fs::create_dir_all(root.join("models")).unwrap();
+        fs::write(
+            root.join("models/triangle.obj"),
+            "v 1 2 3\nv 4 2 3\nv 1 5 6\nf 1 2 3\n",
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn assets_are_resolved_against_the_asset_root() {
+        let root = asset_root("relative");
+        let absolute = env::temp_dir().join("earthmap.jpg");
+        let assets = Assets::new(root.clone(), false);
+
+        assert_eq!(
+            assets.path("models/earthmap.jpg"),
+            root.join("models/earthmap.jpg").to_string_lossy()
+        );
+        assert_eq!(
+            assets.path(absolute.to_str().unwrap()),
+            absolute.to_string_lossy()
+        );
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let triangle = assets.load_obj("models/triangle.obj", &mut rng);
+        let bounding_box = triangle.bounding_box(0.0, 1.0).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!((bounding_box.min() - Point3::new(1.0, 2.0, 3.0)).length() < 1e-3);
+        assert!((bounding_box.max() - Point3::new(4.0, 5.0, 6.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn bvh_caches_are_written_next_to_the_model_in_the_asset_root() {
+        let root = asset_root("cached");
+        let assets = Assets::new(root.clone(), true);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        assets.load_obj("models/triangle.obj", &mut rng);
+        let is_cached = root.join("models/triangle.bvh").is_file();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(is_cached);
+    }
+}
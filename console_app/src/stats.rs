@@ -0,0 +1,59 @@
+//! Reporting of the render statistics collected by the library.
+
+use std::{fs, io, path::Path};
+
+use raytracer_weekend_lib::stats::StatsSnapshot;
+
+pub fn print_summary(stats: &StatsSnapshot) {
+    let seconds = stats.elapsed.as_secs_f64();
+    let per_second = |count: u64| match seconds > 0.0 {
+        true => count as f64 / seconds,
+        false => 0.0,
+    };
+
+    println!("{:<18} {:>15} {:>15}", "", "total", "per second");
+    for (name, count) in [
+        ("Camera rays", stats.camera_rays),
+        ("Scattered rays", stats.scattered_rays),
+        ("Shadow rays", stats.shadow_rays),
+        ("All rays", stats.total_rays()),
+        ("BVH node tests", stats.bvh_node_tests),
+        ("Triangle tests", stats.triangle_tests),
+    ] {
+        println!("{:<18} {:>15} {:>15.0}", name, count, per_second(count));
+    }
+    println!(
+        "{:<18} {:>15.3}",
+        "Bounces per path",
+        stats.average_bounces()
+    );
+    println!("{:<18} {:>14.3}s", "Wall-clock time", seconds);
+}
+
+/// Writes the statistics as a flat JSON object.
+pub fn write_json(path: &Path, stats: &StatsSnapshot) -> io::Result<()> {
+    let json = format!(
+        concat!(
+            "{{\n",
+            "  \"camera_rays\": {},\n",
+            "  \"scattered_rays\": {},\n",
+            "  \"shadow_rays\": {},\n",
+            "  \"total_rays\": {},\n",
+            "  \"average_bounces\": {},\n",
+            "  \"bvh_node_tests\": {},\n",
+            "  \"triangle_tests\": {},\n",
+            "  \"seconds\": {}\n",
+            "}}\n"
+        ),
+        stats.camera_rays,
+        stats.scattered_rays,
+        stats.shadow_rays,
+        stats.total_rays(),
+        stats.average_bounces(),
+        stats.bvh_node_tests,
+        stats.triangle_tests,
+        stats.elapsed.as_secs_f64()
+    );
+
+    fs::write(path, json)
+}
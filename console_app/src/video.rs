@@ -0,0 +1,82 @@
+//! Assembly of the frames of an animation into a single GIF or video file.
+//!
+//! GIFs are encoded right here. Every other format is left to `ffmpeg`, which has to be on the
+//! `PATH` and gets the frames piped in one after the other.
+
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame,
+};
+
+/// Writes `frames`, in order, to `output`, showing `fps` of them per second. The format follows
+/// the file extension of `output`.
+pub fn assemble(frames: &[PathBuf], output: &Path, fps: u32) -> Result<(), Box<dyn Error>> {
+    let is_gif = output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+
+    match is_gif {
+        true => write_gif(frames, output, fps),
+        false => run_ffmpeg(frames, output, fps),
+    }
+}
+
+fn write_gif(frames: &[PathBuf], output: &Path, fps: u32) -> Result<(), Box<dyn Error>> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(output)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    // Loading the frames one at a time keeps only one of them in memory.
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    for path in frames {
+        let image = image::open(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+            .to_rgba8();
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+    }
+
+    Ok(())
+}
+
+fn run_ffmpeg(frames: &[PathBuf], output: &Path, fps: u32) -> Result<(), Box<dyn Error>> {
+    // yuv420p is what most players can handle.
+    let spawned = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "image2pipe"])
+        .args(["-framerate", &fps.to_string(), "-i", "-"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(output)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut ffmpeg = match spawned {
+        Ok(ffmpeg) => ffmpeg,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let message = format!(
+                "Writing {} needs ffmpeg, which is not on the PATH. GIFs can do without.",
+                output.display()
+            );
+            return Err(message.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut stdin = ffmpeg.stdin.take().expect("stdin was requested");
+    for path in frames {
+        stdin.write_all(&fs::read(path)?)?;
+    }
+    // ffmpeg only finishes once its input is closed.
+    drop(stdin);
+
+    let status = ffmpeg.wait()?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed to write {}: {}", output.display(), status).into());
+    }
+
+    Ok(())
+}
@@ -13,6 +13,7 @@ use heapless;
 use postcard::{to_vec, to_vec_cobs};
 use rand::{prelude::SmallRng, Rng, SeedableRng};
 use raytracer_weekend_lib::{
+    background::Background,
     bvh::BvhNode,
     camera::Camera,
     hittable::{
@@ -58,11 +59,16 @@ fn main() -> ! {
     let aspect_ratio = image_width as f32 / image_height as f32;
 
     let samples_per_pixel = 50;
+    // Few bounces keep the render time bearable; the Cornell box needs little more than that.
+    let max_depth = 8;
+    // Stream the image in several coarse passes so the host preview sharpens over time.
+    let passes = 5;
 
     let image_start_msg: heapless::Vec<u8, 256> = to_vec_cobs(&ProgressMessage::ImageStart {
         width: image_width,
         height: image_height,
         samples_per_pixel,
+        max_depth: max_depth as u32,
     })
     .unwrap();
 
@@ -73,19 +79,21 @@ fn main() -> ! {
     defmt::info!("Creating world...");
 
     let (world, cams, background) = cornell_box(aspect_ratio, &mut rng);
+    let background = Background::from(background);
     defmt::info!("World created.");
 
     for (frame_no, cam) in cams.iter().enumerate() {
         let raytracer = Raytracer::new(
             &world,
-            &cam,
-            background,
+            cam,
+            &background,
             image_width,
             image_height,
             samples_per_pixel,
-        );
+        )
+        .with_max_depth(max_depth);
 
-        let all_pixels = raytracer.render();
+        let all_pixels = raytracer.render_progressive(passes).flatten();
 
         for (idx, pixel) in all_pixels.enumerate() {
             /*if idx % 1 == 0*/
@@ -0,0 +1,45 @@
+//! Copies stdin to stdout while damaging the stream, for debugging flaky links, e.g.
+//!
+//! ```text
+//! socat /dev/ttyACM0,raw,b115200 EXEC:"fault_proxy bursty 42"
+//! ```
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    process,
+};
+
+use discovery_host_receiver::fault_injection::{FailureProfile, FaultInjector};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let profile = args.next().unwrap_or_else(|| "clean".to_owned());
+    let seed = args.next().unwrap_or_else(|| "0".to_owned());
+
+    let profile: FailureProfile = profile.parse().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(2);
+    });
+    let seed: u64 = seed.parse().unwrap_or_else(|_| {
+        eprintln!("seed must be an unsigned integer, got {:?}", seed);
+        process::exit(2);
+    });
+
+    let mut injector = FaultInjector::new(io::stdin().lock(), profile, seed);
+    let mut stdout = io::stdout().lock();
+    let mut buffer = [0; 4096];
+
+    loop {
+        let count = injector.read(&mut buffer).expect("Failed to read stdin");
+        if count == 0 {
+            break;
+        }
+
+        // Flush right away so the proxy doesn't add latency of its own.
+        stdout
+            .write_all(&buffer[..count])
+            .and_then(|_| stdout.flush())
+            .expect("Failed to write stdout");
+    }
+}
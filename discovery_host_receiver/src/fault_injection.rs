@@ -0,0 +1,374 @@
+//! Deliberately damages a byte stream, to see how the receiver copes with a bad link.
+//!
+//! Messages on the wire are COBS frames terminated by a zero byte. Bit flips, drops and
+//! duplication work on single bytes, while reordering shuffles whole frames. All decisions come
+//! from a seeded RNG, so a failure is reproducible from the profile and the seed.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+const FRAME_DELIMITER: u8 = 0;
+
+/// How often each kind of damage happens. All rates are probabilities per byte.
+#[derive(Debug, Clone)]
+pub struct FailureProfile {
+    /// Flips one random bit of the byte.
+    pub bit_flip_rate: f64,
+    /// Drops the byte.
+    pub byte_drop_rate: f64,
+    /// Starts dropping a burst of bytes, whose length is picked uniformly from `burst_length`.
+    pub burst_loss_rate: f64,
+    pub burst_length: RangeInclusive<usize>,
+    /// Sends the byte twice.
+    pub duplication_rate: f64,
+    /// Frames are held back until this many are waiting, and then one of them is sent at random.
+    /// Zero or one keeps them in order.
+    pub reorder_window: usize,
+}
+
+impl FailureProfile {
+    pub const NAMES: [&'static str; 6] = [
+        "clean",
+        "noisy",
+        "lossy",
+        "bursty",
+        "duplicating",
+        "reordering",
+    ];
+
+    /// Passes everything through untouched.
+    pub fn clean() -> Self {
+        Self {
+            bit_flip_rate: 0.0,
+            byte_drop_rate: 0.0,
+            burst_loss_rate: 0.0,
+            burst_length: 1..=1,
+            duplication_rate: 0.0,
+            reorder_window: 0,
+        }
+    }
+}
+
+impl FromStr for FailureProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let clean = Self::clean();
+
+        match s {
+            "clean" => Ok(clean),
+            "noisy" => Ok(Self {
+                bit_flip_rate: 1e-3,
+                ..clean
+            }),
+            "lossy" => Ok(Self {
+                byte_drop_rate: 1e-3,
+                ..clean
+            }),
+            "bursty" => Ok(Self {
+                burst_loss_rate: 1e-4,
+                burst_length: 16..=256,
+                ..clean
+            }),
+            "duplicating" => Ok(Self {
+                duplication_rate: 1e-3,
+                ..clean
+            }),
+            "reordering" => Ok(Self {
+                reorder_window: 4,
+                ..clean
+            }),
+            _ => Err(format!(
+                "unknown failure profile {:?}, expected one of {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Wraps a reader and damages everything read through it according to a [`FailureProfile`].
+pub struct FaultInjector<R> {
+    inner: R,
+    profile: FailureProfile,
+    rng: SmallRng,
+    burst_remaining: usize,
+    frame: Vec<u8>,
+    held_frames: Vec<Vec<u8>>,
+    output: VecDeque<u8>,
+}
+
+impl<R: Read> FaultInjector<R> {
+    pub fn new(inner: R, profile: FailureProfile, seed: u64) -> Self {
+        Self {
+            inner,
+            profile,
+            rng: SmallRng::seed_from_u64(seed),
+            burst_remaining: 0,
+            frame: Vec::new(),
+            held_frames: Vec::new(),
+            output: VecDeque::new(),
+        }
+    }
+
+    fn process(&mut self, mut byte: u8) {
+        if self.burst_remaining > 0 {
+            self.burst_remaining -= 1;
+            return;
+        }
+
+        if self.rng.gen_bool(self.profile.burst_loss_rate) {
+            let length = self.rng.gen_range(self.profile.burst_length.clone());
+            self.burst_remaining = length.saturating_sub(1);
+            return;
+        }
+
+        if self.rng.gen_bool(self.profile.byte_drop_rate) {
+            return;
+        }
+
+        if self.rng.gen_bool(self.profile.bit_flip_rate) {
+            byte ^= 1 << self.rng.gen_range(0..8);
+        }
+
+        let copies = if self.rng.gen_bool(self.profile.duplication_rate) {
+            2
+        } else {
+            1
+        };
+
+        for _ in 0..copies {
+            self.frame.push(byte);
+            if byte == FRAME_DELIMITER {
+                self.finish_frame();
+            }
+        }
+    }
+
+    fn finish_frame(&mut self) {
+        self.held_frames.push(std::mem::take(&mut self.frame));
+
+        if self.held_frames.len() >= self.profile.reorder_window.max(1) {
+            let index = self.rng.gen_range(0..self.held_frames.len());
+            self.output.extend(self.held_frames.swap_remove(index));
+        }
+    }
+
+    /// Sends whatever is still held back once the inner reader runs dry.
+    fn flush(&mut self) {
+        if !self.frame.is_empty() {
+            self.held_frames.push(std::mem::take(&mut self.frame));
+        }
+
+        while !self.held_frames.is_empty() {
+            let index = self.rng.gen_range(0..self.held_frames.len());
+            self.output.extend(self.held_frames.swap_remove(index));
+        }
+    }
+}
+
+impl<R: Read> Read for FaultInjector<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.output.is_empty() {
+            let mut byte = [0];
+            if self.inner.read(&mut byte)? == 0 {
+                self.flush();
+                break;
+            }
+
+            self.process(byte[0]);
+        }
+
+        let count = buf.len().min(self.output.len());
+        for (target, byte) in buf.iter_mut().zip(self.output.drain(..count)) {
+            *target = byte;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use postcard::to_slice_cobs;
+    use raytracer_weekend_lib::{vec3::Color, Pixel, ProgressMessage, RenderTotals};
+
+    use super::*;
+    use crate::receiver::MessageReader;
+
+    fn encode(message: &ProgressMessage) -> Vec<u8> {
+        let mut buffer = [0; 256];
+        to_slice_cobs(message, &mut buffer).unwrap().to_vec()
+    }
+
+    fn pixel(row: u32, column: u32) -> ProgressMessage {
+        ProgressMessage::Pixel(Pixel {
+            row,
+            column,
+            color: Color::new(0.25, 0.5, row as f32 + column as f32 / 64.0),
+            sample_count: 4,
+            aux: None,
+        })
+    }
+
+    /// One of each message, as a small render would send them.
+    fn every_message() -> Vec<ProgressMessage> {
+        vec![
+            ProgressMessage::ImageStart {
+                width: 4,
+                height: 4,
+                samples_per_pixel: 4,
+                max_depth: 50,
+            },
+            pixel(1, 2),
+            ProgressMessage::ImageEnd,
+            ProgressMessage::RenderStats(RenderTotals {
+                total_rays: 1234,
+                bvh_tests: 56789,
+                primitive_tests: 4321,
+                shadow_rays: 321,
+                elapsed_ms: 42,
+            }),
+        ]
+    }
+
+    /// Everything the receiver makes of `stream`, and how many frames it reported damaged.
+    fn receive(stream: impl Read) -> (Vec<ProgressMessage>, usize) {
+        let mut receiver = MessageReader::new(stream);
+        let mut messages = Vec::new();
+        while let Some(message) = receiver.next_message().unwrap() {
+            messages.push(message);
+        }
+        (messages, receiver.damaged_frames())
+    }
+
+    fn describe(messages: &[ProgressMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| format!("{:?}", message))
+            .collect()
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Damage {
+        Drop,
+        Corrupt,
+        Truncate,
+    }
+
+    /// Damages the middle of `frame`, whose last byte is the delimiter, and keeps the delimiter.
+    fn damage(frame: &mut Vec<u8>, damage: Damage) {
+        let middle = (frame.len() - 1) / 2;
+        match damage {
+            Damage::Drop => {
+                frame.remove(middle);
+            }
+            Damage::Corrupt => frame[middle] = if frame[middle] == 0x55 { 0xaa } else { 0x55 },
+            Damage::Truncate => {
+                frame.drain(middle..frame.len() - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn receiver_recovers_from_a_damaged_frame_of_every_message_type() {
+        let messages = every_message();
+
+        for kind in [Damage::Drop, Damage::Corrupt, Damage::Truncate] {
+            for damaged in 0..messages.len() {
+                let mut frames: Vec<Vec<u8>> = messages.iter().map(encode).collect();
+                damage(&mut frames[damaged], kind);
+
+                let (mut received, damaged_frames) = receive(frames.concat().as_slice());
+
+                // The damaged frame either got reported or decoded into something else, but
+                // every other message has to come through untouched.
+                assert_eq!(
+                    received.len() + damaged_frames,
+                    messages.len(),
+                    "{:?} message {}",
+                    kind,
+                    damaged
+                );
+                if damaged_frames == 0 {
+                    received.remove(damaged);
+                }
+                let mut expected = messages.clone();
+                expected.remove(damaged);
+                assert_eq!(
+                    describe(&received),
+                    describe(&expected),
+                    "{:?} message {}",
+                    kind,
+                    damaged
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn receiver_reports_a_frame_cut_off_by_the_end_of_the_stream() {
+        let messages = every_message();
+        let mut stream: Vec<u8> = messages.iter().flat_map(encode).collect();
+        stream.truncate(stream.len() - 2);
+
+        let (received, damaged_frames) = receive(stream.as_slice());
+
+        assert_eq!(
+            describe(&received),
+            describe(&messages[..messages.len() - 1])
+        );
+        assert_eq!(damaged_frames, 1);
+    }
+
+    #[test]
+    fn receiver_survives_every_failure_profile() {
+        const SIZE: u32 = 64;
+
+        let mut messages = vec![ProgressMessage::ImageStart {
+            width: SIZE,
+            height: SIZE,
+            samples_per_pixel: 4,
+            max_depth: 50,
+        }];
+        messages.extend((0..SIZE).flat_map(|row| (0..SIZE).map(move |column| pixel(row, column))));
+        messages.push(ProgressMessage::ImageEnd);
+        let stream: Vec<u8> = messages.iter().flat_map(encode).collect();
+        let pixels: HashSet<String> = describe(&messages[1..messages.len() - 1])
+            .into_iter()
+            .collect();
+
+        for name in FailureProfile::NAMES {
+            let profile: FailureProfile = name.parse().unwrap();
+            let injector = FaultInjector::new(stream.as_slice(), profile, 7);
+
+            let (received, damaged_frames) = receive(injector);
+            let intact_pixels = describe(&received)
+                .iter()
+                .filter(|message| pixels.contains(*message))
+                .count();
+            let fraction = intact_pixels as f32 / pixels.len() as f32;
+
+            match name {
+                "clean" | "reordering" => {
+                    assert_eq!(received.len(), messages.len(), "{}", name);
+                    assert_eq!(intact_pixels, pixels.len(), "{}", name);
+                    assert_eq!(damaged_frames, 0, "{}", name);
+                }
+                "lossy" | "bursty" => {
+                    assert!(fraction >= 0.9, "{} kept {} of the pixels", name, fraction);
+                    assert!(damaged_frames > 0, "{} reported no damage", name);
+                }
+                _ => assert!(damaged_frames > 0, "{} reported no damage", name),
+            }
+        }
+    }
+}
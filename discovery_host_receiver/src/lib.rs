@@ -0,0 +1,2 @@
+pub mod fault_injection;
+pub mod receiver;
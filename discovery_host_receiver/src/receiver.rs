@@ -0,0 +1,61 @@
+//! Splits the byte stream from the board into messages.
+//!
+//! Every message is a COBS encoded postcard frame, terminated by a zero byte. A frame that got
+//! damaged on the way is skipped and counted, and the next one is decoded as if nothing happened.
+
+use std::io::{self, Bytes, Read};
+
+use postcard::from_bytes_cobs;
+use raytracer_weekend_lib::ProgressMessage;
+
+const FRAME_DELIMITER: u8 = 0;
+
+pub struct MessageReader<R> {
+    bytes: Bytes<R>,
+    damaged_frames: usize,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            bytes: inner.bytes(),
+            damaged_frames: 0,
+        }
+    }
+
+    /// The next message that could be decoded, or `None` once the stream ends.
+    pub fn next_message(&mut self) -> io::Result<Option<ProgressMessage>> {
+        loop {
+            let mut frame = Vec::new();
+            let mut is_terminated = false;
+
+            for byte in self.bytes.by_ref() {
+                let byte = byte?;
+                if byte == FRAME_DELIMITER {
+                    is_terminated = true;
+                    break;
+                }
+                frame.push(byte);
+            }
+
+            if !is_terminated {
+                // Whatever came after the last delimiter lost its end.
+                if !frame.is_empty() {
+                    self.damaged_frames += 1;
+                }
+                return Ok(None);
+            }
+
+            match from_bytes_cobs::<ProgressMessage>(&mut frame) {
+                Ok(message) => return Ok(Some(message)),
+                Err(_) => self.damaged_frames += 1,
+            }
+        }
+    }
+
+    /// How many frames were skipped so far because they couldn't be decoded. Damage that still
+    /// decodes, like a flipped bit in a color, goes unnoticed.
+    pub fn damaged_frames(&self) -> usize {
+        self.damaged_frames
+    }
+}
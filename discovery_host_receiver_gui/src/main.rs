@@ -6,10 +6,10 @@ use dioxus::{
     core::exports::futures_channel::mpsc::{unbounded, UnboundedReceiver},
     prelude::*,
 };
-use image::Rgb;
+use image::{GenericImageView, Rgb};
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use postcard::from_bytes_cobs;
-use raytracer_weekend_lib::{Pixel, ProgressMessage};
+use raytracer_weekend_lib::{tonemap::Tonemap, vec3::Color, Pixel, ProgressMessage};
 use tokio_serial::{ClearBuffer, SerialPort};
 
 fn main() {
@@ -94,45 +94,46 @@ fn serial_rx_loop(serial_port: Box<impl SerialPort + ?Sized>) -> ! {
                 width,
                 height,
                 samples_per_pixel,
+                max_depth,
             } => {
-                let progress_bar = ProgressBar::new((width * height) as u64);
+                let progress_bar =
+                    ProgressBar::new((width * height) as u64 * samples_per_pixel as u64);
                 progress_bar.set_style(ProgressStyle::default_bar().template(
                     "[{elapsed_precise} / {eta_precise}/ {duration_precise}] {wide_bar:cyan/blue} {pos:>7}/{len:7} {msg}",
                 ));
                 progress_bar.set_position(0);
+                progress_bar.set_message(format!("max depth {}", max_depth));
 
                 state = Some((
                     image::DynamicImage::new_rgb8(width, height),
+                    vec![(Color::new(0.0, 0.0, 0.0), 0); (width * height) as usize],
                     progress_bar,
-                    samples_per_pixel,
                 ));
             }
-            ProgressMessage::Pixel(Pixel { row, column, color }) => {
-                let Some((img, progress_bar, samples_per_pixel)) = state.as_mut() else {
+            ProgressMessage::Pixel(Pixel {
+                row,
+                column,
+                color,
+                sample_count,
+                ..
+            }) => {
+                let Some((img, accumulator, progress_bar)) = state.as_mut() else {
                     continue;
                 };
 
-                let r = color.x();
-                let g = color.y();
-                let b = color.z();
-
-                // Divide the color by the number of samples and gamma-correct for gamma=2.0.
-                let scale = 1.0 / *samples_per_pixel as f32;
-                let r = (scale * r).sqrt();
-                let g = (scale * g).sqrt();
-                let b = (scale * b).sqrt();
-
-                let ir = (255.999 * r.clamp(0.0, 0.999)) as u8;
-                let ig = (255.999 * g.clamp(0.0, 0.999)) as u8;
-                let ib = (255.999 * b.clamp(0.0, 0.999)) as u8;
+                // Progressive renders send every pixel once per pass, so sum up all passes so far.
+                let (accumulated_color, accumulated_samples) =
+                    &mut accumulator[(row * img.width() + column) as usize];
+                *accumulated_color += color;
+                *accumulated_samples += sample_count;
 
                 let p = img.as_mut_rgb8().unwrap().get_pixel_mut(column, row);
-                *p = Rgb([ir, ig, ib]);
+                *p = Rgb(Tonemap::default().to_rgb8(*accumulated_color, *accumulated_samples));
 
-                progress_bar.inc(1);
+                progress_bar.inc(sample_count as u64);
             }
             ProgressMessage::ImageEnd => {
-                let Some((img, progress_bar, ..)) = state.as_mut() else {
+                let Some((img, _, progress_bar)) = state.as_mut() else {
                     continue;
                 };
 
@@ -140,6 +141,16 @@ fn serial_rx_loop(serial_port: Box<impl SerialPort + ?Sized>) -> ! {
                 let rotated = img.rotate180();
                 rotated.save("foo.png").unwrap();
             }
+            ProgressMessage::RenderStats(totals) => {
+                println!(
+                    "{} rays ({} shadow rays), {} BVH node tests, {} primitive tests in {} ms",
+                    totals.total_rays,
+                    totals.shadow_rays,
+                    totals.bvh_tests,
+                    totals.primitive_tests,
+                    totals.elapsed_ms
+                );
+            }
         }
     }
 }
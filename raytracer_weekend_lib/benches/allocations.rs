@@ -0,0 +1,168 @@
+//! Counts heap allocations made while rendering, to catch allocations sneaking into the inner
+//! loop.
+//!
+//! Every scene is rendered twice on a single thread, over the same small image but with
+//! different sample counts. Setup costs like building the world and starting the thread pool
+//! cancel out in the difference, which leaves the allocations per sample. Scenes without meshes
+//! or image textures have to report zero, the bench fails otherwise.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rand::prelude::*;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use raytracer_weekend_lib::{
+    background::Background,
+    camera::Camera,
+    hittable::{
+        rectangular::{Cuboid, XZRectangle},
+        spherical::Sphere,
+        volumes::ConstantMedium,
+        Hittable,
+    },
+    light_source::DiffuseLight,
+    material::{Dielectric, Lambertian, Metal},
+    perlin::Perlin,
+    texture::{Noise, SolidColor},
+    vec3::{Color, Point3, Vec3},
+    Raytracer,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const WIDTH: u32 = 8;
+const HEIGHT: u32 = 8;
+const FEW_SAMPLES: u32 = 4;
+const MANY_SAMPLES: u32 = 8;
+
+/// Builds the world of a scene.
+type SceneBuilder = fn(&mut StdRng) -> Vec<Box<dyn Hittable>>;
+
+fn materials_scene(rng: &mut impl Rng) -> Vec<Box<dyn Hittable>> {
+    vec![
+        Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new(Noise::new(Perlin::new(rng), 4.0))),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            1.0,
+            Box::new(Dielectric::new(1.5)),
+        )),
+        Box::new(Sphere::new(
+            Point3::new(-4.0, 1.0, 0.0),
+            1.0,
+            Box::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.1)),
+        )),
+        Box::new(XZRectangle::new(
+            -2.0,
+            2.0,
+            -2.0,
+            2.0,
+            6.0,
+            Box::new(DiffuseLight::new(SolidColor::new_rgb(4.0, 4.0, 4.0))),
+        )),
+    ]
+}
+
+fn smoke_scene(_rng: &mut impl Rng) -> Vec<Box<dyn Hittable>> {
+    let boundary = Cuboid::new(
+        Point3::new(-1.0, 0.0, -1.0),
+        Point3::new(1.0, 2.0, 1.0),
+        Box::new(Lambertian::new_solid_color(Color::new(0.73, 0.73, 0.73))),
+    );
+
+    vec![
+        Box::new(ConstantMedium::new(
+            boundary,
+            0.5,
+            SolidColor::new_rgb(0.2, 0.4, 0.9),
+        )),
+        Box::new(XZRectangle::new(
+            -2.0,
+            2.0,
+            -2.0,
+            2.0,
+            6.0,
+            Box::new(DiffuseLight::new(SolidColor::new_rgb(4.0, 4.0, 4.0))),
+        )),
+    ]
+}
+
+fn count_allocations(raytracer: &Raytracer) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    raytracer.render().for_each(drop);
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn main() {
+    ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build_global()
+        .unwrap();
+
+    let cam = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        1.0,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+    let background = Background::from(Color::new(0.7, 0.8, 1.0));
+
+    let scenes: [(&str, SceneBuilder); 2] =
+        [("materials", materials_scene), ("smoke", smoke_scene)];
+
+    for (name, scene) in scenes {
+        let world = scene(&mut StdRng::seed_from_u64(1337));
+
+        let few = Raytracer::new(&world, &cam, &background, WIDTH, HEIGHT, FEW_SAMPLES);
+        let many = Raytracer::new(&world, &cam, &background, WIDTH, HEIGHT, MANY_SAMPLES);
+
+        // Warm up thread locals and the thread pool first.
+        count_allocations(&few);
+
+        let few_allocations = count_allocations(&few);
+        let many_allocations = count_allocations(&many);
+        let extra_samples = (WIDTH * HEIGHT * (MANY_SAMPLES - FEW_SAMPLES)) as f64;
+        let per_sample = many_allocations.saturating_sub(few_allocations) as f64 / extra_samples;
+
+        println!(
+            "{:<10} {:>6} allocations per render, {:.3} per additional sample",
+            name, few_allocations, per_sample
+        );
+        assert_eq!(
+            per_sample, 0.0,
+            "the {} scene allocates while sampling",
+            name
+        );
+    }
+}
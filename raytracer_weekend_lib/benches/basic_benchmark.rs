@@ -1,7 +1,8 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use rand::prelude::*;
 use rayon::{prelude::*, ThreadPoolBuilder};
 use raytracer_weekend_lib::{
+    background::Background,
     bvh::BvhNode,
     camera::Camera,
     hittable::{
@@ -21,7 +22,7 @@ use raytracer_weekend_lib::{
 };
 
 pub fn book2_final_scene(
-    aspect_ratio: f64,
+    aspect_ratio: f32,
     rng: &mut impl Rng,
 ) -> (Vec<Box<dyn Hittable>>, Camera, Color) {
     let mut boxes1: Vec<Box<dyn Hittable>> = Vec::new();
@@ -30,8 +31,8 @@ pub fn book2_final_scene(
     let boxes_per_side = 20;
     for i in 0..boxes_per_side {
         for j in 0..boxes_per_side {
-            let i = i as f64;
-            let j = j as f64;
+            let i = i as f32;
+            let j = j as f32;
 
             let w = 100.0;
             let x0 = -1000.0 + i * w;
@@ -165,13 +166,45 @@ fn criterion_benchmark(c: &mut Criterion) {
         .unwrap();
     let mut rng = StdRng::seed_from_u64(1337);
     let (world, cam, background) = book2_final_scene(16.0 / 9.0, &mut rng);
+    let background = Background::from(background);
 
     c.bench_function("book2_final_scene", |b| {
         b.iter(|| {
-            let raytracer = Raytracer::new(&world, &cam, background, 40, 22, 100);
+            let raytracer = Raytracer::new(&world, &cam, &background, 40, 22, 100);
             raytracer.render().for_each(drop);
         })
     });
+
+    let mut group = c.benchmark_group("book2_final_scene_tiled");
+    for tile_size in [8, 16, 32, 64] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(tile_size),
+            &tile_size,
+            |b, &tile_size| {
+                b.iter(|| {
+                    let raytracer = Raytracer::new(&world, &cam, &background, 40, 22, 100);
+                    raytracer.render_tiled(tile_size).for_each(drop);
+                })
+            },
+        );
+    }
+    group.finish();
+
+    // Parallel efficiency is the single thread time divided by threads × the time measured here.
+    let mut group = c.benchmark_group("book2_final_scene_threads");
+    for threads in [1, 8, 16, 32] {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &pool, |b, pool| {
+            b.iter(|| {
+                let raytracer = Raytracer::new(&world, &cam, &background, 40, 22, 100);
+                pool.install(|| raytracer.render().for_each(drop));
+            })
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
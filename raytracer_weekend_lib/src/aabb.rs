@@ -1,11 +1,17 @@
 ///! An implementation of an Axis-Aligned Bounding Box (AABB)
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use {
-    super::{ray::Ray, vec3::Point3},
+    super::{
+        ray::Ray,
+        vec3::{Point3, Vec3},
+    },
     core::mem::swap,
     derive_more::Constructor,
 };
 
 #[derive(Constructor, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Aabb {
     minimum: Point3,
     maximum: Point3,
@@ -20,6 +26,31 @@ impl Aabb {
         self.maximum
     }
 
+    pub fn surface_area(&self) -> f32 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.x() * d.z())
+    }
+
+    pub fn volume(&self) -> f32 {
+        let d = self.maximum - self.minimum;
+        d.x() * d.y() * d.z()
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        self.minimum.midpoint(&self.maximum)
+    }
+
+    /// Whether `p` lies strictly inside the box, i.e. not on its faces.
+    pub fn contains(&self, p: &Point3) -> bool {
+        (0..3).all(|a| self.minimum[a] < p[a] && p[a] < self.maximum[a])
+    }
+
+    /// The box grown by `amount` on every side. Negative amounts shrink it.
+    pub fn expand(&self, amount: f32) -> Aabb {
+        let margin = Vec3::new(amount, amount, amount);
+        Aabb::new(self.minimum - margin, self.maximum + margin)
+    }
+
     pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
         let minimum = self.minimum;
         let maximum = self.maximum;
@@ -87,3 +118,67 @@ impl Aabb {
         Aabb::new(small, big)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::assert_near;
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn unit_cube_measures() {
+        assert_eq!(unit_cube().surface_area(), 6.0);
+        assert_eq!(unit_cube().volume(), 1.0);
+    }
+
+    #[test]
+    fn measures_boxes_with_unequal_sides() {
+        let aabb = Aabb::new(Point3::new(-1.0, 0.0, 2.0), Point3::new(1.0, 3.0, 6.0));
+
+        // 2 × (2 × 3 + 3 × 4 + 2 × 4)
+        assert_eq!(aabb.surface_area(), 52.0);
+        assert_eq!(aabb.volume(), 24.0);
+        assert_near(aabb.centroid(), Point3::new(0.0, 1.5, 4.0));
+    }
+
+    #[test]
+    fn centroid_is_the_center() {
+        let aabb = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 2.0, 2.0));
+        assert_near(aabb.centroid(), Point3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn contains_only_points_strictly_inside() {
+        let cube = unit_cube();
+
+        assert!(cube.contains(&Point3::new(0.5, 0.5, 0.5)));
+        assert!(cube.contains(&Point3::new(0.999, 0.001, 0.5)));
+        assert!(!cube.contains(&Point3::new(0.0, 0.5, 0.5)));
+        assert!(!cube.contains(&Point3::new(0.5, 1.0, 0.5)));
+        assert!(!cube.contains(&Point3::new(0.5, 0.5, 1.5)));
+        assert!(!cube.contains(&Point3::new(-0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn expand_grows_every_side() {
+        let expanded = unit_cube().expand(0.1);
+
+        assert_near(expanded.min(), Point3::new(-0.1, -0.1, -0.1));
+        assert_near(expanded.max(), Point3::new(1.1, 1.1, 1.1));
+        // Six faces of 1.2 × 1.2 instead of 1 × 1.
+        assert!((expanded.surface_area() - unit_cube().surface_area() - 2.64).abs() < 1e-5);
+        assert!(expanded.contains(&Point3::new(1.05, 0.5, -0.05)));
+    }
+
+    #[test]
+    fn expand_by_a_negative_amount_shrinks() {
+        let shrunk = unit_cube().expand(-0.25);
+
+        assert_near(shrunk.min(), Point3::new(0.25, 0.25, 0.25));
+        assert_near(shrunk.max(), Point3::new(0.75, 0.75, 0.75));
+        assert!((shrunk.volume() - 0.125).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,145 @@
+//! Adaptive sampling, which stops sampling pixels as soon as their color has settled.
+
+use crate::{
+    clamp_fireflies, vec3::Color, ActiveRng, Pixel, Raytracer, RenderIterator, TileIterator,
+};
+
+/// Renders like its inner [`Raytracer`], but takes between `min_samples` and `max_samples`
+/// samples per pixel, depending on how noisy the pixel is.
+///
+/// A pixel counts as converged once the variance of its mean color, relative to the squared
+/// length of that mean, drops below `variance_threshold`. Pixels that come out exactly the same
+/// every sample, such as the background, stop right at `min_samples`. The number of samples a
+/// pixel ended up with is its [`Pixel::sample_count`].
+pub struct AdaptiveRaytracer<'a> {
+    inner: Raytracer<'a>,
+    min_samples: u32,
+    max_samples: u32,
+    variance_threshold: f32,
+}
+
+impl<'a> AdaptiveRaytracer<'a> {
+    pub fn new(
+        inner: Raytracer<'a>,
+        min_samples: u32,
+        max_samples: u32,
+        variance_threshold: f32,
+    ) -> Self {
+        // A single sample says nothing about the variance.
+        assert!(2 <= min_samples && min_samples <= max_samples);
+        assert!(variance_threshold >= 0.0);
+
+        Self {
+            inner,
+            min_samples,
+            max_samples,
+            variance_threshold,
+        }
+    }
+
+    pub fn render(&self) -> impl RenderIterator + '_ {
+        self.inner
+            .render_pixels_with(0, move |j, i, rng| self.sample_pixel(j, i, rng))
+    }
+
+    /// See [`Raytracer::render_tiles`].
+    pub fn render_tiles(&self, tile_size: u32) -> impl TileIterator + '_ {
+        self.inner
+            .render_tiles_with(tile_size, move |j, i, rng| self.sample_pixel(j, i, rng))
+    }
+
+    pub fn tile_count(&self, tile_size: u32) -> u32 {
+        self.inner.tile_count(tile_size)
+    }
+
+    fn sample_pixel(&self, pixel_row: u32, pixel_column: u32, rng: &mut ActiveRng) -> Pixel {
+        // Welford's online algorithm, per channel.
+        let mut mean = Color::new(0.0, 0.0, 0.0);
+        let mut squared_deviations = Color::new(0.0, 0.0, 0.0);
+        let mut sample_count = 0;
+
+        while sample_count < self.max_samples {
+            let sample = match self.inner.camera_ray(pixel_row, pixel_column, rng) {
+                Some(r) => clamp_fireflies(
+                    self.inner
+                        .sample_ray(&r, rng, self.inner.max_depth, 1.0, None),
+                    self.inner.max_luminance(),
+                ),
+                None => Color::new(0.0, 0.0, 0.0),
+            };
+
+            sample_count += 1;
+            let deviation = sample - mean;
+            mean += deviation / sample_count as f32;
+            squared_deviations += deviation * (sample - mean);
+
+            if sample_count >= self.min_samples
+                && self.has_converged(mean, squared_deviations, sample_count)
+            {
+                break;
+            }
+        }
+
+        Pixel {
+            row: pixel_row,
+            column: pixel_column,
+            color: sample_count as f32 * mean,
+            sample_count,
+            aux: self.inner.pixel_aux(pixel_row, pixel_column, rng),
+        }
+    }
+
+    fn has_converged(&self, mean: Color, squared_deviations: Color, sample_count: u32) -> bool {
+        let variance = squared_deviations / (sample_count - 1) as f32;
+        let variance_of_mean = (variance.x() + variance.y() + variance.z()) / sample_count as f32;
+        if variance_of_mean <= 0.0 {
+            return true;
+        }
+
+        variance_of_mean / mean.length_squared() < self.variance_threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, vec::Vec};
+
+    use super::*;
+    use crate::{
+        background::Background,
+        camera::Camera,
+        hittable::Hittable,
+        vec3::{assert_near, Point3, Vec3},
+    };
+
+    #[test]
+    fn pixels_on_a_uniform_background_stop_at_min_samples() {
+        let world: Vec<Box<dyn Hittable>> = Vec::new();
+        let cam = Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let sky = Color::new(0.5, 0.7, 1.0);
+        let background = Background::SolidColor(sky);
+        let raytracer = AdaptiveRaytracer::new(
+            Raytracer::new(&world, &cam, &background, 4, 4, 1),
+            4,
+            256,
+            1e-4,
+        );
+
+        let pixels: Vec<Pixel> = raytracer.render().collect();
+        assert_eq!(pixels.len(), 16);
+        for pixel in pixels {
+            assert_eq!(pixel.sample_count, 4);
+            assert_near(pixel.color / 4.0, sky);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "std")]
+//! Load-time checks for non-finite or out-of-range values in textures and meshes.
+
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+
+/// How loaders treat NaN, infinite or absurdly large values in asset files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetCheck {
+    /// Replace broken values with plausible ones and report how many were touched.
+    Repair,
+    /// Refuse to load assets containing broken values.
+    Strict,
+    /// Skip the scan entirely, e.g. for baked assets that are known to be clean.
+    Trusted,
+}
+
+#[derive(Debug)]
+pub struct InvalidAssetData {
+    pub path: String,
+    pub what: &'static str,
+    pub count: usize,
+}
+
+impl Display for InvalidAssetData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}: {} {} contain non-finite or out-of-range values",
+            self.path, self.count, self.what
+        )
+    }
+}
+
+impl std::error::Error for InvalidAssetData {}
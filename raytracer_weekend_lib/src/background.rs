@@ -0,0 +1,103 @@
+use crate::vec3::{Color, Vec3};
+#[cfg(feature = "image")]
+use crate::{hittable::spherical::get_sphere_uv, image_texture::ImageTexture, texture::Texture};
+
+/// What rays see when they leave the scene without hitting anything.
+#[derive(Debug, Clone)]
+pub enum Background {
+    SolidColor(Color),
+    /// The sky from the first book, blending from `bottom` straight down to `top` straight up.
+    VerticalGradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// A sky with separate colors straight up, at the horizon and straight down, blended by the
+    /// height of the direction above or below the horizon.
+    GradientSky {
+        zenith: Color,
+        horizon: Color,
+        nadir: Color,
+    },
+    /// An equirectangular (latitude-longitude) image wrapped around the scene, using the same
+    /// mapping as textured spheres.
+    #[cfg(feature = "image")]
+    EnvironmentMap(ImageTexture),
+}
+
+impl Background {
+    pub fn sample(&self, direction: Vec3) -> Color {
+        match self {
+            Self::SolidColor(color) => *color,
+            Self::VerticalGradient { bottom, top } => {
+                let t = 0.5 * (direction.unit_vector().y() + 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Self::GradientSky {
+                zenith,
+                horizon,
+                nadir,
+            } => {
+                let height = direction.unit_vector().y();
+                let (pole, t) = match height >= 0.0 {
+                    true => (zenith, height),
+                    false => (nadir, -height),
+                };
+                (1.0 - t) * *horizon + t * *pole
+            }
+            #[cfg(feature = "image")]
+            Self::EnvironmentMap(texture) => {
+                let direction = direction.unit_vector();
+                texture.value(get_sphere_uv(&direction), &direction)
+            }
+        }
+    }
+}
+
+impl From<Color> for Background {
+    fn from(color: Color) -> Self {
+        Self::SolidColor(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::assert_near;
+
+    fn sky() -> Background {
+        Background::GradientSky {
+            zenith: Color::new(0.1, 0.3, 0.9),
+            horizon: Color::new(0.8, 0.85, 0.9),
+            nadir: Color::new(0.2, 0.15, 0.1),
+        }
+    }
+
+    #[test]
+    fn gradient_sky_is_exactly_the_horizon_color_at_the_horizon() {
+        for direction in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-3.0, 0.0, 4.0),
+            Vec3::new(0.0, 0.0, -0.01),
+        ] {
+            let color = sky().sample(direction);
+            assert_eq!(
+                [color.x(), color.y(), color.z()],
+                [0.8, 0.85, 0.9],
+                "looking along {}",
+                direction
+            );
+        }
+    }
+
+    #[test]
+    fn gradient_sky_reaches_the_zenith_and_nadir_colors_at_the_poles() {
+        assert_near(
+            sky().sample(Vec3::new(0.0, 2.0, 0.0)),
+            Color::new(0.1, 0.3, 0.9),
+        );
+        assert_near(
+            sky().sample(Vec3::new(0.0, -2.0, 0.0)),
+            Color::new(0.2, 0.15, 0.1),
+        );
+    }
+}
@@ -4,6 +4,8 @@ use core::{cmp::Ordering, fmt::Debug};
 use rand::prelude::Rng;
 
 use super::{aabb::Aabb, hittable::Hittable};
+#[cfg(feature = "stats")]
+use crate::stats::{self, Counter};
 use crate::{hittable::HitRecord, ray::Ray, ActiveRng};
 
 ///! An implementation of a Boundary Volume Hierarchy thingamajig.
@@ -97,8 +99,30 @@ impl BvhNode {
     }
 }
 
+/// Builds a world list out of `objects`, with everything that has a bounding box inside one
+/// [`BvhNode`]. Unbounded objects, like infinite planes, can't go into the hierarchy and are kept
+/// next to it, to be tested one after another.
+pub fn build_world(
+    objects: Vec<Box<dyn Hittable>>,
+    time0: f32,
+    time1: f32,
+    rng: &mut impl Rng,
+) -> Vec<Box<dyn Hittable>> {
+    let (bounded, mut world): (Vec<_>, Vec<_>) = objects
+        .into_iter()
+        .partition(|object| object.bounding_box(time0, time1).is_some());
+
+    if !bounded.is_empty() {
+        world.push(Box::new(BvhNode::new(bounded, time0, time1, rng)));
+    }
+
+    world
+}
+
 impl Hittable for BvhNode {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord<'_>> {
+        #[cfg(feature = "stats")]
+        stats::record(Counter::BvhNodeTests);
         if !self.bounding_box.hit(r, t_min, t_max) {
             return None;
         };
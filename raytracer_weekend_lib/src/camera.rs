@@ -1,12 +1,29 @@
+use alloc::vec::Vec;
+use core::ops::{Add, Mul, Sub};
+
+use derive_more::Constructor;
 #[cfg(feature = "no_std")]
 use micromath::F32Ext;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 use super::{
     ray::Ray,
     vec3::{Point3, Vec3},
 };
 
+/// Turns screen coordinates into camera rays. `s` and `t` run from 0 at the bottom left to 1 at
+/// the top right of the image.
+pub trait Projection: Sync + Send {
+    /// The ray for screen coordinates `s` and `t`, or `None` where the projection doesn't cover
+    /// the image, which then stays black.
+    fn get_ray(&self, s: f32, t: f32, rng: &mut dyn RngCore) -> Option<Ray>;
+
+    /// The point that appears at screen coordinates `s` and `t` at `distance` in front of the
+    /// camera, measured along the viewing direction.
+    fn screen_point(&self, s: f32, t: f32, distance: f32) -> Point3;
+}
+
+#[derive(Debug)]
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
@@ -14,7 +31,7 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    _w: Vec3,
+    w: Vec3,
     lens_radius: f32,
     time0: f32,
     time1: f32,
@@ -56,13 +73,26 @@ impl Camera {
             vertical,
             u,
             v,
-            _w: w,
+            w,
             lens_radius,
             time0,
             time1,
         }
     }
 
+    /// The point that appears at screen coordinates `s` and `t` (as passed to [`Camera::get_ray`])
+    /// at `distance` in front of the camera, measured along the viewing direction.
+    ///
+    /// Points placed this way are always screen-aligned, which makes it easy to overlay things on
+    /// the render and to know exactly which pixels they cover.
+    pub fn screen_point(&self, s: f32, t: f32, distance: f32) -> Point3 {
+        let direction =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let focus_dist = -direction.dot(&self.w);
+
+        self.origin + (distance / focus_dist) * direction
+    }
+
     pub fn get_ray(&self, s: f32, t: f32, rng: &mut impl Rng) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk(rng);
         let offset = self.u * rd.x() + self.v * rd.y();
@@ -73,3 +103,459 @@ impl Camera {
         )
     }
 }
+
+impl Projection for Camera {
+    fn get_ray(&self, s: f32, t: f32, mut rng: &mut dyn RngCore) -> Option<Ray> {
+        Some(Camera::get_ray(self, s, t, &mut rng))
+    }
+
+    fn screen_point(&self, s: f32, t: f32, distance: f32) -> Point3 {
+        Camera::screen_point(self, s, t, distance)
+    }
+}
+
+/// A camera with parallel rays, as used for technical drawings. Objects keep their size no
+/// matter how far away they are.
+///
+/// `width` and `height` are the size of the visible area in scene units. Everything is in focus.
+pub struct OrthoCamera {
+    lower_left_corner: Point3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    direction: Vec3,
+    time0: f32,
+    time1: f32,
+}
+
+impl OrthoCamera {
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        up_vector: Vec3,
+        width: f32,
+        height: f32,
+        time0: f32,
+        time1: f32,
+    ) -> Self {
+        let w = (look_from - look_at).unit_vector();
+        let u = up_vector.cross(&w).unit_vector();
+        let v = w.cross(&u);
+
+        let horizontal = width * u;
+        let vertical = height * v;
+        let lower_left_corner = look_from - horizontal / 2.0 - vertical / 2.0;
+
+        Self {
+            lower_left_corner,
+            horizontal,
+            vertical,
+            direction: -w,
+            time0,
+            time1,
+        }
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32, rng: &mut impl Rng) -> Ray {
+        Ray::new(
+            self.lower_left_corner + s * self.horizontal + t * self.vertical,
+            self.direction,
+            rng.gen_range(self.time0..self.time1),
+        )
+    }
+}
+
+impl Projection for OrthoCamera {
+    fn get_ray(&self, s: f32, t: f32, mut rng: &mut dyn RngCore) -> Option<Ray> {
+        Some(OrthoCamera::get_ray(self, s, t, &mut rng))
+    }
+
+    fn screen_point(&self, s: f32, t: f32, distance: f32) -> Point3 {
+        self.lower_left_corner + s * self.horizontal + t * self.vertical + distance * self.direction
+    }
+}
+
+/// A fisheye lens with equidistant projection: the angle between a ray and the viewing direction
+/// grows linearly with its distance from the image center.
+///
+/// The image circle touches the top and bottom of the image and spans `field_of_view` degrees,
+/// up to a full 360 degrees. The corners outside of it see nothing. Everything is in focus.
+pub struct FisheyeCamera {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    half_field_of_view: f32,
+    aspect_ratio: f32,
+    time0: f32,
+    time1: f32,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        up_vector: Vec3,
+        field_of_view: f32,
+        aspect_ratio: f32,
+        time0: f32,
+        time1: f32,
+    ) -> Self {
+        assert!(0.0 < field_of_view && field_of_view <= 360.0);
+
+        let w = (look_from - look_at).unit_vector();
+        let u = up_vector.cross(&w).unit_vector();
+        let v = w.cross(&u);
+
+        Self {
+            origin: look_from,
+            u,
+            v,
+            w,
+            half_field_of_view: field_of_view.to_radians() / 2.0,
+            aspect_ratio,
+            time0,
+            time1,
+        }
+    }
+
+    /// The direction through screen coordinates `s` and `t`, and whether that is inside the
+    /// image circle.
+    fn direction(&self, s: f32, t: f32) -> (Vec3, bool) {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+
+        let radius = (x * x + y * y).sqrt();
+        let azimuth = y.atan2(x);
+        let polar = radius.min(1.0) * self.half_field_of_view;
+
+        let direction =
+            polar.sin() * (azimuth.cos() * self.u + azimuth.sin() * self.v) - polar.cos() * self.w;
+
+        (direction, radius <= 1.0)
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32, rng: &mut impl Rng) -> Option<Ray> {
+        let (direction, is_inside) = self.direction(s, t);
+        if !is_inside {
+            return None;
+        }
+
+        Some(Ray::new(
+            self.origin,
+            direction,
+            rng.gen_range(self.time0..self.time1),
+        ))
+    }
+}
+
+impl Projection for FisheyeCamera {
+    fn get_ray(&self, s: f32, t: f32, mut rng: &mut dyn RngCore) -> Option<Ray> {
+        FisheyeCamera::get_ray(self, s, t, &mut rng)
+    }
+
+    /// Measures `distance` along the ray rather than the viewing direction, since rays of wide
+    /// fisheyes may point sideways or even backwards.
+    fn screen_point(&self, s: f32, t: f32, distance: f32) -> Point3 {
+        self.origin + distance * self.direction(s, t).0
+    }
+}
+
+/// Where a [`CameraPath`] is at a given animation time.
+#[derive(Debug, Clone, Copy, Constructor)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub up_vector: Vec3,
+    pub vertical_field_of_view: f32,
+    pub aperture: f32,
+    pub focus_distance: f32,
+}
+
+impl CameraKeyframe {
+    pub fn to_camera(&self, aspect_ratio: f32, time0: f32, time1: f32) -> Camera {
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.up_vector,
+            self.vertical_field_of_view,
+            aspect_ratio,
+            self.aperture,
+            self.focus_distance,
+            time0,
+            time1,
+        )
+    }
+}
+
+/// How a [`CameraPath`] moves between its keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    /// A cubic Bezier curve between every pair of keyframes, with the tangents of a uniform
+    /// Catmull-Rom spline. This avoids the sudden changes of direction linear interpolation has
+    /// at every keyframe.
+    CatmullRom,
+}
+
+/// A camera flight through a scene, expanded into one [`Camera`] per frame.
+///
+/// Every setting of the keyframes is interpolated on its own.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    pub fn new(keyframes: Vec<CameraKeyframe>, interpolation: Interpolation) -> Self {
+        assert!(!keyframes.is_empty());
+        assert!(keyframes.windows(2).all(|pair| pair[0].time < pair[1].time));
+
+        Self {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    /// Spreads `frames` cameras evenly over the time span of the keyframes. The last keyframe
+    /// marks the end of the animation and is not rendered itself, so looping animations don't
+    /// show the same view twice.
+    pub fn to_cameras(
+        &self,
+        frames: usize,
+        aspect_ratio: f32,
+        time0: f32,
+        time1: f32,
+    ) -> Vec<Camera> {
+        let start = self.keyframes[0].time;
+        let duration = self.keyframes[self.keyframes.len() - 1].time - start;
+
+        (0..frames)
+            .map(|frame| {
+                self.camera_at_time(start + duration * frame as f32 / frames as f32)
+                    .to_camera(aspect_ratio, time0, time1)
+            })
+            .collect()
+    }
+
+    /// Like [`CameraPath::to_cameras`], but the shutter of every camera opens at the time of its
+    /// frame and stays open for `shutter`. Objects animated over ray time, like a
+    /// [`KeyframedTranslation`], thus move from frame to frame, rather than only within each.
+    ///
+    /// [`KeyframedTranslation`]: crate::hittable::transformations::KeyframedTranslation
+    pub fn to_cameras_with_shutter(
+        &self,
+        frames: usize,
+        aspect_ratio: f32,
+        shutter: f32,
+    ) -> Vec<Camera> {
+        let start = self.keyframes[0].time;
+        let duration = self.keyframes[self.keyframes.len() - 1].time - start;
+
+        (0..frames)
+            .map(|frame| {
+                let time = start + duration * frame as f32 / frames as f32;
+                self.camera_at_time(time)
+                    .to_camera(aspect_ratio, time, time + shutter)
+            })
+            .collect()
+    }
+
+    /// The interpolated camera settings at `time`. At the time of a keyframe, that keyframe's
+    /// settings are reproduced exactly. Before the first and after the last keyframe, the camera
+    /// stands still.
+    pub fn camera_at_time(&self, time: f32) -> CameraKeyframe {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return CameraKeyframe { time, ..*first };
+        }
+        if time >= last.time {
+            return CameraKeyframe { time, ..*last };
+        }
+
+        // The keyframes are sorted and the time lies strictly inside their span, so there is a
+        // keyframe on either side of it.
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap();
+        let previous = next - 1;
+
+        let k1 = &self.keyframes[previous];
+        let k2 = &self.keyframes[next];
+        let s = ((time - k1.time) / (k2.time - k1.time)).clamp(0.0, 1.0);
+
+        // The outer control points of the spline repeat the segment ends at the path's ends.
+        let k0 = &self.keyframes[previous.saturating_sub(1)];
+        let k3 = &self.keyframes[(next + 1).min(self.keyframes.len() - 1)];
+
+        let interpolation = self.interpolation;
+        let blend = |value: fn(&CameraKeyframe) -> Vec3| {
+            interpolate(interpolation, [k0, k1, k2, k3].map(value), s)
+        };
+        let blend_scalar = |value: fn(&CameraKeyframe) -> f32| {
+            interpolate(interpolation, [k0, k1, k2, k3].map(value), s)
+        };
+
+        CameraKeyframe {
+            time,
+            look_from: blend(|k| k.look_from),
+            look_at: blend(|k| k.look_at),
+            up_vector: blend(|k| k.up_vector),
+            vertical_field_of_view: blend_scalar(|k| k.vertical_field_of_view),
+            aperture: blend_scalar(|k| k.aperture).max(0.0),
+            focus_distance: blend_scalar(|k| k.focus_distance),
+        }
+    }
+}
+
+/// Interpolates between the middle two of four consecutive `points`.
+pub(crate) fn interpolate<T>(interpolation: Interpolation, points: [T; 4], s: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T>,
+    f32: Mul<T, Output = T>,
+{
+    let [p0, p1, p2, p3] = points;
+
+    match interpolation {
+        Interpolation::Linear => p1 + s * (p2 - p1),
+        Interpolation::CatmullRom => {
+            let a = 2.0 * p1;
+            let b = p2 - p0;
+            let c = 2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3;
+            let d = 3.0 * (p1 - p2) + p3 - p0;
+
+            0.5 * (a + s * b + s.powi(2) * c + s.powi(3) * d)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{vec3::assert_near, ActiveRng};
+
+    /// Looks down the negative z axis at a 4 by 2 area.
+    fn ortho_camera() -> OrthoCamera {
+        OrthoCamera::new(
+            Point3::new(1.0, 2.0, 5.0),
+            Point3::new(1.0, 2.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            4.0,
+            2.0,
+            0.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = ortho_camera();
+        let mut rng = ActiveRng::seed_from_u64(0);
+
+        for (s, t) in [(0.0, 0.0), (0.25, 0.75), (0.5, 0.5), (1.0, 0.1), (1.0, 1.0)] {
+            let ray = camera.get_ray(s, t, &mut rng);
+            assert_near(ray.direction(), Vec3::new(0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn orthographic_corners_map_to_the_corners_of_the_view() {
+        let camera = ortho_camera();
+        let mut rng = ActiveRng::seed_from_u64(0);
+
+        let lower_left = camera.get_ray(0.0, 0.0, &mut rng).origin();
+        let upper_right = camera.get_ray(1.0, 1.0, &mut rng).origin();
+        let center = camera.get_ray(0.5, 0.5, &mut rng).origin();
+
+        assert_near(lower_left, Point3::new(-1.0, 1.0, 5.0));
+        assert_near(upper_right, Point3::new(3.0, 3.0, 5.0));
+        assert_near(center, Point3::new(1.0, 2.0, 5.0));
+    }
+
+    fn keyframes() -> Vec<CameraKeyframe> {
+        vec![
+            CameraKeyframe::new(
+                0.0,
+                Point3::new(0.1, 0.2, 0.3),
+                Point3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                40.0,
+                0.1,
+                3.0,
+            ),
+            CameraKeyframe::new(
+                0.3,
+                Point3::new(5.0, 1.0, -2.0),
+                Point3::new(1.0, 1.0, 1.0),
+                Vec3::new(0.1, 1.0, 0.0),
+                60.0,
+                0.0,
+                7.0,
+            ),
+            CameraKeyframe::new(
+                0.7,
+                Point3::new(-3.0, 4.0, 2.0),
+                Point3::new(0.0, 2.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.2),
+                25.0,
+                0.3,
+                5.0,
+            ),
+            CameraKeyframe::new(
+                1.0,
+                Point3::new(0.7, 0.9, 1.3),
+                Point3::new(0.5, -0.5, 0.25),
+                Vec3::new(0.0, 0.9, 0.1),
+                33.0,
+                0.2,
+                4.5,
+            ),
+        ]
+    }
+
+    fn components(keyframe: &CameraKeyframe) -> [f32; 13] {
+        let CameraKeyframe {
+            time,
+            look_from: f,
+            look_at: a,
+            up_vector: u,
+            vertical_field_of_view,
+            aperture,
+            focus_distance,
+        } = *keyframe;
+
+        [
+            time,
+            f.x(),
+            f.y(),
+            f.z(),
+            a.x(),
+            a.y(),
+            a.z(),
+            u.x(),
+            u.y(),
+            u.z(),
+            vertical_field_of_view,
+            aperture,
+            focus_distance,
+        ]
+    }
+
+    #[test]
+    fn camera_paths_start_and_end_exactly_at_their_outer_keyframes() {
+        let keyframes = keyframes();
+        let first = &keyframes[0];
+        let last = &keyframes[keyframes.len() - 1];
+
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let path = CameraPath::new(keyframes.clone(), interpolation);
+
+            assert_eq!(components(&path.camera_at_time(0.0)), components(first));
+            assert_eq!(components(&path.camera_at_time(1.0)), components(last));
+        }
+    }
+}
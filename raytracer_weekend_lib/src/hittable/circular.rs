@@ -0,0 +1,188 @@
+use alloc::boxed::Box;
+use core::f32::consts::PI;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{infinite_plane::intersect_plane, HitRecord, Hittable},
+    light_source::{DiffuseLight, LightSample},
+    material::Material,
+    onb::OrthonormalBase,
+    ray::Ray,
+    texture::{Point2d, SolidColor},
+    vec3::{Color, Point3, Vec3},
+    ActiveRng,
+};
+
+/// A flat, round disk facing along `normal`.
+///
+/// Texture coordinates map the disk onto the unit square, with the center at `(0.5, 0.5)`.
+#[derive(Debug, Clone)]
+pub struct Disk {
+    center: Point3,
+    radius: f32,
+    tangents: OrthonormalBase,
+    material: Box<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, material: Box<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            tangents: OrthonormalBase::from_w(&normal),
+            material,
+        }
+    }
+
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    /// A point on the disk, uniformly distributed over its area.
+    fn random_point(&self, rng: &mut ActiveRng) -> Point3 {
+        let local = self.radius * Vec3::random_in_unit_disk(rng);
+        self.center + self.tangents.to_world(local)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut ActiveRng) -> Option<HitRecord> {
+        let normal = self.tangents.w();
+        let t = intersect_plane(r, &self.center, &normal, t_min, t_max)?;
+
+        let p = r.at(t);
+        let local = self.tangents.to_local(p - self.center);
+        if local.x() * local.x() + local.y() * local.y() > self.radius * self.radius {
+            return None;
+        }
+
+        let texture_uv = Point2d::new(
+            0.5 + 0.5 * local.x() / self.radius,
+            0.5 + 0.5 * local.y() / self.radius,
+        );
+
+        Some(HitRecord::new_with_face_normal(
+            p,
+            t,
+            texture_uv,
+            self.material.as_ref(),
+            r,
+            normal,
+        ))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        // Along each axis, the rim reaches as far as the radius times the sine of the angle
+        // between that axis and the normal. Pad a little so axis-aligned disks aren't flat boxes.
+        let normal = self.tangents.w();
+        let extent = |n: f32| self.radius * (1.0 - n * n).max(0.0).sqrt() + 0.0001;
+        let extent = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        let rec = match self.hit(&ray, 0.001, f32::INFINITY, rng) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+
+        // Convert the uniform density over the area into one over solid angle.
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random_point_towards(&self, _origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.random_point(rng)
+    }
+}
+
+/// A disk that emits `radiance` towards where its normal points, and nothing from its back.
+/// Saves putting together a [`Disk`] and a one-sided [`DiffuseLight`] by hand.
+#[derive(Debug, Clone)]
+pub struct DiskLight {
+    disk: Disk,
+    radiance: Color,
+}
+
+impl DiskLight {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, radiance: Color) -> Self {
+        let material = DiffuseLight::new(SolidColor::new(radiance)).with_two_sided(false);
+
+        Self {
+            disk: Disk::new(center, normal, radius, Box::new(material)),
+            radiance,
+        }
+    }
+
+    /// The light arriving at `point` from a point on the disk picked uniformly by area, or none if
+    /// `point` is behind the disk or in its plane. The light isn't checked for occluders.
+    pub fn sample_toward(&self, point: Point3, rng: &mut ActiveRng) -> Option<LightSample> {
+        let direction = self.disk.random_point(rng) - point;
+        let distance_squared = direction.length_squared();
+        let cosine = -direction.dot(&self.disk.tangents.w()) / distance_squared.sqrt();
+        if cosine <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            t: 1.0,
+            pdf: distance_squared / (cosine * self.disk.area()),
+            radiance: self.radiance,
+        })
+    }
+}
+
+impl Hittable for DiskLight {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        self.disk.hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.disk.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.disk.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.disk.random_point_towards(origin, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_light_pdf_integrates_to_one_over_the_disk() {
+        const SAMPLES: u32 = 200_000;
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let light = DiskLight::new(
+            Point3::new(0.3, 2.0, 0.2),
+            Vec3::new(0.2, -1.0, 0.1),
+            1.0,
+            Color::new(1.0, 1.0, 1.0),
+        );
+
+        // Monte Carlo integration over the sphere of directions, most of which miss the disk.
+        let integral = (0..SAMPLES)
+            .map(|_| {
+                let direction = Vec3::random_unit_vector(&mut rng);
+                light.pdf_value(&origin, &direction, &mut rng) * 4.0 * PI
+            })
+            .sum::<f32>()
+            / SAMPLES as f32;
+
+        assert!((integral - 1.0).abs() < 0.03, "integrates to {}", integral);
+    }
+}
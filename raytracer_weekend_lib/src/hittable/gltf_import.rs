@@ -0,0 +1,273 @@
+#![cfg(feature = "gltf")]
+//! Imports the triangle meshes of glTF 2.0 files.
+//!
+//! Materials are mapped onto the closest ones this renderer has: emissive materials become
+//! [`DiffuseLight`]s, metallic ones [`RoughConductor`]s, translucent ones [`RoughDielectric`]s,
+//! and everything else [`Lambertian`]s, textured with the base color texture if there is one. Node transforms are
+//! baked into the vertices. Every primitive becomes a [`TriangleMesh`], and the primitives of a
+//! mesh share a BVH. Points, lines, strips, fans and skinning are ignored.
+
+use alloc::sync::Arc;
+use std::collections::HashMap;
+
+use gltf::{buffer, image::Format, material::AlphaMode, mesh::Mode, Node};
+use image::{DynamicImage, RgbImage, RgbaImage};
+use rand::Rng;
+
+use crate::{
+    bvh::BvhNode,
+    hittable::{
+        triangle_mesh::{MeshTriangle, TriangleMesh},
+        Hittable,
+    },
+    image_texture::ImageTexture,
+    light_source::DiffuseLight,
+    material::{Lambertian, Material, RoughConductor, RoughDielectric},
+    texture::{Point2d, SolidColor},
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Materials at least this metallic are loaded as metals, all others as diffuse surfaces.
+const METAL_MIN_METALLIC: f32 = 0.5;
+
+/// The index of refraction glTF assumes for all materials.
+const DEFAULT_IOR: f32 = 1.5;
+
+/// Loads the default scene of a `.gltf` or `.glb` file, or its first scene if it doesn't name a
+/// default. All meshes are put into one BVH.
+pub fn load_gltf(
+    path: &str,
+    rng: &mut impl Rng,
+) -> Result<Box<dyn Hittable>, Box<dyn std::error::Error>> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| format!("{} contains no scene", path))?;
+
+    let mut loader = Loader {
+        path,
+        buffers: &buffers,
+        images: &images,
+        materials: HashMap::new(),
+        meshes: Vec::new(),
+    };
+    for node in scene.nodes() {
+        loader.load_node(&node, Transform::IDENTITY, rng);
+    }
+
+    if loader.meshes.is_empty() {
+        return Err(format!("{} contains no triangle meshes", path).into());
+    }
+
+    // TODO: Sort out this time thing
+    Ok(Box::new(BvhNode::new(loader.meshes, 0.0, 1.0, rng)))
+}
+
+struct Loader<'a> {
+    path: &'a str,
+    buffers: &'a [buffer::Data],
+    images: &'a [gltf::image::Data],
+    /// The materials converted so far, by their index in the file. Primitives without a material
+    /// share the one under `None`.
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    meshes: Vec<Box<dyn Hittable>>,
+}
+
+impl Loader<'_> {
+    fn load_node(&mut self, node: &Node, parent_transform: Transform, rng: &mut impl Rng) {
+        let transform = parent_transform.compose(Transform(node.transform().matrix()));
+
+        if let Some(mesh) = node.mesh() {
+            // Every primitive has a single material, so each becomes a mesh of its own.
+            let mut primitives: Vec<Box<dyn Hittable>> = Vec::new();
+            for primitive in mesh.primitives() {
+                if primitive.mode() != Mode::Triangles {
+                    continue;
+                }
+
+                let material = self.material(&primitive.material());
+                let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+                let positions: Vec<Point3> = match reader.read_positions() {
+                    Some(positions) => positions.map(|p| transform.point(p)).collect(),
+                    None => continue,
+                };
+                let normals: Option<Vec<Vec3>> = reader
+                    .read_normals()
+                    .map(|normals| normals.map(|n| transform.normal(n)).collect());
+                // glTF puts the origin of texture coordinates at the top left, not bottom left.
+                let texture_uvs: Option<Vec<Point2d>> = reader.read_tex_coords(0).map(|uvs| {
+                    uvs.into_f32()
+                        .map(|[u, v]| Point2d::new(u, 1.0 - v))
+                        .collect()
+                });
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+
+                let triangles: Vec<MeshTriangle> = indices
+                    .chunks_exact(3)
+                    .map(|corners| MeshTriangle {
+                        vertices: [corners[0], corners[1], corners[2]],
+                        normals: [0, 1, 2].map(|i| normals.as_ref().map(|_| corners[i])),
+                        texture_uvs: [0, 1, 2].map(|i| texture_uvs.as_ref().map(|_| corners[i])),
+                    })
+                    .collect();
+                if triangles.is_empty() {
+                    continue;
+                }
+
+                primitives.push(Box::new(TriangleMesh::new(
+                    positions,
+                    normals.unwrap_or_default(),
+                    texture_uvs.unwrap_or_default(),
+                    triangles,
+                    material,
+                )));
+            }
+
+            match primitives.len() {
+                0 => {}
+                1 => self.meshes.append(&mut primitives),
+                _ => self
+                    .meshes
+                    .push(Box::new(BvhNode::new(primitives, 0.0, 1.0, rng))),
+            }
+        }
+
+        for child in node.children() {
+            self.load_node(&child, transform, rng);
+        }
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Arc<dyn Material> {
+        if let Some(material) = self.materials.get(&material.index()) {
+            return material.clone();
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [red, green, blue, alpha] = pbr.base_color_factor();
+        let base_color = Color::new(red, green, blue);
+        let [red, green, blue] = material.emissive_factor();
+        let emissive = Color::new(red, green, blue);
+        let roughness = pbr.roughness_factor().clamp(0.0, 1.0);
+
+        // Like the OBJ loader's dissolve, blended transparency is taken to mean glass.
+        let converted: Arc<dyn Material> = if emissive.max_component() > 0.0 {
+            Arc::new(DiffuseLight::new(SolidColor::new(emissive)))
+        } else if pbr.metallic_factor() >= METAL_MIN_METALLIC {
+            Arc::new(RoughConductor::new(base_color, roughness, 0.0))
+        } else if material.alpha_mode() == AlphaMode::Blend && alpha < 1.0 {
+            Arc::new(RoughDielectric::new(DEFAULT_IOR, roughness))
+        } else {
+            let texture = pbr
+                .base_color_texture()
+                .and_then(|info| self.texture(info.texture().source().index()));
+            match texture {
+                Some(texture) => Arc::new(Lambertian::new(texture)),
+                None => Arc::new(Lambertian::new(SolidColor::new(base_color))),
+            }
+        };
+
+        self.materials.insert(material.index(), converted.clone());
+        converted
+    }
+
+    /// The image at `index` as a texture, unless it is in a format other than 8 bit RGB(A).
+    fn texture(&self, index: usize) -> Option<ImageTexture> {
+        let data = &self.images[index];
+        let image: DynamicImage = match data.format {
+            Format::R8G8B8 => {
+                RgbImage::from_raw(data.width, data.height, data.pixels.clone())?.into()
+            }
+            Format::R8G8B8A8 => {
+                RgbaImage::from_raw(data.width, data.height, data.pixels.clone())?.into()
+            }
+            _ => return None,
+        };
+
+        let name = format!("{} (image {})", self.path, index);
+        Some(ImageTexture::from_image(image, &name))
+    }
+}
+
+/// An affine transform as a column-major 4×4 matrix, the way glTF stores them.
+#[derive(Clone, Copy)]
+struct Transform([[f32; 4]; 4]);
+
+impl Transform {
+    const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    /// The transform that applies `child` first and `self` after it, i.e. `self · child`.
+    fn compose(&self, child: Self) -> Self {
+        let mut product = [[0.0; 4]; 4];
+        for (column, product_column) in product.iter_mut().enumerate() {
+            for (row, entry) in product_column.iter_mut().enumerate() {
+                *entry = (0..4).map(|k| self.0[k][row] * child.0[column][k]).sum();
+            }
+        }
+
+        Self(product)
+    }
+
+    fn point(&self, [x, y, z]: [f32; 3]) -> Point3 {
+        let m = &self.0;
+        Point3::new(
+            m[0][0] * x + m[1][0] * y + m[2][0] * z + m[3][0],
+            m[0][1] * x + m[1][1] * y + m[2][1] * z + m[3][1],
+            m[0][2] * x + m[1][2] * y + m[2][2] * z + m[3][2],
+        )
+    }
+
+    /// Normals transform with the inverse transpose of the linear part, which is its cofactor
+    /// matrix up to the sign of the determinant.
+    fn normal(&self, [x, y, z]: [f32; 3]) -> Vec3 {
+        let column = |i: usize| Vec3::new(self.0[i][0], self.0[i][1], self.0[i][2]);
+        let (a, b, c) = (column(0), column(1), column(2));
+        let (bc, ca, ab) = (b.cross(&c), c.cross(&a), a.cross(&b));
+        let sign = a.dot(&bc).signum();
+
+        (sign * (x * bc + y * ca + z * ab)).unit_vector()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, ActiveRng};
+
+    #[test]
+    fn box_is_hit_by_rays_aimed_at_the_origin() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/Box.gltf");
+        let mut rng = ActiveRng::seed_from_u64(0);
+        let unit_box = load_gltf(path, &mut rng).unwrap();
+
+        for origin in [
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::new(3.0, 4.0, 0.0),
+            Point3::new(-2.0, -1.5, -3.5),
+        ] {
+            let ray = Ray::new(origin, -origin, 0.0);
+            let rec = unit_box
+                .hit(&ray, 0.001, f32::INFINITY, &mut rng)
+                .unwrap_or_else(|| panic!("{:?} missed the box", ray));
+
+            // The box spans -0.5 to 0.5 along every axis, so the hit is on one of its faces.
+            let p = rec.p;
+            let extent = p.x().abs().max(p.y().abs()).max(p.z().abs());
+            assert!((extent - 0.5).abs() < 1e-4, "hit at {}", p);
+            assert!(rec.normal.dot(&ray.direction()) < 0.0);
+            assert_eq!(
+                rec.material.type_name(),
+                core::any::type_name::<Lambertian<SolidColor>>()
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use alloc::boxed::Box;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    onb::OrthonormalBase,
+    ray::Ray,
+    texture::Point2d,
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// Rays running this close to parallel to the plane are treated as missing it.
+const EPSILON: f32 = 1e-8;
+
+/// Where `r` crosses the plane through `point` with the given `normal`, if that is within range.
+pub(super) fn intersect_plane(
+    r: &Ray,
+    point: &Point3,
+    normal: &Vec3,
+    t_min: f32,
+    t_max: f32,
+) -> Option<f32> {
+    let denominator = r.direction().dot(normal);
+    if denominator.abs() < EPSILON {
+        return None;
+    }
+
+    let t = (*point - r.origin()).dot(normal) / denominator;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some(t)
+}
+
+/// A plane extending infinitely in all directions, defined by a point on it and its normal.
+///
+/// Unlike the usual huge ground sphere, a plane stays perfectly flat even at glancing angles.
+/// Since it has no finite bounding box, it cannot be placed inside a [`BvhNode`](crate::bvh::BvhNode)
+/// and has to live in the top-level world list instead, see [`crate::bvh::build_world`].
+///
+/// The texture coordinates are the distances from `point` along two tangents of the plane, so
+/// they are not limited to `0.0..=1.0`.
+#[derive(Debug)]
+pub struct InfinitePlane {
+    point: Point3,
+    tangents: OrthonormalBase,
+    material: Box<dyn Material>,
+}
+
+impl InfinitePlane {
+    pub fn new(point: Point3, normal: Vec3, material: Box<dyn Material>) -> Self {
+        Self {
+            point,
+            tangents: OrthonormalBase::from_w(&normal),
+            material,
+        }
+    }
+}
+
+impl Hittable for InfinitePlane {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut ActiveRng) -> Option<HitRecord> {
+        let normal = self.tangents.w();
+        let t = intersect_plane(r, &self.point, &normal, t_min, t_max)?;
+
+        let p = r.at(t);
+        let local = self.tangents.to_local(p - self.point);
+
+        Some(HitRecord::new_with_face_normal(
+            p,
+            t,
+            Point2d::new(local.x(), local.y()),
+            self.material.as_ref(),
+            r,
+            normal,
+        ))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        vec3::{assert_near, Color},
+    };
+
+    fn ground() -> InfinitePlane {
+        InfinitePlane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn hit(plane: &InfinitePlane, origin: Point3, direction: Vec3) -> Option<HitRecord<'_>> {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        plane.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            f32::INFINITY,
+            &mut rng,
+        )
+    }
+
+    #[test]
+    fn hits_from_above() {
+        let plane = ground();
+        let rec = hit(
+            &plane,
+            Point3::new(1.0, 2.0, 3.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+
+        assert_eq!(rec.t, 2.0);
+        assert_near(rec.p, Point3::new(1.0, 0.0, 3.0));
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn hits_from_below_with_flipped_normal() {
+        let plane = ground();
+        let rec = hit(
+            &plane,
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        )
+        .unwrap();
+
+        assert_eq!(rec.t, 1.0);
+        assert_near(rec.p, Point3::new(1.0, 0.0, 0.0));
+        assert_near(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+        assert!(!rec.is_front_face);
+    }
+
+    #[test]
+    fn misses_parallel_rays() {
+        let plane = ground();
+        assert!(hit(&plane, Point3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        assert!(hit(&plane, Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn misses_rays_pointing_away() {
+        let plane = ground();
+        assert!(hit(&plane, Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn texture_coordinates_are_distances_in_the_plane() {
+        let plane = ground();
+        let rec = hit(
+            &plane,
+            Point3::new(3.0, 1.0, 4.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+
+        let uv = rec.texture_uv;
+        assert!(((uv.u * uv.u + uv.v * uv.v).sqrt() - 5.0).abs() < 1e-5);
+    }
+}
@@ -1,7 +1,7 @@
 use alloc::{boxed::Box, vec::Vec};
 use core::fmt::Debug;
 
-use derive_more::Constructor;
+use rand::Rng;
 
 use super::{
     aabb::Aabb,
@@ -12,13 +12,20 @@ use super::{
 };
 use crate::ActiveRng;
 
+pub mod circular;
+pub mod gltf_import;
+pub mod infinite_plane;
+pub mod named;
 pub mod rectangular;
 pub mod spherical;
+pub mod toroidal;
 pub mod transformations;
+pub mod triangle_mesh;
 pub mod triangular;
+pub mod visibility;
 pub mod volumes;
 
-#[derive(Debug, Constructor)]
+#[derive(Debug)]
 pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
@@ -26,9 +33,38 @@ pub struct HitRecord<'a> {
     pub t: f32,
     pub texture_uv: Point2d,
     pub is_front_face: bool,
+    /// The name of the object that was hit, if it was given one with [`Named`].
+    ///
+    /// [`Named`]: named::Named
+    pub object_name: Option<&'a str>,
 }
 
 impl<'a> HitRecord<'a> {
+    pub fn new(
+        p: Point3,
+        normal: Vec3,
+        material: &'a (dyn Material + 'a),
+        t: f32,
+        texture_uv: Point2d,
+        is_front_face: bool,
+    ) -> Self {
+        Self {
+            p,
+            normal,
+            material,
+            t,
+            texture_uv,
+            is_front_face,
+            object_name: None,
+        }
+    }
+
+    /// Wrappers that build a new record from the inner object's hit pass its name on with this.
+    pub fn with_object_name(mut self, object_name: Option<&'a str>) -> Self {
+        self.object_name = object_name;
+        self
+    }
+
     pub fn new_with_face_normal(
         p: Point3,
         t: f32,
@@ -51,6 +87,19 @@ impl<'a> HitRecord<'a> {
 pub trait Hittable: Sync + Send + Debug {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord>;
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb>;
+
+    /// The density, with respect to solid angle, with which aiming at
+    /// [`Hittable::random_point_towards`] from `origin` produces `direction`. Objects that can't
+    /// be sampled as lights return zero.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3, _rng: &mut ActiveRng) -> f32 {
+        0.0
+    }
+
+    /// A random point in the direction of this object as seen from `origin`, for aiming rays at
+    /// lights.
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        *origin + Vec3::random_unit_vector(rng)
+    }
 }
 
 impl Hittable for [Box<dyn Hittable>] {
@@ -85,6 +134,26 @@ impl Hittable for [Box<dyn Hittable>] {
 
         output_box
     }
+
+    /// Lists pick one of their objects uniformly, so the density is the average.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f32 = self
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, rng))
+            .sum();
+        sum / self.len() as f32
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        match self.len() {
+            0 => *origin + Vec3::random_unit_vector(rng),
+            len => self[rng.gen_range(0..len)].random_point_towards(origin, rng),
+        }
+    }
 }
 
 impl Hittable for Vec<Box<dyn Hittable>> {
@@ -95,6 +164,14 @@ impl Hittable for Vec<Box<dyn Hittable>> {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
         self.as_slice().bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.as_slice().pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.as_slice().random_point_towards(origin, rng)
+    }
 }
 
 impl Hittable for &[Box<dyn Hittable>] {
@@ -105,6 +182,14 @@ impl Hittable for &[Box<dyn Hittable>] {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
         (*self).bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        (*self).pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        (*self).random_point_towards(origin, rng)
+    }
 }
 
 impl Hittable for Box<dyn Hittable> {
@@ -115,4 +200,12 @@ impl Hittable for Box<dyn Hittable> {
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
         self.as_ref().bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.as_ref().pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.as_ref().random_point_towards(origin, rng)
+    }
 }
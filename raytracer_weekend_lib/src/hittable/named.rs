@@ -0,0 +1,39 @@
+use alloc::string::String;
+
+use derive_more::Constructor;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// Gives `inner` a name that hits on it carry along as [`HitRecord::object_name`], so it can be
+/// told apart from the rest of the scene in path traces. Names of objects further in are
+/// replaced.
+#[derive(Debug, Constructor)]
+pub struct Named<T: Hittable> {
+    inner: T,
+    name: String,
+}
+
+impl<T: Hittable> Hittable for Named<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let hit = self.inner.hit(r, t_min, t_max, rng)?;
+        Some(hit.with_object_name(Some(&self.name)))
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.inner.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.inner.random_point_towards(origin, rng)
+    }
+}
@@ -2,6 +2,7 @@ use alloc::boxed::Box;
 use core::fmt::Debug;
 
 use derive_more::Constructor;
+use rand::Rng;
 
 use crate::{
     aabb::Aabb,
@@ -32,7 +33,8 @@ impl Hittable for XYRectangle {
         let k = self.k;
 
         let t = (k - r.origin().z()) / r.direction().z();
-        if t < t_min || t > t_max {
+        // Rays within the plane of the rectangle get a t of NaN, which this rejects as well.
+        if !(t_min..=t_max).contains(&t) {
             return None;
         }
         let x = r.origin().x() + t * r.direction().x();
@@ -49,7 +51,7 @@ impl Hittable for XYRectangle {
         return Some(HitRecord::new_with_face_normal(
             p,
             t,
-            Point2d { u, v },
+            Point2d::new(u, v),
             self.material.as_ref(),
             r,
             outward_normal,
@@ -83,7 +85,7 @@ impl Hittable for XZRectangle {
         let k = self.k;
 
         let t = (k - r.origin().y()) / r.direction().y();
-        if t < t_min || t > t_max {
+        if !(t_min..=t_max).contains(&t) {
             return None;
         }
         let x = r.origin().x() + t * r.direction().x();
@@ -100,7 +102,7 @@ impl Hittable for XZRectangle {
         return Some(HitRecord::new_with_face_normal(
             p,
             t,
-            Point2d { u, v },
+            Point2d::new(u, v),
             self.material.as_ref(),
             r,
             outward_normal,
@@ -113,6 +115,29 @@ impl Hittable for XZRectangle {
             Point3::new(self.x1, self.k + 0.0001, self.z1),
         ))
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        let rec = match self.hit(&ray, 0.001, f32::INFINITY, rng) {
+            Some(rec) => rec,
+            None => return 0.0,
+        };
+
+        // Convert the uniform density over the area into one over solid angle.
+        let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * area)
+    }
+
+    fn random_point_towards(&self, _origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        Point3::new(
+            rng.gen_range(self.x0..self.x1),
+            self.k,
+            rng.gen_range(self.z0..self.z1),
+        )
+    }
 }
 
 #[derive(Debug, Constructor)]
@@ -134,7 +159,7 @@ impl Hittable for YZRectangle {
         let k = self.k;
 
         let t = (k - r.origin().x()) / r.direction().x();
-        if t < t_min || t > t_max {
+        if !(t_min..=t_max).contains(&t) {
             return None;
         }
         let y = r.origin().y() + t * r.direction().y();
@@ -151,7 +176,7 @@ impl Hittable for YZRectangle {
         return Some(HitRecord::new_with_face_normal(
             p,
             t,
-            Point2d { u, v },
+            Point2d::new(u, v),
             self.material.as_ref(),
             r,
             outward_normal,
@@ -243,3 +268,174 @@ impl Hittable for Cuboid {
         Some(Aabb::new(self.box_min, self.box_max))
     }
 }
+
+/// A [`Cuboid`] whose edges look rounded off, so they catch highlights the way real objects do.
+///
+/// Only the shading normals are rounded: within `bevel_radius` of an edge, they are tilted
+/// towards the neighbouring face, reaching the halfway point right at the edge. The silhouette
+/// and the bounding box stay those of the sharp box. A radius of zero renders exactly like a
+/// plain [`Cuboid`].
+#[derive(Debug)]
+pub struct RoundedCuboid {
+    cuboid: Cuboid,
+    bevel_radius: f32,
+}
+
+impl RoundedCuboid {
+    pub fn new(p0: Point3, p1: Point3, bevel_radius: f32, material: Box<dyn Material>) -> Self {
+        assert!(bevel_radius >= 0.0);
+
+        Self {
+            cuboid: Cuboid::new(p0, p1, material),
+            bevel_radius,
+        }
+    }
+
+    fn bevel_normal(&self, p: &Point3, outward_normal: Vec3) -> Vec3 {
+        let center = 0.5 * (self.cuboid.box_min + self.cuboid.box_max);
+        let half_extent = 0.5 * (self.cuboid.box_max - self.cuboid.box_min);
+        let local = *p - center;
+
+        let mut normal = outward_normal;
+        for axis in 0..3 {
+            // The face that was hit is perpendicular to its own axis, so that one has no edges.
+            if outward_normal[axis] != 0.0 {
+                continue;
+            }
+
+            let distance_to_edge = half_extent[axis] - local[axis].abs();
+            let weight = 1.0 - (distance_to_edge / self.bevel_radius).clamp(0.0, 1.0);
+            normal[axis] = if local[axis] < 0.0 { -weight } else { weight };
+        }
+
+        normal.unit_vector()
+    }
+}
+
+impl Hittable for RoundedCuboid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let mut rec = self.cuboid.hit(r, t_min, t_max, rng)?;
+
+        if self.bevel_radius > 0.0 {
+            let outward_normal = match rec.is_front_face {
+                true => rec.normal,
+                false => -rec.normal,
+            };
+            let normal = self.bevel_normal(&rec.p, outward_normal);
+            rec.normal = match rec.is_front_face {
+                true => normal,
+                false => -normal,
+            };
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.cuboid.bounding_box(time0, time1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        vec3::{assert_near, Color},
+    };
+
+    fn gray() -> Box<dyn Material> {
+        Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn rounded(bevel_radius: f32) -> RoundedCuboid {
+        RoundedCuboid::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            bevel_radius,
+            gray(),
+        )
+    }
+
+    fn hit(hittable: &impl Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord<'_>> {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        hittable.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            f32::INFINITY,
+            &mut rng,
+        )
+    }
+
+    #[test]
+    fn bevel_normals_are_unit_length_and_continuous_across_the_edge() {
+        let cuboid = rounded(0.3);
+        let steps = 200;
+
+        // Down the top face towards the edge at x = 1, y = 1, then down the side face from it.
+        let top = (0..=steps).map(|i| {
+            let x = 0.5 + 0.5 * i as f32 / steps as f32;
+            hit(&cuboid, Point3::new(x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0))
+        });
+        let side = (0..=steps).map(|i| {
+            let y = 1.0 - 0.5 * i as f32 / steps as f32;
+            hit(&cuboid, Point3::new(5.0, y, 0.0), Vec3::new(-1.0, 0.0, 0.0))
+        });
+        let normals: Vec<Vec3> = top.chain(side).map(|rec| rec.unwrap().normal).collect();
+
+        for normal in &normals {
+            assert!((normal.length() - 1.0).abs() < 1e-5, "{}", normal);
+        }
+        for pair in normals.windows(2) {
+            assert!(
+                (pair[1] - pair[0]).length() < 0.05,
+                "the normal jumps from {} to {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        // Flat faces away from the edge, and halfway between both right at it.
+        assert_near(normals[0], Vec3::new(0.0, 1.0, 0.0));
+        assert_near(normals[steps], Vec3::new(1.0, 1.0, 0.0).unit_vector());
+        assert_near(normals[steps + 1], Vec3::new(1.0, 1.0, 0.0).unit_vector());
+        assert_near(normals[2 * steps + 1], Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn zero_radius_hits_like_a_cuboid() {
+        let cuboid = Cuboid::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            gray(),
+        );
+        let rounded = rounded(0.0);
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        for _ in 0..1_000 {
+            let origin = 3.0 * Vec3::random_unit_vector(&mut rng);
+            let target = Vec3::random_in_unit_sphere(&mut rng);
+            // Some rays start inside, to see the back faces too.
+            let origin = if rng.gen::<f32>() < 0.2 {
+                0.5 * target
+            } else {
+                origin
+            };
+            let direction = target - origin + 0.1 * Vec3::random_unit_vector(&mut rng);
+
+            let expected = hit(&cuboid, origin, direction);
+            let actual = hit(&rounded, origin, direction);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    assert_eq!(actual.t, expected.t);
+                    assert_eq!(actual.is_front_face, expected.is_front_face);
+                    for axis in 0..3 {
+                        assert_eq!(actual.p[axis], expected.p[axis]);
+                        assert_eq!(actual.normal[axis], expected.normal[axis]);
+                    }
+                }
+                (expected, actual) => panic!("{:?} instead of {:?}", actual, expected),
+            }
+        }
+    }
+}
@@ -4,14 +4,17 @@ use core::f32::consts::PI;
 use derive_more::Constructor;
 #[cfg(feature = "no_std")]
 use micromath::F32Ext;
+use rand::Rng;
 
 use crate::{
     aabb::Aabb,
     hittable::{HitRecord, Hittable},
+    light_source::{DiffuseLight, LightSample},
     material::Material,
+    onb::OrthonormalBase,
     ray::Ray,
-    texture::Point2d,
-    vec3::{Point3, Vec3},
+    texture::{Point2d, SolidColor},
+    vec3::{Color, Point3, Vec3},
     ActiveRng,
 };
 
@@ -59,7 +62,7 @@ fn hit_sphere<'a>(
     ))
 }
 
-fn get_sphere_uv(p: &Point3) -> Point2d {
+pub(crate) fn get_sphere_uv(p: &Point3) -> Point2d {
     // p: a given point on the sphere of radius one, centered at the origin.
     // u: returned value [0,1] of angle around the Y axis from X=-1.
     // v: returned value [0,1] of angle from Y=-1 to Y=+1.
@@ -73,7 +76,7 @@ fn get_sphere_uv(p: &Point3) -> Point2d {
     let u = phi / (2.0 * PI);
     let v = theta / PI;
 
-    Point2d { u, v }
+    Point2d::new(u, v)
 }
 
 #[derive(Constructor, Debug, Clone)]
@@ -101,6 +104,114 @@ impl Hittable for Sphere {
         let radius_vector = Vec3::new(radius, radius, radius);
         Some(Aabb::new(center - radius_vector, center + radius_vector))
     }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        if self.hit(&ray, 0.001, f32::INFINITY, rng).is_none() {
+            return 0.0;
+        }
+
+        // Uniform over the cone of directions the sphere covers.
+        let distance_squared = (self.center - *origin).length_squared();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared)
+            .max(0.0)
+            .sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        match self.random_direction_towards(origin, rng) {
+            Some((direction, _)) => *origin + direction,
+            None => *origin + Vec3::random_unit_vector(rng),
+        }
+    }
+}
+
+impl Sphere {
+    /// A unit vector from `origin` towards the sphere, uniformly distributed over the cone of
+    /// directions the sphere covers, together with the solid angle of that cone. None from inside
+    /// the sphere, where there is no such cone.
+    fn random_direction_towards(
+        &self,
+        origin: &Point3,
+        rng: &mut ActiveRng,
+    ) -> Option<(Vec3, f32)> {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return None;
+        }
+
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let z = 1.0 + rng.gen::<f32>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        let onb = OrthonormalBase::from_w(&direction);
+        let sampled = onb.to_world(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z));
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        Some((sampled, solid_angle))
+    }
+}
+
+/// A small glowing ball, the path tracer's stand-in for a point light. Saves putting together a
+/// [`Sphere`] and a [`DiffuseLight`] by hand.
+#[derive(Debug, Clone)]
+pub struct SphericalLight {
+    sphere: Sphere,
+    radiance: Color,
+}
+
+impl SphericalLight {
+    pub fn new(center: Point3, radius: f32, radiance: Color) -> Self {
+        let material = DiffuseLight::new(SolidColor::new(radiance));
+
+        Self {
+            sphere: Sphere::new(center, radius, Box::new(material)),
+            radiance,
+        }
+    }
+
+    /// The light arriving at `point` from a direction picked uniformly from the cone the sphere
+    /// covers, or none if `point` is inside the sphere. The light isn't checked for occluders.
+    pub fn sample_toward(&self, point: Point3, rng: &mut ActiveRng) -> Option<LightSample> {
+        let (direction, solid_angle) = self.sphere.random_direction_towards(&point, rng)?;
+
+        // The nearer intersection with the sphere. Directions along the rim graze it, where
+        // rounding can push the discriminant just below zero.
+        let origin_to_center = point - self.sphere.center;
+        let half_b = origin_to_center.dot(&direction);
+        let c = origin_to_center.length_squared() - self.sphere.radius * self.sphere.radius;
+        let t = -half_b - (half_b * half_b - c).max(0.0).sqrt();
+
+        Some(LightSample {
+            direction,
+            t,
+            pdf: 1.0 / solid_angle,
+            radiance: self.radiance,
+        })
+    }
+}
+
+impl Hittable for SphericalLight {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        self.sphere.hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.sphere.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.sphere.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.sphere.random_point_towards(origin, rng)
+    }
 }
 
 #[derive(Constructor, Debug)]
@@ -149,3 +260,48 @@ impl Hittable for MovingSphere {
         Some(Aabb::surrounding_box(&start_box, &end_box))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spherical_light_samples_spread_evenly_over_the_visible_cap() {
+        const SAMPLES: u32 = 100_000;
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let point = Point3::new(0.0, 0.0, 0.0);
+        let center = Point3::new(1.0, 3.0, -2.0);
+        let radius = 1.5;
+        let light = SphericalLight::new(center, radius, Color::new(1.0, 1.0, 1.0));
+
+        let axis = (center - point).unit_vector();
+        let distance = (center - point).length();
+        let cos_theta_max = (1.0 - radius * radius / (distance * distance)).sqrt();
+        let onb = OrthonormalBase::from_w(&axis);
+
+        // Uniform over the cone of directions means uniform in the cosine of the angle to its
+        // axis and in the angle around it.
+        let mut cosine_bins = [0; 4];
+        let mut azimuth_bins = [0; 4];
+        for _ in 0..SAMPLES {
+            let sample = light.sample_toward(point, &mut rng).unwrap();
+            let hit_point = point + sample.t * sample.direction;
+            assert!(((hit_point - center).length() - radius).abs() < 1e-3);
+            // Only the cap facing the point is visible from it.
+            assert!((hit_point - center).dot(&(point - center)) >= 0.0);
+
+            let local = onb.to_local(sample.direction.unit_vector());
+            let cosine = (local.z() - cos_theta_max) / (1.0 - cos_theta_max);
+            let azimuth = (local.y().atan2(local.x()) + PI) / (2.0 * PI);
+            cosine_bins[((cosine * 4.0) as usize).min(3)] += 1;
+            azimuth_bins[((azimuth * 4.0) as usize).min(3)] += 1;
+        }
+
+        for bins in [cosine_bins, azimuth_bins] {
+            for count in bins {
+                let fraction = count as f32 / SAMPLES as f32;
+                assert!((fraction - 0.25).abs() < 0.01, "{:?}", bins);
+            }
+        }
+    }
+}
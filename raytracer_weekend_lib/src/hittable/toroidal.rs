@@ -0,0 +1,229 @@
+use alloc::boxed::Box;
+use core::f32::consts::PI;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    polynomial::solve_monic_quartic,
+    ray::Ray,
+    texture::Point2d,
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// A torus around `center`, lying in the XZ plane with the Y axis running through its hole.
+///
+/// `major_radius` is the distance from the center to the middle of the tube, `minor_radius` the
+/// radius of the tube itself. Use the transformations to orient it differently.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    center: Point3,
+    major_radius: f32,
+    minor_radius: f32,
+    material: Box<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Box<dyn Material>,
+    ) -> Self {
+        assert!(0.0 < minor_radius && minor_radius < major_radius);
+
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut ActiveRng) -> Option<HitRecord> {
+        let direction_length = r.direction().length();
+        let direction = r.direction() / direction_length;
+
+        // Solve from the point of the ray closest to the center, which keeps the roots small and
+        // the quartic well conditioned even for distant cameras.
+        let origin = r.origin() - self.center;
+        let start = -origin.dot(&direction);
+        let origin = origin + start * direction;
+
+        // Substituting the ray into (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2).
+        let major_squared = self.major_radius * self.major_radius;
+        let n = origin.dot(&direction);
+        let k = origin.length_squared() + major_squared - self.minor_radius * self.minor_radius;
+        let a = direction.x() * direction.x() + direction.z() * direction.z();
+        let b = origin.x() * direction.x() + origin.z() * direction.z();
+        let c = origin.x() * origin.x() + origin.z() * origin.z();
+
+        let roots = solve_monic_quartic(
+            4.0 * n,
+            4.0 * n * n + 2.0 * k - 4.0 * major_squared * a,
+            4.0 * n * k - 8.0 * major_squared * b,
+            k * k - 4.0 * major_squared * c,
+        );
+
+        // Convert back from distances along the unit direction to the ray's own t.
+        let t = roots
+            .as_slice()
+            .iter()
+            .map(|&distance| (start + distance) / direction_length)
+            .filter(|t| (t_min..=t_max).contains(t))
+            .fold(f32::INFINITY, f32::min);
+        if !t.is_finite() {
+            return None;
+        }
+
+        let hit_point = r.at(t);
+        let local = hit_point - self.center;
+
+        // The normal points away from the nearest point on the circle through the tube's middle.
+        let radial = Vec3::new(local.x(), 0.0, local.z());
+        let radial_length = radial.length();
+        let tube_center = self.major_radius / radial_length * radial;
+        let outward_normal = (local - tube_center).unit_vector();
+
+        // u runs around the Y axis like on a sphere, v around the tube starting on its inside.
+        let u = ((-local.z()).atan2(local.x()) + PI) / (2.0 * PI);
+        let v = (local.y().atan2(self.major_radius - radial_length) + PI) / (2.0 * PI);
+
+        Some(HitRecord::new_with_face_normal(
+            hit_point,
+            t,
+            Point2d::new(u, v),
+            self.material.as_ref(),
+            r,
+            outward_normal,
+        ))
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(outer, self.minor_radius, outer);
+
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        vec3::{assert_near, Color},
+    };
+
+    /// A torus around the origin whose tube spans 1.5 to 2.5 from the Y axis.
+    fn ring() -> Torus {
+        Torus::new(
+            Point3::new(0.0, 0.0, 0.0),
+            2.0,
+            0.5,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn hit(torus: &Torus, origin: Point3, direction: Vec3) -> Option<HitRecord<'_>> {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        torus.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            f32::INFINITY,
+            &mut rng,
+        )
+    }
+
+    #[test]
+    fn rays_through_the_hole_miss() {
+        let torus = ring();
+        assert!(hit(
+            &torus,
+            Point3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+        assert!(hit(
+            &torus,
+            Point3::new(1.0, -5.0, 0.5),
+            Vec3::new(0.0, 1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn returns_the_nearest_of_four_roots() {
+        // This ray crosses the tube at x = -2.5, -1.5, 1.5 and 2.5.
+        let torus = ring();
+        let rec = hit(
+            &torus,
+            Point3::new(-5.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+
+        assert!((rec.t - 2.5).abs() < 1e-4, "hit at t = {}", rec.t);
+        assert_near(rec.p, Point3::new(-2.5, 0.0, 0.0));
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(rec.is_front_face);
+    }
+
+    #[test]
+    fn normals_have_unit_length() {
+        let torus = ring();
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let mut hits = 0;
+
+        for _ in 0..10_000 {
+            let origin = 6.0 * Vec3::random_unit_vector(&mut rng);
+            let target = Point3::new(
+                rng.gen_range(-2.5..2.5),
+                rng.gen_range(-0.5..0.5),
+                rng.gen_range(-2.5..2.5),
+            );
+
+            if let Some(rec) = hit(&torus, origin, target - origin) {
+                hits += 1;
+                assert!(
+                    (rec.normal.length() - 1.0).abs() < 1e-4,
+                    "normal {} at {}",
+                    rec.normal,
+                    rec.p
+                );
+            }
+        }
+
+        assert!(hits > 1_000, "only {} rays hit", hits);
+    }
+
+    #[test]
+    fn grazing_rays_produce_no_nan() {
+        // Rays skimming the top of the tube, where the quartic has double roots.
+        let torus = ring();
+
+        for i in -100..=100 {
+            let y = 0.5 + i as f32 * 1e-4;
+            for origin in [Point3::new(-5.0, y, 0.0), Point3::new(-5.0, y, 2.0)] {
+                if let Some(rec) = hit(&torus, origin, Vec3::new(1.0, 0.0, 0.0)) {
+                    let values = [rec.t, rec.texture_uv.u, rec.texture_uv.v];
+                    assert!(
+                        values.iter().all(|v| v.is_finite())
+                            && (0..3).all(|a| rec.p[a].is_finite() && rec.normal[a].is_finite()),
+                        "grazing ray at y = {} gave {:?}",
+                        y,
+                        rec
+                    );
+                }
+            }
+        }
+    }
+}
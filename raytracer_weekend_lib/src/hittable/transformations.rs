@@ -1,3 +1,4 @@
+use alloc::{sync::Arc, vec, vec::Vec};
 use core::fmt::Debug;
 
 use derive_more::Constructor;
@@ -7,6 +8,7 @@ use rand::prelude::Rng;
 
 use crate::{
     aabb::Aabb,
+    camera::{interpolate, Interpolation},
     hittable::{HitRecord, Hittable},
     ray::Ray,
     vec3::{Point3, Vec3},
@@ -21,20 +23,22 @@ pub struct Translation<T: Hittable> {
 
 impl<T: Hittable> Hittable for Translation<T> {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
-        let translated_ray = Ray::new(r.origin() - self.offset, r.direction(), r.time());
+        let translated_ray =
+            Ray::new(r.origin() - self.offset, r.direction(), r.time()).with_kind(r.kind());
 
         let hit = self.inner.hit(&translated_ray, t_min, t_max, rng)?;
 
         let translated_hitpoint = hit.p + self.offset;
 
-        Some(HitRecord::new_with_face_normal(
+        let translated_hit = HitRecord::new_with_face_normal(
             translated_hitpoint,
             hit.t,
             hit.texture_uv,
             hit.material,
             &translated_ray,
             hit.normal,
-        ))
+        );
+        Some(translated_hit.with_object_name(hit.object_name))
     }
 
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
@@ -47,16 +51,134 @@ impl<T: Hittable> Hittable for Translation<T> {
     }
 }
 
+/// Moves the inner object over ray time, along offsets given at keyframes and interpolated like a
+/// [`CameraPath`]. Before the first and after the last keyframe, it stands still.
+///
+/// Within the shutter time of a camera, this blurs the object like a [`MovingSphere`]. With
+/// cameras from [`CameraPath::to_cameras_with_shutter`], it also moves from frame to frame.
+///
+/// [`CameraPath`]: crate::camera::CameraPath
+/// [`CameraPath::to_cameras_with_shutter`]: crate::camera::CameraPath::to_cameras_with_shutter
+/// [`MovingSphere`]: super::spherical::MovingSphere
 #[derive(Debug)]
-pub struct YRotation<T: Hittable> {
+pub struct KeyframedTranslation<T: Hittable> {
+    inner: T,
+    /// Times and the offsets at them, sorted by time.
+    keyframes: Vec<(f32, Vec3)>,
+    interpolation: Interpolation,
+}
+
+impl<T: Hittable> KeyframedTranslation<T> {
+    pub fn new(inner: T, keyframes: Vec<(f32, Vec3)>, interpolation: Interpolation) -> Self {
+        assert!(!keyframes.is_empty());
+        assert!(keyframes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        Self {
+            inner,
+            keyframes,
+            interpolation,
+        }
+    }
+
+    fn offset_at(&self, time: f32) -> Vec3 {
+        let (first_time, first_offset) = self.keyframes[0];
+        let (last_time, last_offset) = self.keyframes[self.keyframes.len() - 1];
+        if time <= first_time {
+            return first_offset;
+        }
+        if time >= last_time {
+            return last_offset;
+        }
+
+        let next = self
+            .keyframes
+            .iter()
+            .position(|&(keyframe_time, _)| keyframe_time > time)
+            .unwrap();
+        let (previous_time, _) = self.keyframes[next - 1];
+        let (next_time, _) = self.keyframes[next];
+        let s = ((time - previous_time) / (next_time - previous_time)).clamp(0.0, 1.0);
+
+        interpolate(self.interpolation, self.control_points(next), s)
+    }
+
+    /// The offsets the segment ending at keyframe `next` is interpolated from. The outer ones
+    /// repeat the segment ends at the ends of the animation.
+    fn control_points(&self, next: usize) -> [Vec3; 4] {
+        let previous = next - 1;
+        [
+            previous.saturating_sub(1),
+            previous,
+            next,
+            (next + 1).min(self.keyframes.len() - 1),
+        ]
+        .map(|i| self.keyframes[i].1)
+    }
+}
+
+impl<T: Hittable> Hittable for KeyframedTranslation<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let offset = self.offset_at(r.time());
+        let translated_ray =
+            Ray::new(r.origin() - offset, r.direction(), r.time()).with_kind(r.kind());
+
+        let hit = self.inner.hit(&translated_ray, t_min, t_max, rng)?;
+
+        let translated_hit = HitRecord::new_with_face_normal(
+            hit.p + offset,
+            hit.t,
+            hit.texture_uv,
+            hit.material,
+            &translated_ray,
+            hit.normal,
+        );
+        Some(translated_hit.with_object_name(hit.object_name))
+    }
+
+    /// The path between two keyframes stays within the convex hull of the segment's Bezier
+    /// control points, which are the keyframes themselves for linear interpolation.
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let bounding_box = self.inner.bounding_box(time0, time1)?;
+
+        let mut offsets = vec![self.offset_at(time0), self.offset_at(time1)];
+        for next in 1..self.keyframes.len() {
+            if self.keyframes[next].0 < time0 || self.keyframes[next - 1].0 > time1 {
+                continue;
+            }
+
+            let [p0, p1, p2, p3] = self.control_points(next);
+            offsets.extend([p1, p2]);
+            if self.interpolation == Interpolation::CatmullRom {
+                offsets.extend([p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0]);
+            }
+        }
+
+        offsets
+            .iter()
+            .map(|&offset| Aabb::new(bounding_box.min() + offset, bounding_box.max() + offset))
+            .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+    }
+}
+
+/// Rotates the inner object about one of the coordinate axes, counter-clockwise when looking down
+/// the axis towards the origin. `AXIS` is 0, 1 or 2 for x, y or z; use the [`XRotation`],
+/// [`YRotation`] and [`ZRotation`] aliases rather than naming it directly.
+#[derive(Debug)]
+pub struct AxisRotation<T: Hittable, const AXIS: usize> {
     inner: T,
     sin_theta: f32,
     cos_theta: f32,
     bounding_box: Option<Aabb>,
 }
 
-impl<T: Hittable> YRotation<T> {
+pub type XRotation<T> = AxisRotation<T, 0>;
+pub type YRotation<T> = AxisRotation<T, 1>;
+pub type ZRotation<T> = AxisRotation<T, 2>;
+
+impl<T: Hittable, const AXIS: usize> AxisRotation<T, AXIS> {
     pub fn new(inner: T, angle_degrees: f32) -> Self {
+        assert!(AXIS < 3);
+
         let angle_radians = angle_degrees.to_radians();
 
         let sin_theta = angle_radians.sin();
@@ -74,6 +196,19 @@ impl<T: Hittable> YRotation<T> {
         }
     }
 
+    /// Rotates `v` by the angle given as its sine and cosine. The two coordinates other than
+    /// `AXIS` are taken in cyclic order, which keeps the sense of rotation the same for all axes.
+    fn rotate(v: Vec3, sin_theta: f32, cos_theta: f32) -> Vec3 {
+        let a = (AXIS + 1) % 3;
+        let b = (AXIS + 2) % 3;
+
+        let mut rotated = v;
+        rotated[a] = cos_theta * v[a] - sin_theta * v[b];
+        rotated[b] = sin_theta * v[a] + cos_theta * v[b];
+
+        rotated
+    }
+
     fn rotate_bounding_box(bbox: Aabb, sin_theta: f32, cos_theta: f32) -> Aabb {
         let mut min = Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
         let mut max = Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
@@ -85,19 +220,11 @@ impl<T: Hittable> YRotation<T> {
                     let j = j as f32;
                     let k = k as f32;
 
-                    let ijk: Vec3 = (i, j, k).into();
-                    let one: Vec3 = (1.0, 1.0, 1.0).into();
-
-                    let xyz = ijk * bbox.max() + (one - ijk) * bbox.min();
-
                     let x = i * bbox.max().x() + (1.0 - i) * bbox.min().x();
                     let y = j * bbox.max().y() + (1.0 - j) * bbox.min().y();
                     let z = k * bbox.max().z() + (1.0 - k) * bbox.min().z();
 
-                    let new_x = cos_theta * x + sin_theta * z;
-                    let new_z = -sin_theta * x + cos_theta * z;
-
-                    let tester = Vec3::new(new_x, y, new_z);
+                    let tester = Self::rotate(Vec3::new(x, y, z), sin_theta, cos_theta);
 
                     for axis in 0..3 {
                         min[axis] = min[axis].min(tester[axis]);
@@ -111,40 +238,333 @@ impl<T: Hittable> YRotation<T> {
     }
 }
 
-impl<T: Hittable> Hittable for YRotation<T> {
+impl<T: Hittable, const AXIS: usize> Hittable for AxisRotation<T, AXIS> {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
         let sin_theta = self.sin_theta;
         let cos_theta = self.cos_theta;
 
-        let mut origin = r.origin();
-        let mut direction = r.direction();
+        // Rotate the ray into object space, i.e. by the opposite angle.
+        let origin = Self::rotate(r.origin(), -sin_theta, cos_theta);
+        let direction = Self::rotate(r.direction(), -sin_theta, cos_theta);
 
-        origin[0] = cos_theta * r.origin()[0] - sin_theta * r.origin()[2];
-        origin[2] = sin_theta * r.origin()[0] + cos_theta * r.origin()[2];
+        let rotated_r = Ray::new(origin, direction, r.time()).with_kind(r.kind());
+        let rec = self.inner.hit(&rotated_r, t_min, t_max, rng)?;
 
-        direction[0] = cos_theta * r.direction()[0] - sin_theta * r.direction()[2];
-        direction[2] = sin_theta * r.direction()[0] + cos_theta * r.direction()[2];
+        let p = Self::rotate(rec.p, sin_theta, cos_theta);
+        let normal = Self::rotate(rec.normal, sin_theta, cos_theta);
 
-        let rotated_r = Ray::new(origin, direction, r.time());
-        let rec = self.inner.hit(&rotated_r, t_min, t_max, rng)?;
+        // The normal already faces the ray, so keep the inner object's idea of which side was hit.
+        Some(HitRecord { p, normal, ..rec })
+    }
 
-        let mut p = rec.p;
-        let mut normal = rec.normal;
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        self.bounding_box.clone()
+    }
+}
 
-        p[0] = cos_theta * rec.p[0] + sin_theta * rec.p[2];
-        p[2] = -sin_theta * rec.p[0] + cos_theta * rec.p[2];
+/// Turns the inner object inside out: hits report the opposite normal and the opposite side as
+/// the front face. Everything else about them stays the same.
+#[derive(Debug, Constructor)]
+pub struct FlipNormals<T: Hittable> {
+    inner: T,
+}
 
-        normal[0] = cos_theta * rec.normal[0] + sin_theta * rec.normal[2];
-        normal[2] = -sin_theta * rec.normal[0] + cos_theta * rec.normal[2];
+impl<T: Hittable> Hittable for FlipNormals<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let rec = self.inner.hit(r, t_min, t_max, rng)?;
 
-        Some(HitRecord::new_with_face_normal(
-            p,
-            rec.t,
-            rec.texture_uv,
-            rec.material,
-            &rotated_r,
-            normal,
-        ))
+        Some(HitRecord {
+            normal: -rec.normal,
+            is_front_face: !rec.is_front_face,
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.inner.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.inner.random_point_towards(origin, rng)
+    }
+}
+
+/// Makes the inner object one-sided: hits on its back face are dropped, so it can only be seen,
+/// and only emits light, towards the side its normals point to. Combine with [`FlipNormals`] to
+/// pick the other side, e.g. for a ceiling light that should only shine downwards.
+#[derive(Debug, Constructor)]
+pub struct FlipFace<T: Hittable> {
+    inner: T,
+}
+
+impl<T: Hittable> Hittable for FlipFace<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let rec = self.inner.hit(r, t_min, t_max, rng)?;
+
+        rec.is_front_face.then_some(rec)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.inner.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.inner.random_point_towards(origin, rng)
+    }
+}
+
+/// A 3x3 matrix in row-major order, for the linear part of [`Transform`].
+#[derive(Debug, Clone, Copy)]
+struct Matrix3 {
+    rows: [Vec3; 3],
+}
+
+impl Matrix3 {
+    fn from_rows(r0: [f32; 3], r1: [f32; 3], r2: [f32; 3]) -> Self {
+        Self {
+            rows: [r0, r1, r2].map(|[x, y, z]| Vec3::new(x, y, z)),
+        }
+    }
+
+    fn scale(scale: Vec3) -> Self {
+        Self::from_rows(
+            [scale.x(), 0.0, 0.0],
+            [0.0, scale.y(), 0.0],
+            [0.0, 0.0, scale.z()],
+        )
+    }
+
+    /// Rotation about the x, y and z axes in that order, with the same sense as [`AxisRotation`].
+    fn euler_rotation(angles_degrees: Vec3) -> Self {
+        let sin_cos = |degrees: f32| (degrees.to_radians().sin(), degrees.to_radians().cos());
+        let (sx, cx) = sin_cos(angles_degrees.x());
+        let (sy, cy) = sin_cos(angles_degrees.y());
+        let (sz, cz) = sin_cos(angles_degrees.z());
+
+        let x = Self::from_rows([1.0, 0.0, 0.0], [0.0, cx, -sx], [0.0, sx, cx]);
+        let y = Self::from_rows([cy, 0.0, sy], [0.0, 1.0, 0.0], [-sy, 0.0, cy]);
+        let z = Self::from_rows([cz, -sz, 0.0], [sz, cz, 0.0], [0.0, 0.0, 1.0]);
+
+        z.mul(&y).mul(&x)
+    }
+
+    fn column(&self, index: usize) -> Vec3 {
+        Vec3::new(
+            self.rows[0][index],
+            self.rows[1][index],
+            self.rows[2][index],
+        )
+    }
+
+    fn transpose(&self) -> Self {
+        Self {
+            rows: [self.column(0), self.column(1), self.column(2)],
+        }
+    }
+
+    fn mul(&self, rhs: &Self) -> Self {
+        let row = |r: Vec3| [0, 1, 2].map(|index| r.dot(&rhs.column(index)));
+
+        Self::from_rows(row(self.rows[0]), row(self.rows[1]), row(self.rows[2]))
+    }
+
+    fn mul_vec(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.rows[0].dot(&v),
+            self.rows[1].dot(&v),
+            self.rows[2].dot(&v),
+        )
+    }
+
+    fn inverse(&self) -> Self {
+        let [r0, r1, r2] = self.rows;
+        let determinant = r0.dot(&r1.cross(&r2));
+        assert!(determinant != 0.0, "transform is not invertible");
+
+        // The columns of the inverse are the cross products of the rows.
+        Self {
+            rows: [r1.cross(&r2), r2.cross(&r0), r0.cross(&r1)],
+        }
+        .transpose()
+        .divide(determinant)
+    }
+
+    fn divide(&self, divisor: f32) -> Self {
+        Self {
+            rows: self.rows.map(|row| row / divisor),
+        }
+    }
+}
+
+/// An arbitrary affine transform of the inner object: scaling, then rotation, then translation.
+///
+/// Unlike stacking [`Translation`] and [`AxisRotation`], this also supports non-uniform scaling.
+/// Rays are taken into object space with the inverse transform, and normals come back out with its
+/// transpose so they stay perpendicular to scaled surfaces.
+#[derive(Debug)]
+pub struct Transform<T: Hittable> {
+    inner: T,
+    linear: Matrix3,
+    inverse: Matrix3,
+    /// The inverse transpose, for taking normals from object space to world space.
+    normal_matrix: Matrix3,
+    translation: Vec3,
+    bounding_box: Option<Aabb>,
+}
+
+impl<T: Hittable> Transform<T> {
+    /// `rotation_degrees` holds the angles about the x, y and z axes, which are applied in that
+    /// order.
+    pub fn new(inner: T, translation: Vec3, rotation_degrees: Vec3, scale: Vec3) -> Self {
+        let linear = Matrix3::euler_rotation(rotation_degrees).mul(&Matrix3::scale(scale));
+        let inverse = linear.inverse();
+
+        let bounding_box = inner
+            .bounding_box(0.0, 1.0)
+            .map(|b| transform_bounding_box(b, &linear, translation));
+
+        Self {
+            inner,
+            linear,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            translation,
+            bounding_box,
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for Transform<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        // The direction isn't renormalised, so t means the same in both spaces.
+        let origin = self.inverse.mul_vec(r.origin() - self.translation);
+        let direction = self.inverse.mul_vec(r.direction());
+
+        let transformed_r = Ray::new(origin, direction, r.time()).with_kind(r.kind());
+        let rec = self.inner.hit(&transformed_r, t_min, t_max, rng)?;
+
+        let p = self.linear.mul_vec(rec.p) + self.translation;
+        let normal = self.normal_matrix.mul_vec(rec.normal).unit_vector();
+
+        Some(HitRecord { p, normal, ..rec })
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        self.bounding_box.clone()
+    }
+}
+
+/// The box around the eight corners of `bbox` after transforming them.
+fn transform_bounding_box(bbox: Aabb, linear: &Matrix3, translation: Vec3) -> Aabb {
+    let mut min = Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut max = Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let i = i as f32;
+                let j = j as f32;
+                let k = k as f32;
+
+                let x = i * bbox.max().x() + (1.0 - i) * bbox.min().x();
+                let y = j * bbox.max().y() + (1.0 - j) * bbox.min().y();
+                let z = k * bbox.max().z() + (1.0 - k) * bbox.min().z();
+
+                let tester = linear.mul_vec(Vec3::new(x, y, z)) + translation;
+
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(tester[axis]);
+                    max[axis] = max[axis].max(tester[axis]);
+                }
+            }
+        }
+    }
+
+    Aabb::new(min, max)
+}
+
+/// An affine transform given as a column-major 4×4 matrix, the layout of glTF and OpenGL, for
+/// placing [`Instance`]s.
+#[derive(Debug, Clone)]
+pub struct InstanceTransform {
+    linear: Matrix3,
+    inverse: Matrix3,
+    normal_matrix: Matrix3,
+    translation: Vec3,
+}
+
+impl InstanceTransform {
+    /// The bottom row of `matrix` has to be `0 0 0 1`, as for any affine transform.
+    pub fn new(matrix: [[f32; 4]; 4]) -> Self {
+        assert!(
+            matrix[0][3] == 0.0
+                && matrix[1][3] == 0.0
+                && matrix[2][3] == 0.0
+                && matrix[3][3] == 1.0,
+            "instance transforms must be affine"
+        );
+
+        let row = |r: usize| [matrix[0][r], matrix[1][r], matrix[2][r]];
+        let linear = Matrix3::from_rows(row(0), row(1), row(2));
+        let inverse = linear.inverse();
+
+        Self {
+            linear,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            translation: Vec3::new(matrix[3][0], matrix[3][1], matrix[3][2]),
+        }
+    }
+}
+
+/// A copy of a shared prototype object, placed by its own transform. However many instances
+/// there are, the prototype's geometry and materials exist only once.
+#[derive(Debug)]
+pub struct Instance<T: Hittable + ?Sized> {
+    prototype: Arc<T>,
+    transform: InstanceTransform,
+    bounding_box: Option<Aabb>,
+}
+
+impl<T: Hittable + ?Sized> Instance<T> {
+    pub fn new(prototype: Arc<T>, transform: InstanceTransform) -> Self {
+        let bounding_box = prototype
+            .bounding_box(0.0, 1.0)
+            .map(|b| transform_bounding_box(b, &transform.linear, transform.translation));
+
+        Self {
+            prototype,
+            transform,
+            bounding_box,
+        }
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Instance<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let transform = &self.transform;
+
+        // As in Transform, the direction isn't renormalised so t stays the same.
+        let origin = transform
+            .inverse
+            .mul_vec(r.origin() - transform.translation);
+        let direction = transform.inverse.mul_vec(r.direction());
+
+        let transformed_r = Ray::new(origin, direction, r.time()).with_kind(r.kind());
+        let rec = self.prototype.hit(&transformed_r, t_min, t_max, rng)?;
+
+        let p = transform.linear.mul_vec(rec.p) + transform.translation;
+        let normal = transform.normal_matrix.mul_vec(rec.normal).unit_vector();
+
+        Some(HitRecord { p, normal, ..rec })
     }
 
     fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
@@ -155,18 +575,448 @@ impl<T: Hittable> Hittable for YRotation<T> {
 pub trait Transformable {
     type Inner: Hittable;
 
+    fn rotate_x(self, angle_degrees: f32) -> XRotation<Self::Inner>;
     fn rotate_y(self, angle_degrees: f32) -> YRotation<Self::Inner>;
+    fn rotate_z(self, angle_degrees: f32) -> ZRotation<Self::Inner>;
     fn translate(self, offset: Vec3) -> Translation<Self::Inner>;
+    fn flip_normals(self) -> FlipNormals<Self::Inner>;
+    fn flip_face(self) -> FlipFace<Self::Inner>;
+    fn transform(
+        self,
+        translation: Vec3,
+        rotation_degrees: Vec3,
+        scale: Vec3,
+    ) -> Transform<Self::Inner>;
 }
 
 impl<T: Hittable> Transformable for T {
     type Inner = T;
 
+    fn rotate_x(self, angle_degrees: f32) -> XRotation<Self::Inner> {
+        XRotation::new(self, angle_degrees)
+    }
+
     fn rotate_y(self, angle_degrees: f32) -> YRotation<Self::Inner> {
         YRotation::new(self, angle_degrees)
     }
 
+    fn rotate_z(self, angle_degrees: f32) -> ZRotation<Self::Inner> {
+        ZRotation::new(self, angle_degrees)
+    }
+
     fn translate(self, offset: Vec3) -> Translation<Self::Inner> {
         Translation::new(self, offset)
     }
+
+    fn flip_normals(self) -> FlipNormals<Self::Inner> {
+        FlipNormals::new(self)
+    }
+
+    fn flip_face(self) -> FlipFace<Self::Inner> {
+        FlipFace::new(self)
+    }
+
+    fn transform(
+        self,
+        translation: Vec3,
+        rotation_degrees: Vec3,
+        scale: Vec3,
+    ) -> Transform<Self::Inner> {
+        Transform::new(self, translation, rotation_degrees, scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+    use crate::{
+        hittable::{
+            rectangular::{Cuboid, XZRectangle},
+            spherical::Sphere,
+        },
+        material::Lambertian,
+        vec3::{assert_near, Color},
+    };
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    /// The unit square at `y = 1`, facing up.
+    fn square() -> XZRectangle {
+        XZRectangle::new(
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+            1.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    fn hit(object: &impl Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord<'_>> {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        object.hit(
+            &Ray::new(origin, direction, 0.0),
+            0.001,
+            f32::INFINITY,
+            &mut rng,
+        )
+    }
+
+    #[test]
+    fn flip_normals_inverts_normal_and_face() {
+        let origin = Point3::new(0.0, 0.0, 5.0);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+
+        let sphere = unit_sphere();
+        let plain = hit(&sphere, origin, direction).unwrap();
+        let flipped_sphere = unit_sphere().flip_normals();
+        let flipped = hit(&flipped_sphere, origin, direction).unwrap();
+
+        assert!(plain.is_front_face);
+        assert!(!flipped.is_front_face);
+        assert_near(flipped.normal, -plain.normal);
+        assert_near(flipped.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(flipped.t, plain.t);
+        assert_near(flipped.p, plain.p);
+    }
+
+    #[test]
+    fn transform_moves_hits() {
+        let moved = unit_sphere().transform(
+            Vec3::new(3.0, 0.0, 0.0),
+            Vec3::new(0.0, 90.0, 0.0),
+            Vec3::new(2.0, 2.0, 2.0),
+        );
+
+        let rec = hit(
+            &moved,
+            Point3::new(3.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(3.0, 2.0, 0.0));
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((rec.t - 8.0).abs() < 1e-4);
+
+        assert!(hit(
+            &moved,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn transform_keeps_normals_perpendicular_under_non_uniform_scale() {
+        // The sphere stretched into the ellipsoid x^2 / 4 + y^2 + z^2 = 1.
+        let ellipsoid = unit_sphere().transform(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+
+        let rec = hit(
+            &ellipsoid,
+            Point3::new(1.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        let y = 0.75_f32.sqrt();
+        assert_near(rec.p, Point3::new(1.0, y, 0.0));
+        // The gradient of the implicit surface.
+        assert_near(rec.normal, Vec3::new(0.25, y, 0.0).unit_vector());
+    }
+
+    #[test]
+    fn transform_bounds_the_transformed_object() {
+        let moved = unit_sphere().transform(
+            Vec3::new(3.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 90.0),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+
+        let bbox = moved.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.min(), Point3::new(2.0, -2.0, -1.0));
+        assert_near(bbox.max(), Point3::new(4.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn flip_face_hides_the_back_face() {
+        let above = Point3::new(0.5, 3.0, 0.5);
+        let below = Point3::new(0.5, -3.0, 0.5);
+        let (down, up) = (Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let two_sided = square();
+        assert!(hit(&two_sided, above, down).is_some());
+        assert!(hit(&two_sided, below, up).is_some());
+
+        let one_sided = square().flip_face();
+        let rec = hit(&one_sided, above, down).unwrap();
+        assert!(rec.is_front_face);
+        assert_near(rec.p, Point3::new(0.5, 1.0, 0.5));
+        assert!(hit(&one_sided, below, up).is_none());
+    }
+
+    #[test]
+    fn flip_face_after_flip_normals_shows_the_other_side() {
+        // A ceiling light that only shines down.
+        let light = square().flip_normals().flip_face();
+
+        assert!(hit(
+            &light,
+            Point3::new(0.5, 3.0, 0.5),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+        let rec = hit(
+            &light,
+            Point3::new(0.5, -3.0, 0.5),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert!(rec.is_front_face);
+        assert_near(rec.p, Point3::new(0.5, 1.0, 0.5));
+    }
+
+    /// A box with a different extent along each axis.
+    fn brick() -> Cuboid {
+        Cuboid::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 2.0, 3.0),
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    /// The unit sphere moved up to `(0, 2, 0)`.
+    fn raised_sphere() -> Translation<Sphere> {
+        unit_sphere().translate(Vec3::new(0.0, 2.0, 0.0))
+    }
+
+    #[test]
+    fn rotating_about_x_swaps_the_y_and_z_extents() {
+        let bbox = brick().rotate_x(90.0).bounding_box(0.0, 1.0).unwrap();
+
+        assert_near(bbox.min(), Point3::new(0.0, -3.0, 0.0));
+        assert_near(bbox.max(), Point3::new(1.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn rotating_about_z_swaps_the_x_and_y_extents() {
+        let bbox = brick().rotate_z(90.0).bounding_box(0.0, 1.0).unwrap();
+
+        assert_near(bbox.min(), Point3::new(-2.0, 0.0, 0.0));
+        assert_near(bbox.max(), Point3::new(0.0, 1.0, 3.0));
+    }
+
+    #[test]
+    fn rotating_about_x_rotates_the_normals() {
+        // Y turns into Z, so the sphere ends up at (0, 0, 2).
+        let rotated = raised_sphere().rotate_x(90.0);
+        let center = Point3::new(0.0, 0.0, 2.0);
+
+        for origin in [Point3::new(0.0, 0.0, 10.0), Point3::new(0.5, -0.3, 10.0)] {
+            let rec = hit(&rotated, origin, Vec3::new(0.0, 0.0, -1.0)).unwrap();
+            assert!(((rec.p - center).length() - 1.0).abs() < 1e-4);
+            assert_near(rec.normal, rec.p - center);
+        }
+    }
+
+    #[test]
+    fn rotating_about_z_rotates_the_normals() {
+        // Y turns into -X, so the sphere ends up at (-2, 0, 0).
+        let rotated = raised_sphere().rotate_z(90.0);
+        let center = Point3::new(-2.0, 0.0, 0.0);
+
+        for origin in [Point3::new(-10.0, 0.0, 0.0), Point3::new(-10.0, 0.3, 0.5)] {
+            let rec = hit(&rotated, origin, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+            assert!(((rec.p - center).length() - 1.0).abs() < 1e-4);
+            assert_near(rec.normal, rec.p - center);
+        }
+    }
+
+    #[test]
+    fn wrappers_apply_from_the_inside_out() {
+        // Rotating first spins the square around the y axis at its corner, then it moves.
+        let rotated_then_moved = square().rotate_y(90.0).translate(Vec3::new(5.0, 0.0, 0.0));
+        let rec = hit(
+            &rotated_then_moved,
+            Point3::new(5.5, 3.0, -0.5),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(5.5, 1.0, -0.5));
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // Moving first takes the square away from the axis, so rotating swings it around.
+        let moved_then_rotated = square().translate(Vec3::new(5.0, 0.0, 0.0)).rotate_y(90.0);
+        let rec = hit(
+            &moved_then_rotated,
+            Point3::new(0.5, 3.0, -5.5),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(0.5, 1.0, -5.5));
+    }
+
+    #[test]
+    fn transforms_compose() {
+        let nested = unit_sphere()
+            .transform(
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(2.0, 1.0, 1.0),
+            )
+            .transform(
+                Vec3::new(0.0, 4.0, 0.0),
+                Vec3::new(0.0, 0.0, 90.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            );
+
+        // The stretched sphere, standing upright at (0, 5, 0) after the rotation about z.
+        let rec = hit(
+            &nested,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(0.0, 7.0, 0.0));
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        let bbox = nested.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.min(), Point3::new(-1.0, 3.0, -1.0));
+        assert_near(bbox.max(), Point3::new(1.0, 7.0, 1.0));
+    }
+
+    /// The cube from `-1` to `1` on every axis.
+    fn cube() -> Arc<Cuboid> {
+        Arc::new(Cuboid::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    /// Scales by `scale` and then moves by `offset`, as a column-major matrix.
+    fn scale_then_translate(scale: Vec3, offset: Vec3) -> InstanceTransform {
+        InstanceTransform::new([
+            [scale.x(), 0.0, 0.0, 0.0],
+            [0.0, scale.y(), 0.0, 0.0],
+            [0.0, 0.0, scale.z(), 0.0],
+            [offset.x(), offset.y(), offset.z(), 1.0],
+        ])
+    }
+
+    #[test]
+    fn scaled_instance_is_hit_at_the_scaled_location() {
+        let transform = scale_then_translate(Vec3::new(2.0, 3.0, 4.0), Vec3::new(0.0, 0.0, 0.0));
+        let instance = Instance::new(cube(), transform);
+
+        let rec = hit(
+            &instance,
+            Point3::new(10.0, 0.5, 0.5),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(2.0, 0.5, 0.5));
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+        assert!((rec.t - 8.0).abs() < 1e-4);
+
+        let rec = hit(
+            &instance,
+            Point3::new(0.5, 10.0, 0.5),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(0.5, 3.0, 0.5));
+
+        let rec = hit(
+            &instance,
+            Point3::new(0.5, 0.5, -10.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(0.5, 0.5, -4.0));
+
+        // Inside the scaled cube, but outside the original one.
+        assert!(hit(
+            &instance,
+            Point3::new(1.5, 10.0, 1.5),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_some());
+        assert!(hit(
+            &instance,
+            Point3::new(2.5, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn instance_translation_is_the_last_column() {
+        let transform = scale_then_translate(Vec3::new(1.0, 1.0, 1.0), Vec3::new(5.0, 0.0, 0.0));
+        let instance = Instance::new(cube(), transform);
+
+        let rec = hit(
+            &instance,
+            Point3::new(5.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        )
+        .unwrap();
+        assert_near(rec.p, Point3::new(5.0, 1.0, 0.0));
+        assert!(hit(
+            &instance,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn instances_share_their_prototype() {
+        let prototype = cube();
+        let left = Instance::new(
+            prototype.clone(),
+            scale_then_translate(Vec3::new(1.0, 1.0, 1.0), Vec3::new(-5.0, 0.0, 0.0)),
+        );
+        let right = Instance::new(
+            prototype.clone(),
+            scale_then_translate(Vec3::new(1.0, 1.0, 1.0), Vec3::new(5.0, 0.0, 0.0)),
+        );
+        assert_eq!(Arc::strong_count(&prototype), 3);
+
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!(hit(&left, Point3::new(-5.0, 10.0, 0.0), down).is_some());
+        assert!(hit(&right, Point3::new(5.0, 10.0, 0.0), down).is_some());
+        assert!(hit(&left, Point3::new(5.0, 10.0, 0.0), down).is_none());
+    }
+
+    #[test]
+    fn instance_bounds_the_transformed_prototype() {
+        let transform = scale_then_translate(Vec3::new(2.0, 3.0, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        let instance = Instance::new(cube(), transform);
+
+        let bbox = instance.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.min(), Point3::new(-1.0, -3.0, -4.0));
+        assert_near(bbox.max(), Point3::new(3.0, 3.0, 4.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "instance transforms must be affine")]
+    fn instance_transforms_must_be_affine() {
+        InstanceTransform::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 2.0],
+        ]);
+    }
 }
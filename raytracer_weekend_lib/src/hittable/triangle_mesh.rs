@@ -0,0 +1,366 @@
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::cmp::Ordering;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "stats")]
+use crate::stats::{self, Counter};
+use crate::{
+    aabb::Aabb,
+    hittable::{
+        triangular::{default_texture_uv, hit_triangle, triangle_bounding_box},
+        HitRecord, Hittable,
+    },
+    material::Material,
+    ray::Ray,
+    texture::Point2d,
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// Leaves of the mesh's BVH hold at most this many triangles.
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// Deep enough for any BVH built by median splits over `u32` triangle indices.
+const MAX_BVH_DEPTH: usize = 64;
+
+/// The corners of a [`TriangleMesh`] triangle, as indices into the mesh's attribute lists.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MeshTriangle {
+    pub vertices: [u32; 3],
+    /// Corners without a normal fall back to the face normal, like [`Triangle`]'s.
+    ///
+    /// [`Triangle`]: super::triangular::Triangle
+    pub normals: [Option<u32>; 3],
+    pub texture_uvs: [Option<u32>; 3],
+}
+
+/// Many triangles of the same material, sharing their vertices, normals and texture coordinates
+/// instead of each carrying copies of their own. They are found through a BVH over the triangles
+/// that is laid out in a single list, rather than one allocation per node and triangle.
+///
+/// Hits look exactly like those of the equivalent [`Triangle`]s.
+///
+/// [`Triangle`]: super::triangular::Triangle
+#[derive(Debug)]
+pub struct TriangleMesh {
+    vertices: Vec<Point3>,
+    normals: Vec<Vec3>,
+    texture_uvs: Vec<Point2d>,
+    /// Ordered so that the triangles of every BVH node are next to each other.
+    triangles: Vec<MeshTriangle>,
+    /// The root comes first. Empty for meshes without triangles.
+    nodes: Vec<MeshBvhNode>,
+    material: Arc<dyn Material>,
+    cull_backfaces: bool,
+}
+
+/// The triangles of a [`TriangleMesh`] in BVH order together with the BVH over them, i.e.
+/// everything that takes time to build. It can be stored and handed to [`TriangleMesh::with_bvh`]
+/// later to skip that work.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MeshBvh {
+    triangles: Vec<MeshTriangle>,
+    nodes: Vec<MeshBvhNode>,
+}
+
+impl MeshBvh {
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Whether the BVH can be used for a mesh with that many vertices, normals and texture
+    /// coordinates: all its indices need to be in range, and its nodes need to form a tree over
+    /// the triangles that traversal can handle. This rules out BVHs read back from corrupt files.
+    pub fn fits(&self, vertex_count: usize, normal_count: usize, texture_uv_count: usize) -> bool {
+        indices_in_range(
+            &self.triangles,
+            vertex_count,
+            normal_count,
+            texture_uv_count,
+        ) && self.is_tree()
+    }
+
+    /// Every node is stored before its children, which rules out cycles.
+    fn is_tree(&self) -> bool {
+        if self.nodes.is_empty() != self.triangles.is_empty() {
+            return false;
+        }
+
+        let mut depths = vec![0; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node.contents {
+                NodeContents::Leaf { first, count } => {
+                    if first as usize + count as usize > self.triangles.len() {
+                        return false;
+                    }
+                }
+                NodeContents::Inner { left, right } => {
+                    for child in [left as usize, right as usize] {
+                        if child <= index || child >= self.nodes.len() {
+                            return false;
+                        }
+                        depths[child] = depths[index] + 1;
+                    }
+                }
+            }
+        }
+
+        // Traversal keeps at most one pending node per level, plus the one it is looking at.
+        depths.iter().all(|&depth| depth < MAX_BVH_DEPTH - 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct MeshBvhNode {
+    bounding_box: Aabb,
+    contents: NodeContents,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum NodeContents {
+    Leaf { first: u32, count: u32 },
+    Inner { left: u32, right: u32 },
+}
+
+impl TriangleMesh {
+    pub fn new(
+        vertices: Vec<Point3>,
+        normals: Vec<Vec3>,
+        texture_uvs: Vec<Point2d>,
+        mut triangles: Vec<MeshTriangle>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(triangles.len() <= u32::MAX as usize);
+        assert!(indices_in_range(
+            &triangles,
+            vertices.len(),
+            normals.len(),
+            texture_uvs.len()
+        ));
+
+        let mut nodes = Vec::new();
+        if !triangles.is_empty() {
+            build_node(&vertices, &mut triangles, 0, &mut nodes);
+        }
+
+        Self {
+            vertices,
+            normals,
+            texture_uvs,
+            triangles,
+            nodes,
+            material,
+            cull_backfaces: false,
+        }
+    }
+
+    /// See [`Triangle::with_cull_backfaces`].
+    ///
+    /// [`Triangle::with_cull_backfaces`]: super::triangular::Triangle::with_cull_backfaces
+    pub fn with_cull_backfaces(mut self, cull_backfaces: bool) -> Self {
+        self.cull_backfaces = cull_backfaces;
+        self
+    }
+
+    /// Like [`TriangleMesh::new`], but takes the triangles in the order of an earlier built BVH
+    /// instead of building it again. The BVH has to [fit](MeshBvh::fits) the attribute lists.
+    pub fn with_bvh(
+        vertices: Vec<Point3>,
+        normals: Vec<Vec3>,
+        texture_uvs: Vec<Point2d>,
+        bvh: MeshBvh,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(bvh.fits(vertices.len(), normals.len(), texture_uvs.len()));
+
+        Self {
+            vertices,
+            normals,
+            texture_uvs,
+            triangles: bvh.triangles,
+            nodes: bvh.nodes,
+            material,
+            cull_backfaces: false,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// A copy of the BVH, for [`TriangleMesh::with_bvh`].
+    pub fn bvh(&self) -> MeshBvh {
+        MeshBvh {
+            triangles: self.triangles.clone(),
+            nodes: self.nodes.clone(),
+        }
+    }
+
+    fn hit_triangle(
+        &self,
+        triangle: &MeshTriangle,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let vertices = triangle.vertices.map(|i| self.vertices[i as usize]);
+        let face_normal = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0]));
+        let normals = triangle
+            .normals
+            .map(|i| i.map_or(face_normal, |i| self.normals[i as usize]));
+        let mut texture_uv = default_texture_uv();
+        for (uv, index) in texture_uv.iter_mut().zip(triangle.texture_uvs) {
+            if let Some(index) = index {
+                *uv = self.texture_uvs[index as usize];
+            }
+        }
+
+        hit_triangle(
+            &vertices,
+            &normals,
+            &texture_uv,
+            self.material.as_ref(),
+            self.cull_backfaces,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _rng: &mut ActiveRng,
+    ) -> Option<HitRecord<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_so_far = t_max;
+        let mut rec = None;
+
+        let mut stack = [0_u32; MAX_BVH_DEPTH];
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len] as usize];
+
+            #[cfg(feature = "stats")]
+            stats::record(Counter::BvhNodeTests);
+            if !node.bounding_box.hit(ray, t_min, closest_so_far) {
+                continue;
+            }
+
+            match node.contents {
+                NodeContents::Leaf { first, count } => {
+                    let triangles = &self.triangles[first as usize..(first + count) as usize];
+                    for triangle in triangles {
+                        if let Some(hit) = self.hit_triangle(triangle, ray, t_min, closest_so_far) {
+                            closest_so_far = hit.t;
+                            rec = Some(hit);
+                        }
+                    }
+                }
+                NodeContents::Inner { left, right } => {
+                    // The left child goes on top, so it is visited first.
+                    stack[stack_len] = right;
+                    stack[stack_len + 1] = left;
+                    stack_len += 2;
+                }
+            }
+        }
+
+        rec
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bounding_box.clone())
+    }
+}
+
+fn indices_in_range(
+    triangles: &[MeshTriangle],
+    vertex_count: usize,
+    normal_count: usize,
+    texture_uv_count: usize,
+) -> bool {
+    triangles.iter().all(|triangle| {
+        triangle
+            .vertices
+            .iter()
+            .all(|&i| (i as usize) < vertex_count)
+            && triangle
+                .normals
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < normal_count)
+            && triangle
+                .texture_uvs
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < texture_uv_count)
+    })
+}
+
+/// Builds the BVH node over `triangles`, which start at `offset` in the whole mesh, and all nodes
+/// below it. Returns the index of the node. Triangles are split at the median of their centroids
+/// along the longest axis of the node, which reorders them.
+fn build_node(
+    vertices: &[Point3],
+    triangles: &mut [MeshTriangle],
+    offset: usize,
+    nodes: &mut Vec<MeshBvhNode>,
+) -> u32 {
+    let corners = |triangle: &MeshTriangle| triangle.vertices.map(|i| vertices[i as usize]);
+    let bounding_box = triangles
+        .iter()
+        .map(|triangle| triangle_bounding_box(&corners(triangle)))
+        .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+        .expect("BVH nodes are only built over some triangles");
+
+    let index = nodes.len() as u32;
+    nodes.push(MeshBvhNode {
+        bounding_box: bounding_box.clone(),
+        contents: NodeContents::Leaf {
+            first: offset as u32,
+            count: triangles.len() as u32,
+        },
+    });
+
+    if triangles.len() <= MAX_LEAF_TRIANGLES {
+        return index;
+    }
+
+    let extent = bounding_box.max() - bounding_box.min();
+    let axis = (0..3)
+        .max_by(|&a, &b| extent[a].partial_cmp(&extent[b]).unwrap_or(Ordering::Equal))
+        .unwrap();
+    let centroid = |triangle: &MeshTriangle| {
+        let [a, b, c] = corners(triangle);
+        a[axis] + b[axis] + c[axis]
+    };
+
+    let mid = triangles.len() / 2;
+    triangles.select_nth_unstable_by(mid, |a, b| {
+        centroid(a)
+            .partial_cmp(&centroid(b))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let (left_triangles, right_triangles) = triangles.split_at_mut(mid);
+    let left = build_node(vertices, left_triangles, offset, nodes);
+    let right = build_node(vertices, right_triangles, offset + mid, nodes);
+    nodes[index as usize].contents = NodeContents::Inner { left, right };
+
+    index
+}
@@ -1,34 +1,48 @@
 use alloc::sync::Arc;
-use core::ops::{Add, Mul};
+use core::{
+    cmp::Ordering,
+    ops::{Add, Mul},
+};
 use iter_fixed::IntoIteratorFixed;
 use itertools::{Itertools, MinMaxResult};
 #[cfg(feature = "no_std")]
 use micromath::F32Ext;
 #[cfg(feature = "std")]
 use {
-    std::collections::HashMap,
+    rand::Rng,
     std::fs,
+    std::{collections::HashMap, hash::Hash},
     wavefront_obj::{
         mtl,
         mtl::{Illumination, MtlSet},
         obj,
-        obj::{Geometry, Normal, Object, Primitive, TVertex, Vertex},
+        obj::{Geometry, Normal, Object, Primitive, Shape, TVertex, Vertex},
     },
 };
 
-#[cfg(feature = "std")]
-use crate::image_texture::ImageTexture;
 use crate::{
     aabb::Aabb,
     bvh::BvhNode,
-    hittable::{HitRecord, Hittable},
+    hittable::{
+        triangle_mesh::{MeshTriangle, TriangleMesh},
+        HitRecord, Hittable,
+    },
     light_source::DiffuseLight,
-    material::{Lambertian, Material},
+    material::{Dielectric, Lambertian, Material, Metal},
     ray::Ray,
     texture::{Checker, Point2d, SolidColor},
     vec3::{Color, Point3, Vec3},
     ActiveRng,
 };
+#[cfg(feature = "stats")]
+use crate::stats::{self, Counter};
+#[cfg(feature = "bvh_cache")]
+use crate::hittable::triangle_mesh::MeshBvh;
+#[cfg(feature = "std")]
+use crate::{
+    asset_check::{AssetCheck, InvalidAssetData},
+    image_texture::ImageTexture,
+};
 
 #[derive(Debug, Clone)]
 pub struct Triangle {
@@ -36,6 +50,7 @@ pub struct Triangle {
     normals: [Vec3; 3],
     texture_uv: [Point2d; 3],
     material: Arc<dyn Material>,
+    cull_backfaces: bool,
 }
 
 impl Triangle {
@@ -54,13 +69,8 @@ impl Triangle {
 
         let normals = normals.map(|vertex_normal| vertex_normal.unwrap_or(triangle_normal));
 
-        let default_uv = [
-            Point2d { u: 0.0, v: 0.0 },
-            Point2d { u: 1.0, v: 0.0 },
-            Point2d { u: 0.0, v: 1.0 },
-        ];
         let texture_uv = texture_uv.into_iter_fixed()
-            .zip(default_uv)
+            .zip(default_texture_uv())
             .map(|(param, default)| param.unwrap_or(default))
             .collect();
 
@@ -69,6 +79,7 @@ impl Triangle {
             normals,
             texture_uv,
             material,
+            cull_backfaces: false,
         }
     }
 
@@ -76,6 +87,14 @@ impl Triangle {
         Self::new(vertices, [None, None, None], [None, None, None], material)
     }
 
+    /// Makes the triangle invisible from behind, i.e. from the side its corners run clockwise on.
+    /// This saves work on closed meshes, whose back faces are always hidden by front faces, but
+    /// breaks anything seen from the inside, like the far side of a glass object.
+    pub fn with_cull_backfaces(mut self, cull_backfaces: bool) -> Self {
+        self.cull_backfaces = cull_backfaces;
+        self
+    }
+
     fn min_max(nums: impl Iterator<Item=f32>) -> (f32, f32) {
         let mut min_max = match nums.minmax() {
             MinMaxResult::NoElements => {
@@ -95,58 +114,135 @@ impl Triangle {
 
 impl Hittable for Triangle {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, _rng: &mut ActiveRng) -> Option<HitRecord> {
-        let vertex_a = self.vertices[0];
-        let vertex_b = self.vertices[1];
-        let vertex_c = self.vertices[2];
-        let a_to_b = vertex_b - vertex_a;
-        let a_to_c = vertex_c - vertex_a;
-        let normal = a_to_b.cross(&a_to_c);
-        let determinant = -ray.direction().dot(&normal);
-        let inv_determinant = 1.0 / determinant;
-        let a_to_ray_origin = ray.origin() - vertex_a;
-        let a_to_ray_origin_cross_direction = a_to_ray_origin.cross(&ray.direction());
+        hit_triangle(
+            &self.vertices,
+            &self.normals,
+            &self.texture_uv,
+            self.material.as_ref(),
+            self.cull_backfaces,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
 
-        let u = a_to_c.dot(&a_to_ray_origin_cross_direction) * inv_determinant;
-        let v = -a_to_b.dot(&a_to_ray_origin_cross_direction) * inv_determinant;
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.vertices))
+    }
+}
 
-        let t = a_to_ray_origin.dot(&normal) * inv_determinant;
+/// The texture coordinates of triangles that come without.
+pub(super) fn default_texture_uv() -> [Point2d; 3] {
+    [
+        Point2d::new(0.0, 0.0),
+        Point2d::new(1.0, 0.0),
+        Point2d::new(0.0, 1.0),
+    ]
+}
 
-        if t < t_min || t > t_max {
-            return None;
-        }
+/// Intersects `ray` with the triangle spanned by `vertices`, interpolating the normals and
+/// texture coordinates at its corners. Shared by [`Triangle`] and [`TriangleMesh`].
+///
+/// This is the watertight test of Woop, Benthin and Wald: the triangle is sheared into the space
+/// where the ray runs along the z axis, and the edges are tested there by 2D cross products.
+/// Edges shared by two triangles are computed from the same values for both, so rays through them
+/// can't slip through the gap between. Back faces, those whose corners run clockwise as seen by
+/// the ray, are skipped if `cull_backfaces` is set.
+///
+/// [`TriangleMesh`]: super::triangle_mesh::TriangleMesh
+#[allow(clippy::too_many_arguments)]
+pub(super) fn hit_triangle<'a>(
+    vertices: &[Point3; 3],
+    normals: &[Vec3; 3],
+    texture_uv: &[Point2d; 3],
+    material: &'a dyn Material,
+    cull_backfaces: bool,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    #[cfg(feature = "stats")]
+    stats::record(Counter::TriangleTests);
+    let direction = ray.direction();
 
-        let triangle_was_hit = t >= 0.0 && u >= 0.0 && v >= 0.0 && (u + v) <= 1.0;
+    // Make the largest component of the direction z, keeping the winding of the triangle.
+    let kz = (0..3)
+        .max_by(|&a, &b| {
+            direction[a]
+                .abs()
+                .partial_cmp(&direction[b].abs())
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap();
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if direction[kz] < 0.0 {
+        core::mem::swap(&mut kx, &mut ky);
+    }
 
-        if !triangle_was_hit {
-            return None;
-        }
+    let shear_x = direction[kx] / direction[kz];
+    let shear_y = direction[ky] / direction[kz];
+    let shear_z = 1.0 / direction[kz];
 
-        let p = ray.at(t);
+    let [a, b, c] = vertices.map(|vertex| vertex - ray.origin());
+    let [(a_x, a_y), (b_x, b_y), (c_x, c_y)] =
+        [a, b, c].map(|p| (p[kx] - shear_x * p[kz], p[ky] - shear_y * p[kz]));
 
-        let hit_normal = Self::interpolate_barycentric(u, v, &self.normals);
-        let hit_uv = Self::interpolate_barycentric(u, v, &self.texture_uv);
+    // Twice the signed areas of the triangles between the ray and each edge, which make up the
+    // barycentric coordinates of the hit.
+    let mut edge_bc = c_x * b_y - c_y * b_x;
+    let mut edge_ca = a_x * c_y - a_y * c_x;
+    let mut edge_ab = b_x * a_y - b_y * a_x;
 
-        // TODO: Compute texture u/v properly
-        Some(HitRecord::new_with_face_normal(
-            p,
-            t,
-            hit_uv,
-            self.material.as_ref(),
-            ray,
-            hit_normal,
-        ))
+    // A ray exactly through an edge needs the exact sign of its area, which f32 may round to zero.
+    if edge_bc == 0.0 || edge_ca == 0.0 || edge_ab == 0.0 {
+        let cross = |(p_x, p_y): (f32, f32), (q_x, q_y): (f32, f32)| {
+            (p_x as f64 * q_y as f64 - p_y as f64 * q_x as f64) as f32
+        };
+        edge_bc = cross((c_x, c_y), (b_x, b_y));
+        edge_ca = cross((a_x, a_y), (c_x, c_y));
+        edge_ab = cross((b_x, b_y), (a_x, a_y));
     }
 
-    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
-        let min_max_x = Self::min_max(self.vertices.iter().map(|v| v.x()));
-        let min_max_y = Self::min_max(self.vertices.iter().map(|v| v.y()));
-        let min_max_z = Self::min_max(self.vertices.iter().map(|v| v.z()));
+    let any_negative = edge_bc < 0.0 || edge_ca < 0.0 || edge_ab < 0.0;
+    let any_positive = edge_bc > 0.0 || edge_ca > 0.0 || edge_ab > 0.0;
+    if any_negative && (cull_backfaces || any_positive) {
+        return None;
+    }
 
-        let min = Point3::new(min_max_x.0, min_max_y.0, min_max_z.0);
-        let max = Point3::new(min_max_x.1, min_max_y.1, min_max_z.1);
+    let determinant = edge_bc + edge_ca + edge_ab;
+    if determinant == 0.0 {
+        return None;
+    }
 
-        Some(Aabb::new(min, max))
+    let scaled_t = shear_z * (edge_bc * a[kz] + edge_ca * b[kz] + edge_ab * c[kz]);
+    let t = scaled_t / determinant;
+    if t < t_min || t > t_max {
+        return None;
     }
+
+    let u = edge_ca / determinant;
+    let v = edge_ab / determinant;
+    let p = ray.at(t);
+
+    let hit_normal = Triangle::interpolate_barycentric(u, v, normals);
+    let hit_uv = Triangle::interpolate_barycentric(u, v, texture_uv);
+
+    // TODO: Compute texture u/v properly
+    Some(HitRecord::new_with_face_normal(
+        p, t, hit_uv, material, ray, hit_normal,
+    ))
+}
+
+/// The bounding box of a triangle, padded a little along axes the triangle is flat in.
+pub(super) fn triangle_bounding_box(vertices: &[Point3; 3]) -> Aabb {
+    let min_max_x = Triangle::min_max(vertices.iter().map(|v| v.x()));
+    let min_max_y = Triangle::min_max(vertices.iter().map(|v| v.y()));
+    let min_max_z = Triangle::min_max(vertices.iter().map(|v| v.z()));
+
+    let min = Point3::new(min_max_x.0, min_max_y.0, min_max_z.0);
+    let max = Point3::new(min_max_x.1, min_max_y.1, min_max_z.1);
+
+    Aabb::new(min, max)
 }
 
 #[cfg(feature = "wavefront_obj")]
@@ -156,32 +252,96 @@ impl From<Vertex> for Point3 {
     }
 }
 
+#[cfg(feature = "wavefront_obj")]
+impl From<mtl::Color> for Color {
+    fn from(c: mtl::Color) -> Self {
+        Self::new(c.r as f32, c.g as f32, c.b as f32)
+    }
+}
+
 #[cfg(feature = "wavefront_obj")]
 impl From<TVertex> for Point2d {
     fn from(v: TVertex) -> Self {
-        Self {
-            u: v.u as f32,
-            v: v.v as f32,
-        }
+        Self::new(v.u as f32, v.v as f32)
     }
 }
 
+/// Coordinates beyond this magnitude are treated as corrupt, since f32 has lost all sub-unit
+/// precision out there anyway.
 #[cfg(feature = "std")]
-fn parse_geometry<'a>(
-    geometry: &'a Geometry,
-    vertices: &'a [Vertex],
-    normals: &'a [Normal],
-    texture_vertices: &'a [TVertex],
-    materials: &Option<HashMap<String, Arc<dyn Material>>>,
-) -> impl Iterator<Item=Box<dyn Hittable>> + 'a {
-    let material = if let Some(mat_name) = geometry.material_name.as_ref() {
-        let mat_lib = materials.as_ref().unwrap();
-        mat_lib[mat_name].clone()
-    } else {
-        Arc::new(DiffuseLight::new(SolidColor::new_rgb(1.0, 0.0, 1.0)))
-    };
+const MAX_COORDINATE_MAGNITUDE: f32 = 1.0e9;
+
+/// Which normals OBJ meshes are shaded with.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalMode {
+    /// The normals in the file. Faces without any are shaded flat.
+    #[default]
+    FromFile,
+    /// The normals in the file where there are any. Vertices without get the area-weighted
+    /// average of the normals of the faces around them, so the surface shades smoothly. Faces
+    /// only share normals within their smoothing groups, and faces with smoothing turned off stay
+    /// flat. Files without any smoothing groups are smoothed as a whole.
+    Auto,
+}
+
+/// Counts how much of a mesh had to be fixed up while loading it.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ObjLoadReport {
+    /// Triangles dropped because one of their vertex positions was non-finite or out of range.
+    pub dropped_triangles: usize,
+    /// Vertex normals and texture coordinates replaced by the defaults `Triangle` falls back to.
+    pub repaired_attributes: usize,
+    /// Materials whose diffuse map couldn't be loaded, which show up magenta instead.
+    pub missing_textures: usize,
+}
+
+#[cfg(feature = "std")]
+impl ObjLoadReport {
+    fn is_clean(&self) -> bool {
+        self.dropped_triangles == 0 && self.repaired_attributes == 0
+    }
+}
+
+#[cfg(feature = "std")]
+fn is_sane_vector(v: &Vec3) -> bool {
+    (0..3).all(|axis| v[axis].is_finite() && v[axis].abs() < MAX_COORDINATE_MAGNITUDE)
+}
+
+#[cfg(feature = "std")]
+fn is_sane_uv(uv: &Point2d) -> bool {
+    uv.u.is_finite() && uv.v.is_finite()
+}
+
+#[cfg(feature = "std")]
+fn sanitize_attribute<T>(
+    attribute: Option<T>,
+    is_sane: impl Fn(&T) -> bool,
+    report: &mut ObjLoadReport,
+) -> Option<T> {
+    match attribute {
+        Some(value) if !is_sane(&value) => {
+            report.repaired_attributes += 1;
+            None
+        }
+        attribute => attribute,
+    }
+}
 
-    geometry.shapes.iter().map(move |shape| {
+#[cfg(feature = "std")]
+#[allow(clippy::too_many_arguments)]
+fn parse_geometry(
+    geometry: &Geometry,
+    vertices: &[Vertex],
+    normals: &[Normal],
+    texture_vertices: &[TVertex],
+    check: AssetCheck,
+    smooth_normals: Option<&SmoothNormals>,
+    mesh: &mut MeshBuilder,
+    report: &mut ObjLoadReport,
+) {
+    for shape in &geometry.shapes {
         match shape.primitive {
             Primitive::Point(_) => {
                 panic!()
@@ -190,73 +350,450 @@ fn parse_geometry<'a>(
                 panic!()
             }
             Primitive::Triangle(vertex_1_idx, vertex_2_idx, vertex_3_idx) => {
-                let vertex_1 = vertices[vertex_1_idx.0];
-                let vertex_2 = vertices[vertex_2_idx.0];
-                let vertex_3 = vertices[vertex_3_idx.0];
+                let vertex_1: Point3 = vertices[vertex_1_idx.0].into();
+                let vertex_2: Point3 = vertices[vertex_2_idx.0].into();
+                let vertex_3: Point3 = vertices[vertex_3_idx.0].into();
 
-                let texture_uv_1: Option<Point2d> =
+                let mut texture_uv_1: Option<Point2d> =
                     vertex_1_idx.1.map(|idx| texture_vertices[idx].into());
-                let texture_uv_2: Option<Point2d> =
+                let mut texture_uv_2: Option<Point2d> =
                     vertex_2_idx.1.map(|idx| texture_vertices[idx].into());
-                let texture_uv_3: Option<Point2d> =
+                let mut texture_uv_3: Option<Point2d> =
                     vertex_3_idx.1.map(|idx| texture_vertices[idx].into());
 
-                let normal_1: Option<Vec3> = vertex_1_idx.2.map(|idx| normals[idx].into());
-                let normal_2: Option<Vec3> = vertex_2_idx.2.map(|idx| normals[idx].into());
-                let normal_3: Option<Vec3> = vertex_3_idx.2.map(|idx| normals[idx].into());
+                let mut normal_1: Option<Vec3> = vertex_1_idx.2.map(|idx| normals[idx].into());
+                let mut normal_2: Option<Vec3> = vertex_2_idx.2.map(|idx| normals[idx].into());
+                let mut normal_3: Option<Vec3> = vertex_3_idx.2.map(|idx| normals[idx].into());
+
+                if let Some(smooth_normals) = smooth_normals {
+                    normal_1 = normal_1.or_else(|| smooth_normals.normal(vertex_1, shape));
+                    normal_2 = normal_2.or_else(|| smooth_normals.normal(vertex_2, shape));
+                    normal_3 = normal_3.or_else(|| smooth_normals.normal(vertex_3, shape));
+                }
 
-                // TODO: Handle materials properly
-                Box::new(Triangle::new(
-                    [vertex_1.into(), vertex_2.into(), vertex_3.into()],
+                if check != AssetCheck::Trusted {
+                    if ![vertex_1, vertex_2, vertex_3].iter().all(is_sane_vector) {
+                        report.dropped_triangles += 1;
+                        continue;
+                    }
+
+                    texture_uv_1 = sanitize_attribute(texture_uv_1, is_sane_uv, report);
+                    texture_uv_2 = sanitize_attribute(texture_uv_2, is_sane_uv, report);
+                    texture_uv_3 = sanitize_attribute(texture_uv_3, is_sane_uv, report);
+
+                    normal_1 = sanitize_attribute(normal_1, is_sane_vector, report);
+                    normal_2 = sanitize_attribute(normal_2, is_sane_vector, report);
+                    normal_3 = sanitize_attribute(normal_3, is_sane_vector, report);
+                }
+
+                mesh.push_triangle(
+                    [vertex_1, vertex_2, vertex_3],
                     [normal_1, normal_2, normal_3],
                     [texture_uv_1, texture_uv_2, texture_uv_3],
-                    material.clone(),
-                )) as Box<dyn Hittable>
+                );
             }
         }
-    })
+    }
+}
+
+/// Vertex normals averaged from the faces around each vertex, for meshes that come without.
+///
+/// The averages span all objects of a file, as the OBJ parser starts a new one wherever vertices
+/// follow faces, which splits up meshes that were written in chunks.
+#[cfg(feature = "std")]
+struct SmoothNormals {
+    /// Whether the file assigns smoothing groups at all. If not, all faces count as one group.
+    uses_groups: bool,
+    /// The sums of the normals of the faces around a vertex, by the bit pattern of its position
+    /// and smoothing group. Vertices the file repeats at the same position, as along texture
+    /// seams, thus share their normal. The normals are left at the length of the cross product,
+    /// which weights them by face area.
+    sums: HashMap<([u32; 3], u32), Vec3>,
+}
+
+#[cfg(feature = "std")]
+impl SmoothNormals {
+    fn new(objects: &[Object]) -> Self {
+        let shapes = || {
+            objects.iter().flat_map(|object| {
+                let shapes = object.geometry.iter().flat_map(|geometry| &geometry.shapes);
+                shapes.map(move |shape| (object, shape))
+            })
+        };
+        let mut smooth_normals = Self {
+            uses_groups: shapes().any(|(_, shape)| shape.smoothing_groups.iter().any(|&g| g != 0)),
+            sums: HashMap::new(),
+        };
+
+        for (object, shape) in shapes() {
+            if let Primitive::Triangle(a, b, c) = shape.primitive {
+                let [vertex_a, vertex_b, vertex_c]: [Point3; 3] =
+                    [a.0, b.0, c.0].map(|idx| object.vertices[idx].into());
+                let face_normal = (vertex_b - vertex_a).cross(&(vertex_c - vertex_a));
+                // Broken faces are dropped or repaired later and mustn't spoil their neighbours.
+                if !is_sane_vector(&face_normal) {
+                    continue;
+                }
+
+                for group in smooth_normals.groups(shape) {
+                    for vertex in [vertex_a, vertex_b, vertex_c] {
+                        let key = (Self::position_key(vertex), group);
+                        *smooth_normals.sums.entry(key).or_default() += face_normal;
+                    }
+                }
+            }
+        }
+
+        smooth_normals
+    }
+
+    /// The smoothing groups a face belongs to; none if it is to be shaded flat.
+    fn groups<'s>(&self, shape: &'s Shape) -> impl Iterator<Item = u32> + 's {
+        let uses_groups = self.uses_groups;
+        let ungrouped = (!uses_groups).then_some(0);
+
+        shape
+            .smoothing_groups
+            .iter()
+            .copied()
+            .filter(move |&group| uses_groups && group != 0)
+            .chain(ungrouped)
+    }
+
+    fn position_key(vertex: Point3) -> [u32; 3] {
+        [vertex.x(), vertex.y(), vertex.z()].map(f32::to_bits)
+    }
+
+    /// The normal of the corner at `vertex` in the face `shape`, or `None` for flat faces.
+    fn normal(&self, vertex: Point3, shape: &Shape) -> Option<Vec3> {
+        let position = Self::position_key(vertex);
+        let sum = self
+            .groups(shape)
+            .filter_map(|group| self.sums.get(&(position, group)))
+            .fold(Vec3::default(), |sum, &normal| sum + normal);
+
+        (sum.length_squared() > 0.0).then(|| sum.unit_vector())
+    }
 }
 
 #[cfg(feature = "std")]
 fn parse_individual_object(
     object: &Object,
+    check: AssetCheck,
+    smooth_normals: Option<&SmoothNormals>,
+    meshes: &mut Vec<(Option<String>, MeshBuilder)>,
+    report: &mut ObjLoadReport,
+) {
+    for geometry in &object.geometry {
+        // A linear search keeps the meshes in the order of the file, which hashing wouldn't.
+        let mesh_index = match meshes
+            .iter()
+            .position(|(material_name, _)| *material_name == geometry.material_name)
+        {
+            Some(index) => index,
+            None => {
+                meshes.push((geometry.material_name.clone(), MeshBuilder::default()));
+                meshes.len() - 1
+            }
+        };
+
+        parse_geometry(
+            geometry,
+            &object.vertices,
+            &object.normals,
+            &object.tex_vertices,
+            check,
+            smooth_normals,
+            &mut meshes[mesh_index].1,
+            report,
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+fn geometry_material(
+    material_name: Option<&String>,
     materials: &Option<HashMap<String, Arc<dyn Material>>>,
-) -> Vec<Box<dyn Hittable>> {
-    object
-        .geometry
-        .iter()
-        .flat_map(|geometry| {
-            parse_geometry(
-                geometry,
-                &object.vertices,
-                &object.normals,
-                &object.tex_vertices,
-                materials,
-            )
-        })
-        .collect()
+) -> Arc<dyn Material> {
+    if let Some(mat_name) = material_name {
+        let mat_lib = materials.as_ref().unwrap();
+        mat_lib[mat_name].clone()
+    } else {
+        Arc::new(DiffuseLight::new(SolidColor::new_rgb(1.0, 0.0, 1.0)))
+    }
+}
+
+/// Collects the triangles of one [`TriangleMesh`], storing every distinct vertex position,
+/// normal and texture coordinate only once.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Point3>,
+    normals: Vec<Vec3>,
+    texture_uvs: Vec<Point2d>,
+    triangles: Vec<MeshTriangle>,
+    /// The indices of the values collected so far, by their bit patterns.
+    vertex_indices: HashMap<[u32; 3], u32>,
+    normal_indices: HashMap<[u32; 3], u32>,
+    texture_uv_indices: HashMap<[u32; 2], u32>,
+}
+
+#[cfg(feature = "std")]
+impl MeshBuilder {
+    fn push_triangle(
+        &mut self,
+        vertices: [Point3; 3],
+        normals: [Option<Vec3>; 3],
+        texture_uvs: [Option<Point2d>; 3],
+    ) {
+        let vertices = vertices.map(|vertex| {
+            let key = [vertex.x(), vertex.y(), vertex.z()].map(f32::to_bits);
+            intern(&mut self.vertices, &mut self.vertex_indices, vertex, key)
+        });
+        let normals = normals.map(|normal| {
+            normal.map(|normal| {
+                let key = [normal.x(), normal.y(), normal.z()].map(f32::to_bits);
+                intern(&mut self.normals, &mut self.normal_indices, normal, key)
+            })
+        });
+        let texture_uvs = texture_uvs.map(|uv| {
+            uv.map(|uv| {
+                let key = [uv.u, uv.v].map(f32::to_bits);
+                intern(&mut self.texture_uvs, &mut self.texture_uv_indices, uv, key)
+            })
+        });
+
+        self.triangles.push(MeshTriangle {
+            vertices,
+            normals,
+            texture_uvs,
+        });
+    }
+
+    fn build(self, material: Arc<dyn Material>) -> TriangleMesh {
+        TriangleMesh::new(
+            self.vertices,
+            self.normals,
+            self.texture_uvs,
+            self.triangles,
+            material,
+        )
+    }
+
+    /// Builds the mesh around a BVH built for the same triangles before.
+    #[cfg(feature = "bvh_cache")]
+    fn build_with_bvh(self, bvh: MeshBvh, material: Arc<dyn Material>) -> TriangleMesh {
+        TriangleMesh::with_bvh(self.vertices, self.normals, self.texture_uvs, bvh, material)
+    }
+}
+
+/// The index of `value` in `values`, which it is appended to unless it is there already.
+#[cfg(feature = "std")]
+fn intern<T, K: Eq + Hash>(
+    values: &mut Vec<T>,
+    indices: &mut HashMap<K, u32>,
+    value: T,
+    key: K,
+) -> u32 {
+    *indices.entry(key).or_insert_with(|| {
+        values.push(value);
+        (values.len() - 1) as u32
+    })
 }
 
 #[cfg(feature = "std")]
 pub fn load_wavefront_obj(
     path: &str,
-    rng: &mut ActiveRng,
+    rng: &mut impl Rng,
+) -> Result<Box<dyn Hittable>, Box<dyn std::error::Error>> {
+    load_wavefront_obj_with_check(path, AssetCheck::Repair, NormalMode::FromFile, rng)
+        .map(|(mesh, _)| mesh)
+}
+
+/// Loads an OBJ file like [`load_wavefront_obj`], but with [`NormalMode::Auto`], so curved
+/// surfaces without normals in the file shade smoothly rather than faceted.
+#[cfg(feature = "std")]
+pub fn load_wavefront_obj_smooth(
+    path: &str,
+    rng: &mut impl Rng,
+) -> Result<Box<dyn Hittable>, Box<dyn std::error::Error>> {
+    load_wavefront_obj_with_check(path, AssetCheck::Repair, NormalMode::Auto, rng)
+        .map(|(mesh, _)| mesh)
+}
+
+/// Loads an OBJ file like [`load_wavefront_obj`], additionally reporting how many triangles had
+/// to be dropped or repaired because of non-finite or out-of-range data.
+#[cfg(feature = "std")]
+pub fn load_wavefront_obj_with_check(
+    path: &str,
+    check: AssetCheck,
+    normal_mode: NormalMode,
+    rng: &mut impl Rng,
+) -> Result<(Box<dyn Hittable>, ObjLoadReport), Box<dyn std::error::Error>> {
+    let (meshes, report) = parse_wavefront_obj(path, check, normal_mode)?;
+
+    let meshes: Vec<Box<dyn Hittable>> = meshes
+        .into_iter()
+        .map(|parsed| -> Box<dyn Hittable> { Box::new(parsed.mesh.build(parsed.material)) })
+        .collect();
+
+    // TODO: Sort out this time thing
+    Ok((Box::new(BvhNode::new(meshes, 0.0, 1.0, rng)), report))
+}
+
+/// Loads an OBJ file like [`load_wavefront_obj`], but keeps the BVHs of its meshes in a file
+/// next to it, with the extension `.bvh`, and reuses them as long as the OBJ file hasn't been
+/// modified since. This saves building them on every run, which takes a while for large meshes.
+///
+/// A missing, stale or unreadable cache file is replaced by a fresh one. Failing to write it
+/// isn't an error, the BVHs are just built again next time.
+#[cfg(feature = "bvh_cache")]
+pub fn load_wavefront_obj_cached(
+    path: &str,
+    rng: &mut impl Rng,
 ) -> Result<Box<dyn Hittable>, Box<dyn std::error::Error>> {
+    let (meshes, _) = parse_wavefront_obj(path, AssetCheck::Repair, NormalMode::FromFile)?;
+
+    let cache_path = std::path::Path::new(path).with_extension("bvh");
+    let obj_modified = fs::metadata(path)?
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)?;
+    let cache = BvhCache {
+        version: BvhCache::VERSION,
+        obj_modified: (obj_modified.as_secs(), obj_modified.subsec_nanos()),
+        meshes: Vec::new(),
+    };
+
+    let cached_bvhs = fs::read(&cache_path)
+        .ok()
+        .and_then(|bytes| postcard::from_bytes::<BvhCache>(&bytes).ok())
+        .filter(|cached| {
+            (cached.version, cached.obj_modified) == (cache.version, cache.obj_modified)
+                && cached.meshes.len() == meshes.len()
+        })
+        .map(|cached| cached.meshes);
+
+    let mut built_meshes = Vec::with_capacity(meshes.len());
+    let mut is_fresh = cached_bvhs.is_some();
+    let mut cached_bvhs = cached_bvhs.into_iter().flatten();
+    for parsed in meshes {
+        let mesh = &parsed.mesh;
+        let cached_bvh = cached_bvhs.next().filter(|(cached_name, bvh)| {
+            *cached_name == parsed.material_name
+                && bvh.triangle_count() == mesh.triangles.len()
+                && bvh.fits(
+                    mesh.vertices.len(),
+                    mesh.normals.len(),
+                    mesh.texture_uvs.len(),
+                )
+        });
+
+        is_fresh &= cached_bvh.is_some();
+        let built = match cached_bvh {
+            Some((_, bvh)) => parsed.mesh.build_with_bvh(bvh, parsed.material),
+            None => parsed.mesh.build(parsed.material),
+        };
+        built_meshes.push((parsed.material_name, built));
+    }
+
+    if !is_fresh {
+        let cache = BvhCache {
+            meshes: built_meshes
+                .iter()
+                .map(|(material_name, mesh)| (material_name.clone(), mesh.bvh()))
+                .collect(),
+            ..cache
+        };
+        if let Ok(bytes) = postcard::to_stdvec(&cache) {
+            let _ = fs::write(&cache_path, bytes);
+        }
+    }
+
+    let meshes: Vec<Box<dyn Hittable>> = built_meshes
+        .into_iter()
+        .map(|(_, mesh)| -> Box<dyn Hittable> { Box::new(mesh) })
+        .collect();
+
+    // TODO: Sort out this time thing
+    Ok(Box::new(BvhNode::new(meshes, 0.0, 1.0, rng)))
+}
+
+/// What [`load_wavefront_obj_cached`] stores next to an OBJ file.
+#[cfg(feature = "bvh_cache")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BvhCache {
+    version: u32,
+    /// The modification time of the OBJ file, in seconds and nanoseconds since the Unix epoch.
+    obj_modified: (u64, u32),
+    /// The BVH of every mesh, along with the name of its material, in the order of the file.
+    meshes: Vec<(Option<String>, MeshBvh)>,
+}
+
+#[cfg(feature = "bvh_cache")]
+impl BvhCache {
+    /// Changes whenever the file layout or the way meshes are built does.
+    const VERSION: u32 = 1;
+}
+
+/// One mesh of an OBJ file, not built yet.
+#[cfg(feature = "std")]
+struct ParsedMesh {
+    material_name: Option<String>,
+    mesh: MeshBuilder,
+    material: Arc<dyn Material>,
+}
+
+/// Reads the meshes of an OBJ file, one per material and in the order of the file. Meshes
+/// without triangles are left out.
+#[cfg(feature = "std")]
+fn parse_wavefront_obj(
+    path: &str,
+    check: AssetCheck,
+    normal_mode: NormalMode,
+) -> Result<(Vec<ParsedMesh>, ObjLoadReport), Box<dyn std::error::Error>> {
     let obj_file = fs::read_to_string(path)?;
     let object_set = obj::parse(obj_file)?;
+    let mut report = ObjLoadReport::default();
     let materials = object_set
         .material_library
         .as_ref()
         .map(|filename| path_to_file_in_same_folder(path, filename))
-        .map(load_wavefront_mtl)
+        .map(|mtl_path| load_wavefront_mtl(mtl_path, &mut report))
         .transpose()?;
-    let triangles: Vec<Box<dyn Hittable>> = object_set
-        .objects
-        .iter()
-        .flat_map(|obj| parse_individual_object(obj, &materials))
+
+    let smooth_normals =
+        (normal_mode == NormalMode::Auto).then(|| SmoothNormals::new(&object_set.objects));
+    let mut meshes = Vec::new();
+    for object in &object_set.objects {
+        parse_individual_object(
+            object,
+            check,
+            smooth_normals.as_ref(),
+            &mut meshes,
+            &mut report,
+        );
+    }
+
+    if check == AssetCheck::Strict && !report.is_clean() {
+        return Err(Box::new(InvalidAssetData {
+            path: path.to_string(),
+            what: "triangles",
+            count: report.dropped_triangles + report.repaired_attributes,
+        }));
+    }
+
+    // One mesh per material, as every mesh has a single one.
+    let meshes = meshes
+        .into_iter()
+        .filter(|(_, mesh)| !mesh.triangles.is_empty())
+        .map(|(material_name, mesh)| ParsedMesh {
+            material: geometry_material(material_name.as_ref(), &materials),
+            material_name,
+            mesh,
+        })
         .collect();
-    // TODO: Sort out this time thing
-    Ok(Box::new(BvhNode::new(triangles, 0.0, 1.0, rng)))
+
+    Ok((meshes, report))
 }
 
 #[cfg(feature = "std")]
@@ -277,6 +814,7 @@ fn path_to_file_in_same_folder(path: &str, filename: &str) -> String {
 #[cfg(feature = "std")]
 fn load_wavefront_mtl(
     path: String,
+    report: &mut ObjLoadReport,
 ) -> Result<HashMap<String, Arc<dyn Material>>, Box<dyn std::error::Error>> {
     let mtl_file = fs::read_to_string(path.clone())?;
     let material_set = mtl::parse(mtl_file)?;
@@ -286,7 +824,7 @@ fn load_wavefront_mtl(
         .iter()
         .map(|mtl| {
             let name = mtl.name.clone();
-            let parsed_mtl = parse_material(mtl, path.as_ref());
+            let parsed_mtl = parse_material(mtl, path.as_ref(), report);
 
             (name, parsed_mtl)
         })
@@ -295,20 +833,58 @@ fn load_wavefront_mtl(
     Ok(materials)
 }
 
+/// Specular exponents from which on materials with a specular highlight are loaded as metals
+/// rather than as diffuse surfaces.
+#[cfg(feature = "std")]
+const METAL_MIN_SPECULAR_EXPONENT: f64 = 100.0;
+
+/// Picks the material closest to what an MTL entry describes. Emission takes precedence over
+/// transparency, which takes precedence over a specular highlight; everything else is diffuse.
 #[cfg(feature = "std")]
-fn parse_material(obj_material: &mtl::Material, mtl_path: &str) -> Arc<dyn Material> {
-    if obj_material.illumination != Illumination::AmbientDiffuse {
-        panic!()
+fn parse_material(
+    obj_material: &mtl::Material,
+    mtl_path: &str,
+    report: &mut ObjLoadReport,
+) -> Arc<dyn Material> {
+    if let Some(emissive) = obj_material
+        .color_emissive
+        .map(Color::from)
+        .filter(|emissive| emissive.max_component() > 0.0)
+    {
+        return Arc::new(DiffuseLight::new(SolidColor::new(emissive)));
     }
 
-    let texture = obj_material
-        .diffuse_map
-        .as_ref()
-        .map(|filename| path_to_file_in_same_folder(mtl_path, filename))
-        .map(|path| ImageTexture::open(&path).unwrap())
-        .unwrap();
+    if obj_material.alpha < 1.0 {
+        let ir = obj_material.optical_density.unwrap_or(1.5);
+        return Arc::new(Dielectric::new(ir as f32));
+    }
+
+    if obj_material.illumination == Illumination::AmbientDiffuseSpecular
+        && obj_material.specular_coefficient >= METAL_MIN_SPECULAR_EXPONENT
+    {
+        // Walter et al.'s mapping from Phong exponents to microfacet roughness.
+        let fuzz = (2.0 / (obj_material.specular_coefficient + 2.0)).sqrt() as f32;
+        return Arc::new(Metal::new(
+            obj_material.color_specular.into(),
+            fuzz.min(1.0),
+        ));
+    }
 
-    Arc::new(Lambertian::new(texture))
+    let diffuse_map = match &obj_material.diffuse_map {
+        Some(filename) => filename,
+        None => {
+            return Arc::new(Lambertian::new(SolidColor::new(
+                obj_material.color_diffuse.into(),
+            )))
+        }
+    };
+    match ImageTexture::open(&path_to_file_in_same_folder(mtl_path, diffuse_map)) {
+        Ok(texture) => Arc::new(Lambertian::new(texture)),
+        Err(_) => {
+            report.missing_textures += 1;
+            Arc::new(Lambertian::new(SolidColor::new_rgb(1.0, 0.0, 1.0)))
+        }
+    }
 }
 
 impl Triangle {
@@ -322,3 +898,225 @@ impl Triangle {
             + v * interpolatee[2].clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+
+    /// Two triangles sharing the edge from `a` to `c`, folded so the edge isn't axis aligned.
+    fn folded_quad(a: Point3, b: Point3, c: Point3, d: Point3) -> [Box<dyn Hittable>; 2] {
+        let material: Arc<dyn Material> =
+            Arc::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5)));
+
+        [
+            Box::new(Triangle::new_flat_shaded([a, b, c], material.clone())),
+            Box::new(Triangle::new_flat_shaded([a, c, d], material)),
+        ]
+    }
+
+    #[test]
+    fn rays_through_a_shared_edge_hit() {
+        let (a, c) = (Point3::new(0.1, 0.3, -0.7), Point3::new(0.9, 1.4, 0.5));
+        let quad = folded_quad(a, Point3::new(1.3, 0.2, 0.1), c, Point3::new(-0.2, 1.1, -0.3));
+        let origins = [
+            Point3::new(0.4, 0.6, 3.0),
+            Point3::new(-2.5, 1.7, 0.2),
+            Point3::new(3.1, -0.4, -2.9),
+        ];
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        for origin in origins {
+            for step in 1..1000 {
+                let on_edge = a + (step as f32 / 1000.0) * (c - a);
+                let ray = Ray::new(origin, on_edge - origin, 0.0);
+
+                assert!(
+                    quad.hit(&ray, 0.001, f32::INFINITY, &mut rng).is_some(),
+                    "{:?} slipped through the edge at {}",
+                    ray,
+                    on_edge
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rays_exactly_along_a_shared_edge_hit() {
+        // The diagonal of the unit square, where the edge tests come out as exactly zero.
+        let quad = folded_quad(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        );
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        for step in 1..1000 {
+            let s = step as f32 / 1000.0;
+            for direction in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.3, 0.3, -1.0)] {
+                let ray = Ray::new(Point3::new(s, s, 0.0) - direction, direction, 0.0);
+                let hits = quad
+                    .iter()
+                    .filter(|triangle| triangle.hit(&ray, 0.001, f32::INFINITY, &mut rng).is_some())
+                    .count();
+
+                assert!(hits >= 1, "{:?} slipped through the shared edge", ray);
+            }
+        }
+    }
+
+    #[test]
+    fn culled_back_faces_are_missed() {
+        let material: Arc<dyn Material> =
+            Arc::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5)));
+        let vertices = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        let triangle = Triangle::new_flat_shaded(vertices, material).with_cull_backfaces(true);
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        let from_front = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let from_back = Ray::new(Point3::new(0.2, 0.2, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(triangle.hit(&from_front, 0.001, f32::INFINITY, &mut rng).is_some());
+        assert!(triangle.hit(&from_back, 0.001, f32::INFINITY, &mut rng).is_none());
+    }
+
+    /// The single material in `fixtures/mtl/<name>.mtl`, and how many textures were missing.
+    #[cfg(feature = "std")]
+    fn fixture_material(name: &str) -> (Arc<dyn Material>, usize) {
+        let path = format!("{}/fixtures/mtl/{}.mtl", env!("CARGO_MANIFEST_DIR"), name);
+        let mut report = ObjLoadReport::default();
+        let materials = load_wavefront_mtl(path, &mut report).unwrap();
+
+        (materials[name].clone(), report.missing_textures)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mtl_materials_map_onto_the_closest_material() {
+        let solid = |[r, g, b]: [f32; 3]| {
+            format!(
+                "SolidColor {{ color_value: GenericVec3 {{ e: [{:?}, {:?}, {:?}] }} }}",
+                r, g, b
+            )
+        };
+        let cases = [
+            (
+                "diffuse",
+                format!("Lambertian {{ albedo: {} }}", solid([0.2, 0.4, 0.6])),
+            ),
+            (
+                "missing_texture",
+                format!("Lambertian {{ albedo: {} }}", solid([1.0, 0.0, 1.0])),
+            ),
+            // The fuzz of Ns 198 is sqrt(2 / (198 + 2)).
+            (
+                "metal",
+                "Metal { albedo: GenericVec3 { e: [0.9, 0.8, 0.7] }, fuzz: 0.1 }".to_string(),
+            ),
+            ("glass", "Dielectric { ir: 1.33 }".to_string()),
+            (
+                "emissive",
+                format!(
+                    "DiffuseLight {{ emit: {}, intensity: 1.0, two_sided: true }}",
+                    solid([4.0, 3.0, 2.0])
+                ),
+            ),
+        ];
+
+        for (name, expected) in cases {
+            let (material, missing_textures) = fixture_material(name);
+            assert_eq!(format!("{:?}", material), expected);
+            assert_eq!(missing_textures, usize::from(name == "missing_texture"));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mtl_diffuse_maps_are_loaded_from_next_to_the_file() {
+        let (material, missing_textures) = fixture_material("textured");
+
+        assert_eq!(
+            material.type_name(),
+            core::any::type_name::<Lambertian<ImageTexture>>()
+        );
+        assert_eq!(missing_textures, 0);
+    }
+
+    /// The vertex positions and normals at every corner of the OBJ file at `path`, relative to
+    /// this crate.
+    #[cfg(feature = "std")]
+    fn obj_corners(path: &str, normal_mode: NormalMode) -> Vec<(Point3, Option<Vec3>)> {
+        let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), path);
+        let (meshes, _) = parse_wavefront_obj(&path, AssetCheck::Strict, normal_mode).unwrap();
+
+        meshes
+            .iter()
+            .flat_map(|parsed| {
+                let mesh = &parsed.mesh;
+                mesh.triangles.iter().flat_map(move |triangle| {
+                    (0..3).map(move |corner| {
+                        let vertex = mesh.vertices[triangle.vertices[corner] as usize];
+                        let normal = triangle.normals[corner].map(|i| mesh.normals[i as usize]);
+                        (vertex, normal)
+                    })
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn generated_icosphere_normals_point_straight_outward() {
+        let corners = obj_corners("fixtures/icosphere.obj", NormalMode::Auto);
+        assert_eq!(corners.len(), 20 * 3);
+
+        // By symmetry, the faces around each vertex of a regular icosahedron average out to the
+        // analytic normal of the sphere through its vertices.
+        for (vertex, normal) in corners {
+            let normal = normal.expect("a generated normal");
+            assert!((normal.length() - 1.0).abs() < 1e-5, "{}", normal);
+            assert!(
+                normal.dot(&vertex.unit_vector()) > 1.0 - 1e-5,
+                "{} at {}",
+                normal,
+                vertex
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn smoothed_cow_normals_are_continuous() {
+        let corners = obj_corners("../models/cow-nonormals.obj", NormalMode::Auto);
+
+        // The cow has no smoothing groups, so all corners at a vertex share one normal.
+        let mut normals: HashMap<[u32; 3], Vec3> = HashMap::new();
+        for (vertex, normal) in corners {
+            let normal = normal.expect("a generated normal");
+            assert!((normal.length() - 1.0).abs() < 1e-5, "{}", normal);
+
+            let key = [vertex.x(), vertex.y(), vertex.z()].map(f32::to_bits);
+            let first = *normals.entry(key).or_insert(normal);
+            assert!(
+                normal.dot(&first) > 1.0 - 1e-6,
+                "{} and {} at {}",
+                first,
+                normal,
+                vertex
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn cow_without_normals_is_flat_unless_smoothed() {
+        let corners = obj_corners("../models/cow-nonormals.obj", NormalMode::FromFile);
+
+        assert!(corners.iter().all(|(_, normal)| normal.is_none()));
+    }
+}
@@ -0,0 +1,76 @@
+use derive_more::Constructor;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    ray::{Ray, RayKind},
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// Which kinds of rays can hit an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    pub camera: bool,
+    /// Objects hidden from shadow rays don't cast shadows from sampled lights.
+    pub shadow: bool,
+    pub bounce: bool,
+}
+
+impl Visibility {
+    pub const ALL: Self = Self {
+        camera: true,
+        shadow: true,
+        bounce: true,
+    };
+
+    /// Lights the camera shouldn't see, but that still light up the scene.
+    pub const HIDDEN_FROM_CAMERA: Self = Self {
+        camera: false,
+        ..Self::ALL
+    };
+
+    pub fn includes(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Bounce => self.bounce,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Lets rays of the kinds `visibility` excludes pass straight through `inner`. Sampling `inner` as
+/// a light is unaffected.
+#[derive(Debug, Constructor)]
+pub struct VisibilityWrapper<T: Hittable> {
+    inner: T,
+    visibility: Visibility,
+}
+
+impl<T: Hittable> Hittable for VisibilityWrapper<T> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        if !self.visibility.includes(r.kind()) {
+            return None;
+        }
+
+        self.inner.hit(r, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.inner.pdf_value(origin, direction, rng)
+    }
+
+    fn random_point_towards(&self, origin: &Point3, rng: &mut ActiveRng) -> Point3 {
+        self.inner.random_point_towards(origin, rng)
+    }
+}
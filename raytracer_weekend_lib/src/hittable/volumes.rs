@@ -1,4 +1,4 @@
-use core::fmt::Debug;
+use core::{f32::consts::PI, fmt::Debug};
 
 #[cfg(feature = "no_std")]
 use micromath::F32Ext;
@@ -7,77 +7,373 @@ use rand::Rng;
 use crate::{
     aabb::Aabb,
     hittable::{HitRecord, Hittable},
-    material::Isotropic,
+    material::{Dielectric, Isotropic, Material, ScatterRecord, Scattered},
+    pdf::{MaterialPdf, SpherePdf},
     ray::Ray,
-    texture::{Point2d, Texture},
-    vec3::Vec3,
+    texture::{Point2d, SolidColor, Texture},
+    vec3::{Color, Point3, Vec3},
     ActiveRng,
 };
 
+/// A medium that scatters light somewhere along the way through it, like fog, smoke or clouds.
+///
+/// The density at a point is `max_density` times the brightness of the `density` texture there,
+/// so a noise texture between black and white yields anything between empty space and
+/// `max_density`. Scattering distances are found with delta tracking: steps are sampled as if the
+/// whole medium had `max_density`, and each tentative collision is kept with the ratio of the
+/// actual density to that maximum. Media made with [`ConstantMedium::new`] have the same density
+/// everywhere, which makes the first collision the one.
 #[derive(Debug)]
-pub struct ConstantMedium<H: Hittable, T: Texture> {
+pub struct ConstantMedium<H: Hittable, T: Texture, D: Texture = SolidColor> {
     boundary: H,
     phase_function: Isotropic<T>,
-    neg_inv_density: f32,
+    density: D,
+    max_density: f32,
+    is_uniform: bool,
 }
 
 impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
     pub fn new(boundary: H, density: f32, texture: T) -> Self {
-        let neg_inv_density = -1.0 / density;
-        let phase_function = Isotropic::new(texture);
+        Self {
+            is_uniform: true,
+            ..ConstantMedium::with_density_texture(
+                boundary,
+                SolidColor::new_rgb(1.0, 1.0, 1.0),
+                density,
+                texture,
+            )
+        }
+    }
+}
+
+impl<H: Hittable, T: Texture, D: Texture> ConstantMedium<H, T, D> {
+    /// A medium whose density varies through space with the brightness of `density`, up to
+    /// `max_density` where it is white.
+    pub fn with_density_texture(boundary: H, density: D, max_density: f32, texture: T) -> Self {
+        assert!(max_density > 0.0);
 
         Self {
             boundary,
-            phase_function,
-            neg_inv_density,
+            phase_function: Isotropic::new(texture),
+            density,
+            max_density,
+            is_uniform: false,
         }
     }
+
+    fn density_at(&self, p: &Vec3) -> f32 {
+        let color = self.density.value(Point2d::new(0.0, 0.0), p);
+        let brightness = (color.x() + color.y() + color.z()) / 3.0;
+
+        self.max_density * brightness.clamp(0.0, 1.0)
+    }
 }
 
-impl<H: Hittable, T: Texture> Hittable for ConstantMedium<H, T> {
+impl<H: Hittable, T: Texture, D: Texture> Hittable for ConstantMedium<H, T, D> {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
-        let rec1 = self
-            .boundary
-            .hit(r, f32::NEG_INFINITY, f32::INFINITY, rng)?;
-        let rec2 = self.boundary.hit(r, rec1.t + 0.0001, f32::INFINITY, rng)?;
+        let (rec1_t, rec2_t) = inside_boundary(&self.boundary, r, t_min, t_max, rng)?;
 
-        let mut rec1_t = rec1.t;
-        let mut rec2_t = rec2.t;
+        let ray_length = r.direction().length();
+        let neg_inv_max_density = -1.0 / self.max_density;
+        let mut t = rec1_t;
 
-        rec1_t = rec1_t.max(t_min);
-        rec2_t = rec2_t.min(t_max);
+        loop {
+            t += neg_inv_max_density * rng.gen::<f32>().ln() / ray_length;
+            if t >= rec2_t {
+                return None;
+            }
 
-        if rec1_t >= rec2_t {
-            return None;
+            if self.is_uniform || rng.gen::<f32>() * self.max_density < self.density_at(&r.at(t)) {
+                return Some(volume_hit_record(r, t, &self.phase_function));
+            }
         }
+    }
+
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+}
 
-        rec1_t = rec1_t.max(0.0);
+/// Translucent stuff like wax, marble or skin: a glass surface around a medium that light enters,
+/// scatters around in, and eventually leaves again, somewhere other than where it came in.
+///
+/// Light is followed through the medium one scattering event at a time, like in a
+/// [`ConstantMedium`]. The coefficients are per color channel, so e.g. red light can travel
+/// further than blue. Distances are sampled with the average extinction of the channels, and the
+/// difference to the actual ones is made up for in the attenuation. The boundary has to be closed,
+/// and should not contain other objects.
+#[derive(Debug)]
+pub struct Subsurface<H: Hittable> {
+    boundary: H,
+    surface: SubsurfaceSurface,
+    phase_function: SubsurfacePhase,
+}
 
-        let ray_length = r.direction().length();
-        let distance_inside_boundary = (rec2_t - rec1_t) * ray_length;
-        let hit_distance = self.neg_inv_density * rng.gen::<f32>().log10();
+impl<H: Hittable> Subsurface<H> {
+    /// `sigma_s` and `sigma_a` are the scattering and absorption coefficients, per unit of length.
+    pub fn new(boundary: H, sigma_s: Color, sigma_a: Color, ior: f32) -> Self {
+        let sigma_t = sigma_s + sigma_a;
+        assert!(sigma_s.x() >= 0.0 && sigma_s.y() >= 0.0 && sigma_s.z() >= 0.0);
+        assert!(sigma_a.x() >= 0.0 && sigma_a.y() >= 0.0 && sigma_a.z() >= 0.0);
+        assert!(sigma_t.x() > 0.0 && sigma_t.y() > 0.0 && sigma_t.z() > 0.0);
+
+        let medium = SubsurfaceMedium {
+            sigma_s,
+            sigma_t,
+            sampled_sigma_t: (sigma_t.x() + sigma_t.y() + sigma_t.z()) / 3.0,
+        };
 
-        if hit_distance > distance_inside_boundary {
-            return None;
+        Self {
+            boundary,
+            surface: SubsurfaceSurface {
+                dielectric: Dielectric::new(ior),
+                medium: medium.clone(),
+            },
+            phase_function: SubsurfacePhase { medium },
         }
+    }
+}
+
+impl<H: Hittable> Hittable for Subsurface<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut ActiveRng) -> Option<HitRecord> {
+        let mut surface = self.boundary.hit(r, t_min, f32::INFINITY, rng)?;
+        surface.material = &self.surface;
 
-        let t = rec1_t + hit_distance / ray_length;
-        let p = r.at(t);
-        let normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
-        let front_face = true;
-        let dummy_texture_uv = Point2d { u: 0.0, v: 0.0 };
+        // Seen from inside, the ray might scatter before it gets back out.
+        if !surface.is_front_face {
+            let sampled_distance =
+                -rng.gen::<f32>().ln() / self.phase_function.medium.sampled_sigma_t;
+            let t = (sampled_distance / r.direction().length()).max(t_min);
+            if t < surface.t {
+                return (t <= t_max).then(|| volume_hit_record(r, t, &self.phase_function));
+            }
+        }
 
-        Some(HitRecord::new(
-            p,
-            normal,
-            &self.phase_function,
-            t,
-            dummy_texture_uv,
-            front_face,
-        ))
+        (surface.t <= t_max).then_some(surface)
     }
 
     fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
         self.boundary.bounding_box(time0, time1)
     }
 }
+
+#[derive(Debug, Clone)]
+struct SubsurfaceMedium {
+    sigma_s: Color,
+    sigma_t: Color,
+    /// The extinction distances are sampled with.
+    sampled_sigma_t: f32,
+}
+
+impl SubsurfaceMedium {
+    /// How much more or less light of each channel makes it `distance` through the medium than
+    /// the sampling accounted for.
+    fn transmittance_weight(&self, distance: f32) -> Color {
+        let weight = |sigma_t: f32| (-(sigma_t - self.sampled_sigma_t) * distance).exp();
+
+        Color::new(
+            weight(self.sigma_t.x()),
+            weight(self.sigma_t.y()),
+            weight(self.sigma_t.z()),
+        )
+    }
+}
+
+/// The glass boundary of a [`Subsurface`]. Light leaving the medium through it is weighted for the
+/// way there.
+#[derive(Debug, Clone)]
+struct SubsurfaceSurface {
+    dielectric: Dielectric,
+    medium: SubsurfaceMedium,
+}
+
+impl Material for SubsurfaceSurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        let mut scatter = self.dielectric.scatter(r_in, rec, rng)?;
+        if !rec.is_front_face {
+            let distance = rec.t * r_in.direction().length();
+            scatter.attenuation = scatter.attenuation * self.medium.transmittance_weight(distance);
+        }
+
+        Some(scatter)
+    }
+
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
+/// Scattering events inside a [`Subsurface`], weighted for the way there like the exits are.
+#[derive(Debug, Clone)]
+struct SubsurfacePhase {
+    medium: SubsurfaceMedium,
+}
+
+impl Material for SubsurfacePhase {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, _rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        // The sampled distance had a density of sampled_sigma_t times the sampled transmittance.
+        let distance = rec.t * r_in.direction().length();
+        let attenuation = self.medium.sigma_s / self.medium.sampled_sigma_t
+            * self.medium.transmittance_weight(distance);
+
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Scattered::Pdf(MaterialPdf::Sphere(SpherePdf)),
+        })
+    }
+
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn albedo(&self, _uv: Point2d, _p: &Point3) -> Color {
+        self.medium.sigma_s / self.medium.sigma_t
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// The part of `r` within `t_min..t_max` that runs inside `boundary`, which has to be convex.
+fn inside_boundary(
+    boundary: &impl Hittable,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+    rng: &mut ActiveRng,
+) -> Option<(f32, f32)> {
+    let rec1 = boundary.hit(r, f32::NEG_INFINITY, f32::INFINITY, rng)?;
+    let rec2 = boundary.hit(r, rec1.t + 0.0001, f32::INFINITY, rng)?;
+
+    let rec1_t = rec1.t.max(t_min);
+    let rec2_t = rec2.t.min(t_max);
+
+    if rec1_t >= rec2_t {
+        return None;
+    }
+
+    Some((rec1_t.max(0.0), rec2_t))
+}
+
+fn volume_hit_record<'a>(r: &Ray, t: f32, phase_function: &'a dyn Material) -> HitRecord<'a> {
+    let p = r.at(t);
+    let normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
+    let front_face = true;
+    let dummy_texture_uv = Point2d::new(0.0, 0.0);
+
+    HitRecord::new(p, normal, phase_function, t, dummy_texture_uv, front_face)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use super::*;
+    use crate::{
+        hittable::rectangular::Cuboid,
+        material::Lambertian,
+        texture::{Gradient, SolidColor},
+    };
+
+    const RAYS: u32 = 100_000;
+
+    /// A slab of unit thickness between `z = 0` and `z = 1`, wide enough to look infinite.
+    fn slab() -> Cuboid {
+        Cuboid::new(
+            Point3::new(-100.0, -100.0, 0.0),
+            Point3::new(100.0, 100.0, 1.0),
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    /// The fraction of rays that cross the slab along its normal without scattering.
+    fn transmission(medium: &impl Hittable) -> f32 {
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let r = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+
+        let passed = (0..RAYS)
+            .filter(|_| medium.hit(&r, 0.001, f32::INFINITY, &mut rng).is_none())
+            .count();
+
+        passed as f32 / RAYS as f32
+    }
+
+    #[track_caller]
+    fn assert_beer_lambert(transmission: f32, density: f32) {
+        let expected = (-density).exp();
+        assert!(
+            (transmission - expected).abs() < 0.01,
+            "{} of the light crosses a unit slab of density {}, expected {}",
+            transmission,
+            density,
+            expected
+        );
+    }
+
+    #[test]
+    fn constant_medium_transmits_like_beer_lambert() {
+        for density in [0.5, 1.0, 2.0] {
+            let medium = ConstantMedium::new(slab(), density, SolidColor::new_rgb(1.0, 1.0, 1.0));
+            assert_beer_lambert(transmission(&medium), density);
+        }
+    }
+
+    #[test]
+    fn textured_medium_transmits_like_beer_lambert() {
+        // A uniform half grey density texture makes it a constant medium of half max_density.
+        for max_density in [1.0, 2.0, 4.0] {
+            let medium = ConstantMedium::with_density_texture(
+                slab(),
+                SolidColor::new_rgb(0.5, 0.5, 0.5),
+                max_density,
+                SolidColor::new_rgb(1.0, 1.0, 1.0),
+            );
+            assert_beer_lambert(transmission(&medium), 0.5 * max_density);
+        }
+    }
+
+    #[test]
+    fn textured_medium_collides_less_where_it_is_sparse() {
+        // Density runs from 0 at the entry face of the slab to max_density at the exit face.
+        let density = Gradient::new(
+            SolidColor::new_rgb(0.0, 0.0, 0.0),
+            SolidColor::new_rgb(1.0, 1.0, 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            0.0,
+            1.0,
+        );
+        let medium = ConstantMedium::with_density_texture(
+            slab(),
+            density,
+            2.0,
+            SolidColor::new_rgb(1.0, 1.0, 1.0),
+        );
+
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let r = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let (mut sparse, mut dense) = (0, 0);
+        for _ in 0..RAYS {
+            if let Some(rec) = medium.hit(&r, 0.001, f32::INFINITY, &mut rng) {
+                if rec.p.z() < 0.5 {
+                    sparse += 1;
+                } else {
+                    dense += 1;
+                }
+            }
+        }
+
+        // With a density of 2z, 1 - exp(-1/4) of the rays collide in the sparse half and
+        // exp(-1/4) - exp(-1) in the dense half.
+        let expected_sparse = 1.0 - (-0.25f32).exp();
+        let expected_dense = (-0.25f32).exp() - (-1.0f32).exp();
+        assert!(
+            sparse < dense,
+            "{} collisions in the sparse half, {} in the dense half",
+            sparse,
+            dense
+        );
+        assert!((sparse as f32 / RAYS as f32 - expected_sparse).abs() < 0.01);
+        assert!((dense as f32 / RAYS as f32 - expected_dense).abs() < 0.01);
+    }
+}
@@ -1,54 +1,352 @@
 #![cfg(feature = "image")]
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::{
     any::type_name,
     fmt::{Debug, Formatter},
 };
 
-use image::{io::Reader as ImageReader, DynamicImage, GenericImageView};
+use image::{
+    imageops::FilterType, io::Reader as ImageReader, DynamicImage, GenericImageView, Rgb32FImage,
+};
 
 use crate::{
-    texture::{Point2d, Texture},
+    asset_check::{AssetCheck, InvalidAssetData},
+    texture::{Point2d, Texture, UvDerivatives},
     vec3::{Color, Vec3},
 };
 
+/// How texels are looked up for a texture coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterMode {
+    /// The single texel the coordinate falls into.
+    #[default]
+    Nearest,
+    /// A blend of the four texels around the coordinate.
+    Bilinear,
+    /// Bilinear filtering on a downsampled copy of the image, picked from the
+    /// [`UvDerivatives`] of the coordinate. Without derivatives the full resolution is used.
+    Mipmapped,
+}
+
+/// What texture coordinates outside of `0.0..=1.0` map to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// The texel at the nearest edge of the image.
+    #[default]
+    Clamp,
+    /// The image tiles the plane.
+    Repeat,
+    /// The image tiles the plane, mirrored in every other tile so neighbouring tiles meet without
+    /// a seam.
+    MirrorRepeat,
+}
+
+impl WrapMode {
+    /// The texel a column or row index lands on in an image `size` texels across.
+    fn texel_index(self, index: i64, size: u32) -> u32 {
+        let size = size as i64;
+
+        let index = match self {
+            WrapMode::Clamp => index.clamp(0, size - 1),
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::MirrorRepeat => {
+                let index = index.rem_euclid(2 * size);
+                if index < size {
+                    index
+                } else {
+                    2 * size - 1 - index
+                }
+            }
+        };
+
+        index as u32
+    }
+}
+
 #[derive(Clone)]
 pub struct ImageTexture {
-    image: DynamicImage,
+    /// The image at full resolution, followed by the mip levels when they are in use.
+    levels: Vec<DynamicImage>,
+    filter: FilterMode,
+    wrap: WrapMode,
     path: String,
+    repaired_texels: usize,
 }
 
 impl ImageTexture {
     pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let image = ImageReader::open(path)?.decode()?;
+        Self::open_with_check(path, AssetCheck::Repair)
+    }
+
+    pub fn open_with_check(
+        path: &str,
+        check: AssetCheck,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut image = ImageReader::open(path)?.decode()?;
+
+        let repaired_texels = match (check, float_texels(&mut image)) {
+            (AssetCheck::Trusted, _) | (_, None) => 0,
+            (AssetCheck::Repair, Some((texels, width, channels))) => {
+                repair_non_finite_texels(texels, width, channels)
+            }
+            (AssetCheck::Strict, Some((texels, _, channels))) => {
+                let count = count_non_finite_texels(texels, channels);
+                if count > 0 {
+                    return Err(Box::new(InvalidAssetData {
+                        path: path.to_string(),
+                        what: "texels",
+                        count,
+                    }));
+                }
+                0
+            }
+        };
 
         Ok(Self {
-            image,
+            levels: vec![image],
+            filter: FilterMode::Nearest,
+            wrap: WrapMode::Clamp,
             path: path.to_string(),
+            repaired_texels,
         })
     }
+
+    /// Wraps an already decoded image, e.g. one embedded in a model file. `name` stands in for
+    /// the path in debug output.
+    pub fn from_image(image: DynamicImage, name: &str) -> Self {
+        Self {
+            levels: vec![image],
+            filter: FilterMode::Nearest,
+            wrap: WrapMode::Clamp,
+            path: name.to_string(),
+            repaired_texels: 0,
+        }
+    }
+
+    pub fn open_with_filter(
+        path: &str,
+        filter: FilterMode,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::open(path)?.with_filter(filter))
+    }
+
+    /// Switches to another filter mode, generating or dropping the mip levels as needed.
+    pub fn with_filter(mut self, filter: FilterMode) -> Self {
+        self.levels.truncate(1);
+        if filter == FilterMode::Mipmapped {
+            let (mut width, mut height) = self.levels[0].dimensions();
+
+            while width > 1 || height > 1 {
+                width = (width / 2).max(1);
+                height = (height / 2).max(1);
+
+                let previous = self.levels.last().unwrap();
+                let level = match previous.as_rgb32f() {
+                    // The image crate clamps float images to 0..=1 while resizing them.
+                    Some(previous) => {
+                        DynamicImage::ImageRgb32F(downsample_float(previous, width, height))
+                    }
+                    None => previous.resize_exact(width, height, FilterType::Triangle),
+                };
+                self.levels.push(level);
+            }
+        }
+
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// The number of texels that contained non-finite values and were patched up while loading.
+    pub fn repaired_texels(&self) -> usize {
+        self.repaired_texels
+    }
+}
+
+/// Shrinks `image` to `width` by `height` texels, each the average of the texels it covers. Unlike
+/// the image crate's filters, this keeps values above one.
+fn downsample_float(image: &Rgb32FImage, width: u32, height: u32) -> Rgb32FImage {
+    let span = |index: u32, size: u32, source_size: u32| {
+        let start = index * source_size / size;
+        let end = ((index + 1) * source_size / size).max(start + 1);
+        start..end
+    };
+
+    Rgb32FImage::from_fn(width, height, |x, y| {
+        let mut sum = [0.0; 3];
+        let mut count = 0.0;
+        for source_y in span(y, height, image.height()) {
+            for source_x in span(x, width, image.width()) {
+                let texel = image.get_pixel(source_x, source_y);
+                for (sum, value) in sum.iter_mut().zip(texel.0) {
+                    *sum += value;
+                }
+                count += 1.0;
+            }
+        }
+
+        image::Rgb(sum.map(|value| value / count))
+    })
+}
+
+/// Only floating point images (HDR, EXR) can hold non-finite values at all.
+fn float_texels(image: &mut DynamicImage) -> Option<(&mut [f32], usize, usize)> {
+    let width = image.width() as usize;
+
+    match image {
+        DynamicImage::ImageRgb32F(buffer) => Some((&mut **buffer, width, 3)),
+        DynamicImage::ImageRgba32F(buffer) => Some((&mut **buffer, width, 4)),
+        _ => None,
+    }
+}
+
+fn count_non_finite_texels(texels: &[f32], channels: usize) -> usize {
+    texels
+        .chunks_exact(channels)
+        .filter(|texel| texel.iter().any(|value| !value.is_finite()))
+        .count()
+}
+
+/// Replaces every texel with a non-finite channel by the average of its finite neighbours and
+/// returns the number of texels touched.
+fn repair_non_finite_texels(texels: &mut [f32], width: usize, channels: usize) -> usize {
+    // Almost all textures are clean, so do a cheap vectorisable pass before anything else.
+    if texels.iter().all(|value| value.is_finite()) {
+        return 0;
+    }
+
+    let height = texels.len() / (width * channels);
+    let broken_texels: Vec<usize> = texels
+        .chunks_exact(channels)
+        .enumerate()
+        .filter(|(_, texel)| texel.iter().any(|value| !value.is_finite()))
+        .map(|(index, _)| index)
+        .collect();
+
+    // Compute all replacements before writing any, so repaired texels don't feed into each other.
+    let mut replacements = Vec::with_capacity(broken_texels.len() * channels);
+    for &index in &broken_texels {
+        let x = (index % width) as isize;
+        let y = (index / width) as isize;
+
+        for channel in 0..channels {
+            let mut sum = 0.0;
+            let mut count = 0;
+
+            for neighbour_y in (y - 1)..=(y + 1) {
+                for neighbour_x in (x - 1)..=(x + 1) {
+                    let is_inside = (0..width as isize).contains(&neighbour_x)
+                        && (0..height as isize).contains(&neighbour_y);
+                    if !is_inside || (neighbour_x, neighbour_y) == (x, y) {
+                        continue;
+                    }
+
+                    let neighbour = neighbour_y as usize * width + neighbour_x as usize;
+                    let value = texels[neighbour * channels + channel];
+                    if value.is_finite() {
+                        sum += value;
+                        count += 1;
+                    }
+                }
+            }
+
+            replacements.push(if count > 0 { sum / count as f32 } else { 0.0 });
+        }
+    }
+
+    for (&index, replacement) in broken_texels
+        .iter()
+        .zip(replacements.chunks_exact(channels))
+    {
+        texels[index * channels..(index + 1) * channels].copy_from_slice(replacement);
+    }
+
+    broken_texels.len()
 }
 
 impl Texture for ImageTexture {
     fn value(&self, uv: Point2d, _p: &Vec3) -> Color {
-        let image = &self.image;
+        let u = uv.u;
+        let v = 1.0 - uv.v;
 
-        let u = uv.u.clamp(0.0, 1.0);
-        let v = 1.0 - uv.v.clamp(0.0, 1.0);
+        match self.filter {
+            FilterMode::Nearest => sample_nearest(&self.levels[0], self.wrap, u, v),
+            FilterMode::Bilinear => sample_bilinear(&self.levels[0], self.wrap, u, v),
+            FilterMode::Mipmapped => {
+                sample_bilinear(self.mip_level(uv.derivatives), self.wrap, u, v)
+            }
+        }
+    }
+}
 
-        let i = ((u * image.width() as f32) as u32).clamp(0, image.width() - 1);
-        let j = ((v * image.height() as f32) as u32).clamp(0, image.height() - 1);
+impl ImageTexture {
+    fn mip_level(&self, derivatives: Option<UvDerivatives>) -> &DynamicImage {
+        let base = &self.levels[0];
 
-        let color_scale = 1.0 / 255.0;
-        let pixel = image.get_pixel(i, j);
+        // The number of full resolution texels covered by one pixel. Every level halves that.
+        let footprint = derivatives.map_or(0.0, |derivatives| {
+            let resolution = base.width().max(base.height()) as f32;
+            derivatives.ddx.abs().max(derivatives.ddy.abs()) * resolution
+        });
+        let level = if footprint > 1.0 {
+            footprint.log2() as usize
+        } else {
+            0
+        };
+
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+}
+
+fn sample_nearest(image: &DynamicImage, wrap: WrapMode, u: f32, v: f32) -> Color {
+    let i = wrap.texel_index((u * image.width() as f32).floor() as i64, image.width());
+    let j = wrap.texel_index((v * image.height() as f32).floor() as i64, image.height());
+
+    texel(image, i, j)
+}
+
+/// Blends the four texels whose centers surround `(u, v)`. At the edges of the image, the
+/// neighbours are picked by `wrap` like any other texel outside of it.
+fn sample_bilinear(image: &DynamicImage, wrap: WrapMode, u: f32, v: f32) -> Color {
+    let x = u * image.width() as f32 - 0.5;
+    let y = v * image.height() as f32 - 0.5;
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
 
-        Color::new(
-            pixel[0] as f32 * color_scale,
-            pixel[1] as f32 * color_scale,
-            pixel[2] as f32 * color_scale,
-        )
+    let column = |x: f32| wrap.texel_index(x as i64, image.width());
+    let row = |y: f32| wrap.texel_index(y as i64, image.height());
+    let (i0, i1) = (column(x0), column(x0 + 1.0));
+    let (j0, j1) = (row(y0), row(y0 + 1.0));
+
+    let top = (1.0 - tx) * texel(image, i0, j0) + tx * texel(image, i1, j0);
+    let bottom = (1.0 - tx) * texel(image, i0, j1) + tx * texel(image, i1, j1);
+
+    (1.0 - ty) * top + ty * bottom
+}
+
+fn texel(image: &DynamicImage, i: u32, j: u32) -> Color {
+    // Float images (e.g. HDR environment maps) keep their full range.
+    if let Some(image) = image.as_rgb32f() {
+        let pixel = image.get_pixel(i, j);
+        return Color::new(pixel[0], pixel[1], pixel[2]);
     }
+
+    let color_scale = 1.0 / 255.0;
+    let pixel = image.get_pixel(i, j);
+
+    Color::new(
+        pixel[0] as f32 * color_scale,
+        pixel[1] as f32 * color_scale,
+        pixel[2] as f32 * color_scale,
+    )
 }
 
 impl Debug for ImageTexture {
@@ -58,3 +356,113 @@ impl Debug for ImageTexture {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2×2 float image with texels 0 and 1 in the top row and 2 and 4 in the bottom one, the
+    /// same in every channel.
+    fn checker(filter: FilterMode, wrap: WrapMode) -> ImageTexture {
+        let texels = [0.0, 1.0, 2.0, 4.0]
+            .iter()
+            .flat_map(|&t| [t, t, t])
+            .collect();
+        let image = Rgb32FImage::from_vec(2, 2, texels).unwrap();
+
+        ImageTexture::from_image(DynamicImage::ImageRgb32F(image), "checker")
+            .with_filter(filter)
+            .with_wrap(wrap)
+    }
+
+    fn brightness(texture: &ImageTexture, uv: Point2d) -> f32 {
+        let color = texture.value(uv, &Vec3::new(0.0, 0.0, 0.0));
+        assert!(color.x() == color.y() && color.y() == color.z());
+        color.x()
+    }
+
+    #[test]
+    fn nearest_filter_picks_the_texel_under_the_coordinate() {
+        let texture = checker(FilterMode::Nearest, WrapMode::Clamp);
+
+        // V runs up, so the top row of the image is at v = 1.
+        for (u, v, expected) in [
+            (0.25, 0.75, 0.0),
+            (0.75, 0.75, 1.0),
+            (0.25, 0.25, 2.0),
+            (0.75, 0.25, 4.0),
+            (0.49, 0.51, 0.0),
+            (0.51, 0.49, 4.0),
+        ] {
+            let value = brightness(&texture, Point2d::new(u, v));
+            assert_eq!(value, expected, "at ({}, {})", u, v);
+        }
+    }
+
+    #[test]
+    fn bilinear_filter_blends_the_texels_around_the_coordinate() {
+        let texture = checker(FilterMode::Bilinear, WrapMode::Clamp);
+
+        for (u, v, expected) in [
+            // Texel centers.
+            (0.25, 0.75, 0.0),
+            (0.75, 0.25, 4.0),
+            // Halfway between two texel centers.
+            (0.5, 0.75, 0.5),
+            (0.25, 0.5, 1.0),
+            // The middle of the image, where all four meet.
+            (0.5, 0.5, 1.75),
+            (0.375, 0.625, 0.8125),
+        ] {
+            let value = brightness(&texture, Point2d::new(u, v));
+            assert_eq!(value, expected, "at ({}, {})", u, v);
+        }
+    }
+
+    #[test]
+    fn mipmapped_filter_picks_the_level_by_footprint() {
+        let texture = checker(FilterMode::Mipmapped, WrapMode::Clamp);
+        let at = |derivative: Option<f32>| Point2d {
+            derivatives: derivative.map(|d| UvDerivatives::new(d, d)),
+            ..Point2d::new(0.375, 0.625)
+        };
+
+        // Without derivatives or within a texel per pixel, it's bilinear at full resolution.
+        assert_eq!(brightness(&texture, at(None)), 0.8125);
+        assert_eq!(brightness(&texture, at(Some(0.25))), 0.8125);
+        // A pixel covering the whole image sees the single texel of the last level, the average.
+        let average = brightness(&texture, at(Some(1.0)));
+        assert!((average - 1.75).abs() < 1e-5, "{}", average);
+    }
+
+    #[test]
+    fn wrap_modes_pick_the_texels_outside_of_the_image() {
+        // Along the top row, whose texels are 0 and 1, past the left and right edges.
+        let us = [-0.25, 1.25, 1.75];
+        for (wrap, expected) in [
+            (WrapMode::Clamp, [0.0, 1.0, 1.0]),
+            (WrapMode::Repeat, [1.0, 0.0, 1.0]),
+            (WrapMode::MirrorRepeat, [0.0, 1.0, 0.0]),
+        ] {
+            let texture = checker(FilterMode::Nearest, wrap);
+            let values = us.map(|u| brightness(&texture, Point2d::new(u, 0.75)));
+            assert_eq!(values, expected, "{:?}", wrap);
+        }
+    }
+
+    #[test]
+    fn wrap_modes_pick_the_neighbours_of_bilinear_filtering() {
+        // On the left edge, on the center of the first texel past the right edge, and halfway
+        // between the first two texels past it, all through the centers of the top row.
+        let us = [0.0, 1.25, 1.5];
+        for (wrap, expected) in [
+            (WrapMode::Clamp, [0.0, 1.0, 1.0]),
+            (WrapMode::Repeat, [0.5, 0.0, 0.5]),
+            (WrapMode::MirrorRepeat, [0.0, 1.0, 0.5]),
+        ] {
+            let texture = checker(FilterMode::Bilinear, wrap);
+            let values = us.map(|u| brightness(&texture, Point2d::new(u, 0.75)));
+            assert_eq!(values, expected, "{:?}", wrap);
+        }
+    }
+}
@@ -4,47 +4,105 @@
 extern crate alloc;
 
 mod aabb;
+pub mod adaptive;
+pub mod asset_check;
+pub mod background;
 pub mod bvh;
 pub mod camera;
 pub mod hittable;
 pub mod image_texture;
 pub mod light_source;
 pub mod material;
+mod microfacet;
+mod onb;
+pub mod path_trace;
+pub mod pdf;
 pub mod perlin;
+mod polynomial;
 mod ray;
+pub mod ray_batch;
+pub mod sampler;
+pub mod scene_generator;
+pub mod stats;
 pub mod texture;
+pub mod tonemap;
 pub mod vec3;
 
 use alloc::{boxed::Box, vec::Vec};
 
-use camera::Camera;
-use derive_more::Constructor;
+use background::Background;
+use camera::Projection;
+#[cfg(feature = "nee")]
+use hittable::HitRecord;
 use hittable::Hittable;
+#[cfg(not(feature = "rayon"))]
 use itertools::iproduct;
+#[cfg(feature = "nee")]
+use light_source::{LightSampler, UniformLightSampler};
+use material::{luminance, Scattered};
+use path_trace::{PathEvent, Recorder};
+#[cfg(feature = "nee")]
+use pdf::{mis_weight, MisWeight};
+use pdf::{HemispherePdf, HittablePdf, MaterialPdf, MixturePdf, Pdf};
 use rand::prelude::*;
-use ray::Ray;
+use ray::{Ray, RayKind};
+use ray_batch::PrimaryRay;
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
+use sampler::{HaltonSampler, Sampler, SamplerKind, StratifiedSampler, UniformSampler};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use vec3::Color;
+#[cfg(feature = "stats")]
+use stats::{Counter, RenderStats};
+use vec3::{Color, Vec3};
+
+/// How often rays bounce, unless [`Raytracer::with_max_depth`] says otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 50;
 
-const MAX_DEPTH: usize = 50;
+/// How many times paths bounce before Russian roulette may end them.
+const RUSSIAN_ROULETTE_MIN_BOUNCES: usize = 3;
+/// Even paths through white surfaces end with at least this probability per bounce once Russian
+/// roulette kicks in.
+const RUSSIAN_ROULETTE_MAX_SURVIVAL: f32 = 0.95;
 
-#[cfg(feature = "std")]
-type ActiveRng = ThreadRng;
+/// Seeds the RNGs of tiled renders, unless [`Raytracer::with_seed`] says otherwise.
+const DEFAULT_SEED: u64 = 0x5eed_7153_a3c1_09b7;
 
-#[cfg(not(feature = "std"))]
 type ActiveRng = SmallRng;
 
-#[derive(Constructor)]
 pub struct Raytracer<'a> {
     world: &'a [Box<dyn Hittable>],
-    cam: &'a Camera,
-    background: Color,
+    lights: &'a [Box<dyn Hittable>],
+    cam: &'a dyn Projection,
+    background: &'a Background,
     image_width: u32,
     image_height: u32,
     samples_per_pixel: u32,
+    estimator: Estimator,
+    max_depth: usize,
+    seed: u64,
+    russian_roulette: bool,
+    sampler: SamplerKind,
+    max_luminance: f32,
+    record_aux: bool,
+    crop: Option<PixelRect>,
+    #[cfg(feature = "nee")]
+    next_event_estimation: bool,
+    #[cfg(feature = "nee")]
+    mis_weight: MisWeight,
+}
+
+/// How much effort goes into reducing noise, at the risk of getting something wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Estimator {
+    /// Importance samples materials and lights.
+    #[default]
+    Optimized,
+    /// Brute force path tracing for ground truth images, to validate optimisations against.
+    /// Scattered rays only follow the materials' own distributions and are never aimed at lights.
+    /// With `uniform_hemisphere`, diffuse surfaces don't even importance sample their cosine
+    /// term. Expect to need many times the samples for the same noise level.
+    Reference { uniform_hemisphere: bool },
 }
 
 #[cfg(feature = "rayon")]
@@ -53,67 +111,699 @@ pub trait RenderIterator = ParallelIterator<Item=Pixel>;
 #[cfg(not(feature = "rayon"))]
 pub trait RenderIterator = Iterator<Item=Pixel>;
 
+#[cfg(feature = "rayon")]
+pub trait TileIterator = IndexedParallelIterator<Item = Tile>;
+
+#[cfg(not(feature = "rayon"))]
+pub trait TileIterator = Iterator<Item = Tile>;
+
 impl<'a> Raytracer<'a> {
+    pub fn new(
+        world: &'a [Box<dyn Hittable>],
+        cam: &'a dyn Projection,
+        background: &'a Background,
+        image_width: u32,
+        image_height: u32,
+        samples_per_pixel: u32,
+    ) -> Self {
+        Self {
+            world,
+            lights: &[],
+            cam,
+            background,
+            image_width,
+            image_height,
+            samples_per_pixel,
+            estimator: Estimator::Optimized,
+            max_depth: DEFAULT_MAX_DEPTH,
+            seed: DEFAULT_SEED,
+            russian_roulette: true,
+            sampler: SamplerKind::Uniform,
+            max_luminance: f32::INFINITY,
+            record_aux: false,
+            crop: None,
+            #[cfg(feature = "nee")]
+            next_event_estimation: true,
+            #[cfg(feature = "nee")]
+            mis_weight: MisWeight::Balance,
+        }
+    }
+
+    pub fn with_estimator(mut self, estimator: Estimator) -> Self {
+        self.estimator = estimator;
+        self
+    }
+
+    /// How many times rays may bounce off surfaces. With zero, only emitters and the background
+    /// show up, with one, surfaces are lit by direct light only.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Whether paths that have bounced a few times are ended at random, with a probability that
+    /// grows the darker the surfaces get. Surviving paths are weighted up accordingly, so this
+    /// only trades noise for speed. The maximum depth still applies on top. Reference renders never
+    /// use it.
+    pub fn with_russian_roulette(mut self, russian_roulette: bool) -> Self {
+        self.russian_roulette = russian_roulette;
+        self
+    }
+
+    /// How the samples of a pixel are spread over it. Only applies to renders with a fixed sample
+    /// count; adaptive sampling doesn't know its sample count up front.
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Scales down samples brighter than `max_luminance`, see [`clamp_fireflies`]. Infinite by
+    /// default, which leaves all samples alone. Reference renders ignore it, since clamping loses
+    /// energy.
+    pub fn with_max_luminance(mut self, max_luminance: f32) -> Self {
+        self.max_luminance = max_luminance;
+        self
+    }
+
+    /// The luminance samples are actually clamped to, which is infinite for reference renders.
+    pub(crate) fn max_luminance(&self) -> f32 {
+        match self.estimator {
+            Estimator::Optimized => self.max_luminance,
+            Estimator::Reference { .. } => f32::INFINITY,
+        }
+    }
+
+    /// Seeds the RNGs of [`Raytracer::render_tiles`].
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Whether to fill in [`Pixel::aux`] with what the camera sees first in every pixel.
+    pub fn with_aux(mut self, record_aux: bool) -> Self {
+        self.record_aux = record_aux;
+        self
+    }
+
+    /// Renders only the pixels in `crop`, which is cut down to the image if it reaches past it.
+    /// All other pixels are left out of the rendered pixels and tiles.
+    pub fn with_crop(mut self, crop: PixelRect) -> Self {
+        self.crop = Some(PixelRect {
+            x0: crop.x0.min(self.image_width),
+            y0: crop.y0.min(self.image_height),
+            x1: crop.x1.min(self.image_width),
+            y1: crop.y1.min(self.image_height),
+        });
+        self
+    }
+
+    /// The pixels that get rendered: those of the crop, if there is one, or else all of them.
+    pub fn region(&self) -> PixelRect {
+        self.crop.unwrap_or(PixelRect {
+            x0: 0,
+            y0: 0,
+            x1: self.image_width,
+            y1: self.image_height,
+        })
+    }
+
+    /// How many pixels a render yields.
+    pub fn pixel_count(&self) -> u32 {
+        self.region().pixel_count()
+    }
+
+    /// Whether diffuse bounces sample a light directly through a shadow ray, rather than sending
+    /// half of their scattered rays towards the lights. Only applies to scenes with lights.
+    #[cfg(feature = "nee")]
+    pub fn with_next_event_estimation(mut self, next_event_estimation: bool) -> Self {
+        self.next_event_estimation = next_event_estimation;
+        self
+    }
+
+    /// How next event estimation weights light samples against scattered rays.
+    #[cfg(feature = "nee")]
+    pub fn with_mis_weight(mut self, mis_weight: MisWeight) -> Self {
+        self.mis_weight = mis_weight;
+        self
+    }
+
+    /// Objects to aim scattered rays at, in addition to letting materials pick directions
+    /// themselves. These are usually copies of the emitters in the world; only rectangles and
+    /// spheres support being sampled.
+    pub fn with_lights(mut self, lights: &'a [Box<dyn Hittable>]) -> Self {
+        self.lights = lights;
+        self
+    }
+
     pub fn render(&self) -> impl RenderIterator + '_ {
-        let pixel_range = iproduct!((0..self.image_height).rev(), 0..self.image_width);
+        self.render_pass(self.samples_per_pixel, 0)
+    }
+
+    /// Like [`Raytracer::render`], but also counts the work done rendering. See
+    /// [`RenderStats`] for how to read the counts.
+    #[cfg(feature = "stats")]
+    pub fn render_with_stats(&self) -> (impl RenderIterator + '_, RenderStats) {
+        let stats = RenderStats::start();
+        (self.render(), stats)
+    }
+
+    /// Renders the image in `passes` successive passes, each of which yields every pixel once with
+    /// its share of `samples_per_pixel`. Consumers are expected to accumulate the colors and
+    /// sample counts of all passes to arrive at the final image.
+    pub fn render_progressive(
+        &self,
+        passes: u32,
+    ) -> impl Iterator<Item = impl RenderIterator + '_> + '_ {
+        let samples_per_pixel = self.samples_per_pixel;
+        let passes = passes.clamp(1, samples_per_pixel.max(1));
+
+        (0..passes).map(move |pass| {
+            let remainder = u32::from(pass < samples_per_pixel % passes);
+            self.render_pass(samples_per_pixel / passes + remainder, pass)
+        })
+    }
+
+    fn render_pass(&self, samples: u32, pass: u32) -> impl RenderIterator + '_ {
+        self.render_pixels_with(pass, move |j, i, rng| self.sample_pixel(j, i, samples, rng))
+    }
 
+    /// Renders every pixel of the [region](Raytracer::region) with `sample_pixel`, which is handed
+    /// the pixel's row and column.
+    /// Serial renders seed their RNG with `pass`, so that successive passes differ.
+    #[cfg_attr(feature = "rayon", allow(unused_variables))]
+    fn render_pixels_with<'s, F>(&'s self, pass: u32, sample_pixel: F) -> impl RenderIterator + 's
+    where
+        F: Fn(u32, u32, &mut ActiveRng) -> Pixel + Sync + Send + 's,
+    {
         #[cfg(feature = "rayon")]
         {
-            let pixel_range: Vec<_> = pixel_range.collect();
-            pixel_range.into_par_iter().map(move |(j, i)| {
-                let mut rng = thread_rng();
-                self.sample_pixel(j, i, &mut rng)
+            // Work out row and column from a flat index rather than collecting the pixel
+            // coordinates, so starting a pass doesn't allocate. The order matches the iproduct
+            // below: top row first, left to right.
+            let region = self.region();
+            let width = region.width();
+            (0..region.pixel_count()).into_par_iter().map(move |index| {
+                let j = region.y1 - 1 - index / width;
+                let i = region.x0 + index % width;
+                let mut rng = SmallRng::seed_from_u64(thread_rng().gen());
+                sample_pixel(j, i, &mut rng)
             })
         }
 
         #[cfg(not(feature = "rayon"))]
         {
-            let mut rng = SmallRng::seed_from_u64(0xb234e6fea3886a1e);
-            pixel_range
-                .into_iter()
-                .map(move |(j, i)| self.sample_pixel(j, i, &mut rng))
+            let region = self.region();
+            let pixel_range = iproduct!((region.y0..region.y1).rev(), region.x0..region.x1);
+            let mut rng = SmallRng::seed_from_u64(mix_seed(0xb234e6fea3886a1e, pass as u64));
+            pixel_range.map(move |(j, i)| sample_pixel(j, i, &mut rng))
         }
     }
 
-    fn sample_pixel(&self, pixel_row: u32, pixel_column: u32, rng: &mut ActiveRng) -> Pixel {
-        let image_width = self.image_width;
-        let image_height = self.image_height;
+    /// Renders the image in square tiles of `tile_size` pixels, each of which is rendered as a
+    /// whole by a single thread. This keeps neighbouring pixels, which tend to hit the same
+    /// objects, together and saves scheduling a task per pixel. Tiles at the right and bottom
+    /// edges may be smaller. With a crop, the tiles cover just the crop, starting at its top left.
+    ///
+    /// The tiles come row by row from the top left, like the pixels of [`Raytracer::render`].
+    /// Every tile seeds its own RNG from its position and the renderer's seed, so the same seed
+    /// always yields the same image, no matter how the tiles are spread over threads.
+    pub fn render_tiles(&self, tile_size: u32) -> impl TileIterator + '_ {
+        let samples = self.samples_per_pixel;
+        self.render_tiles_with(tile_size, move |j, i, rng| {
+            self.sample_pixel(j, i, samples, rng)
+        })
+    }
+
+    /// The pixels of [`Raytracer::render_tiles`], tile by tile.
+    pub fn render_tiled(&self, tile_size: u32) -> impl RenderIterator + '_ {
+        #[cfg(feature = "rayon")]
+        {
+            self.render_tiles(tile_size)
+                .flat_map_iter(|tile| tile.pixels)
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            self.render_tiles(tile_size).flat_map(|tile| tile.pixels)
+        }
+    }
+
+    /// Like [`Raytracer::render_tiles`], but renders every pixel with `sample_pixel`.
+    fn render_tiles_with<'s, F>(&'s self, tile_size: u32, sample_pixel: F) -> impl TileIterator + 's
+    where
+        F: Fn(u32, u32, &mut ActiveRng) -> Pixel + Sync + Send + 's,
+    {
+        assert!(tile_size > 0);
+        let tile_columns = self.region().width().div_ceil(tile_size);
+        let render_tile = move |index| {
+            let (tile_row, tile_column) = (index / tile_columns, index % tile_columns);
+            let tile_index = (tile_row as u64) << 32 | tile_column as u64;
+            let mut rng = SmallRng::seed_from_u64(mix_seed(self.seed, tile_index));
+
+            self.render_tile(tile_size, tile_row, tile_column, &sample_pixel, &mut rng)
+        };
+
+        #[cfg(feature = "rayon")]
+        {
+            (0..self.tile_count(tile_size))
+                .into_par_iter()
+                .map(render_tile)
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            (0..self.tile_count(tile_size)).map(render_tile)
+        }
+    }
+
+    /// How many tiles [`Raytracer::render_tiles`] yields.
+    pub fn tile_count(&self, tile_size: u32) -> u32 {
+        let region = self.region();
+        let tile_columns = region.width().div_ceil(tile_size);
+        let tile_rows = region.height().div_ceil(tile_size);
+
+        tile_columns * tile_rows
+    }
 
+    /// Renders the tile in the given row and column of tiles, counting rows from the top of the
+    /// region.
+    fn render_tile(
+        &self,
+        tile_size: u32,
+        tile_row: u32,
+        tile_column: u32,
+        sample_pixel: &impl Fn(u32, u32, &mut ActiveRng) -> Pixel,
+        rng: &mut ActiveRng,
+    ) -> Tile {
+        let region = self.region();
+        let x = region.x0 + tile_column * tile_size;
+        let width = tile_size.min(region.x1 - x);
+        let top = tile_row * tile_size;
+        let height = tile_size.min(region.height() - top);
+        let y = region.y1 - top - height;
+
+        let pixels = (y..y + height)
+            .rev()
+            .flat_map(|row| (x..x + width).map(move |column| (row, column)))
+            .map(|(row, column)| sample_pixel(row, column, rng))
+            .collect();
+
+        Tile {
+            x,
+            y,
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn sample_pixel(
+        &self,
+        pixel_row: u32,
+        pixel_column: u32,
+        samples: u32,
+        rng: &mut ActiveRng,
+    ) -> Pixel {
+        match self.sampler {
+            SamplerKind::Uniform => {
+                let sampler = UniformSampler::new(rng.gen());
+                self.sample_pixel_with(pixel_row, pixel_column, samples, sampler, rng)
+            }
+            SamplerKind::Stratified => {
+                let sampler = StratifiedSampler::new(samples, rng.gen());
+                self.sample_pixel_with(pixel_row, pixel_column, samples, sampler, rng)
+            }
+            SamplerKind::Halton => {
+                // The pixel's RNG goes into the scrambling too, so successive passes over the
+                // same pixel don't repeat its samples.
+                let pixel_index = pixel_row * self.image_width + pixel_column;
+                let sampler = HaltonSampler::new(pixel_index ^ rng.gen::<u32>());
+                self.sample_pixel_with(pixel_row, pixel_column, samples, sampler, rng)
+            }
+        }
+    }
+
+    fn sample_pixel_with(
+        &self,
+        pixel_row: u32,
+        pixel_column: u32,
+        samples: u32,
+        mut sampler: impl Sampler,
+        rng: &mut ActiveRng,
+    ) -> Pixel {
         let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-        for _ in 0..self.samples_per_pixel {
-            let u = (pixel_column as f32 + rng.gen::<f32>()) / ((image_width - 1) as f32);
-            let v = (pixel_row as f32 + rng.gen::<f32>()) / ((image_height - 1) as f32);
-            let r = self.cam.get_ray(u, v, rng);
-            pixel_color += self.sample_ray(&r, rng, MAX_DEPTH);
+        for _ in 0..samples {
+            let offset = sampler.next_2d();
+            if let Some(r) = self.camera_ray_at(pixel_row, pixel_column, offset, rng) {
+                let sample = self.sample_ray(&r, rng, self.max_depth, 1.0, None);
+                pixel_color += clamp_fireflies(sample, self.max_luminance());
+            }
         }
 
         Pixel {
             row: pixel_row,
             column: pixel_column,
             color: pixel_color,
+            sample_count: samples,
+            aux: self.pixel_aux(pixel_row, pixel_column, rng),
         }
     }
 
-    fn sample_ray(&self, r: &Ray, rng: &mut ActiveRng, depth: usize) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
+    /// The first hit of a ray through the given pixel, if [`Raytracer::with_aux`] asked for it.
+    fn pixel_aux(
+        &self,
+        pixel_row: u32,
+        pixel_column: u32,
+        rng: &mut ActiveRng,
+    ) -> Option<PixelAux> {
+        if !self.record_aux {
+            return None;
         }
 
-        let hit_record = match self.world.hit(r, 0.001, f32::INFINITY, rng) {
-            Some(hit) => hit,
-            _ => return self.background,
+        let r = self.camera_ray(pixel_row, pixel_column, rng)?;
+        let hit = self.world.hit(&r, 0.001, f32::INFINITY, rng)?;
+
+        Some(PixelAux {
+            normal: hit.normal,
+            depth: hit.t * r.direction().length(),
+            albedo: hit.material.albedo(hit.texture_uv, &hit.p),
+        })
+    }
+
+    /// A ray through a random point of the given pixel, unless the camera doesn't cover it.
+    fn camera_ray(&self, pixel_row: u32, pixel_column: u32, rng: &mut impl Rng) -> Option<Ray> {
+        let offset = (rng.gen(), rng.gen());
+        self.camera_ray_at(pixel_row, pixel_column, offset, rng)
+    }
+
+    /// A ray through the point of the given pixel at `offset` from its corner.
+    fn camera_ray_at(
+        &self,
+        pixel_row: u32,
+        pixel_column: u32,
+        (offset_u, offset_v): (f32, f32),
+        rng: &mut impl Rng,
+    ) -> Option<Ray> {
+        let u = (pixel_column as f32 + offset_u) / ((self.image_width - 1) as f32);
+        let v = (pixel_row as f32 + offset_v) / ((self.image_height - 1) as f32);
+
+        let ray = self.cam.get_ray(u, v, rng)?;
+        Some(ray.with_kind(RayKind::Camera))
+    }
+
+    /// The camera rays for `samples` samples of every pixel of the [region](Raytracer::region), in
+    /// the order the pixels are rendered.
+    ///
+    /// They are generated the same way as while rendering, but from an RNG seeded per pixel with
+    /// `seed`, so that the same seed always yields the same rays. Samples the camera doesn't cover
+    /// are left out.
+    pub fn primary_rays(&self, samples: u32, seed: u64) -> impl Iterator<Item = PrimaryRay> + '_ {
+        let region = self.region();
+
+        (region.y0..region.y1)
+            .rev()
+            .flat_map(move |row| (region.x0..region.x1).map(move |column| (row, column)))
+            .flat_map(move |(row, column)| {
+                let pixel_index = (row as u64) << 32 | column as u64;
+                let mut rng = SmallRng::seed_from_u64(mix_seed(seed, pixel_index));
+
+                (0..samples).filter_map(move |sample| {
+                    let ray = self.camera_ray(row, column, &mut rng)?;
+                    Some(PrimaryRay {
+                        row,
+                        column,
+                        sample,
+                        origin: ray.origin(),
+                        direction: ray.direction(),
+                        time: ray.time(),
+                    })
+                })
+            })
+    }
+
+    /// Traces a single sample of the given pixel and returns its color, along with every step of
+    /// the path it took. The color is the raw sample, which [`Raytracer::with_max_luminance`]
+    /// doesn't apply to.
+    ///
+    /// The sample is drawn from an RNG seeded with the renderer's seed, the pixel and
+    /// `sample_index`, so the same index always yields the same path. It isn't the path of any
+    /// sample of an actual render, though.
+    pub fn trace_pixel(
+        &self,
+        pixel_row: u32,
+        pixel_column: u32,
+        sample_index: u32,
+    ) -> (Color, Vec<PathEvent>) {
+        let pixel_index = (pixel_row as u64) << 32 | pixel_column as u64;
+        let seed = mix_seed(mix_seed(self.seed, pixel_index), sample_index as u64);
+        let mut rng = SmallRng::seed_from_u64(seed);
+
+        let mut events = Vec::new();
+        let color = match self.camera_ray(pixel_row, pixel_column, &mut rng) {
+            Some(r) => self.sample_ray(&r, &mut rng, self.max_depth, 1.0, Some(&mut events)),
+            None => Color::new(0.0, 0.0, 0.0),
         };
 
-        let emitted = hit_record
-            .material
-            .emitted(hit_record.texture_uv, &hit_record.p);
+        (color, events)
+    }
+
+    /// The light arriving along `r`, which may bounce `depth` more times. Light emitted by the
+    /// surface `r` hits is scaled by `emission_weight`, which lets next event estimation avoid
+    /// counting lights twice. The path is written down into `recorder`, if there is one.
+    fn sample_ray(
+        &self,
+        r: &Ray,
+        rng: &mut ActiveRng,
+        depth: usize,
+        emission_weight: f32,
+        mut recorder: Recorder,
+    ) -> Color {
+        // Secondary rays pass straight through surfaces only meant for the camera.
+        let is_camera_ray = r.kind() == RayKind::Camera;
+        #[cfg(feature = "stats")]
+        stats::record(match is_camera_ray {
+            true => Counter::CameraRays,
+            false => Counter::ScatteredRays,
+        });
+        let bounces = self.max_depth - depth;
+        let mut t_min = 0.001;
+        let hit_record = loop {
+            match self.world.hit(r, t_min, f32::INFINITY, rng) {
+                Some(hit)
+                    if !is_camera_ray && !hit.material.is_visible_to_secondary_rays(&hit, rng) =>
+                {
+                    t_min = hit.t + 0.001;
+                }
+                Some(hit) => break hit,
+                _ => {
+                    let radiance = self.background.sample(r.direction());
+                    path_trace::record_background(&mut recorder, bounces, radiance);
+                    return radiance;
+                }
+            }
+        };
+
+        if let Some(ambient_occlusion) = hit_record.material.ambient_occlusion(&hit_record, rng) {
+            let shade = ambient_occlusion.shade(self.world, &hit_record, r.time(), rng);
+            path_trace::record_hit(&mut recorder, bounces, &hit_record, shade);
+            return shade;
+        }
+
+        let emitted = emission_weight
+            * hit_record.material.emitted_with_face(
+                hit_record.texture_uv,
+                &hit_record.p,
+                hit_record.is_front_face,
+            );
+        path_trace::record_hit(&mut recorder, bounces, &hit_record, emitted);
+        if depth == 0 {
+            return emitted;
+        }
 
         let scatter = match hit_record.material.scatter(r, &hit_record, rng) {
             Some(scatter) => scatter,
             _ => return emitted,
         };
 
-        emitted + scatter.attenuation * self.sample_ray(&scatter.scattered_ray, rng, depth - 1)
+        let mut attenuation = scatter.attenuation;
+        if self.russian_roulette
+            && self.estimator == Estimator::Optimized
+            && bounces >= RUSSIAN_ROULETTE_MIN_BOUNCES
+        {
+            let survival = attenuation
+                .max_component()
+                .min(RUSSIAN_ROULETTE_MAX_SURVIVAL);
+            if rng.gen::<f32>() >= survival {
+                return emitted;
+            }
+            attenuation /= survival;
+        }
+
+        let material_pdf = match scatter.scattered {
+            Scattered::Ray(scattered_ray) => {
+                path_trace::record_continuation(&mut recorder, attenuation, None);
+                return emitted
+                    + attenuation * self.sample_ray(&scattered_ray, rng, depth - 1, 1.0, recorder);
+            }
+            Scattered::Pdf(pdf) => pdf,
+        };
+        let material_pdf = match (self.estimator, material_pdf) {
+            (Estimator::Reference { uniform_hemisphere: true }, MaterialPdf::Cosine(_)) => {
+                MaterialPdf::Hemisphere(HemispherePdf::new(&hit_record.normal))
+            }
+            (_, pdf) => pdf,
+        };
+
+        #[cfg(feature = "nee")]
+        if self.next_event_estimation
+            && self.estimator == Estimator::Optimized
+            && !self.lights.is_empty()
+        {
+            return emitted
+                + self.sample_direct_and_indirect(
+                    r,
+                    &hit_record,
+                    attenuation,
+                    material_pdf,
+                    rng,
+                    depth,
+                    recorder,
+                );
+        }
+
+        // Send half of the rays towards the lights, if there are any.
+        let light_pdf = HittablePdf::new(self.lights, hit_record.p);
+        let light_weight = match self.estimator {
+            Estimator::Optimized if !self.lights.is_empty() => 0.5,
+            _ => 0.0,
+        };
+        let pdf = MixturePdf::new(light_pdf, material_pdf, light_weight);
+
+        let scattered_ray = Ray::new(hit_record.p, pdf.generate(rng), r.time());
+        let pdf_value = pdf.value(&scattered_ray.direction(), rng);
+        if pdf_value <= 0.0 {
+            return emitted;
+        }
+
+        let scattering_pdf = hit_record
+            .material
+            .scattering_pdf(r, &hit_record, &scattered_ray);
+
+        path_trace::record_continuation(&mut recorder, attenuation, Some(pdf_value));
+        emitted
+            + attenuation
+                * scattering_pdf
+                * self.sample_ray(&scattered_ray, rng, depth - 1, 1.0, recorder)
+                / pdf_value
+    }
+
+    /// Next event estimation: samples a light directly, through a shadow ray, in addition to
+    /// following one scattered ray. Both estimates are combined according to the renderer's
+    /// [`MisWeight`], so that each direction is mostly handled by the strategy that is more likely
+    /// to pick it.
+    #[cfg(feature = "nee")]
+    #[allow(clippy::too_many_arguments)]
+    fn sample_direct_and_indirect(
+        &self,
+        r: &Ray,
+        hit_record: &HitRecord,
+        attenuation: Color,
+        material_pdf: MaterialPdf,
+        rng: &mut ActiveRng,
+        depth: usize,
+        mut recorder: Recorder,
+    ) -> Color {
+        let light_sampler = UniformLightSampler::new(self.lights);
+        let mut color = Color::new(0.0, 0.0, 0.0);
+
+        if let Some(light) = light_sampler.sample(hit_record.p, r.time(), rng) {
+            let shadow_ray =
+                Ray::new(hit_record.p, light.direction, r.time()).with_kind(RayKind::Shadow);
+            // Stop short of the light itself, which is part of the world as well.
+            if !self.is_occluded(&shadow_ray, light.t * 0.999, rng) {
+                let material_pdf_value = material_pdf.value(&light.direction, rng);
+                let weight = mis_weight(light.pdf, material_pdf_value, self.mis_weight);
+                let scattering_pdf = hit_record
+                    .material
+                    .scattering_pdf(r, hit_record, &shadow_ray);
+
+                color += weight * attenuation * scattering_pdf * light.radiance / light.pdf;
+            }
+        }
+
+        let scattered_ray = Ray::new(hit_record.p, material_pdf.generate(rng), r.time());
+        let pdf_value = material_pdf.value(&scattered_ray.direction(), rng);
+        if pdf_value <= 0.0 {
+            return color;
+        }
+
+        // Lights hit by the scattered ray only contribute their share of the weight, the rest was
+        // already accounted for by the light sample.
+        let light_pdf_value = light_sampler.pdf(&hit_record.p, &scattered_ray.direction(), rng);
+        let emission_weight = mis_weight(pdf_value, light_pdf_value, self.mis_weight);
+        let scattering_pdf = hit_record
+            .material
+            .scattering_pdf(r, hit_record, &scattered_ray);
+
+        path_trace::record_continuation(&mut recorder, attenuation, Some(pdf_value));
+        color
+            + attenuation
+                * scattering_pdf
+                * self.sample_ray(&scattered_ray, rng, depth - 1, emission_weight, recorder)
+                / pdf_value
+    }
+
+    /// Whether anything visible to secondary rays lies along `r` before `t_max`.
+    #[cfg(feature = "nee")]
+    fn is_occluded(&self, r: &Ray, t_max: f32, rng: &mut ActiveRng) -> bool {
+        #[cfg(feature = "stats")]
+        stats::record(Counter::ShadowRays);
+
+        let mut t_min = 0.001;
+        while let Some(hit) = self.world.hit(r, t_min, t_max, rng) {
+            if hit.material.is_visible_to_secondary_rays(&hit, rng) {
+                return true;
+            }
+            t_min = hit.t + 0.001;
+        }
+
+        false
+    }
+}
+
+/// Derives the seed of one of many RNGs, e.g. one per tile or pixel, from a common `seed` and the
+/// RNG's `index`.
+///
+/// Simply XORing them would hand out related seeds, which differ in just the bits the indices
+/// differ in, and the same seed for e.g. seed 1 with index 0 and seed 0 with index 1. Hashing
+/// with SplitMix64 keeps them apart.
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(index))
+}
+
+/// One step of the SplitMix64 generator, a cheap and well mixing hash.
+fn splitmix64(x: u64) -> u64 {
+    let mut x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Scales `color` down to `max_luminance` if it is brighter, keeping its hue.
+///
+/// Applied to single samples, this keeps the rare very bright paths, which would otherwise show up
+/// as isolated white pixels, from dominating their pixels. The price is a slightly darker image,
+/// as the energy of those paths is lost.
+pub fn clamp_fireflies(color: Color, max_luminance: f32) -> Color {
+    let luminance = luminance(color);
+    if luminance > max_luminance {
+        color * (max_luminance / luminance)
+    } else {
+        color
     }
 }
 
@@ -122,7 +812,65 @@ impl<'a> Raytracer<'a> {
 pub struct Pixel {
     pub row: u32,
     pub column: u32,
+    /// The sum of `sample_count` samples, i.e. not yet divided by the sample count.
     pub color: Color,
+    pub sample_count: u32,
+    /// Auxiliary buffers for denoising, see [`Raytracer::with_aux`].
+    pub aux: Option<PixelAux>,
+}
+
+/// What the camera sees first through a pixel. Pixels looking at the background have none.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PixelAux {
+    /// The unit normal, facing the camera.
+    pub normal: Vec3,
+    /// The distance from the camera.
+    pub depth: f32,
+    pub albedo: Color,
+}
+
+/// The pixels from column `x0` and row `y0` up to, but not including, column `x1` and row `y1`.
+/// Like [`Pixel::row`], rows count from the bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PixelRect {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl PixelRect {
+    pub fn width(&self) -> u32 {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    pub fn pixel_count(&self) -> u32 {
+        self.width() * self.height()
+    }
+
+    pub fn contains(&self, row: u32, column: u32) -> bool {
+        (self.y0..self.y1).contains(&row) && (self.x0..self.x1).contains(&column)
+    }
+}
+
+/// A rectangular part of the image, as rendered by [`Raytracer::render_tiles`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tile {
+    /// The column of the tile's leftmost pixels.
+    pub x: u32,
+    /// The row of the tile's bottom pixels. Like [`Pixel::row`], rows count from the bottom.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The tile's pixels, top row first and left to right.
+    pub pixels: Vec<Pixel>,
 }
 
 #[derive(Debug, Clone)]
@@ -132,7 +880,230 @@ pub enum ProgressMessage {
         width: u32,
         height: u32,
         samples_per_pixel: u32,
+        max_depth: u32,
     },
     Pixel(Pixel),
     ImageEnd,
+    /// What the image took to render. Only sent by renderers that count their work, after
+    /// [`ProgressMessage::ImageEnd`].
+    RenderStats(RenderTotals),
+}
+
+/// The work that went into a render, in a form that can be sent along with it. With the `stats`
+/// feature, it can be had from a [`stats::StatsSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RenderTotals {
+    /// Camera, scattered and shadow rays together.
+    pub total_rays: u64,
+    /// Bounding box tests of BVH nodes.
+    pub bvh_tests: u64,
+    /// Intersection tests of single primitives. Only triangles are counted so far.
+    pub primitive_tests: u64,
+    pub shadow_rays: u64,
+    pub elapsed_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        camera::Camera, hittable::spherical::Sphere, light_source::DiffuseLight,
+        material::Lambertian, texture::SolidColor, vec3::Point3,
+    };
+
+    type Objects = Vec<Box<dyn Hittable>>;
+
+    /// A small bright sphere hanging over a large diffuse one, seen from above, and the light to
+    /// sample.
+    #[cfg(feature = "nee")]
+    fn lit_scene() -> (Objects, Objects, Camera) {
+        let light = || -> Box<dyn Hittable> {
+            Box::new(Sphere::new(
+                Point3::new(0.0, 3.0, 0.0),
+                0.5,
+                Box::new(DiffuseLight::new(SolidColor::new_rgb(10.0, 10.0, 10.0))),
+            ))
+        };
+        let ground: Box<dyn Hittable> = Box::new(Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let cam = Camera::new(
+            Point3::new(0.0, 1.0, 4.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+
+        (vec![ground, light()], vec![light()], cam)
+    }
+
+    /// A glowing sphere inside a larger diffuse one, where paths bounce many times before they
+    /// find the light, and the light to sample.
+    fn enclosed_scene() -> (Objects, Objects, Camera) {
+        let light = || -> Box<dyn Hittable> {
+            Box::new(Sphere::new(
+                Point3::new(0.0, 3.0, 0.0),
+                2.0,
+                Box::new(DiffuseLight::new(SolidColor::new_rgb(1.0, 1.0, 1.0))),
+            ))
+        };
+        let walls: Box<dyn Hittable> = Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            5.0,
+            Box::new(Lambertian::new_solid_color(Color::new(0.7, 0.7, 0.7))),
+        ));
+        let cam = Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            20.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+
+        (vec![walls, light()], vec![light()], cam)
+    }
+
+    /// The mean and variance of the luminance of `samples` paths through the middle of the image.
+    fn pixel_statistics(raytracer: &Raytracer, samples: u32) -> (f32, f32) {
+        let luminances: Vec<f32> = (0..samples)
+            .map(|sample| luminance(raytracer.trace_pixel(8, 8, sample).0))
+            .collect();
+        let mean = luminances.iter().sum::<f32>() / samples as f32;
+        let variance = luminances
+            .iter()
+            .map(|l| (l - mean) * (l - mean))
+            .sum::<f32>()
+            / (samples - 1) as f32;
+
+        (mean, variance)
+    }
+
+    #[test]
+    fn mixed_seeds_differ_where_xored_ones_collide() {
+        assert_ne!(mix_seed(1, 0), mix_seed(0, 1));
+        assert_ne!(mix_seed(0, 0), mix_seed(1, 1));
+    }
+
+    #[test]
+    fn mixed_seeds_of_neighbouring_tiles_are_unrelated() {
+        let seeds: BTreeSet<_> = (0..64)
+            .flat_map(|row: u64| {
+                (0..64).map(move |column| mix_seed(DEFAULT_SEED, row << 32 | column))
+            })
+            .collect();
+        assert_eq!(seeds.len(), 64 * 64);
+
+        // About half the bits flip between any two seeds, rather than just those of the index.
+        let flipped_bits = (mix_seed(DEFAULT_SEED, 0) ^ mix_seed(DEFAULT_SEED, 1)).count_ones();
+        assert!(
+            (16..=48).contains(&flipped_bits),
+            "{} bits flipped",
+            flipped_bits
+        );
+    }
+
+    #[cfg(feature = "nee")]
+    #[test]
+    fn next_event_estimation_agrees_with_mixture_sampling_with_less_noise() {
+        const SAMPLES: u32 = 20_000;
+        let (world, lights, cam) = lit_scene();
+        let background = Background::SolidColor(Color::new(0.0, 0.0, 0.0));
+        let raytracer = |next_event_estimation| {
+            Raytracer::new(&world, &cam, &background, 16, 16, 1)
+                .with_lights(&lights)
+                .with_next_event_estimation(next_event_estimation)
+        };
+
+        let (nee_mean, nee_variance) = pixel_statistics(&raytracer(true), SAMPLES);
+        let (mixture_mean, mixture_variance) = pixel_statistics(&raytracer(false), SAMPLES);
+
+        let standard_error = ((nee_variance + mixture_variance) / SAMPLES as f32).sqrt();
+        assert!(
+            (nee_mean - mixture_mean).abs() < 3.0 * standard_error,
+            "mean luminance {} with next event estimation, {} without",
+            nee_mean,
+            mixture_mean
+        );
+        assert!(
+            nee_variance < mixture_variance,
+            "variance {} with next event estimation, {} without",
+            nee_variance,
+            mixture_variance
+        );
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_mean() {
+        const SAMPLES: u32 = 100_000;
+        let (world, lights, cam) = enclosed_scene();
+        let background = Background::SolidColor(Color::new(0.0, 0.0, 0.0));
+        let raytracer = |russian_roulette| {
+            Raytracer::new(&world, &cam, &background, 16, 16, 1)
+                .with_lights(&lights)
+                .with_seed(1)
+                .with_russian_roulette(russian_roulette)
+        };
+
+        let (roulette_mean, roulette_variance) = pixel_statistics(&raytracer(true), SAMPLES);
+        let (full_mean, full_variance) = pixel_statistics(&raytracer(false), SAMPLES);
+
+        let standard_error = ((roulette_variance + full_variance) / SAMPLES as f32).sqrt();
+        assert!(
+            (roulette_mean - full_mean).abs() < 3.0 * standard_error,
+            "mean luminance {} with Russian roulette, {} without",
+            roulette_mean,
+            full_mean
+        );
+    }
+
+    #[test]
+    fn samples_of_very_bright_emitters_are_clamped_keeping_their_hue() {
+        let world: Objects = vec![Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            100.0,
+            Box::new(
+                DiffuseLight::new(SolidColor::new_rgb(1.0, 0.5, 0.25)).with_intensity(10_000.0),
+            ),
+        ))];
+        // Every pixel looks at the inside of the glowing sphere.
+        let (_, _, cam) = enclosed_scene();
+        let background = Background::SolidColor(Color::new(0.0, 0.0, 0.0));
+        let raytracer = Raytracer::new(&world, &cam, &background, 2, 2, 4).with_max_luminance(10.0);
+
+        let pixels: Vec<Pixel> = raytracer.render().collect();
+        assert_eq!(pixels.len(), 4);
+        for pixel in pixels {
+            let color = pixel.color / pixel.sample_count as f32;
+            assert!(
+                (luminance(color) - 10.0).abs() < 1e-3,
+                "{} has a luminance of {}",
+                color,
+                luminance(color)
+            );
+            assert!(
+                (color.y() / color.x() - 0.5).abs() < 1e-5,
+                "{} changed hue",
+                color
+            );
+            assert!(
+                (color.z() / color.x() - 0.25).abs() < 1e-5,
+                "{} changed hue",
+                color
+            );
+        }
+    }
 }
@@ -1,25 +1,129 @@
+use alloc::boxed::Box;
+
 use derive_more::Constructor;
+use rand::Rng;
 
 use crate::{
-    hittable::HitRecord,
-    material::{Material, Scatter},
+    hittable::{HitRecord, Hittable},
+    material::{Material, ScatterRecord},
     ray::Ray,
     texture::{Point2d, Texture},
-    vec3::{Color, Point3},
+    vec3::{Color, Point3, Vec3},
     ActiveRng,
 };
 
-#[derive(Constructor, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct DiffuseLight<T: Texture> {
     emit: T,
+    intensity: f32,
+    two_sided: bool,
+}
+
+impl<T: Texture> DiffuseLight<T> {
+    /// A light emitting the color of `emit` from both sides.
+    pub fn new(emit: T) -> Self {
+        Self {
+            emit,
+            intensity: 1.0,
+            two_sided: true,
+        }
+    }
+
+    /// Scales the emitted light, so the texture can hold a plain color.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        assert!(intensity >= 0.0);
+        self.intensity = intensity;
+        self
+    }
+
+    /// One-sided lights only emit from the front face of whatever surface they are on, e.g. a
+    /// ceiling light whose normals point down.
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
 }
 
 impl<T: Texture> Material for DiffuseLight<T> {
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _rng: &mut ActiveRng) -> Option<Scatter> {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _rng: &mut ActiveRng,
+    ) -> Option<ScatterRecord> {
         None
     }
 
     fn emitted(&self, uv: Point2d, p: &Point3) -> Color {
-        self.emit.value(uv, p)
+        self.intensity * self.emit.value(uv, p)
+    }
+
+    fn emitted_with_face(&self, uv: Point2d, p: &Point3, is_front_face: bool) -> Color {
+        if !self.two_sided && !is_front_face {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        self.emitted(uv, p)
+    }
+}
+
+/// Light arriving at a point from a direction picked by a [`LightSampler`].
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Towards the light. Not normalised: the light is hit at `t` times this.
+    pub direction: Vec3,
+    pub t: f32,
+    /// The density, with respect to solid angle, with which `direction` was picked.
+    pub pdf: f32,
+    /// The light emitted towards the point, not yet checked for anything in between.
+    pub radiance: Color,
+}
+
+/// Picks directions towards lights, for sampling direct light at every bounce.
+pub trait LightSampler {
+    /// A direction from `point` towards a light, or none if the light turned out to be missed.
+    fn sample(&self, point: Point3, time: f32, rng: &mut ActiveRng) -> Option<LightSample>;
+
+    /// The density with which `sample` picks `direction` from `point`.
+    fn pdf(&self, point: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32;
+}
+
+/// Picks one of the lights uniformly at random, then a point on it.
+#[derive(Debug, Clone, Copy, Constructor)]
+pub struct UniformLightSampler<'a> {
+    lights: &'a [Box<dyn Hittable>],
+}
+
+impl<'a> LightSampler for UniformLightSampler<'a> {
+    fn sample(&self, point: Point3, time: f32, rng: &mut ActiveRng) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let light = &self.lights[rng.gen_range(0..self.lights.len())];
+        let direction = light.random_point_towards(&point, rng) - point;
+
+        // The direction might as well hit another light in front of the chosen one.
+        let rec = self
+            .lights
+            .hit(&Ray::new(point, direction, time), 0.001, f32::INFINITY, rng)?;
+        let pdf = self.pdf(&point, &direction, rng);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            t: rec.t,
+            pdf,
+            radiance: rec
+                .material
+                .emitted_with_face(rec.texture_uv, &rec.p, rec.is_front_face),
+        })
+    }
+
+    /// Lights can overlap as seen from `point`, so this is the average over all of them.
+    fn pdf(&self, point: &Point3, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.lights.pdf_value(point, direction, rng)
     }
 }
@@ -1,3 +1,6 @@
+use alloc::boxed::Box;
+use core::f32::consts::PI;
+
 use derive_more::Constructor;
 use dyn_clone::{clone_trait_object, DynClone};
 #[cfg(feature = "no_std")]
@@ -5,24 +8,77 @@ use micromath::F32Ext;
 use rand::Rng;
 
 use super::{
-    hittable::HitRecord,
+    hittable::{HitRecord, Hittable},
     ray::Ray,
     vec3::{Color, Vec3},
 };
 use crate::{
+    microfacet::{fresnel_dielectric, schlick_fresnel, schlick_weight, Ggx, Gtr1},
+    onb::OrthonormalBase,
+    pdf::{CosinePdf, MaterialPdf, Pdf, SpherePdf},
     texture::{Point2d, SolidColor, Texture},
     vec3::Point3,
     ActiveRng,
 };
 
-pub struct Scatter {
+pub struct ScatterRecord {
     pub attenuation: Color,
-    pub scattered_ray: Ray,
+    pub scattered: Scattered,
+}
+
+pub enum Scattered {
+    /// The material picked the outgoing ray itself, and `attenuation` already accounts for how.
+    /// Mirrors, glass and microfacet materials work this way.
+    Ray(Ray),
+    /// The outgoing direction is left to the renderer, which may mix in directions towards
+    /// lights. Light from a direction is weighted by `attenuation` times
+    /// [`Material::scattering_pdf`], divided by the density the direction was actually drawn with.
+    Pdf(MaterialPdf),
 }
 
 pub trait Material: core::fmt::Debug + Sync + Send + DynClone {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<Scatter>;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord>;
     fn emitted(&self, uv: Point2d, p: &Point3) -> Color;
+
+    /// The light emitted at a hit on the front or back face of a surface. Most emitters don't
+    /// care which side they are seen from, so this is [`Material::emitted`] by default.
+    fn emitted_with_face(&self, uv: Point2d, p: &Point3, _is_front_face: bool) -> Color {
+        self.emitted(uv, p)
+    }
+
+    /// The base color of the surface, for the albedo buffer denoisers take alongside the image.
+    /// Materials without one, like clear glass, count as white.
+    fn albedo(&self, _uv: Point2d, _p: &Point3) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    /// The density with which the material scatters light into the direction of `scattered`.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f32 {
+        0.0
+    }
+
+    /// Whether reflected, refracted or bounced rays can hit this material at all. If not, the
+    /// surface is only ever seen directly by the camera. Like `scatter`, this may be decided per
+    /// hit, and at random.
+    fn is_visible_to_secondary_rays(&self, _rec: &HitRecord, _rng: &mut ActiveRng) -> bool {
+        true
+    }
+
+    /// Materials that shade by looking at the surrounding geometry return their settings here.
+    /// The renderer then asks them for the final color of the hit `rec` instead of calling
+    /// `scatter`.
+    fn ambient_occlusion(
+        &self,
+        _rec: &HitRecord,
+        _rng: &mut ActiveRng,
+    ) -> Option<&AmbientOcclusion> {
+        None
+    }
+
+    /// The full path of the material's type, e.g. for telling materials apart in path traces.
+    fn type_name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
 }
 
 clone_trait_object!(Material);
@@ -39,25 +95,103 @@ impl Lambertian<SolidColor> {
 }
 
 impl<T: Texture> Material for Lambertian<T> {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<Scatter> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.texture_uv, &rec.p),
+            scattered: Scattered::Pdf(MaterialPdf::Cosine(CosinePdf::new(&rec.normal))),
+        })
+    }
 
-        if scatter_direction.is_near_zero() {
-            scatter_direction = rec.normal;
-        }
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        emit_black()
+    }
 
-        let scattered_ray = Ray::new(rec.p, scatter_direction, r_in.time());
-        let attenuation = self.albedo.value(rec.texture_uv, &rec.p);
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.albedo.value(uv, p)
+    }
 
-        Some(Scatter {
-            attenuation,
-            scattered_ray,
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let cosine = rec.normal.dot(&scattered.direction().unit_vector());
+        cosine.max(0.0) / PI
+    }
+}
+
+/// A rough diffuse material following the Oren-Nayar reflectance model.
+///
+/// `sigma_degrees` is the standard deviation of the microfacet slope angle. At zero roughness the
+/// material behaves exactly like [`Lambertian`].
+#[derive(Debug, Constructor, Clone)]
+pub struct OrenNayar<T: Texture> {
+    albedo: T,
+    sigma_degrees: f32,
+}
+
+impl<T: Texture> OrenNayar<T> {
+    fn coefficients(&self) -> (f32, f32) {
+        let sigma = self.sigma_degrees.to_radians();
+        let sigma_squared = sigma * sigma;
+
+        let a = 1.0 - 0.5 * sigma_squared / (sigma_squared + 0.33);
+        let b = 0.45 * sigma_squared / (sigma_squared + 0.09);
+
+        (a, b)
+    }
+
+    fn reflectance_factor(&self, incoming: Vec3, outgoing: Vec3, normal: Vec3) -> f32 {
+        let (a, b) = self.coefficients();
+
+        let cos_in = incoming.dot(&normal).clamp(0.0, 1.0);
+        let cos_out = outgoing.dot(&normal).clamp(0.0, 1.0);
+        let sin_in = (1.0 - cos_in * cos_in).sqrt();
+        let sin_out = (1.0 - cos_out * cos_out).sqrt();
+
+        // Cosine of the azimuthal angle between both directions, measured in the tangent plane.
+        let incoming_tangent = incoming - cos_in * normal;
+        let outgoing_tangent = outgoing - cos_out * normal;
+        let tangent_lengths = incoming_tangent.length() * outgoing_tangent.length();
+        let cos_phi_difference = if tangent_lengths > 1e-6 {
+            incoming_tangent.dot(&outgoing_tangent) / tangent_lengths
+        } else {
+            0.0
+        };
+
+        // alpha is the larger of both polar angles, beta the smaller one.
+        let (sin_alpha, tan_beta) = if cos_in > cos_out {
+            (sin_out, sin_in / cos_in)
+        } else {
+            (sin_in, sin_out / cos_out.max(1e-6))
+        };
+
+        a + b * cos_phi_difference.max(0.0) * sin_alpha * tan_beta
+    }
+}
+
+impl<T: Texture> Material for OrenNayar<T> {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.texture_uv, &rec.p),
+            scattered: Scattered::Pdf(MaterialPdf::Cosine(CosinePdf::new(&rec.normal))),
         })
     }
 
     fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
         emit_black()
     }
+
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.albedo.value(uv, p)
+    }
+
+    /// Cosine weighted like [`Lambertian`], with the Oren-Nayar correction on top. This makes it
+    /// the reflectance relative to the albedo rather than a normalised density, which is all the
+    /// renderer needs.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let direction = scattered.direction().unit_vector();
+        let factor =
+            self.reflectance_factor(-r_in.direction().unit_vector(), direction, rec.normal);
+
+        factor * rec.normal.dot(&direction).max(0.0) / PI
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -75,7 +209,7 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<Scatter> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
         let reflected = r_in.direction().unit_vector().reflect(&rec.normal);
         let scattered_ray = Ray::new(
             rec.p,
@@ -85,8 +219,8 @@ impl Material for Metal {
         let attenuation = self.albedo;
 
         if scattered_ray.direction().dot(&rec.normal) > 0.0 {
-            Some(Scatter {
-                scattered_ray,
+            Some(ScatterRecord {
+                scattered: Scattered::Ray(scattered_ray),
                 attenuation,
             })
         } else {
@@ -97,6 +231,88 @@ impl Material for Metal {
     fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
         emit_black()
     }
+
+    fn albedo(&self, _uv: Point2d, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+/// A metal with a GGX microfacet distribution, which unlike the fuzz sphere of [`Metal`] gets the
+/// stretched highlights and brighter grazing reflections of real metals right.
+///
+/// `albedo` is the reflectance at normal incidence. A `roughness` of zero approaches a perfect
+/// mirror. `anisotropy` stretches the highlight along an arbitrary tangent, since hits carry no
+/// tangent frame yet.
+#[derive(Debug, Clone)]
+pub struct RoughConductor {
+    albedo: Color,
+    roughness: f32,
+    anisotropy: f32,
+}
+
+impl RoughConductor {
+    pub fn new(albedo: Color, roughness: f32, anisotropy: f32) -> Self {
+        assert!((0.0..=1.0).contains(&roughness));
+        assert!((0.0..=1.0).contains(&anisotropy));
+
+        Self {
+            albedo,
+            roughness,
+            anisotropy,
+        }
+    }
+
+    fn distribution(&self) -> Ggx {
+        Ggx::new(self.roughness, self.anisotropy)
+    }
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        let onb = OrthonormalBase::from_w(&rec.normal);
+        let wo = onb.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let distribution = self.distribution();
+        let m = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+        let wi = (-wo).reflect(&m);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // With visible normals sampled, D and the cosine terms cancel against the pdf.
+        let fresnel = schlick_fresnel(self.albedo, wo.dot(&m));
+        let attenuation = distribution.g2(wo, wi) / distribution.g1(wo) * fresnel;
+        let scattered_ray = Ray::new(rec.p, onb.to_world(wi), r_in.time());
+
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Scattered::Ray(scattered_ray),
+        })
+    }
+
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        emit_black()
+    }
+
+    fn albedo(&self, _uv: Point2d, _p: &Point3) -> Color {
+        self.albedo
+    }
+
+    /// The density with which [`Material::scatter`] picks the direction of `scattered`.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let onb = OrthonormalBase::from_w(&rec.normal);
+        let wo = onb.to_local(-r_in.direction().unit_vector());
+        let wi = onb.to_local(scattered.direction().unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let m = (wo + wi).unit_vector();
+        self.distribution().visible_normal_pdf(wo, m) / (4.0 * wo.dot(&m))
+    }
 }
 
 #[derive(Debug, Constructor, Clone)]
@@ -113,7 +329,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<Scatter> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
         let ir = self.ir;
 
         let attenuation = Color::new(1.0, 1.0, 1.0);
@@ -135,15 +351,467 @@ impl Material for Dielectric {
 
         let scattered_ray = Ray::new(rec.p, direction, r_in.time());
 
-        Some(Scatter {
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Scattered::Ray(scattered_ray),
+        })
+    }
+
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        emit_black()
+    }
+}
+
+/// Frosted glass: a dielectric whose surface is made of GGX distributed microfacets.
+///
+/// Each scattering event picks a microfacet normal, then reflects or refracts through it with a
+/// probability given by the exact Fresnel term for that microfacet.
+#[derive(Debug, Clone)]
+pub struct RoughDielectric {
+    ior: f32,
+    roughness: f32,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: f32, roughness: f32) -> Self {
+        assert!((0.0..=1.0).contains(&roughness));
+
+        Self { ior, roughness }
+    }
+
+    fn distribution(&self) -> Ggx {
+        Ggx::new(self.roughness, 0.0)
+    }
+
+    /// Ratio of the refractive index behind the surface to the one in front of it.
+    fn eta(&self, rec: &HitRecord) -> f32 {
+        if rec.is_front_face {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        let onb = OrthonormalBase::from_w(&rec.normal);
+        let wo = onb.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let distribution = self.distribution();
+        let eta = self.eta(rec);
+
+        let m = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+        let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+
+        // Choosing reflection with probability F cancels F out of the weight.
+        let wi = if rng.gen::<f32>() < fresnel {
+            let wi = (-wo).reflect(&m);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = (-wo).refract(&m, 1.0 / eta);
+            if wi.z() >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let attenuation = distribution.g2(wo, wi) / distribution.g1(wo) * Color::new(1.0, 1.0, 1.0);
+        let scattered_ray = Ray::new(rec.p, onb.to_world(wi), r_in.time());
+
+        Some(ScatterRecord {
             attenuation,
-            scattered_ray,
+            scattered: Scattered::Ray(scattered_ray),
+        })
+    }
+
+    fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
+        emit_black()
+    }
+
+    /// The density with which [`Material::scatter`] picks the direction of `scattered`, including
+    /// the probability of choosing reflection or refraction.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let onb = OrthonormalBase::from_w(&rec.normal);
+        let wo = onb.to_local(-r_in.direction().unit_vector());
+        let wi = onb.to_local(scattered.direction().unit_vector());
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        let distribution = self.distribution();
+        let eta = self.eta(rec);
+
+        if wi.z() > 0.0 {
+            let m = (wo + wi).unit_vector();
+            let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+
+            fresnel * distribution.visible_normal_pdf(wo, m) / (4.0 * wo.dot(&m))
+        } else {
+            // The generalised half vector for refraction, flipped into the upper hemisphere.
+            let m = -(wo + eta * wi).unit_vector();
+            let m = if m.z() < 0.0 { -m } else { m };
+            if wo.dot(&m) <= 0.0 || wi.dot(&m) >= 0.0 {
+                return 0.0;
+            }
+
+            let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+            let denominator = wo.dot(&m) + eta * wi.dot(&m);
+            let jacobian = eta * eta * wi.dot(&m).abs() / (denominator * denominator);
+
+            (1.0 - fresnel) * distribution.visible_normal_pdf(wo, m) * jacobian
+        }
+    }
+}
+
+/// The Disney "principled" BRDF from Burley, "Physically-Based Shading at Disney" (2012), extended
+/// by a rough dielectric transmission lobe.
+///
+/// All parameters except `ior` are in `0.0..=1.0`. Each scattering event picks one of the
+/// diffuse, specular, clearcoat and transmission lobes at random, weighted by how much each
+/// contributes, and returns that lobe's sample weight divided by its selection probability.
+#[derive(Debug, Clone)]
+pub struct Principled<B: Texture, E: Texture> {
+    pub base_color: B,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub specular: f32,
+    pub specular_tint: f32,
+    pub subsurface: f32,
+    pub anisotropic: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub ior: f32,
+    pub transmission: f32,
+    pub emission: E,
+}
+
+impl<B: Texture> Principled<B, SolidColor> {
+    /// A non-emissive material with the defaults of the Disney BRDF explorer.
+    pub fn new(base_color: B) -> Self {
+        Self {
+            base_color,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            subsurface: 0.0,
+            anisotropic: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            ior: 1.5,
+            transmission: 0.0,
+            emission: SolidColor::new(emit_black()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PrincipledLobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl<B: Texture, E: Texture> Principled<B, E> {
+    /// The relative weight of each lobe, used both to pick one and to scale its contribution.
+    fn lobe_weights(&self) -> [(PrincipledLobe, f32); 4] {
+        let dielectric = 1.0 - self.metallic;
+
+        [
+            (
+                PrincipledLobe::Diffuse,
+                dielectric * (1.0 - self.transmission),
+            ),
+            (
+                PrincipledLobe::Specular,
+                1.0 - dielectric * self.transmission,
+            ),
+            (PrincipledLobe::Clearcoat, 0.25 * self.clearcoat),
+            (PrincipledLobe::Transmission, dielectric * self.transmission),
+        ]
+    }
+
+    fn tint(base_color: Color) -> Color {
+        let luminance = luminance(base_color);
+        if luminance > 0.0 {
+            base_color / luminance
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    /// Diffuse and sheen, which share a cosine weighted sampling strategy. Returns `f * cos / pdf`.
+    fn diffuse_weight(&self, base_color: Color, wo: Vec3, wi: Vec3) -> Color {
+        let cos_l = wi.z();
+        let cos_v = wo.z();
+        let h = (wo + wi).unit_vector();
+        let cos_d = wi.dot(&h);
+
+        let f_l = schlick_weight(cos_l);
+        let f_v = schlick_weight(cos_v);
+
+        let fd90 = 0.5 + 2.0 * cos_d * cos_d * self.roughness;
+        let fd = lerp(1.0, fd90, f_l) * lerp(1.0, fd90, f_v);
+
+        let fss90 = cos_d * cos_d * self.roughness;
+        let fss = lerp(1.0, fss90, f_l) * lerp(1.0, fss90, f_v);
+        let ss = 1.25 * (fss * (1.0 / (cos_l + cos_v).max(1e-6) - 0.5) + 0.5);
+
+        let white = Color::new(1.0, 1.0, 1.0);
+        let sheen_color = white + self.sheen_tint * (Self::tint(base_color) - white);
+        let sheen = schlick_weight(cos_d) * self.sheen * sheen_color;
+
+        // The pdf of cosine weighted sampling is cos / PI, so the PI of the Lambertian term and
+        // the cosine cancel.
+        lerp(fd, ss, self.subsurface) * base_color + PI * sheen
+    }
+
+    fn sample_lobe(
+        &self,
+        lobe: PrincipledLobe,
+        base_color: Color,
+        wo: Vec3,
+        is_front_face: bool,
+        rng: &mut ActiveRng,
+    ) -> Option<(Vec3, Color)> {
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        match lobe {
+            PrincipledLobe::Diffuse => {
+                let mut wi = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit_vector(rng);
+                if wi.is_near_zero() {
+                    wi = Vec3::new(0.0, 0.0, 1.0);
+                }
+                let wi = wi.unit_vector();
+
+                Some((wi, self.diffuse_weight(base_color, wo, wi)))
+            }
+            PrincipledLobe::Specular => {
+                let distribution = Ggx::new(self.roughness, self.anisotropic);
+                let m = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                let wi = (-wo).reflect(&m);
+                if wi.z() <= 0.0 {
+                    return None;
+                }
+
+                let tinted = white + self.specular_tint * (Self::tint(base_color) - white);
+                let dielectric_f0 = self.specular * 0.08 * tinted;
+                let f0 = dielectric_f0 + self.metallic * (base_color - dielectric_f0);
+                let fresnel = schlick_fresnel(f0, wi.dot(&m));
+
+                Some((wi, distribution.g2(wo, wi) / distribution.g1(wo) * fresnel))
+            }
+            PrincipledLobe::Clearcoat => {
+                let distribution = Gtr1::new(lerp(0.1, 0.001, self.clearcoat_gloss));
+                let m = distribution.sample_normal(rng.gen(), rng.gen());
+                let wi = (-wo).reflect(&m);
+                if wi.z() <= 0.0 || wo.dot(&m) <= 0.0 {
+                    return None;
+                }
+
+                // Separable Smith masking with a fixed alpha of 0.25, as in the paper.
+                let masking = Ggx::new(0.5, 0.0);
+                let g = masking.g1(wo) * masking.g1(wi);
+                let fresnel = lerp(0.04, 1.0, schlick_weight(wi.dot(&m)));
+
+                // f * cos / pdf with pdf = D * cos_m / (4 * wo.m), so D cancels.
+                let weight = g * fresnel * wo.dot(&m) / (wo.z() * m.z());
+                Some((wi, weight * white))
+            }
+            PrincipledLobe::Transmission => {
+                let distribution = Ggx::new(self.roughness, 0.0);
+                let eta = if is_front_face {
+                    self.ior
+                } else {
+                    1.0 / self.ior
+                };
+
+                let m = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+
+                // Light passing through the surface picks up the base color.
+                let is_reflection = rng.gen::<f32>() < fresnel;
+                let (wi, tint) = if is_reflection {
+                    ((-wo).reflect(&m), white)
+                } else {
+                    ((-wo).refract(&m, 1.0 / eta), base_color)
+                };
+                if (wi.z() > 0.0) != is_reflection {
+                    return None;
+                }
+
+                Some((wi, distribution.g2(wo, wi) / distribution.g1(wo) * tint))
+            }
+        }
+    }
+}
+
+impl<B: Texture, E: Texture> Material for Principled<B, E> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        let onb = OrthonormalBase::from_w(&rec.normal);
+        let wo = onb.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let lobes = self.lobe_weights();
+        let total_weight: f32 = lobes.iter().map(|(_, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+
+        // Pick a lobe proportionally to its weight. The weight divided by the probability of the
+        // choice then leaves the total weight as a factor.
+        let mut choice = rng.gen::<f32>() * total_weight;
+        let (lobe, weight) = *lobes
+            .iter()
+            .find(|(_, weight)| {
+                choice -= weight;
+                choice < 0.0
+            })
+            .unwrap_or(&lobes[1]);
+        if weight <= 0.0 {
+            return None;
+        }
+
+        let base_color = self.base_color.value(rec.texture_uv, &rec.p);
+        let (wi, lobe_weight) = self.sample_lobe(lobe, base_color, wo, rec.is_front_face, rng)?;
+
+        Some(ScatterRecord {
+            attenuation: total_weight * lobe_weight,
+            scattered: Scattered::Ray(Ray::new(rec.p, onb.to_world(wi), r_in.time())),
         })
     }
 
+    fn emitted(&self, uv: Point2d, p: &Point3) -> Color {
+        self.emission.value(uv, p)
+    }
+
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.base_color.value(uv, p)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+pub(crate) fn luminance(color: Color) -> f32 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// Shades surfaces by how much of the hemisphere above them is blocked by nearby geometry, for
+/// ambient occlusion passes.
+///
+/// Each hit fires `samples` cosine weighted rays and blends from `background_color` to
+/// `occluded_color` by the fraction of them that hit something within `max_distance`. Paths end
+/// at the surface, so any other lighting in the scene is ignored.
+#[derive(Debug, Clone, Constructor)]
+pub struct AmbientOcclusion {
+    samples: u32,
+    max_distance: f32,
+    background_color: Color,
+    occluded_color: Color,
+}
+
+impl AmbientOcclusion {
+    pub fn shade<H: Hittable + ?Sized>(
+        &self,
+        world: &H,
+        rec: &HitRecord,
+        time: f32,
+        rng: &mut ActiveRng,
+    ) -> Color {
+        let samples = self.samples.max(1);
+
+        let occluded = (0..samples)
+            .filter(|_| {
+                let mut direction = rec.normal + Vec3::random_unit_vector(rng);
+                if direction.is_near_zero() {
+                    direction = rec.normal;
+                }
+                let direction = direction.unit_vector();
+
+                // With a unit direction, t is the distance along the ray.
+                let ray = Ray::new(rec.p, direction, time);
+                world.hit(&ray, 0.001, self.max_distance, rng).is_some()
+            })
+            .count();
+
+        let fraction = occluded as f32 / samples as f32;
+        self.background_color + fraction * (self.occluded_color - self.background_color)
+    }
+}
+
+impl Material for AmbientOcclusion {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _rng: &mut ActiveRng,
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
     fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
         emit_black()
     }
+
+    fn albedo(&self, _uv: Point2d, _p: &Point3) -> Color {
+        self.background_color
+    }
+
+    fn ambient_occlusion(
+        &self,
+        _rec: &HitRecord,
+        _rng: &mut ActiveRng,
+    ) -> Option<&AmbientOcclusion> {
+        Some(self)
+    }
+}
+
+/// A self-lit card for matte paintings: the camera sees the texture as is, unaffected by scene
+/// lighting, while it stays invisible to every other ray. It thus occludes and is occluded by
+/// geometry in front of the camera, but casts no shadows, shows up in no reflections and does
+/// not light up its surroundings.
+#[derive(Debug, Clone, Constructor)]
+pub struct MattePainting<T: Texture> {
+    texture: T,
+}
+
+impl<T: Texture> Material for MattePainting<T> {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _rng: &mut ActiveRng,
+    ) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, uv: Point2d, p: &Point3) -> Color {
+        self.texture.value(uv, p)
+    }
+
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.texture.value(uv, p)
+    }
+
+    fn is_visible_to_secondary_rays(&self, _rec: &HitRecord, _rng: &mut ActiveRng) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Constructor)]
@@ -152,21 +820,344 @@ pub struct Isotropic<T: Texture> {
 }
 
 impl<T: Texture> Material for Isotropic<T> {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<Scatter> {
-        let attenuation = self.albedo.value(rec.texture_uv, &rec.p);
-        let scattered_ray = Ray::new(rec.p, Vec3::random_in_unit_sphere(rng), r_in.time());
-
-        Some(Scatter {
-            attenuation,
-            scattered_ray,
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo.value(rec.texture_uv, &rec.p),
+            scattered: Scattered::Pdf(MaterialPdf::Sphere(SpherePdf)),
         })
     }
 
     fn emitted(&self, _uv: Point2d, _p: &Point3) -> Color {
         emit_black()
     }
+
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.albedo.value(uv, p)
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// Two materials blended by a mask, e.g. mostly diffuse with a bit of mirror. Where the mask is
+/// black, the surface is `a`, where it is white `b`. In between, every hit picks one of them at
+/// random, `b` with the luminance of the mask as probability.
+///
+/// The renderer asks a material for its density of directions after the fact, which this one
+/// couldn't answer for whichever material it picked. Directions are thus always picked here, so
+/// light sampling doesn't apply to mixed surfaces.
+#[derive(Debug, Clone, Constructor)]
+pub struct MixMaterial<T: Texture> {
+    a: Box<dyn Material>,
+    b: Box<dyn Material>,
+    mask: T,
+}
+
+impl<T: Texture> MixMaterial<T> {
+    /// The probability of picking `b`.
+    fn weight_b(&self, uv: Point2d, p: &Point3) -> f32 {
+        luminance(self.mask.value(uv, p)).clamp(0.0, 1.0)
+    }
+
+    fn mix(&self, uv: Point2d, p: &Point3, a: Color, b: Color) -> Color {
+        let weight_b = self.weight_b(uv, p);
+        (1.0 - weight_b) * a + weight_b * b
+    }
+
+    /// Picks one of the two materials for the hit `rec`, `b` with probability
+    /// [`weight_b`](Self::weight_b).
+    fn pick(&self, rec: &HitRecord, rng: &mut ActiveRng) -> &dyn Material {
+        if rng.gen::<f32>() < self.weight_b(rec.texture_uv, &rec.p) {
+            self.b.as_ref()
+        } else {
+            self.a.as_ref()
+        }
+    }
+}
+
+impl<T: Texture> Material for MixMaterial<T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut ActiveRng) -> Option<ScatterRecord> {
+        let material = self.pick(rec, rng);
+        let scatter = material.scatter(r_in, rec, rng)?;
+        let pdf = match scatter.scattered {
+            Scattered::Ray(_) => return Some(scatter),
+            Scattered::Pdf(pdf) => pdf,
+        };
+
+        let scattered_ray = Ray::new(rec.p, pdf.generate(rng), r_in.time());
+        let pdf_value = pdf.value(&scattered_ray.direction(), rng);
+        if pdf_value <= 0.0 {
+            return None;
+        }
+
+        let scattering_pdf = material.scattering_pdf(r_in, rec, &scattered_ray);
+        Some(ScatterRecord {
+            attenuation: scatter.attenuation * scattering_pdf / pdf_value,
+            scattered: Scattered::Ray(scattered_ray),
+        })
+    }
+
+    fn emitted(&self, uv: Point2d, p: &Point3) -> Color {
+        self.mix(uv, p, self.a.emitted(uv, p), self.b.emitted(uv, p))
+    }
+
+    fn emitted_with_face(&self, uv: Point2d, p: &Point3, is_front_face: bool) -> Color {
+        self.mix(
+            uv,
+            p,
+            self.a.emitted_with_face(uv, p, is_front_face),
+            self.b.emitted_with_face(uv, p, is_front_face),
+        )
+    }
+
+    fn albedo(&self, uv: Point2d, p: &Point3) -> Color {
+        self.mix(uv, p, self.a.albedo(uv, p), self.b.albedo(uv, p))
+    }
+
+    fn is_visible_to_secondary_rays(&self, rec: &HitRecord, rng: &mut ActiveRng) -> bool {
+        self.pick(rec, rng).is_visible_to_secondary_rays(rec, rng)
+    }
+
+    fn ambient_occlusion(&self, rec: &HitRecord, rng: &mut ActiveRng) -> Option<&AmbientOcclusion> {
+        self.pick(rec, rng).ambient_occlusion(rec, rng)
+    }
 }
 
 fn emit_black() -> Color {
     Color::new(0.0, 0.0, 0.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{rectangular::XZRectangle, spherical::Sphere};
+
+    const SAMPLES: u32 = 100_000;
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    /// A ray arriving at the origin from `theta_degrees` off the `+z` normal.
+    fn incoming(theta_degrees: f32) -> Ray {
+        let theta = theta_degrees.to_radians();
+        let direction = Vec3::new(theta.sin(), 0.0, -theta.cos());
+        Ray::new(-direction, direction, 0.0)
+    }
+
+    fn hit(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            material,
+            1.0,
+            Point2d::new(0.0, 0.0),
+            true,
+        )
+    }
+
+    /// The fraction of the light arriving along `r_in` that `material` scatters, integrated over
+    /// the hemisphere by sampling it the way the renderer does.
+    fn directional_albedo(material: &dyn Material, r_in: &Ray) -> Color {
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let rec = hit(material);
+        let mut total = Color::new(0.0, 0.0, 0.0);
+
+        for _ in 0..SAMPLES {
+            let Some(scatter) = material.scatter(r_in, &rec, &mut rng) else {
+                continue;
+            };
+            total += match scatter.scattered {
+                Scattered::Ray(_) => scatter.attenuation,
+                Scattered::Pdf(pdf) => {
+                    let direction = pdf.generate(&mut rng);
+                    let scattered = Ray::new(rec.p, direction, r_in.time());
+                    scatter.attenuation * material.scattering_pdf(r_in, &rec, &scattered)
+                        / pdf.value(&direction, &mut rng)
+                }
+            };
+        }
+
+        total / SAMPLES as f32
+    }
+
+    #[track_caller]
+    fn assert_conserves_energy(material: &dyn Material) {
+        for theta in [0.0, 30.0, 60.0, 85.0] {
+            let albedo = directional_albedo(material, &incoming(theta));
+            assert!(
+                albedo.max_component() <= 1.0,
+                "{:?} reflects {} of the light arriving at {}°",
+                material,
+                albedo,
+                theta
+            );
+        }
+    }
+
+    #[test]
+    fn rough_conductor_conserves_energy() {
+        for roughness in [0.05, 0.3, 0.7, 1.0] {
+            for anisotropy in [0.0, 0.8] {
+                assert_conserves_energy(&RoughConductor::new(white(), roughness, anisotropy));
+            }
+        }
+    }
+
+    #[test]
+    fn rough_conductor_without_roughness_is_a_mirror() {
+        let material = RoughConductor::new(white(), 0.0, 0.0);
+        let r_in = incoming(45.0);
+        let mirrored = Vec3::new(r_in.direction().x(), 0.0, -r_in.direction().z()).unit_vector();
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        for _ in 0..100 {
+            let scatter = material.scatter(&r_in, &hit(&material), &mut rng).unwrap();
+            let Scattered::Ray(scattered) = scatter.scattered else {
+                panic!("RoughConductor leaves the direction to the renderer");
+            };
+            assert!(scattered.direction().unit_vector().dot(&mirrored) > 0.999);
+            assert!((scatter.attenuation.x() - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn oren_nayar_conserves_energy() {
+        for sigma_degrees in [0.0, 20.0, 45.0, 90.0] {
+            assert_conserves_energy(&OrenNayar::new(SolidColor::new(white()), sigma_degrees));
+        }
+    }
+
+    #[test]
+    fn oren_nayar_without_roughness_matches_lambertian() {
+        let oren_nayar = OrenNayar::new(SolidColor::new(white()), 0.0);
+        let lambertian = Lambertian::new_solid_color(white());
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        for theta in [0.0, 30.0, 60.0, 85.0] {
+            let r_in = incoming(theta);
+            for _ in 0..100 {
+                let scattered = Ray::new(
+                    Point3::new(0.0, 0.0, 0.0),
+                    Vec3::random_unit_vector(&mut rng),
+                    0.0,
+                );
+                assert_eq!(
+                    oren_nayar.scattering_pdf(&r_in, &hit(&oren_nayar), &scattered),
+                    lambertian.scattering_pdf(&r_in, &hit(&lambertian), &scattered),
+                    "light arriving at {}° and leaving along {}",
+                    theta,
+                    scattered.direction()
+                );
+            }
+        }
+    }
+
+    fn gray(level: f32) -> SolidColor {
+        SolidColor::new(Color::new(level, level, level))
+    }
+
+    /// How often out of `SAMPLES` hits `is_visible` holds.
+    fn fraction(
+        material: &dyn Material,
+        is_visible: impl Fn(&HitRecord, &mut ActiveRng) -> bool,
+    ) -> f32 {
+        let mut rng = ActiveRng::seed_from_u64(1);
+        let rec = hit(material);
+        let count = (0..SAMPLES).filter(|_| is_visible(&rec, &mut rng)).count();
+        count as f32 / SAMPLES as f32
+    }
+
+    #[test]
+    fn mix_material_forwards_secondary_visibility_by_mask() {
+        for (mask, expected) in [(0.0, 1.0), (0.5, 0.5), (1.0, 0.0)] {
+            let material = MixMaterial::new(
+                Box::new(Lambertian::new(gray(0.5))),
+                Box::new(MattePainting::new(gray(0.5))),
+                gray(mask),
+            );
+
+            let visible = fraction(&material, |rec, rng| {
+                material.is_visible_to_secondary_rays(rec, rng)
+            });
+            assert!(
+                (visible - expected).abs() < 0.01,
+                "mask {}: {}",
+                mask,
+                visible
+            );
+        }
+    }
+
+    #[test]
+    fn mix_material_forwards_ambient_occlusion_by_mask() {
+        for (mask, expected) in [(0.0, 1.0), (0.25, 0.75), (1.0, 0.0)] {
+            let material = MixMaterial::new(
+                Box::new(AmbientOcclusion::new(
+                    16,
+                    1.0,
+                    white(),
+                    Color::new(0.0, 0.0, 0.0),
+                )),
+                Box::new(Lambertian::new(gray(0.5))),
+                gray(mask),
+            );
+
+            let shaded = fraction(&material, |rec, rng| {
+                material.ambient_occlusion(rec, rng).is_some()
+            });
+            assert!(
+                (shaded - expected).abs() < 0.01,
+                "mask {}: {}",
+                mask,
+                shaded
+            );
+        }
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_the_ground_under_a_sphere() {
+        let material = AmbientOcclusion::new(4096, 10.0, white(), Color::new(0.0, 0.0, 0.0));
+        let world: Vec<Box<dyn Hittable>> = vec![
+            Box::new(XZRectangle::new(
+                -100.0,
+                100.0,
+                -100.0,
+                100.0,
+                0.0,
+                Box::new(material.clone()),
+            )),
+            Box::new(Sphere::new(
+                Point3::new(0.0, 1.5, 0.0),
+                1.0,
+                Box::new(material.clone()),
+            )),
+        ];
+        let mut rng = ActiveRng::seed_from_u64(1);
+
+        let mut shade = |p: Point3| {
+            let rec = HitRecord::new(
+                p,
+                Vec3::new(0.0, 1.0, 0.0),
+                &material,
+                1.0,
+                Point2d::new(0.0, 0.0),
+                true,
+            );
+            material.shade(&world, &rec, 0.0, &mut rng).x()
+        };
+
+        let under = shade(Point3::new(0.0, 0.0, 0.0));
+        let far_away = shade(Point3::new(50.0, 0.0, 50.0));
+
+        // Seen from under its center, the sphere blocks sin² of the cone's half angle, 4/9, of the
+        // cosine weighted hemisphere.
+        assert!(
+            (under - 5.0 / 9.0).abs() < 0.03,
+            "under the sphere: {}",
+            under
+        );
+        assert_eq!(far_away, 1.0);
+    }
+}
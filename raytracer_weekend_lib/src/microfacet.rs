@@ -0,0 +1,166 @@
+use core::f32::consts::PI;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::vec3::{Color, Vec3};
+
+/// Below this, the distribution degenerates numerically. Perfect mirrors are approximated instead.
+const MIN_ALPHA: f32 = 1.0e-4;
+
+/// The GGX (Trowbridge-Reitz) microfacet distribution with Smith shadowing-masking.
+///
+/// All directions are expected in a local frame where the macro surface normal is `+z`, see
+/// [`crate::onb::OrthonormalBase`].
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Ggx {
+    /// `roughness` is perceptual, i.e. squared to get alpha. `anisotropy` in `0.0..=1.0` stretches
+    /// the highlight along the local x axis.
+    pub fn new(roughness: f32, anisotropy: f32) -> Self {
+        let alpha = roughness * roughness;
+        let aspect = (1.0 - 0.9 * anisotropy).sqrt();
+
+        Self {
+            alpha_x: (alpha / aspect).max(MIN_ALPHA),
+            alpha_y: (alpha * aspect).max(MIN_ALPHA),
+        }
+    }
+
+    /// The normal distribution function D(m).
+    pub fn distribution(&self, m: Vec3) -> f32 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let denominator = x * x + y * y + m.z() * m.z();
+
+        1.0 / (PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos_squared = w.z() * w.z();
+        if cos_squared == 0.0 {
+            return f32::INFINITY;
+        }
+
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        let tan_squared_alpha = (x * x + y * y) / cos_squared;
+
+        ((1.0 + tan_squared_alpha).sqrt() - 1.0) / 2.0
+    }
+
+    /// Smith masking for a single direction.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated Smith shadowing-masking for a pair of directions.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from the normals visible from `wo`, following Heitz, "Sampling
+    /// the GGX Distribution of Visible Normals" (2018). `u1` and `u2` are uniform in `0.0..1.0`.
+    pub fn sample_visible_normal(&self, wo: Vec3, u1: f32, u2: f32) -> Vec3 {
+        // Stretch the view direction so the distribution becomes the hemisphere.
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vector();
+
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / length_squared.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Sample the projected area of the visible hemisphere.
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * p2;
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // Unstretch back into the ellipsoid.
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(0.0),
+        )
+        .unit_vector()
+    }
+
+    /// Density of [`Ggx::sample_visible_normal`] returning `m` when seen from `wo`.
+    pub fn visible_normal_pdf(&self, wo: Vec3, m: Vec3) -> f32 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+
+        self.g1(wo) * wo.dot(&m).max(0.0) * self.distribution(m) / wo.z()
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance, with the color at normal incidence `f0`.
+pub fn schlick_fresnel(f0: Color, cos_theta: f32) -> Color {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + weight * (Color::new(1.0, 1.0, 1.0) - f0)
+}
+
+/// The exact unpolarised Fresnel reflectance of a dielectric interface. `eta` is the ratio of the
+/// refractive index on the far side to that on the incident side.
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin_squared_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin_squared_theta_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+
+    let cos_theta_t = (1.0 - sin_squared_theta_t).sqrt();
+    let r_s = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    let r_p = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+
+    0.5 * (r_s * r_s + r_p * r_p)
+}
+
+/// The Schlick Fresnel weight `(1 - cos_theta)^5`, for blending between values at normal and
+/// grazing incidence.
+pub fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+/// The GTR1 (Berry) distribution used for the clearcoat lobe of the Disney BRDF.
+#[derive(Debug, Clone, Copy)]
+pub struct Gtr1 {
+    alpha: f32,
+}
+
+impl Gtr1 {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.max(MIN_ALPHA),
+        }
+    }
+
+    /// Samples a microfacet normal with density `D(m) * cos(theta_m)`.
+    pub fn sample_normal(&self, u1: f32, u2: f32) -> Vec3 {
+        let alpha_squared = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - alpha_squared.powf(1.0 - u1)) / (1.0 - alpha_squared))
+            .clamp(0.0, 1.0)
+            .sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
@@ -0,0 +1,98 @@
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+use crate::vec3::Vec3;
+
+/// An orthonormal basis whose `w` axis points along a given direction, usually a surface normal.
+///
+/// Converting into this basis lets BRDFs work in a local frame where the normal is `+z`.
+#[derive(Debug, Clone, Copy)]
+pub struct OrthonormalBase {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl OrthonormalBase {
+    pub fn from_w(w: &Vec3) -> Self {
+        let w = w.unit_vector();
+        let helper = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        // The helper axis, made perpendicular to w.
+        let u = -helper.reject_from(&w).unit_vector();
+        let v = u.cross(&w);
+
+        Self { u, v, w }
+    }
+
+    /// The axis the base was built around.
+    pub fn w(self) -> Vec3 {
+        self.w
+    }
+
+    pub fn to_local(self, world: Vec3) -> Vec3 {
+        Vec3::new(world.dot(&self.u), world.dot(&self.v), world.dot(&self.w))
+    }
+
+    pub fn to_world(self, local: Vec3) -> Vec3 {
+        local.x() * self.u + local.y() * self.v + local.z() * self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::assert_near;
+
+    #[track_caller]
+    fn assert_orthonormal(base: OrthonormalBase, w: Vec3) {
+        let axes = [base.u, base.v, base.w];
+        for (i, a) in axes.iter().enumerate() {
+            assert!((a.length() - 1.0).abs() < 1e-5, "{:?}", base);
+            for b in &axes[i + 1..] {
+                assert!(a.dot(b).abs() < 1e-5, "{:?}", base);
+            }
+        }
+
+        assert_near(base.w(), w.unit_vector());
+        assert_near(base.u.cross(&base.v), -base.w);
+    }
+
+    #[test]
+    fn from_w_is_orthonormal() {
+        let directions = [
+            Vec3::new(0.3, -0.4, 1.2),
+            Vec3::new(-2.0, 0.5, 0.1),
+            Vec3::new(0.9, 0.4359, 0.0),
+        ];
+
+        for w in directions {
+            assert_orthonormal(OrthonormalBase::from_w(&w), w);
+        }
+    }
+
+    #[test]
+    fn from_w_handles_axis_parallel_directions() {
+        // The x axis is parallel to the helper axis used for most directions.
+        for axis in 0..3 {
+            for sign in [1.0, -1.0] {
+                let mut w = Vec3::new(0.0, 0.0, 0.0);
+                w[axis] = 2.0 * sign;
+
+                assert_orthonormal(OrthonormalBase::from_w(&w), w);
+            }
+        }
+    }
+
+    #[test]
+    fn to_world_undoes_to_local() {
+        let base = OrthonormalBase::from_w(&Vec3::new(0.3, -0.4, 1.2));
+        let v = Vec3::new(1.5, -2.0, 0.7);
+
+        assert_near(base.to_world(base.to_local(v)), v);
+        assert_near(base.to_local(base.w()), Vec3::new(0.0, 0.0, 1.0));
+    }
+}
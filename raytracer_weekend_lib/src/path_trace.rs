@@ -0,0 +1,73 @@
+//! Records of the path a single sample took through the scene, for finding out where fireflies
+//! and other oddities come from. See [`Raytracer::trace_pixel`].
+//!
+//! [`Raytracer::trace_pixel`]: crate::Raytracer::trace_pixel
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    hittable::HitRecord,
+    vec3::{Color, Point3},
+};
+
+/// One step along a traced path.
+#[derive(Debug, Clone)]
+pub enum PathEvent {
+    /// The path hit a surface or volume.
+    Hit {
+        /// How often the path had bounced before, zero for the camera ray.
+        bounce: usize,
+        point: Point3,
+        /// See [`Named`](crate::hittable::named::Named).
+        object_name: Option<String>,
+        /// See [`Material::type_name`](crate::material::Material::type_name).
+        material: &'static str,
+        /// The light emitted back along the path, already weighted for next event estimation.
+        /// Ambient occlusion materials put their shade here.
+        emitted: Color,
+        /// What the light arriving along the next segment is multiplied by, Russian roulette
+        /// included. `None` if the path ends here.
+        attenuation: Option<Color>,
+        /// The density the direction of the next segment was picked with. `None` for directions
+        /// the material fixes, like a mirror's, and for paths ending here.
+        pdf: Option<f32>,
+    },
+    /// The path left the scene.
+    Background { bounce: usize, radiance: Color },
+}
+
+/// Where the renderer writes down the path it follows, if anyone asked for it.
+pub(crate) type Recorder<'a> = Option<&'a mut Vec<PathEvent>>;
+
+pub(crate) fn record_hit(recorder: &mut Recorder, bounce: usize, hit: &HitRecord, emitted: Color) {
+    if let Some(events) = recorder {
+        events.push(PathEvent::Hit {
+            bounce,
+            point: hit.p,
+            object_name: hit.object_name.map(String::from),
+            material: hit.material.type_name(),
+            emitted,
+            attenuation: None,
+            pdf: None,
+        });
+    }
+}
+
+/// Fills in how the path continues from the hit recorded last.
+pub(crate) fn record_continuation(recorder: &mut Recorder, attenuation: Color, pdf: Option<f32>) {
+    if let Some(PathEvent::Hit {
+        attenuation: recorded_attenuation,
+        pdf: recorded_pdf,
+        ..
+    }) = recorder.as_mut().and_then(|events| events.last_mut())
+    {
+        *recorded_attenuation = Some(attenuation);
+        *recorded_pdf = pdf;
+    }
+}
+
+pub(crate) fn record_background(recorder: &mut Recorder, bounce: usize, radiance: Color) {
+    if let Some(events) = recorder {
+        events.push(PathEvent::Background { bounce, radiance });
+    }
+}
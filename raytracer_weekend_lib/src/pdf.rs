@@ -0,0 +1,314 @@
+use core::f32::consts::PI;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+use rand::Rng;
+
+use crate::{
+    hittable::Hittable,
+    onb::OrthonormalBase,
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// A distribution of directions, for picking where to send scattered rays.
+pub trait Pdf {
+    /// The density, with respect to solid angle, with which `generate` produces `direction`.
+    fn value(&self, direction: &Vec3, rng: &mut ActiveRng) -> f32;
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3;
+}
+
+impl<P: Pdf + ?Sized> Pdf for &P {
+    fn value(&self, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        (*self).value(direction, rng)
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        (*self).generate(rng)
+    }
+}
+
+/// Cosine weighted directions around `w`, as scattered by perfectly diffuse surfaces.
+#[derive(Debug, Clone, Copy)]
+pub struct CosinePdf {
+    uvw: OrthonormalBase,
+}
+
+impl CosinePdf {
+    pub fn new(w: &Vec3) -> Self {
+        Self {
+            uvw: OrthonormalBase::from_w(w),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3, _rng: &mut ActiveRng) -> f32 {
+        let cosine = self.uvw.to_local(direction.unit_vector()).z();
+        cosine.max(0.0) / PI
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        self.uvw.to_world(Vec3::random_cosine_direction(rng))
+    }
+}
+
+/// Uniformly distributed directions on the hemisphere around `w`. Noisier than [`CosinePdf`] for
+/// diffuse surfaces, but trivially correct, which makes it useful for reference renders.
+#[derive(Debug, Clone, Copy)]
+pub struct HemispherePdf {
+    uvw: OrthonormalBase,
+}
+
+impl HemispherePdf {
+    pub fn new(w: &Vec3) -> Self {
+        Self {
+            uvw: OrthonormalBase::from_w(w),
+        }
+    }
+}
+
+impl Pdf for HemispherePdf {
+    fn value(&self, direction: &Vec3, _rng: &mut ActiveRng) -> f32 {
+        match self.uvw.to_local(*direction).z() > 0.0 {
+            true => 1.0 / (2.0 * PI),
+            false => 0.0,
+        }
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        let direction = Vec3::random_unit_vector(rng);
+        let local = self.uvw.to_local(direction);
+
+        match local.z() < 0.0 {
+            true => -direction,
+            false => direction,
+        }
+    }
+}
+
+/// Uniformly distributed directions, as scattered by isotropic volumes.
+#[derive(Debug, Clone, Copy)]
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3, _rng: &mut ActiveRng) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        Vec3::random_unit_vector(rng)
+    }
+}
+
+/// Directions from `origin` towards a hittable, usually a light or a list of lights.
+#[derive(Debug)]
+pub struct HittablePdf<'a, H: Hittable + ?Sized> {
+    hittable: &'a H,
+    origin: Point3,
+}
+
+impl<'a, H: Hittable + ?Sized> HittablePdf<'a, H> {
+    pub fn new(hittable: &'a H, origin: Point3) -> Self {
+        Self { hittable, origin }
+    }
+}
+
+impl<'a, H: Hittable + ?Sized> Pdf for HittablePdf<'a, H> {
+    fn value(&self, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.hittable.pdf_value(&self.origin, direction, rng)
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        self.hittable.random_point_towards(&self.origin, rng) - self.origin
+    }
+}
+
+/// Picks directions from `first` with probability `weight` and from `second` otherwise.
+#[derive(Debug)]
+pub struct MixturePdf<A: Pdf, B: Pdf> {
+    first: A,
+    second: B,
+    weight: f32,
+}
+
+impl<A: Pdf, B: Pdf> MixturePdf<A, B> {
+    pub fn new(first: A, second: B, weight: f32) -> Self {
+        assert!((0.0..=1.0).contains(&weight));
+
+        Self {
+            first,
+            second,
+            weight,
+        }
+    }
+}
+
+impl<A: Pdf, B: Pdf> Pdf for MixturePdf<A, B> {
+    fn value(&self, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        self.weight * self.first.value(direction, rng)
+            + (1.0 - self.weight) * self.second.value(direction, rng)
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        if rng.gen::<f32>() < self.weight {
+            self.first.generate(rng)
+        } else {
+            self.second.generate(rng)
+        }
+    }
+}
+
+/// How to split the contribution of a direction between two sampling strategies that could both
+/// have picked it. Every heuristic keeps the combination unbiased.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MisWeight {
+    /// Proportional to the densities.
+    #[default]
+    Balance,
+    /// Proportional to the densities raised to the given exponent, which favours the stronger
+    /// strategy more than [`MisWeight::Balance`]. An exponent of 2 is the usual choice.
+    Power(f32),
+    /// An equal share for every strategy that could have picked the direction at all, regardless
+    /// of how likely it was to.
+    OneHeuristic,
+}
+
+/// The share of a sample drawn with density `pdf_a` that strategy `a` accounts for, when strategy
+/// `b` would have picked the same direction with density `pdf_b`.
+pub fn mis_weight(pdf_a: f32, pdf_b: f32, mode: MisWeight) -> f32 {
+    let (a, b) = match mode {
+        MisWeight::Balance => (pdf_a, pdf_b),
+        MisWeight::Power(exponent) => (pdf_a.powf(exponent), pdf_b.powf(exponent)),
+        MisWeight::OneHeuristic => (
+            f32::from(u8::from(pdf_a > 0.0)),
+            f32::from(u8::from(pdf_b > 0.0)),
+        ),
+    };
+
+    match a + b > 0.0 {
+        true => a / (a + b),
+        false => 0.0,
+    }
+}
+
+/// The distributions materials can hand to the renderer, without boxing them up per sample.
+#[derive(Debug, Clone, Copy)]
+pub enum MaterialPdf {
+    Cosine(CosinePdf),
+    Hemisphere(HemispherePdf),
+    Sphere(SpherePdf),
+}
+
+impl Pdf for MaterialPdf {
+    fn value(&self, direction: &Vec3, rng: &mut ActiveRng) -> f32 {
+        match self {
+            MaterialPdf::Cosine(pdf) => pdf.value(direction, rng),
+            MaterialPdf::Hemisphere(pdf) => pdf.value(direction, rng),
+            MaterialPdf::Sphere(pdf) => pdf.value(direction, rng),
+        }
+    }
+
+    fn generate(&self, rng: &mut ActiveRng) -> Vec3 {
+        match self {
+            MaterialPdf::Cosine(pdf) => pdf.generate(rng),
+            MaterialPdf::Hemisphere(pdf) => pdf.generate(rng),
+            MaterialPdf::Sphere(pdf) => pdf.generate(rng),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [MisWeight; 3] = [
+        MisWeight::Balance,
+        MisWeight::Power(2.0),
+        MisWeight::OneHeuristic,
+    ];
+
+    #[test]
+    fn equal_pdfs_split_a_lambertian_sample_evenly() {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        let lambertian = CosinePdf::new(&Vec3::new(0.0, 1.0, 0.0));
+
+        for _ in 0..100 {
+            let direction = lambertian.generate(&mut rng);
+            let pdf = lambertian.value(&direction, &mut rng);
+            assert!(pdf > 0.0);
+
+            // E.g. a light that happens to be as likely to pick the direction as the surface.
+            assert_eq!(mis_weight(pdf, pdf, MisWeight::Balance), 0.5);
+            assert_eq!(mis_weight(pdf, pdf, MisWeight::Power(2.0)), 0.5);
+            assert_eq!(mis_weight(pdf, pdf, MisWeight::OneHeuristic), 0.5);
+        }
+    }
+
+    #[test]
+    fn weights_of_both_strategies_add_up_to_one() {
+        for mode in MODES {
+            for (a, b) in [(0.1, 2.0), (1.0 / PI, 0.25), (3.0, 0.0)] {
+                let sum = mis_weight(a, b, mode) + mis_weight(b, a, mode);
+                assert!((sum - 1.0).abs() < 1e-6, "{:?} sums to {}", mode, sum);
+            }
+        }
+    }
+
+    #[test]
+    fn balance_weight_is_proportional_to_the_pdfs() {
+        assert_eq!(mis_weight(3.0, 1.0, MisWeight::Balance), 0.75);
+        assert_eq!(mis_weight(3.0, 1.0, MisWeight::Power(2.0)), 0.9);
+        assert_eq!(mis_weight(3.0, 1.0, MisWeight::OneHeuristic), 0.5);
+    }
+
+    #[test]
+    fn a_strategy_that_cannot_pick_the_direction_gets_nothing() {
+        for mode in MODES {
+            assert_eq!(mis_weight(0.0, 1.0, mode), 0.0);
+            assert_eq!(mis_weight(1.0, 0.0, mode), 1.0);
+            assert_eq!(mis_weight(0.0, 0.0, mode), 0.0);
+        }
+    }
+
+    #[test]
+    fn cosine_pdf_integrates_to_one_over_the_sphere() {
+        const SAMPLES: u32 = 100_000;
+        let mut rng = ActiveRng::seed_from_u64(0);
+        let cosine = CosinePdf::new(&Vec3::new(1.0, 2.0, -0.5));
+
+        // Monte Carlo integration with uniformly distributed directions.
+        let integral = (0..SAMPLES)
+            .map(|_| {
+                let direction = SpherePdf.generate(&mut rng);
+                cosine.value(&direction, &mut rng) / SpherePdf.value(&direction, &mut rng)
+            })
+            .sum::<f32>()
+            / SAMPLES as f32;
+
+        assert!((integral - 1.0).abs() < 0.01, "integrates to {}", integral);
+    }
+
+    #[test]
+    fn mixture_pdf_is_the_weighted_sum_of_its_parts() {
+        let mut rng = ActiveRng::seed_from_u64(0);
+        let cosine = CosinePdf::new(&Vec3::new(0.0, 1.0, 0.0));
+        let hemisphere = HemispherePdf::new(&Vec3::new(1.0, 0.0, 0.0));
+        let mixture = MixturePdf::new(cosine, hemisphere, 0.3);
+
+        for _ in 0..100 {
+            let direction = SpherePdf.generate(&mut rng);
+            let expected = 0.3 * cosine.value(&direction, &mut rng)
+                + 0.7 * hemisphere.value(&direction, &mut rng);
+            let value = mixture.value(&direction, &mut rng);
+
+            assert!(
+                (value - expected).abs() < 1e-6,
+                "{} instead of {} for {}",
+                value,
+                expected,
+                direction
+            );
+        }
+    }
+}
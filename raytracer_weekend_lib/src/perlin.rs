@@ -58,15 +58,9 @@ impl Perlin {
             for (y_offset, row) in aisle.iter_mut().enumerate() {
                 for (z_offset, cell) in row.iter_mut().enumerate() {
                     let offset = GenericVec3::new(x_offset, y_offset, z_offset);
-                    let current_lattice_point =
-                        base_point_on_lattice.overflowing_add(offset).0 & 255;
+                    let current_lattice_point = base_point_on_lattice.overflowing_add(offset).0;
 
-                    let hash = self
-                        .permutations
-                        .get(&current_lattice_point)
-                        .internal_bit_xor();
-
-                    *cell = self.gradients[hash];
+                    *cell = self.lattice_gradient(current_lattice_point);
                 }
             }
         }
@@ -74,6 +68,17 @@ impl Perlin {
         Self::perlin_interp(gradient_cube, point_within_lattice_cell)
     }
 
+    /// The random unit gradient assigned to a lattice point. The lattice repeats every
+    /// `POINT_COUNT` units along each axis.
+    pub(crate) fn lattice_gradient(&self, lattice_point: GenericVec3<usize>) -> Vec3 {
+        let hash = self
+            .permutations
+            .get(&(lattice_point & (POINT_COUNT - 1)))
+            .internal_bit_xor();
+
+        self.gradients[hash]
+    }
+
     pub fn turbulence(&self, p: &Point3, depth: usize) -> f32 {
         let mut accum = 0.0;
         let mut temp_p = *p;
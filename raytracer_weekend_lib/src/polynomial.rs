@@ -0,0 +1,201 @@
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+
+/// Newton steps run on every quartic root, to make up for the cancellation in the closed form.
+const POLISH_ITERATIONS: usize = 2;
+
+/// The real roots of a polynomial, in no particular order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Roots {
+    values: [f32; 4],
+    len: usize,
+}
+
+impl Roots {
+    fn push(&mut self, root: f32) {
+        self.values[self.len] = root;
+        self.len += 1;
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.values[..self.len]
+    }
+}
+
+/// Real roots of `x^2 + p x + q`. A double root is reported twice.
+fn solve_monic_quadratic(p: f32, q: f32, roots: &mut Roots) {
+    let discriminant = p * p / 4.0 - q;
+    if discriminant < 0.0 {
+        return;
+    }
+
+    let sqrtd = discriminant.sqrt();
+    roots.push(-p / 2.0 - sqrtd);
+    roots.push(-p / 2.0 + sqrtd);
+}
+
+/// The largest real root of `x^3 + a x^2 + b x + c`. A cubic always has at least one.
+fn largest_cubic_root(a: f32, b: f32, c: f32) -> f32 {
+    // Depress the cubic with x = y - a / 3 into y^3 + p y + q.
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let shift = -a / 3.0;
+
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    if discriminant > 0.0 {
+        // One real root, Cardano's formula.
+        let sqrtd = discriminant.sqrt();
+        return (-q / 2.0 + sqrtd).cbrt() + (-q / 2.0 - sqrtd).cbrt() + shift;
+    }
+
+    if p == 0.0 {
+        return shift;
+    }
+
+    // Three real roots, of which the trigonometric form with k = 0 is the largest.
+    let radius = 2.0 * (-p / 3.0).sqrt();
+    let cos_argument = (3.0 * q / (2.0 * p) * (-3.0 / p).sqrt()).clamp(-1.0, 1.0);
+    let angle = cos_argument.acos() / 3.0;
+
+    radius * angle.cos() + shift
+}
+
+/// Real roots of `x^4 + a x^3 + b x^2 + c x + d`, following Ferrari.
+///
+/// The closed form loses precision when the roots are far apart in magnitude, so every root is
+/// polished with a few Newton steps on the original polynomial. Callers still do well to keep the
+/// roots near zero, e.g. by moving a ray's origin close to the object first.
+pub fn solve_monic_quartic(a: f32, b: f32, c: f32, d: f32) -> Roots {
+    // Depress the quartic with x = y - a / 4 into y^4 + p y^2 + q y + r.
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+    let shift = -a / 4.0;
+
+    let mut depressed_roots = Roots::default();
+
+    if q.abs() < 1e-6 {
+        // Biquadratic: solve for y^2 first.
+        let mut squares = Roots::default();
+        solve_monic_quadratic(p, r, &mut squares);
+
+        for &square in squares.as_slice() {
+            if square >= 0.0 {
+                let y = square.sqrt();
+                depressed_roots.push(-y);
+                depressed_roots.push(y);
+            }
+        }
+    } else {
+        // Complete the square with the positive root m of the resolvent cubic
+        // m^3 + p m^2 + (p^2 / 4 - r) m - q^2 / 8, which exists because q is nonzero.
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return Roots::default();
+        }
+
+        let s = (2.0 * m).sqrt();
+        solve_monic_quadratic(-s, p / 2.0 + m + q / (2.0 * s), &mut depressed_roots);
+        solve_monic_quadratic(s, p / 2.0 + m - q / (2.0 * s), &mut depressed_roots);
+    }
+
+    let mut roots = Roots::default();
+    for &y in depressed_roots.as_slice() {
+        roots.push(polish_quartic_root(a, b, c, d, y + shift));
+    }
+
+    roots
+}
+
+fn polish_quartic_root(a: f32, b: f32, c: f32, d: f32, mut x: f32) -> f32 {
+    let evaluate = |x: f32| (((x + a) * x + b) * x + c) * x + d;
+
+    for _ in 0..POLISH_ITERATIONS {
+        let value = evaluate(x);
+        let derivative = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+
+        // Near double roots, like grazing hits, the step can overshoot. Only keep improvements.
+        let polished = x - value / derivative;
+        if !polished.is_finite() || evaluate(polished).abs() >= value.abs() {
+            break;
+        }
+
+        x = polished;
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    /// The coefficients `a, b, c, d` of `(x - r0)(x - r1)(x - r2)(x - r3)`.
+    fn from_roots([r0, r1, r2, r3]: [f32; 4]) -> (f32, f32, f32, f32) {
+        let a = -(r0 + r1 + r2 + r3);
+        let b = r0 * r1 + r0 * r2 + r0 * r3 + r1 * r2 + r1 * r3 + r2 * r3;
+        let c = -(r0 * r1 * r2 + r0 * r1 * r3 + r0 * r2 * r3 + r1 * r2 * r3);
+        let d = r0 * r1 * r2 * r3;
+
+        (a, b, c, d)
+    }
+
+    fn sorted_roots((a, b, c, d): (f32, f32, f32, f32)) -> Vec<f32> {
+        let mut roots = solve_monic_quartic(a, b, c, d).as_slice().to_vec();
+        roots.sort_by(f32::total_cmp);
+        roots
+    }
+
+    #[track_caller]
+    fn assert_roots(coefficients: (f32, f32, f32, f32), expected: &[f32], tolerance: f32) {
+        let roots = sorted_roots(coefficients);
+        assert_eq!(roots.len(), expected.len(), "got roots {:?}", roots);
+        for (root, expected_root) in roots.iter().zip(expected) {
+            assert!(
+                (root - expected_root).abs() <= tolerance,
+                "got roots {:?}, expected {:?}",
+                roots,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn finds_four_distinct_roots() {
+        assert_roots(from_roots([5.0, -1.0, 2.0, -3.0]), &[-3.0, -1.0, 2.0, 5.0], 1e-4);
+        assert_roots(from_roots([0.25, 0.5, 1.5, 3.0]), &[0.25, 0.5, 1.5, 3.0], 1e-4);
+    }
+
+    #[test]
+    fn finds_the_roots_of_biquadratics() {
+        assert_roots(from_roots([-2.0, -1.0, 1.0, 2.0]), &[-2.0, -1.0, 1.0, 2.0], 1e-4);
+    }
+
+    #[test]
+    fn finds_double_roots() {
+        // Double roots are ill conditioned, hence the looser tolerance.
+        assert_roots(from_roots([1.0, 1.0, 3.0, 3.0]), &[1.0, 1.0, 3.0, 3.0], 1e-3);
+        assert_roots(from_roots([4.0, 1.0, 2.0, 1.0]), &[1.0, 1.0, 2.0, 4.0], 1e-3);
+        assert_roots(from_roots([-1.0, 0.5, 0.5, 4.0]), &[-1.0, 0.5, 0.5, 4.0], 1e-3);
+        assert_roots(from_roots([2.0, 2.0, 2.0, 2.0]), &[2.0, 2.0, 2.0, 2.0], 1e-3);
+    }
+
+    #[test]
+    fn finds_the_real_roots_among_complex_ones() {
+        // (x - 1)(x + 2)(x^2 + 1)
+        assert_roots((1.0, -1.0, 1.0, -2.0), &[-2.0, 1.0], 1e-4);
+    }
+
+    #[test]
+    fn finds_no_roots_without_real_ones() {
+        // x^4 + 1
+        assert_roots((0.0, 0.0, 0.0, 1.0), &[], 0.0);
+        // (x^2 + 1)(x^2 + 4)
+        assert_roots((0.0, 5.0, 0.0, 4.0), &[], 0.0);
+        // (x^2 + 2x + 2)(x^2 - 2x + 5)
+        assert_roots((0.0, 3.0, 6.0, 10.0), &[], 0.0);
+    }
+}
@@ -1,15 +1,42 @@
-use derive_more::Constructor;
-
 use super::vec3::{Point3, Vec3};
 
-#[derive(Constructor, Default, Debug)]
+/// What a ray is traced for, so that objects can choose which rays see them. See
+/// [`VisibilityWrapper`](crate::hittable::visibility::VisibilityWrapper).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RayKind {
+    /// Straight from the camera.
+    Camera,
+    /// Scattered off a surface or in a volume.
+    #[default]
+    Bounce,
+    /// Checking whether anything lies between a point and a light.
+    Shadow,
+}
+
+#[derive(Default, Debug)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
     time: f32,
+    kind: RayKind,
 }
 
 impl Ray {
+    /// A [`RayKind::Bounce`] ray, which is what most rays are.
+    pub fn new(origin: Point3, direction: Vec3, time: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            kind: RayKind::Bounce,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     pub fn origin(&self) -> Point3 {
         self.origin
     }
@@ -22,6 +49,10 @@ impl Ray {
         self.time
     }
 
+    pub fn kind(&self) -> RayKind {
+        self.kind
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.origin + t * self.direction
     }
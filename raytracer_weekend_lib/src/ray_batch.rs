@@ -0,0 +1,61 @@
+//! Tracing bare camera rays, to check intersection code against other renderers.
+//!
+//! [`Raytracer::primary_rays`](crate::Raytracer::primary_rays) produces the rays, and
+//! [`trace_ray_batch`] finds what they hit first, without any shading.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    hittable::Hittable,
+    ray::{Ray, RayKind},
+    vec3::{Point3, Vec3},
+    ActiveRng,
+};
+
+/// One camera ray, along with the pixel and sample it belongs to.
+#[derive(Debug, Clone)]
+pub struct PrimaryRay {
+    pub row: u32,
+    pub column: u32,
+    pub sample: u32,
+    pub origin: Point3,
+    pub direction: Vec3,
+    pub time: f32,
+}
+
+impl PrimaryRay {
+    fn ray(&self) -> Ray {
+        Ray::new(self.origin, self.direction, self.time).with_kind(RayKind::Camera)
+    }
+}
+
+/// Where a ray first hits the world.
+#[derive(Debug, Clone)]
+pub struct FirstHit {
+    pub t: f32,
+    pub p: Point3,
+    /// The surface normal, flipped to face against the ray.
+    pub normal: Vec3,
+    pub is_front_face: bool,
+}
+
+/// Finds the first hit of every ray in `rays`, in the same order, with the same minimum distance
+/// as the renderer uses.
+pub fn trace_ray_batch(
+    world: &[Box<dyn Hittable>],
+    rays: &[PrimaryRay],
+    rng: &mut ActiveRng,
+) -> Vec<Option<FirstHit>> {
+    rays.iter()
+        .map(|ray| {
+            world
+                .hit(&ray.ray(), 0.001, f32::INFINITY, rng)
+                .map(|hit| FirstHit {
+                    t: hit.t,
+                    p: hit.p,
+                    normal: hit.normal,
+                    is_front_face: hit.is_front_face,
+                })
+        })
+        .collect()
+}
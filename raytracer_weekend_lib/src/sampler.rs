@@ -0,0 +1,365 @@
+use alloc::{format, string::String};
+use core::str::FromStr;
+
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where in a pixel the camera rays of its samples go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SamplerKind {
+    /// Anywhere, independently of each other. See [`UniformSampler`].
+    #[default]
+    Uniform,
+    /// Spread evenly over the pixel. See [`StratifiedSampler`].
+    Stratified,
+    /// Along a low-discrepancy sequence. See [`HaltonSampler`].
+    Halton,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "uniform" => Ok(Self::Uniform),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            _ => Err(format!(
+                "unknown sampler {:?}, expected one of uniform, stratified or halton",
+                s
+            )),
+        }
+    }
+}
+
+/// Hands out the sample positions within a single pixel.
+pub trait Sampler {
+    /// The offset of the next sample from the corner of the pixel, in `0.0..1.0` along both axes.
+    fn next_2d(&mut self) -> (f32, f32);
+}
+
+pub struct UniformSampler {
+    rng: SmallRng,
+}
+
+impl UniformSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for UniformSampler {
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+/// Splits a pixel into an `n`×`n` grid of strata and puts one sample at a random point of each,
+/// so no part of the pixel is left out by chance. `n` is as large as the sample count allows;
+/// the samples beyond the `n²` strata go anywhere in the pixel.
+pub struct StratifiedSampler {
+    grid_size: u32,
+    sample: u32,
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples: u32, seed: u64) -> Self {
+        let mut grid_size = 0;
+        while (grid_size + 1) * (grid_size + 1) <= samples {
+            grid_size += 1;
+        }
+
+        Self {
+            grid_size,
+            sample: 0,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn next_2d(&mut self) -> (f32, f32) {
+        let n = self.grid_size;
+        let sample = self.sample;
+        self.sample += 1;
+        if sample >= n * n {
+            return (self.rng.gen(), self.rng.gen());
+        }
+
+        let (column, row) = (sample % n, sample / n);
+        (
+            (column as f32 + self.rng.gen::<f32>()) / n as f32,
+            (row as f32 + self.rng.gen::<f32>()) / n as f32,
+        )
+    }
+}
+
+/// The Halton sequence in two bases, which covers the pixel more evenly than random jitter for
+/// any number of samples, not just square ones.
+///
+/// Every pixel gets its own Owen-style scrambling of the sequence, chosen by `scramble`. Without
+/// it, all pixels would sample the same positions, and the remaining correlation would show up
+/// as patterns instead of noise.
+pub struct HaltonSampler {
+    base_u: u32,
+    base_v: u32,
+    scramble: Option<u32>,
+    index: u32,
+}
+
+impl HaltonSampler {
+    /// The usual bases 2 and 3.
+    pub fn new(scramble: u32) -> Self {
+        Self::with_bases(2, 3, scramble)
+    }
+
+    /// The bases need to be coprime, or the two dimensions correlate.
+    pub fn with_bases(base_u: u32, base_v: u32, scramble: u32) -> Self {
+        Self::build(base_u, base_v, Some(scramble))
+    }
+
+    /// The plain Halton sequence, the same in every pixel.
+    pub fn unscrambled(base_u: u32, base_v: u32) -> Self {
+        Self::build(base_u, base_v, None)
+    }
+
+    fn build(base_u: u32, base_v: u32, scramble: Option<u32>) -> Self {
+        assert!(base_u >= 2 && base_v >= 2);
+        Self {
+            base_u,
+            base_v,
+            scramble,
+            index: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn next_2d(&mut self) -> (f32, f32) {
+        let index = self.index;
+        self.index += 1;
+
+        let seed = |base: u32| self.scramble.map(|scramble| hash(scramble ^ base));
+        (
+            radical_inverse(self.base_u, index, seed(self.base_u)),
+            radical_inverse(self.base_v, index, seed(self.base_v)),
+        )
+    }
+}
+
+/// Mirrors the digits of `index` in `base` at the decimal point. With a `seed`, every digit is
+/// shuffled by a random shift that depends on the seed and on all digits before it. This permutes
+/// the digit tree the way Owen scrambling does, which keeps the sequence stratified.
+fn radical_inverse(base: u32, mut index: u32, seed: Option<u32>) -> f32 {
+    /// The largest `f32` below one.
+    const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+    let mut digit_weight = 1.0 / base as f64;
+    let mut result = 0.0;
+    // Named by the seed and the digits so far, so every node of the digit tree has its own shift.
+    let mut node = seed.unwrap_or_default();
+    // The zeros past the last digit of the index get shifted as well, down to f32 precision.
+    while digit_weight > f32::EPSILON as f64 / 2.0 {
+        let digit = index % base;
+        let shift = seed.map_or(0, |_| hash(node) % base);
+        let shifted = (digit + shift) % base;
+        result += shifted as f64 * digit_weight;
+
+        node = hash(node ^ (digit + 1).wrapping_mul(0x9e37_79b9));
+        index /= base;
+        digit_weight /= base as f64;
+    }
+
+    (result as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// Chris Wellons' lowbias32 integer hash.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn first_samples(mut sampler: HaltonSampler, count: usize) -> Vec<(f32, f32)> {
+        (0..count).map(|_| sampler.next_2d()).collect()
+    }
+
+    /// `index` with its digits in `base` mirrored at the decimal point, as an exact fraction.
+    fn mirrored_digits(base: u32, mut index: u32) -> (u32, u32) {
+        let (mut numerator, mut denominator) = (0, 1);
+        while index > 0 {
+            numerator = numerator * base + index % base;
+            denominator *= base;
+            index /= base;
+        }
+
+        (numerator, denominator)
+    }
+
+    #[test]
+    fn starts_like_the_halton_sequence() {
+        let expected = [
+            (0.0, 0.0),
+            (1.0 / 2.0, 1.0 / 3.0),
+            (1.0 / 4.0, 2.0 / 3.0),
+            (3.0 / 4.0, 1.0 / 9.0),
+            (1.0 / 8.0, 4.0 / 9.0),
+            (5.0 / 8.0, 7.0 / 9.0),
+            (3.0 / 8.0, 2.0 / 9.0),
+            (7.0 / 8.0, 5.0 / 9.0),
+            (1.0 / 16.0, 8.0 / 9.0),
+            (9.0 / 16.0, 1.0 / 27.0),
+        ];
+
+        let samples = first_samples(HaltonSampler::unscrambled(2, 3), expected.len());
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert!((sample.0 - expected.0).abs() < 1e-6, "{:?}", sample);
+            assert!((sample.1 - expected.1).abs() < 1e-6, "{:?}", sample);
+        }
+    }
+
+    #[test]
+    fn first_hundred_samples_are_radical_inverses() {
+        let samples = first_samples(HaltonSampler::unscrambled(2, 3), 100);
+
+        for (index, (u, v)) in samples.into_iter().enumerate() {
+            for (base, value) in [(2, u), (3, v)] {
+                let (numerator, denominator) = mirrored_digits(base, index as u32);
+                let expected = numerator as f32 / denominator as f32;
+                assert!(
+                    (value - expected).abs() < 1e-6,
+                    "sample {} in base {} is {}, expected {}/{}",
+                    index,
+                    base,
+                    value,
+                    numerator,
+                    denominator
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn scrambling_keeps_the_sequence_stratified() {
+        // The first base^k samples of a Halton sequence put one sample into each of the base^k
+        // equal intervals along that axis, and Owen scrambling preserves that.
+        for scramble in [0, 1, 1337] {
+            let samples = first_samples(HaltonSampler::new(scramble), 81);
+
+            for (base, count, axis) in [(2, 64, 0), (3, 81, 1)] {
+                let mut strata: Vec<usize> = samples[..count]
+                    .iter()
+                    .map(|&(u, v)| ([u, v][axis] * count as f32) as usize)
+                    .collect();
+                strata.sort_unstable();
+
+                let all_strata: Vec<usize> = (0..count).collect();
+                assert_eq!(strata, all_strata, "base {}, scramble {}", base, scramble);
+            }
+        }
+    }
+
+    #[test]
+    fn scrambling_differs_between_pixels() {
+        let first = first_samples(HaltonSampler::new(1), 16);
+        let second = first_samples(HaltonSampler::new(2), 16);
+
+        assert_ne!(first, second);
+    }
+
+    /// The variance of a pixel's estimate of `f`, averaged over `samples` points from each of many
+    /// differently seeded samplers.
+    fn estimate_variance<S: Sampler>(
+        make_sampler: impl Fn(u64) -> S,
+        samples: u32,
+        f: impl Fn((f32, f32)) -> f32,
+    ) -> f32 {
+        const PIXELS: u64 = 1_000;
+
+        let estimates: Vec<f32> = (0..PIXELS)
+            .map(|seed| {
+                let mut sampler = make_sampler(seed);
+                (0..samples).map(|_| f(sampler.next_2d())).sum::<f32>() / samples as f32
+            })
+            .collect();
+        let mean = estimates.iter().sum::<f32>() / PIXELS as f32;
+
+        estimates
+            .iter()
+            .map(|estimate| (estimate - mean) * (estimate - mean))
+            .sum::<f32>()
+            / (PIXELS - 1) as f32
+    }
+
+    #[test]
+    fn stratified_sampling_has_less_variance_than_uniform_sampling_at_an_edge() {
+        // An edge running diagonally through the pixel, e.g. the silhouette of an object.
+        let edge = |(u, v): (f32, f32)| if u + 0.6 * v < 0.8 { 1.0 } else { 0.0 };
+
+        for samples in [4, 16, 64] {
+            let uniform = estimate_variance(UniformSampler::new, samples, edge);
+            let stratified =
+                estimate_variance(|seed| StratifiedSampler::new(samples, seed), samples, edge);
+
+            assert!(
+                stratified < uniform,
+                "{} samples: variance {} stratified, {} uniform",
+                samples,
+                stratified,
+                uniform
+            );
+        }
+    }
+
+    /// The L2 star discrepancy of `points`, after Warnock: how far the fraction of the points in
+    /// boxes anchored at the origin strays from the area of those boxes, on average over all boxes.
+    fn l2_star_discrepancy(points: &[(f32, f32)]) -> f64 {
+        let n = points.len() as f64;
+        let points: Vec<[f64; 2]> = points.iter().map(|&(u, v)| [u as f64, v as f64]).collect();
+
+        let single: f64 = points
+            .iter()
+            .map(|p| p.iter().map(|x| (1.0 - x * x) / 2.0).product::<f64>())
+            .sum();
+        let pairs: f64 = points
+            .iter()
+            .flat_map(|p| points.iter().map(move |q| (p, q)))
+            .map(|(p, q)| (0..2).map(|k| 1.0 - p[k].max(q[k])).product::<f64>())
+            .sum();
+
+        (1.0 / 9.0 - 2.0 / n * single + pairs / (n * n)).sqrt()
+    }
+
+    #[test]
+    fn halton_samples_are_less_discrepant_than_uniform_ones() {
+        for seed in [0, 1, 1337] {
+            let mut uniform = UniformSampler::new(seed as u64);
+            let uniform: Vec<_> = (0..64).map(|_| uniform.next_2d()).collect();
+            let halton = first_samples(HaltonSampler::new(seed), 64);
+
+            let uniform = l2_star_discrepancy(&uniform);
+            let halton = l2_star_discrepancy(&halton);
+            assert!(
+                halton < uniform,
+                "seed {}: discrepancy {} of Halton samples, {} of uniform ones",
+                seed,
+                halton,
+                uniform
+            );
+        }
+    }
+}
@@ -0,0 +1,449 @@
+//! Procedurally generated scenes with adjustable complexity, for benchmarks and stress tests.
+//!
+//! Unlike the book scenes, every aspect can be dialled independently: the number of objects, the
+//! mix of primitives and materials, how the objects are spread out and how deeply they are wrapped
+//! in transformations. The same parameters always produce the same scene.
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec, vec::Vec};
+use core::str::FromStr;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng};
+
+use crate::{
+    background::Background,
+    camera::Camera,
+    hittable::{
+        rectangular::Cuboid, spherical::Sphere, transformations::Transformable,
+        triangular::Triangle, Hittable,
+    },
+    light_source::DiffuseLight,
+    material::{Dielectric, Lambertian, Material, Metal},
+    texture::SolidColor,
+    vec3::{Color, Point3, Vec3},
+};
+
+/// Half the edge length of the cube all objects are placed in.
+const EXTENT: f32 = 10.0;
+
+/// Triangles per triangle blob.
+const BLOB_TRIANGLES: usize = 8;
+
+/// Relative amounts of each primitive. Only the ratios matter.
+#[derive(Debug, Clone, Copy)]
+pub struct PrimitiveMix {
+    pub spheres: f32,
+    pub cuboids: f32,
+    /// Small clumps of randomly oriented triangles, as a stand-in for meshes.
+    pub triangle_blobs: f32,
+}
+
+/// Relative amounts of each material. Only the ratios matter.
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialMix {
+    pub diffuse: f32,
+    pub metal: f32,
+    pub glass: f32,
+    pub emissive: f32,
+}
+
+/// Where objects are placed within the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// Uniformly throughout a cube.
+    UniformBox,
+    /// In dense groups with lots of empty space in between.
+    Clustered { clusters: usize },
+    /// On the surface of a sphere, leaving its inside empty.
+    ThinShell,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SceneParameters {
+    pub object_count: usize,
+    pub primitive_mix: PrimitiveMix,
+    pub distribution: Distribution,
+    pub material_mix: MaterialMix,
+    /// How many transformations every object is wrapped in.
+    pub transform_depth: usize,
+    pub seed: u64,
+}
+
+/// Ready-made parameters, named for what they stress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Thousands of small objects, for a deep hierarchy.
+    BvhDepth,
+    /// Lots of emissive spheres, all of them sampled as lights.
+    ManyLights,
+    /// Clusters of mostly glass objects, for long refraction paths.
+    GlassHeavy,
+    /// Every object nested in a deep stack of rotations.
+    TransformDeep,
+}
+
+impl Preset {
+    pub const NAMES: [&'static str; 4] =
+        ["bvh_depth", "many_lights", "glass_heavy", "transform_deep"];
+
+    pub const ALL: [Preset; 4] = [
+        Preset::BvhDepth,
+        Preset::ManyLights,
+        Preset::GlassHeavy,
+        Preset::TransformDeep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::BvhDepth => Self::NAMES[0],
+            Preset::ManyLights => Self::NAMES[1],
+            Preset::GlassHeavy => Self::NAMES[2],
+            Preset::TransformDeep => Self::NAMES[3],
+        }
+    }
+
+    pub fn parameters(&self, seed: u64) -> SceneParameters {
+        let mostly_diffuse = MaterialMix {
+            diffuse: 0.7,
+            metal: 0.3,
+            glass: 0.0,
+            emissive: 0.0,
+        };
+        let all_primitives = PrimitiveMix {
+            spheres: 0.5,
+            cuboids: 0.3,
+            triangle_blobs: 0.2,
+        };
+
+        match self {
+            Preset::BvhDepth => SceneParameters {
+                object_count: 5000,
+                primitive_mix: all_primitives,
+                distribution: Distribution::UniformBox,
+                material_mix: mostly_diffuse,
+                transform_depth: 0,
+                seed,
+            },
+            Preset::ManyLights => SceneParameters {
+                object_count: 400,
+                primitive_mix: PrimitiveMix {
+                    spheres: 1.0,
+                    cuboids: 0.0,
+                    triangle_blobs: 0.0,
+                },
+                distribution: Distribution::ThinShell,
+                material_mix: MaterialMix {
+                    diffuse: 0.7,
+                    metal: 0.0,
+                    glass: 0.0,
+                    emissive: 0.3,
+                },
+                transform_depth: 0,
+                seed,
+            },
+            Preset::GlassHeavy => SceneParameters {
+                object_count: 300,
+                primitive_mix: PrimitiveMix {
+                    spheres: 0.7,
+                    cuboids: 0.3,
+                    triangle_blobs: 0.0,
+                },
+                distribution: Distribution::Clustered { clusters: 6 },
+                material_mix: MaterialMix {
+                    diffuse: 0.2,
+                    metal: 0.0,
+                    glass: 0.8,
+                    emissive: 0.0,
+                },
+                transform_depth: 0,
+                seed,
+            },
+            Preset::TransformDeep => SceneParameters {
+                object_count: 500,
+                primitive_mix: all_primitives,
+                distribution: Distribution::UniformBox,
+                material_mix: mostly_diffuse,
+                transform_depth: 8,
+                seed,
+            },
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown preset {:?}, expected one of {}",
+                    s,
+                    Self::NAMES.join(", ")
+                )
+            })
+    }
+}
+
+pub struct GeneratedScene {
+    pub objects: Vec<Box<dyn Hittable>>,
+    /// Copies of the emissive spheres, for sampling them as lights.
+    pub lights: Vec<Box<dyn Hittable>>,
+    pub camera: Camera,
+    pub background: Background,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Primitive {
+    Sphere,
+    Cuboid,
+    TriangleBlob,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MaterialKind {
+    Diffuse,
+    Metal,
+    Glass,
+    Emissive,
+}
+
+pub fn generate(parameters: &SceneParameters, aspect_ratio: f32) -> GeneratedScene {
+    let mut rng = SmallRng::seed_from_u64(parameters.seed);
+    let count = parameters.object_count;
+
+    let primitive_mix = parameters.primitive_mix;
+    let mut primitives = apportion(
+        count,
+        &[
+            (Primitive::Sphere, primitive_mix.spheres),
+            (Primitive::Cuboid, primitive_mix.cuboids),
+            (Primitive::TriangleBlob, primitive_mix.triangle_blobs),
+        ],
+    );
+    let material_mix = parameters.material_mix;
+    let mut materials = apportion(
+        count,
+        &[
+            (MaterialKind::Diffuse, material_mix.diffuse),
+            (MaterialKind::Metal, material_mix.metal),
+            (MaterialKind::Glass, material_mix.glass),
+            (MaterialKind::Emissive, material_mix.emissive),
+        ],
+    );
+    primitives.shuffle(&mut rng);
+    materials.shuffle(&mut rng);
+
+    let cluster_centers: Vec<Point3> = match parameters.distribution {
+        Distribution::Clustered { clusters } => (0..clusters.max(1))
+            .map(|_| Vec3::random_min_max(&mut rng, -0.7 * EXTENT..0.7 * EXTENT))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // Keep the objects' share of the volume roughly constant, however many there are.
+    let size = 0.5 * EXTENT / (count.max(1) as f32).cbrt();
+
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::with_capacity(count);
+    let mut lights: Vec<Box<dyn Hittable>> = Vec::new();
+
+    for (&primitive, &material_kind) in primitives.iter().zip(&materials) {
+        let position = match parameters.distribution {
+            Distribution::UniformBox => Vec3::random_min_max(&mut rng, -EXTENT..EXTENT),
+            Distribution::Clustered { .. } => {
+                let center = cluster_centers[rng.gen_range(0..cluster_centers.len())];
+                center + 0.15 * EXTENT * rng.gen::<f32>() * Vec3::random_unit_vector(&mut rng)
+            }
+            Distribution::ThinShell => EXTENT * Vec3::random_unit_vector(&mut rng),
+        };
+        let size = size * rng.gen_range(0.5..1.5);
+        let material = random_material(material_kind, &mut rng);
+
+        if let (Primitive::Sphere, MaterialKind::Emissive) = (primitive, material_kind) {
+            lights.push(Box::new(Sphere::new(position, size, material.clone())));
+        }
+
+        let object = match parameters.transform_depth {
+            0 => build_primitive(primitive, position, size, material, &mut rng),
+            depth => {
+                let origin = Point3::new(0.0, 0.0, 0.0);
+                let mut object = build_primitive(primitive, origin, size, material, &mut rng);
+                for level in 1..depth {
+                    let angle = rng.gen_range(-180.0..180.0);
+                    object = match level % 3 {
+                        0 => Box::new(object.rotate_x(angle)),
+                        1 => Box::new(object.rotate_y(angle)),
+                        _ => Box::new(object.rotate_z(angle)),
+                    };
+                }
+                Box::new(object.translate(position))
+            }
+        };
+        objects.push(object);
+    }
+
+    let look_from = Point3::new(0.0, 0.3 * EXTENT, 3.5 * EXTENT);
+    let look_at = Point3::new(0.0, 0.0, 0.0);
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        Vec3::new(0.0, 1.0, 0.0),
+        40.0,
+        aspect_ratio,
+        0.0,
+        (look_at - look_from).length(),
+        0.0,
+        1.0,
+    );
+
+    // Emissive objects only stand out in the dark.
+    let background = match material_mix.emissive > 0.0 {
+        true => Background::SolidColor(Color::new(0.0, 0.0, 0.0)),
+        false => Background::VerticalGradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        },
+    };
+
+    GeneratedScene {
+        objects,
+        lights,
+        camera,
+        background,
+    }
+}
+
+/// Splits `total` into whole counts proportional to the weights, by the largest remainder
+/// method, and lists every item that many times.
+fn apportion<T: Copy>(total: usize, weights: &[(T, f32)]) -> Vec<T> {
+    let weight_sum: f32 = weights.iter().map(|&(_, weight)| weight.max(0.0)).sum();
+    if weight_sum <= 0.0 {
+        return vec![weights[0].0; total];
+    }
+
+    let quotas: Vec<f32> = weights
+        .iter()
+        .map(|&(_, weight)| total as f32 * weight.max(0.0) / weight_sum)
+        .collect();
+    let mut counts: Vec<usize> = quotas.iter().map(|quota| quota.floor() as usize).collect();
+
+    let mut by_remainder: Vec<usize> = (0..weights.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        let remainder = |index: usize| quotas[index] - counts[index] as f32;
+        remainder(b).partial_cmp(&remainder(a)).unwrap()
+    });
+    let missing = total - counts.iter().sum::<usize>();
+    for &index in by_remainder.iter().take(missing) {
+        counts[index] += 1;
+    }
+
+    weights
+        .iter()
+        .zip(counts)
+        .flat_map(|(&(item, _), count)| core::iter::repeat_n(item, count))
+        .collect()
+}
+
+fn random_material(kind: MaterialKind, rng: &mut SmallRng) -> Box<dyn Material> {
+    let color = Color::random_min_max(rng, 0.2..0.9);
+
+    match kind {
+        MaterialKind::Diffuse => Box::new(Lambertian::new_solid_color(color)),
+        MaterialKind::Metal => Box::new(Metal::new(color, rng.gen_range(0.0..0.3))),
+        MaterialKind::Glass => Box::new(Dielectric::new(1.5)),
+        MaterialKind::Emissive => Box::new(DiffuseLight::new(SolidColor::new(4.0 * color))),
+    }
+}
+
+fn build_primitive(
+    primitive: Primitive,
+    center: Point3,
+    size: f32,
+    material: Box<dyn Material>,
+    rng: &mut SmallRng,
+) -> Box<dyn Hittable> {
+    match primitive {
+        Primitive::Sphere => Box::new(Sphere::new(center, size, material)),
+        Primitive::Cuboid => {
+            let half_extents = Vec3::random_min_max(rng, 0.4 * size..size);
+            Box::new(Cuboid::new(
+                center - half_extents,
+                center + half_extents,
+                material,
+            ))
+        }
+        Primitive::TriangleBlob => {
+            let material: Arc<dyn Material> = Arc::from(material);
+            let triangles: Vec<Box<dyn Hittable>> = (0..BLOB_TRIANGLES)
+                .map(|_| {
+                    let mut vertex = || center + size * Vec3::random_in_unit_sphere(rng);
+                    let vertices = [vertex(), vertex(), vertex()];
+                    Box::new(Triangle::new_flat_shaded(vertices, material.clone()))
+                        as Box<dyn Hittable>
+                })
+                .collect();
+            Box::new(triangles)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bvh::build_world, Pixel, Raytracer, RenderIterator};
+
+    #[test]
+    fn the_same_seed_generates_the_same_scene() {
+        let describe = |seed| {
+            let scene = generate(&Preset::TransformDeep.parameters(seed), 1.0);
+            format!("{:?} {:?}", scene.objects, scene.lights)
+        };
+
+        assert_eq!(describe(7), describe(7));
+        assert_ne!(describe(7), describe(8));
+    }
+
+    #[test]
+    fn apportion_splits_by_the_weights() {
+        let counts =
+            |items: &[char]| ['a', 'b', 'c'].map(|c| items.iter().filter(|&&i| i == c).count());
+
+        let items = apportion(400, &[('a', 0.7), ('b', 0.3), ('c', 0.0)]);
+        assert_eq!(counts(&items), [280, 120, 0]);
+
+        // Three equal shares of 10 leave one over, which goes to the first of the tied remainders.
+        let items = apportion(10, &[('a', 1.0), ('b', 1.0), ('c', 1.0)]);
+        assert_eq!(counts(&items), [4, 3, 3]);
+
+        let items = apportion(7, &[('a', 0.0), ('b', 0.0), ('c', 0.0)]);
+        assert_eq!(counts(&items), [7, 0, 0]);
+    }
+
+    #[test]
+    fn generated_scenes_keep_the_material_mix() {
+        // All of the many lights preset's objects are spheres, so each emissive one is a light.
+        let parameters = Preset::ManyLights.parameters(0);
+        let scene = generate(&parameters, 1.0);
+
+        assert_eq!(scene.objects.len(), 400);
+        assert_eq!(scene.lights.len(), 120);
+    }
+
+    #[test]
+    fn every_preset_renders() {
+        for preset in Preset::ALL {
+            let scene = generate(&preset.parameters(0), 1.0);
+            let mut rng = SmallRng::seed_from_u64(0);
+            let world = build_world(scene.objects, 0.0, 1.0, &mut rng);
+
+            let raytracer = Raytracer::new(&world, &scene.camera, &scene.background, 16, 16, 1)
+                .with_lights(&scene.lights);
+            let pixels: Vec<Pixel> = raytracer.render().collect();
+
+            assert_eq!(pixels.len(), 16 * 16, "{}", preset.name());
+        }
+    }
+}
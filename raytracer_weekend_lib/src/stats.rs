@@ -0,0 +1,132 @@
+#![cfg(feature = "stats")]
+//! Counters of the work done while rendering, for comparing scenes and optimisations.
+//!
+//! Every thread counts into its own set of relaxed atomics, so counting costs next to nothing and
+//! threads don't contend. The sets are only summed up when a [`RenderStats::snapshot`] is taken.
+//! Counting is global, so renders running at the same time show up in each other's statistics.
+
+use alloc::{sync::Arc, vec::Vec};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::RenderTotals;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Counter {
+    CameraRays,
+    ScatteredRays,
+    ShadowRays,
+    BvhNodeTests,
+    TriangleTests,
+}
+
+const COUNTER_COUNT: usize = 5;
+
+type Counts = [u64; COUNTER_COUNT];
+
+#[derive(Default)]
+struct ThreadCounters([AtomicU64; COUNTER_COUNT]);
+
+static ALL_THREAD_COUNTERS: Mutex<Vec<Arc<ThreadCounters>>> = Mutex::new(Vec::new());
+
+std::thread_local! {
+    static THREAD_COUNTERS: Arc<ThreadCounters> = {
+        let counters = Arc::new(ThreadCounters::default());
+        ALL_THREAD_COUNTERS.lock().unwrap().push(counters.clone());
+        counters
+    };
+}
+
+pub(crate) fn record(counter: Counter) {
+    THREAD_COUNTERS.with(|counters| counters.0[counter as usize].fetch_add(1, Ordering::Relaxed));
+}
+
+fn totals() -> Counts {
+    let mut totals = [0; COUNTER_COUNT];
+    for counters in ALL_THREAD_COUNTERS.lock().unwrap().iter() {
+        for (total, counter) in totals.iter_mut().zip(&counters.0) {
+            *total += counter.load(Ordering::Relaxed);
+        }
+    }
+
+    totals
+}
+
+/// Measures everything rendered from its creation on.
+#[derive(Debug, Clone)]
+pub struct RenderStats {
+    started: Instant,
+    baseline: Counts,
+}
+
+impl RenderStats {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            baseline: totals(),
+        }
+    }
+
+    /// The work done since [`RenderStats::start`]. Take it once the render iterator has been
+    /// consumed, as nothing is rendered before that.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let totals = totals();
+        let count = |counter: Counter| {
+            let index = counter as usize;
+            totals[index] - self.baseline[index]
+        };
+
+        StatsSnapshot {
+            camera_rays: count(Counter::CameraRays),
+            scattered_rays: count(Counter::ScatteredRays),
+            shadow_rays: count(Counter::ShadowRays),
+            bvh_node_tests: count(Counter::BvhNodeTests),
+            triangle_tests: count(Counter::TriangleTests),
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub camera_rays: u64,
+    /// Rays that bounced off, or through, a surface or volume.
+    pub scattered_rays: u64,
+    /// Rays only checking whether a light is visible.
+    pub shadow_rays: u64,
+    /// Bounding box tests of BVH nodes.
+    pub bvh_node_tests: u64,
+    pub triangle_tests: u64,
+    pub elapsed: Duration,
+}
+
+impl StatsSnapshot {
+    pub fn total_rays(&self) -> u64 {
+        self.camera_rays + self.scattered_rays + self.shadow_rays
+    }
+
+    /// The average number of bounces of a path from the camera.
+    pub fn average_bounces(&self) -> f64 {
+        match self.camera_rays {
+            0 => 0.0,
+            camera_rays => self.scattered_rays as f64 / camera_rays as f64,
+        }
+    }
+}
+
+impl From<&StatsSnapshot> for RenderTotals {
+    fn from(stats: &StatsSnapshot) -> Self {
+        Self {
+            total_rays: stats.total_rays(),
+            bvh_tests: stats.bvh_node_tests,
+            primitive_tests: stats.triangle_tests,
+            shadow_rays: stats.shadow_rays,
+            elapsed_ms: stats.elapsed.as_millis() as u64,
+        }
+    }
+}
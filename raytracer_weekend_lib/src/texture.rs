@@ -4,26 +4,44 @@ use core::{
 };
 
 use derive_more::Constructor;
+use itertools::iproduct;
 #[cfg(feature = "no_std")]
 use micromath::F32Ext;
 
-use super::vec3::{Color, Vec3};
+use super::vec3::{Color, Point3, Vec3};
 use crate::perlin::Perlin;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Point2d {
     pub u: f32,
     pub v: f32,
+    /// How far the texture coordinates move between neighbouring pixels, if known. Filtered
+    /// textures use this to pick a level of detail and fall back to full detail without it.
+    pub derivatives: Option<UvDerivatives>,
+}
+
+impl Point2d {
+    pub fn new(u: f32, v: f32) -> Self {
+        Self {
+            u,
+            v,
+            derivatives: None,
+        }
+    }
+}
+
+/// The change of the texture coordinates per pixel along the screen's X and Y axes.
+#[derive(Debug, Copy, Clone, Default, Constructor)]
+pub struct UvDerivatives {
+    pub ddx: f32,
+    pub ddy: f32,
 }
 
 impl Mul<Point2d> for f32 {
     type Output = Point2d;
 
     fn mul(self, rhs: Point2d) -> Self::Output {
-        Point2d {
-            u: self * rhs.u,
-            v: self * rhs.v,
-        }
+        Point2d::new(self * rhs.u, self * rhs.v)
     }
 }
 
@@ -31,10 +49,7 @@ impl Add for Point2d {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            u: self.u + rhs.u,
-            v: self.v + rhs.v,
-        }
+        Self::new(self.u + rhs.u, self.v + rhs.v)
     }
 }
 
@@ -80,6 +95,43 @@ impl<E: Texture, O: Texture> Texture for Checker<E, O> {
     }
 }
 
+/// Blends from `texture_a` to `texture_b` along a world space axis, e.g. for skies that fade
+/// towards the horizon.
+///
+/// Points whose projection onto `axis` is at most `min_value` get `texture_a`, points at or above
+/// `max_value` get `texture_b`, and everything in between is interpolated linearly.
+#[derive(Debug, Clone)]
+pub struct Gradient<A: Texture, B: Texture> {
+    texture_a: A,
+    texture_b: B,
+    axis: Vec3,
+    min_value: f32,
+    max_value: f32,
+}
+
+impl<A: Texture, B: Texture> Gradient<A, B> {
+    pub fn new(texture_a: A, texture_b: B, axis: Vec3, min_value: f32, max_value: f32) -> Self {
+        assert!(min_value < max_value);
+
+        Self {
+            texture_a,
+            texture_b,
+            axis,
+            min_value,
+            max_value,
+        }
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for Gradient<A, B> {
+    fn value(&self, uv: Point2d, p: &Vec3) -> Color {
+        let t = (p.dot(&self.axis) - self.min_value) / (self.max_value - self.min_value);
+        let t = t.clamp(0.0, 1.0);
+
+        (1.0 - t) * self.texture_a.value(uv, p) + t * self.texture_b.value(uv, p)
+    }
+}
+
 #[derive(Debug, Constructor, Clone)]
 pub struct Noise {
     noise: Perlin,
@@ -94,6 +146,332 @@ impl Texture for Noise {
     }
 }
 
+/// Marble-like veins, following the sine of the Z coordinate disturbed by Perlin turbulence.
+///
+/// `scale` sets the frequency of the veins along Z, `turbulence_depth` the number of noise
+/// octaves summed up to distort them. The stone blends from `base` to `vein` towards the middle
+/// of the veins.
+#[derive(Debug, Constructor, Clone)]
+pub struct Marble<B: Texture, V: Texture> {
+    perlin: Perlin,
+    scale: f32,
+    turbulence_depth: u32,
+    base: B,
+    vein: V,
+}
+
+impl<B: Texture, V: Texture> Texture for Marble<B, V> {
+    fn value(&self, uv: Point2d, p: &Point3) -> Color {
+        let turbulence = self.perlin.turbulence(p, self.turbulence_depth as usize);
+        let phase = self.scale * p.z() + 10.0 * turbulence;
+        let t = 0.5 * (1.0 + phase.sin());
+
+        t * self.base.value(uv, p) + (1.0 - t) * self.vein.value(uv, p)
+    }
+}
+
+/// Perlin turbulence as it is, blending from `low` where there is none to `high` where it
+/// reaches one. Stronger turbulence stays at `high`.
+///
+/// `scale` is the frequency of the first octave, `turbulence_depth` the number of octaves.
+#[derive(Debug, Constructor, Clone)]
+pub struct Turbulence<L: Texture, H: Texture> {
+    perlin: Perlin,
+    scale: f32,
+    turbulence_depth: u32,
+    low: L,
+    high: H,
+}
+
+impl<L: Texture, H: Texture> Texture for Turbulence<L, H> {
+    fn value(&self, uv: Point2d, p: &Point3) -> Color {
+        let t = self
+            .perlin
+            .turbulence(&(self.scale * *p), self.turbulence_depth as usize)
+            .clamp(0.0, 1.0);
+
+        (1.0 - t) * self.low.value(uv, p) + t * self.high.value(uv, p)
+    }
+}
+
+/// Growth rings around the Y axis, as in a log standing upright at the origin.
+///
+/// `ring_frequency` is the number of rings per unit of distance from the axis. `grain` is how far,
+/// in rings, Perlin turbulence pushes them in and out, which makes them wobble like real wood.
+/// The rings blend from `early` in their middle to `late` at their borders.
+#[derive(Debug, Constructor, Clone)]
+pub struct Wood<E: Texture, L: Texture> {
+    perlin: Perlin,
+    ring_frequency: f32,
+    grain: f32,
+    early: E,
+    late: L,
+}
+
+impl<E: Texture, L: Texture> Texture for Wood<E, L> {
+    fn value(&self, uv: Point2d, p: &Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let turbulence = self.perlin.turbulence(&(self.ring_frequency * *p), 4);
+        let rings = self.ring_frequency * radius + self.grain * turbulence;
+        let t = 0.5 * (1.0 + (2.0 * core::f32::consts::PI * rings).cos());
+
+        (1.0 - t) * self.early.value(uv, p) + t * self.late.value(uv, p)
+    }
+}
+
+/// Fractal Brownian motion: several octaves of Perlin noise, each at a higher frequency and a
+/// lower weight than the one before.
+///
+/// The first octave samples at `scale`, and every further octave multiplies the frequency by
+/// `lacunarity` and the weight by `gain`. The sum is divided by the total weight, which keeps
+/// [`FractalBrownianMotion::sample`] within the range of a single octave, `-1.0..=1.0`.
+#[derive(Debug, Clone)]
+pub struct FractalBrownianMotion {
+    perlin: Perlin,
+    scale: f32,
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+}
+
+impl FractalBrownianMotion {
+    pub fn new(perlin: Perlin, scale: f32, octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        assert!(octaves > 0);
+        assert!(gain > 0.0);
+
+        Self {
+            perlin,
+            scale,
+            octaves,
+            lacunarity,
+            gain,
+        }
+    }
+
+    pub fn sample(&self, p: &Point3) -> f32 {
+        let mut sum = 0.0;
+        let mut total_weight = 0.0;
+        let mut frequency = self.scale;
+        let mut weight = 1.0;
+
+        for _ in 0..self.octaves {
+            sum += weight * self.perlin.noise(&(frequency * *p));
+            total_weight += weight;
+            frequency *= self.lacunarity;
+            weight *= self.gain;
+        }
+
+        sum / total_weight
+    }
+}
+
+impl Texture for FractalBrownianMotion {
+    fn value(&self, _uv: Point2d, p: &Vec3) -> Color {
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.sample(p))
+    }
+}
+
+/// Domain warped noise: fractal noise looked up at a position that is itself displaced by
+/// fractal noise, which gives flowing, organic patterns.
+///
+/// `warp_strength` is measured in features of the noise, i.e. in units of `1.0 / scale`. At zero
+/// the texture is the same as a [`FractalBrownianMotion`] with the usual lacunarity of 2 and gain
+/// of 0.5.
+#[derive(Debug, Clone)]
+pub struct WarpedNoise {
+    fbm: FractalBrownianMotion,
+    scale: f32,
+    warp_strength: f32,
+}
+
+impl WarpedNoise {
+    /// Arbitrary shifts that decorrelate the three components of the warp.
+    const OFFSET_Y: Vec3 = Vec3::new_const(5.2, 1.3, 2.8);
+    const OFFSET_Z: Vec3 = Vec3::new_const(1.7, 9.2, 7.4);
+
+    pub fn new(perlin: Perlin, scale: f32, warp_strength: f32, octaves: u32) -> Self {
+        Self {
+            fbm: FractalBrownianMotion::new(perlin, scale, octaves, 2.0, 0.5),
+            scale,
+            warp_strength,
+        }
+    }
+
+    pub fn sample(&self, p: &Point3) -> f32 {
+        let feature = 1.0 / self.scale;
+        let offset = Vec3::new(
+            self.fbm.sample(p),
+            self.fbm.sample(&(*p + feature * Self::OFFSET_Y)),
+            self.fbm.sample(&(*p + feature * Self::OFFSET_Z)),
+        );
+
+        self.fbm
+            .sample(&(*p + self.warp_strength * feature * offset))
+    }
+}
+
+impl Texture for WarpedNoise {
+    fn value(&self, _uv: Point2d, p: &Vec3) -> Color {
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.sample(p))
+    }
+}
+
+/// Which distance a [`Worley`] texture shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorleyMode {
+    /// Distance to the nearest feature point, giving round cells that are dark in the middle.
+    F1,
+    /// Distance to the second nearest feature point.
+    F2,
+    /// Difference of both, which is dark along the cell borders, like cracks or scales.
+    F2MinusF1,
+}
+
+/// Cellular noise after Worley, with one feature point per unit cell of a grid.
+///
+/// Feature points sit at the cell centers, moved by up to `jitter` times half a cell in a random
+/// direction. The random directions come from the lattice gradients of the [`Perlin`] passed in,
+/// so both repeat the same way. The color's brightness is the distance picked by `mode`.
+#[derive(Debug, Clone)]
+pub struct Worley {
+    noise: Perlin,
+    scale: f32,
+    jitter: f32,
+    mode: WorleyMode,
+}
+
+impl Worley {
+    pub fn new(noise: Perlin, scale: f32, jitter: f32, mode: WorleyMode) -> Self {
+        assert!((0.0..=1.0).contains(&jitter));
+
+        Self {
+            noise,
+            scale,
+            jitter,
+            mode,
+        }
+    }
+
+    fn feature_point(&self, cell: Point3) -> Point3 {
+        let gradient = self.noise.lattice_gradient(cell.to_i64().to_usize());
+        cell + Vec3::new(0.5, 0.5, 0.5) + 0.5 * self.jitter * gradient
+    }
+}
+
+impl Texture for Worley {
+    fn value(&self, _uv: Point2d, p: &Vec3) -> Color {
+        let p = self.scale * *p;
+        let cell = p.floor();
+
+        // Feature points never leave their cell, so the nearest two are always among the
+        // neighbouring cells.
+        let mut nearest = f32::INFINITY;
+        let mut second_nearest = f32::INFINITY;
+        for (x, y, z) in iproduct!(-1..=1, -1..=1, -1..=1) {
+            let neighbour = cell + Vec3::new(x as f32, y as f32, z as f32);
+            let distance = (self.feature_point(neighbour) - p).length();
+
+            if distance < nearest {
+                second_nearest = nearest;
+                nearest = distance;
+            } else if distance < second_nearest {
+                second_nearest = distance;
+            }
+        }
+
+        let value = match self.mode {
+            WorleyMode::F1 => nearest,
+            WorleyMode::F2 => second_nearest,
+            WorleyMode::F2MinusF1 => second_nearest - nearest,
+        };
+
+        Color::new(1.0, 1.0, 1.0) * value
+    }
+}
+
+/// Scales, rotates and offsets the texture coordinates before looking them up in `inner`, to tile
+/// or turn a texture without touching the UVs of the geometry. Coordinates are scaled first, then
+/// rotated counter-clockwise about the origin, then offset.
+///
+/// Tiling an [`ImageTexture`] needs it to repeat, see [`ImageTexture::with_wrap`].
+///
+/// [`ImageTexture`]: crate::image_texture::ImageTexture
+/// [`ImageTexture::with_wrap`]: crate::image_texture::ImageTexture::with_wrap
+#[derive(Debug, Clone)]
+pub struct UvTransform<T: Texture> {
+    inner: T,
+    scale_u: f32,
+    scale_v: f32,
+    sin_theta: f32,
+    cos_theta: f32,
+    offset_u: f32,
+    offset_v: f32,
+    transform_point: bool,
+}
+
+impl<T: Texture> UvTransform<T> {
+    pub fn new(
+        inner: T,
+        scale_u: f32,
+        scale_v: f32,
+        rotate_degrees: f32,
+        offset_u: f32,
+        offset_v: f32,
+    ) -> Self {
+        let angle_radians = rotate_degrees.to_radians();
+
+        Self {
+            inner,
+            scale_u,
+            scale_v,
+            sin_theta: angle_radians.sin(),
+            cos_theta: angle_radians.cos(),
+            offset_u,
+            offset_v,
+            transform_point: false,
+        }
+    }
+
+    /// Also transforms the x and y coordinates of the point handed to `inner` the way u and v are,
+    /// for textures like [`Checker`] that go by the point rather than the texture coordinates.
+    pub fn with_point_transform(mut self, transform_point: bool) -> Self {
+        self.transform_point = transform_point;
+        self
+    }
+
+    fn transform(&self, u: f32, v: f32) -> (f32, f32) {
+        let u = self.scale_u * u;
+        let v = self.scale_v * v;
+
+        (
+            self.cos_theta * u - self.sin_theta * v + self.offset_u,
+            self.sin_theta * u + self.cos_theta * v + self.offset_v,
+        )
+    }
+}
+
+impl<T: Texture> Texture for UvTransform<T> {
+    fn value(&self, uv: Point2d, p: &Vec3) -> Color {
+        let (u, v) = self.transform(uv.u, uv.v);
+        // Rotating doesn't change how far the coordinates move per pixel, but scaling does.
+        let scale = self.scale_u.abs().max(self.scale_v.abs());
+        let uv = Point2d {
+            u,
+            v,
+            derivatives: uv
+                .derivatives
+                .map(|d| UvDerivatives::new(scale * d.ddx, scale * d.ddy)),
+        };
+
+        if self.transform_point {
+            let (x, y) = self.transform(p.x(), p.y());
+            self.inner.value(uv, &Vec3::new(x, y, p.z()))
+        } else {
+            self.inner.value(uv, p)
+        }
+    }
+}
+
 #[derive(Debug, Clone, Constructor)]
 pub struct UVDebug {}
 
@@ -102,3 +480,120 @@ impl Texture for UVDebug {
         Color::new(uv.u, uv.v, 0.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    use crate::ActiveRng;
+
+    fn perlin() -> Perlin {
+        Perlin::new(&mut ActiveRng::seed_from_u64(1))
+    }
+
+    fn rgb(color: Color) -> [f32; 3] {
+        [color.x(), color.y(), color.z()]
+    }
+
+    #[test]
+    fn gradient_is_exactly_its_end_colors_at_the_ends_of_the_axis() {
+        let axis = Vec3::new(1.0, 2.0, 2.0) / 3.0;
+        let gradient = Gradient::new(
+            SolidColor::new_rgb(0.9, 0.2, 0.1),
+            SolidColor::new_rgb(0.1, 0.3, 0.7),
+            axis,
+            -1.0,
+            2.5,
+        );
+        let uv = Point2d::new(0.0, 0.0);
+        let sideways = Vec3::new(2.0, -1.0, 0.0);
+
+        assert_eq!(rgb(gradient.value(uv, &(-1.0 * axis))), [0.9, 0.2, 0.1]);
+        assert_eq!(rgb(gradient.value(uv, &(2.5 * axis))), [0.1, 0.3, 0.7]);
+        // Moving across the axis changes nothing.
+        assert_eq!(
+            rgb(gradient.value(uv, &(-1.0 * axis + sideways))),
+            [0.9, 0.2, 0.1]
+        );
+    }
+
+    #[test]
+    fn worley_f1_is_zero_at_feature_points() {
+        let worley = Worley::new(perlin(), 2.5, 0.8, WorleyMode::F1);
+
+        for cell in [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(3.0, -2.0, 7.0),
+            Point3::new(-5.0, 11.0, -1.0),
+        ] {
+            let feature_point = worley.feature_point(cell) / 2.5;
+            let f1 = worley.value(Point2d::new(0.0, 0.0), &feature_point).x();
+            assert!(f1.abs() < 1e-5, "F1 is {} at {}", f1, feature_point);
+        }
+    }
+
+    /// Points scattered over a few noise features in every direction.
+    fn random_points(count: usize) -> impl Iterator<Item = Point3> {
+        let mut rng = ActiveRng::seed_from_u64(2);
+        (0..count).map(move |_| {
+            Point3::new(
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0),
+            )
+        })
+    }
+
+    #[test]
+    fn fbm_stays_within_its_range() {
+        for (octaves, lacunarity, gain) in [(1, 2.0, 0.5), (6, 2.0, 0.5), (4, 3.0, 0.9)] {
+            let fbm = FractalBrownianMotion::new(perlin(), 1.7, octaves, lacunarity, gain);
+
+            for p in random_points(10_000) {
+                let sample = fbm.sample(&p);
+                assert!((-1.0..=1.0).contains(&sample), "{} at {}", sample, p);
+                let brightness = fbm.value(Point2d::new(0.0, 0.0), &p).x();
+                assert!((0.0..=1.0).contains(&brightness), "{} at {}", brightness, p);
+            }
+        }
+    }
+
+    #[test]
+    fn warped_noise_without_warp_is_fbm() {
+        let warped = WarpedNoise::new(perlin(), 1.7, 0.0, 5);
+        let fbm = FractalBrownianMotion::new(perlin(), 1.7, 5, 2.0, 0.5);
+
+        for p in random_points(1_000) {
+            assert_eq!(warped.sample(&p), fbm.sample(&p), "at {}", p);
+        }
+    }
+
+    #[test]
+    fn marble_stays_between_its_colors_and_varies_smoothly() {
+        let marble = Marble::new(
+            perlin(),
+            4.0,
+            7,
+            SolidColor::new_rgb(1.0, 1.0, 1.0),
+            SolidColor::new_rgb(0.0, 0.0, 0.0),
+        );
+        let brightness = |p: &Point3| marble.value(Point2d::new(0.0, 0.0), p).x();
+        let step = Vec3::new(1.0, 1.0, 1.0) * 1e-4;
+
+        for p in random_points(10_000) {
+            let here = brightness(&p);
+            assert!((0.0..=1.0).contains(&here), "{} at {}", here, p);
+
+            // No seams or jumps: a tiny step only changes the color a little.
+            let next = brightness(&(p + step));
+            assert!(
+                (next - here).abs() < 0.02,
+                "jumps from {} to {} at {}",
+                here,
+                next,
+                p
+            );
+        }
+    }
+}
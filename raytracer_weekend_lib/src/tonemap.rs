@@ -0,0 +1,296 @@
+use alloc::{format, string::String};
+use core::str::FromStr;
+
+#[cfg(feature = "no_std")]
+use micromath::F32Ext;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::vec3::Color;
+
+/// Maps unbounded linear radiance into the displayable `0.0..=1.0` range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TonemapOperator {
+    /// Plain clamping, which blows out anything brighter than white.
+    None,
+    /// `x / (1 + x)`, compressing highlights smoothly.
+    Reinhard,
+    /// Reinhard's curve stretched so that `max_white` and above map to white, which keeps
+    /// highlights from turning grey.
+    ReinhardExtended { max_white: f32 },
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2, with his white point of 11.2.
+    Uncharted2,
+}
+
+/// The white point of [`TonemapOperator::ReinhardExtended`] when none is given on parsing.
+const DEFAULT_MAX_WHITE: f32 = 4.0;
+
+impl TonemapOperator {
+    pub fn apply(&self, color: Color) -> Color {
+        Color::new(
+            self.map(color.x()),
+            self.map(color.y()),
+            self.map(color.z()),
+        )
+    }
+
+    fn map(&self, x: f32) -> f32 {
+        match *self {
+            Self::None => x,
+            Self::Reinhard => x / (1.0 + x),
+            Self::ReinhardExtended { max_white } => {
+                x * (1.0 + x / (max_white * max_white)) / (1.0 + x)
+            }
+            // The fit overshoots white a little for very bright input.
+            Self::Aces => ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).min(1.0),
+            Self::Uncharted2 => {
+                const EXPOSURE_BIAS: f32 = 2.0;
+                const WHITE: f32 = 11.2;
+                uncharted2_curve(EXPOSURE_BIAS * x) / uncharted2_curve(WHITE)
+            }
+        }
+    }
+}
+
+fn uncharted2_curve(x: f32) -> f32 {
+    const SHOULDER_STRENGTH: f32 = 0.15;
+    const LINEAR_STRENGTH: f32 = 0.50;
+    const LINEAR_ANGLE: f32 = 0.10;
+    const TOE_STRENGTH: f32 = 0.20;
+    const TOE_NUMERATOR: f32 = 0.02;
+    const TOE_DENOMINATOR: f32 = 0.30;
+
+    let (a, b, c) = (SHOULDER_STRENGTH, LINEAR_STRENGTH, LINEAR_ANGLE);
+    let (d, e, f) = (TOE_STRENGTH, TOE_NUMERATOR, TOE_DENOMINATOR);
+
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+impl FromStr for TonemapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_ascii_lowercase();
+        // Only the extended Reinhard operator takes a parameter, as in `reinhard-extended:8`.
+        let (name, max_white) = match lowercase.split_once(':') {
+            Some((name, max_white)) => (name, Some(max_white)),
+            None => (lowercase.as_str(), None),
+        };
+
+        match (name, max_white) {
+            ("none" | "linear", None) => Ok(Self::None),
+            ("reinhard", None) => Ok(Self::Reinhard),
+            ("reinhard-extended", max_white) => {
+                let max_white = match max_white {
+                    Some(max_white) => max_white
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|&max_white| max_white > 0.0)
+                        .ok_or_else(|| format!("invalid white point {:?}", max_white))?,
+                    None => DEFAULT_MAX_WHITE,
+                };
+                Ok(Self::ReinhardExtended { max_white })
+            }
+            ("aces", None) => Ok(Self::Aces),
+            ("uncharted2", None) => Ok(Self::Uncharted2),
+            _ => Err(format!(
+                "unknown tone mapping operator {:?}, expected one of none, reinhard, \
+                 reinhard-extended[:WHITE], aces or uncharted2",
+                s
+            )),
+        }
+    }
+}
+
+/// Turns accumulated sample sums into display colors: averages, applies the exposure, tone maps
+/// and gamma-corrects for gamma=2.0.
+///
+/// This is the single place where that happens, so all frontends produce identical images.
+#[derive(Debug, Clone, Copy)]
+pub struct Tonemap {
+    operator: TonemapOperator,
+    exposure_scale: f32,
+}
+
+impl Tonemap {
+    /// `exposure` is given in stops, i.e. every +1.0 doubles the brightness.
+    pub fn new(operator: TonemapOperator, exposure: f32) -> Self {
+        Self {
+            operator,
+            exposure_scale: 2.0_f32.powf(exposure),
+        }
+    }
+
+    /// Returns the display color in `0.0..1.0` for a pixel whose `sample_count` samples add up to
+    /// `color_sum`.
+    pub fn display_color(&self, color_sum: Color, sample_count: u32) -> Color {
+        let mapped = self.mapped(color_sum, sample_count);
+        let gamma_corrected = |x: f32| x.sqrt().clamp(0.0, 0.999);
+
+        Color::new(
+            gamma_corrected(mapped.x()),
+            gamma_corrected(mapped.y()),
+            gamma_corrected(mapped.z()),
+        )
+    }
+
+    pub fn to_rgb8(&self, color_sum: Color, sample_count: u32) -> [u8; 3] {
+        self.mapped(color_sum, sample_count).to_srgb_u8(1)
+    }
+
+    /// The tone mapped average, before gamma correction. Averaging has to come first, since the
+    /// operators aren't linear.
+    fn mapped(&self, color_sum: Color, sample_count: u32) -> Color {
+        let scale = self.exposure_scale / sample_count.max(1) as f32;
+        self.operator.apply(scale * color_sum)
+    }
+}
+
+impl Default for Tonemap {
+    fn default() -> Self {
+        Self::new(TonemapOperator::None, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb8(operator: TonemapOperator, r: f32, g: f32, b: f32) -> [u8; 3] {
+        Tonemap::new(operator, 0.0).to_rgb8(Color::new(r, g, b), 1)
+    }
+
+    #[test]
+    fn none_clamps_to_white() {
+        assert_eq!(rgb8(TonemapOperator::None, 0.0, 0.25, 4.0), [0, 127, 255]);
+    }
+
+    #[test]
+    fn reinhard_golden_values() {
+        assert_eq!(
+            rgb8(TonemapOperator::Reinhard, 1.0, 3.0, 0.0),
+            [181, 221, 0]
+        );
+    }
+
+    #[test]
+    fn reinhard_extended_maps_max_white_to_white() {
+        let operator = TonemapOperator::ReinhardExtended { max_white: 4.0 };
+        assert_eq!(rgb8(operator, 1.0, 4.0, 100.0), [186, 255, 255]);
+    }
+
+    #[test]
+    fn aces_golden_values() {
+        assert_eq!(
+            rgb8(TonemapOperator::Aces, 0.18, 1.0, 100.0),
+            [132, 229, 255]
+        );
+    }
+
+    #[test]
+    fn uncharted2_golden_values() {
+        assert_eq!(
+            rgb8(TonemapOperator::Uncharted2, 0.18, 1.0, 4.0),
+            [91, 179, 245]
+        );
+    }
+
+    /// `steps + 1` values spread evenly in log space from `10^from` to `10^to`.
+    fn log_sweep(from: f32, to: f32, steps: u32) -> impl Iterator<Item = f32> {
+        (0..=steps).map(move |i| 10f32.powf(from + (to - from) * i as f32 / steps as f32))
+    }
+
+    #[test]
+    fn reinhard_is_linear_at_low_luminance() {
+        for x in log_sweep(-8.0, -3.0, 500) {
+            let color = Color::new(x, 0.5 * x, 0.0);
+            let linear = TonemapOperator::None.apply(color);
+            let reinhard = TonemapOperator::Reinhard.apply(color);
+
+            // They part by x² / (1 + x), which is within a thousandth of x up to here.
+            assert!(
+                (reinhard - linear).length() <= 1e-3 * linear.length(),
+                "{}",
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn aces_never_exceeds_white() {
+        for x in log_sweep(-6.0, 30.0, 3600).chain([0.0, f32::MAX, f32::INFINITY]) {
+            let mapped = TonemapOperator::Aces.apply(Color::new(x, x, x));
+            assert!(
+                (0.0..=1.0).contains(&mapped.x()),
+                "{} maps to {}",
+                x,
+                mapped.x()
+            );
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let brighter = Tonemap::new(TonemapOperator::None, 1.0);
+        let darker = Tonemap::new(TonemapOperator::None, -2.0);
+        let color = Color::new(0.125, 0.125, 1.0);
+
+        assert_eq!(brighter.to_rgb8(color, 1), [127, 127, 255]);
+        assert_eq!(darker.to_rgb8(color, 1), [45, 45, 127]);
+    }
+
+    #[test]
+    fn averages_before_tone_mapping() {
+        let tonemap = Tonemap::new(TonemapOperator::Reinhard, 0.0);
+
+        // Four samples adding up to 4.0 average to 1.0, which Reinhard maps to 0.5.
+        assert_eq!(
+            tonemap.to_rgb8(Color::new(4.0, 4.0, 4.0), 4),
+            rgb8(TonemapOperator::Reinhard, 1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn display_color_agrees_with_rgb8() {
+        let tonemap = Tonemap::new(TonemapOperator::Aces, 0.5);
+        let color = Color::new(0.3, 2.0, 0.01);
+
+        let display = tonemap.display_color(color, 1);
+        let to_u8 = |x: f32| (255.999 * x) as u8;
+        assert_eq!(
+            [to_u8(display.x()), to_u8(display.y()), to_u8(display.z())],
+            tonemap.to_rgb8(color, 1)
+        );
+    }
+
+    #[test]
+    fn parses_operator_names() {
+        assert_eq!("none".parse(), Ok(TonemapOperator::None));
+        assert_eq!("Reinhard".parse(), Ok(TonemapOperator::Reinhard));
+        assert_eq!("ACES".parse(), Ok(TonemapOperator::Aces));
+        assert_eq!("uncharted2".parse(), Ok(TonemapOperator::Uncharted2));
+        assert_eq!(
+            "reinhard-extended".parse(),
+            Ok(TonemapOperator::ReinhardExtended {
+                max_white: DEFAULT_MAX_WHITE
+            })
+        );
+        assert_eq!(
+            "reinhard-extended:8".parse(),
+            Ok(TonemapOperator::ReinhardExtended { max_white: 8.0 })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_operators() {
+        assert!("filmic".parse::<TonemapOperator>().is_err());
+        assert!("aces:2".parse::<TonemapOperator>().is_err());
+        assert!("reinhard-extended:0".parse::<TonemapOperator>().is_err());
+        assert!("reinhard-extended:bright"
+            .parse::<TonemapOperator>()
+            .is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use core::{
+    f32::consts::PI,
     fmt::{Debug, Display, Formatter},
     ops::{
         Add, AddAssign, BitAnd, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Range, Sub,
@@ -130,6 +131,19 @@ impl GenericVec3<f32> {
         }
     }
 
+    /// A unit vector in the hemisphere around `+z`, with a density proportional to its `z`.
+    pub fn random_cosine_direction(rng: &mut impl Rng) -> Self {
+        let r1: f32 = rng.gen();
+        let r2: f32 = rng.gen();
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+
+        Self::new(x, y, z)
+    }
+
     pub fn is_near_zero(&self) -> bool {
         // Return true if the vector is close to zero in all dimensions.
         const S: f32 = 1e-8;
@@ -137,6 +151,27 @@ impl GenericVec3<f32> {
         (self.e[0].abs() < S) && (self.e[1].abs() < S) && (self.e[2].abs() < S)
     }
 
+    pub fn max_component(&self) -> f32 {
+        self.e[0].max(self.e[1]).max(self.e[2])
+    }
+
+    /// The 8-bit display color of a pixel whose `samples` samples add up to this color: the
+    /// average, gamma-corrected for gamma=2.0 and clamped to the displayable range.
+    pub fn to_srgb_u8(&self, samples: u32) -> [u8; 3] {
+        let scale = 1.0 / samples.max(1) as f32;
+        let to_u8 = |x: f32| (255.999 * (scale * x).sqrt().clamp(0.0, 0.999)) as u8;
+
+        [to_u8(self.e[0]), to_u8(self.e[1]), to_u8(self.e[2])]
+    }
+
+    /// The linear color that [`to_srgb_u8`](Self::to_srgb_u8) turns into `r`, `g` and `b` for a
+    /// single sample.
+    pub fn from_srgb_u8(r: u8, g: u8, b: u8) -> Color {
+        let to_linear = |channel: u8| (channel as f32 / 255.0).powi(2);
+
+        Color::new(to_linear(r), to_linear(g), to_linear(b))
+    }
+
     pub fn reflect(&self, normal: &Vec3) -> Vec3 {
         *self - 2.0 * self.dot(normal) * *normal
     }
@@ -150,6 +185,60 @@ impl GenericVec3<f32> {
         r_out_perpendicular + r_out_parallel
     }
 
+    /// The part of `self` that points along `other`.
+    pub fn project_onto(&self, other: &Vec3) -> Vec3 {
+        (self.dot(other) / other.length_squared()) * *other
+    }
+
+    /// The part of `self` that is perpendicular to `other`.
+    pub fn reject_from(&self, other: &Vec3) -> Vec3 {
+        *self - self.project_onto(other)
+    }
+
+    pub fn midpoint(&self, other: &Vec3) -> Vec3 {
+        0.5 * (*self + *other)
+    }
+
+    /// The point `t` of the way from `self` to `other`.
+    pub fn lerp(&self, other: Vec3, t: f32) -> Vec3 {
+        *self + t * (other - *self)
+    }
+
+    /// Interpolates between the directions of `self` and `other` at a constant angular speed,
+    /// along the great circle through both. The result is a unit vector.
+    pub fn slerp(&self, other: Vec3, t: f32) -> Vec3 {
+        let from = self.unit_vector();
+        let to = other.unit_vector();
+        let angle = from.angle_between(&to);
+
+        let sin_angle = angle.sin();
+        if sin_angle < 1e-4 {
+            // Nearly equal directions make the great circle ill-defined, but the straight line
+            // between them is just as good.
+            if angle < PI / 2.0 {
+                return from.lerp(to, t).unit_vector();
+            }
+
+            // Opposite directions lie on many great circles. Take the one through any
+            // perpendicular direction.
+            let helper = if from.x().abs() > 0.9 {
+                Vec3::new(0.0, 1.0, 0.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            let perpendicular = from.cross(&helper).unit_vector();
+            return (t * PI).cos() * from + (t * PI).sin() * perpendicular;
+        }
+
+        (((1.0 - t) * angle).sin() * from + (t * angle).sin() * to) / sin_angle
+    }
+
+    /// The angle between the two vectors in radians, from zero to π.
+    pub fn angle_between(&self, other: &Vec3) -> f32 {
+        let cosine = self.dot(other) / (self.length() * other.length());
+        cosine.clamp(-1.0, 1.0).acos()
+    }
+
     pub fn floor(self) -> Vec3 {
         let e = self.e;
         Vec3::new(e[0].floor(), e[1].floor(), e[2].floor())
@@ -360,3 +449,132 @@ impl Display for Vec3 {
         write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
     }
 }
+
+/// Asserts that `actual` and `expected` agree up to float rounding, for tests.
+#[cfg(test)]
+#[track_caller]
+pub(crate) fn assert_near(actual: Vec3, expected: Vec3) {
+    let close = (0..3).all(|a| (actual[a] - expected[a]).abs() <= 1e-4 * (1.0 + expected[a].abs()));
+    assert!(close, "expected {}, got {}", expected, actual);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+
+    use super::*;
+
+    #[test]
+    fn lerp_runs_from_self_to_other() {
+        let a = Vec3::new(1.0, -2.0, 3.0);
+        let b = Vec3::new(3.0, 2.0, -1.0);
+
+        assert_near(a.lerp(b, 0.0), a);
+        assert_near(a.lerp(b, 0.5), Vec3::new(2.0, 0.0, 1.0));
+        assert_near(a.lerp(b, 1.0), b);
+        assert_near(a.lerp(b, 2.0), Vec3::new(5.0, 6.0, -5.0));
+    }
+
+    #[test]
+    fn slerp_follows_the_great_circle() {
+        let x = Vec3::new(2.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 0.5, 0.0);
+
+        assert_near(x.slerp(y, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_near(
+            x.slerp(y, 0.5),
+            Vec3::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0),
+        );
+        assert_near(x.slerp(y, 1.0), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn slerp_results_are_unit_vectors() {
+        let from = Vec3::new(0.3, -0.4, 1.2);
+        let others = [
+            Vec3::new(-2.0, 0.5, 0.1),
+            from * 3.0,
+            from + Vec3::new(1e-5, 0.0, 0.0),
+            -from,
+        ];
+
+        for to in others {
+            for step in 0..=10 {
+                let t = step as f32 / 10.0;
+                let length = from.slerp(to, t).length();
+                assert!(
+                    (length - 1.0).abs() < 1e-5,
+                    "{} towards {} at {}",
+                    from,
+                    to,
+                    t
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn angle_between_ignores_length() {
+        let x = Vec3::new(3.0, 0.0, 0.0);
+
+        assert!((x.angle_between(&Vec3::new(0.0, 0.0, 0.2)) - FRAC_PI_2).abs() < 1e-6);
+        assert!((x.angle_between(&Vec3::new(1.0, 1.0, 0.0)) - PI / 4.0).abs() < 1e-6);
+        assert!(x.angle_between(&(x * 5.0)).abs() < 1e-6);
+        assert!((x.angle_between(&-x) - PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn projection_and_rejection_add_up_to_the_original() {
+        let v = Vec3::new(1.5, -2.0, 0.7);
+        let axes = [
+            Vec3::new(0.0, 3.0, 0.0),
+            Vec3::new(1.0, 2.0, -2.0),
+            Vec3::new(-0.1, 0.0, 0.4),
+        ];
+
+        for axis in axes {
+            let projection = v.project_onto(&axis);
+            let rejection = v.reject_from(&axis);
+
+            assert_near(projection + rejection, v);
+            assert!(projection.cross(&axis).length() < 1e-5);
+            assert!(rejection.dot(&axis).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn projection_onto_a_unit_vector_scales_it_by_the_dot_product() {
+        let v = Vec3::new(1.5, -2.0, 0.7);
+        let unit = Vec3::new(2.0, -1.0, 2.0) / 3.0;
+
+        assert_near(v.project_onto(&unit), v.dot(&unit) * unit);
+    }
+
+    #[test]
+    fn rejection_from_a_parallel_vector_is_zero() {
+        let v = Vec3::new(1.0, 2.0, 3.0);
+
+        assert_near(v.reject_from(&(v * -2.0)), Vec3::new(0.0, 0.0, 0.0));
+        assert_near(v.project_onto(&(v * -2.0)), v);
+    }
+
+    #[test]
+    fn midpoint_is_halfway() {
+        let a = Vec3::new(1.0, -2.0, 3.0);
+        let b = Vec3::new(3.0, 2.0, -1.0);
+
+        assert_near(a.midpoint(&b), Vec3::new(2.0, 0.0, 1.0));
+        assert_near(a.midpoint(&b), a.lerp(b, 0.5));
+    }
+
+    #[test]
+    fn every_srgb_byte_survives_a_round_trip() {
+        for value in 0..=255 {
+            // A different value per channel catches mixed up channels too.
+            let (r, g, b) = (value, 255 - value, value / 2);
+            let color = Color::from_srgb_u8(r, g, b);
+
+            assert_eq!(color.to_srgb_u8(1), [r, g, b], "via {}", color);
+        }
+    }
+}